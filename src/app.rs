@@ -1,14 +1,23 @@
 //! High-level orchestration: validate input, run the scan, write output.
 
-use crate::config::{OutputFormat, ScanFilter, ScanOptions};
-use crate::output;
-use crate::scan::{self, Catalog, FileEntry, ScanStats};
-use crate::{cli, exit_error};
+use crate::checkpoint::{Checkpoint, Resume};
+use crate::config::{Encoding, GroupBy, OutputFormat, ScanFilter, ScanOptions};
+use crate::gitstatus::GitStatus;
+use crate::output::color::Palette;
+use crate::output::{self, Output};
+use crate::scan::{self, Catalog, Roots, ScanStats};
+use crate::{charset, cli, duplicates, exit_error, hash, interrupt, lines, watch};
+use clap::ValueEnum;
 use std::fs;
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Exit status of a scan that `--limit` stopped early, so scripts can tell it
+/// from a complete one (0), a failure (1) and Ctrl-C (130).
+const TRUNCATED_EXIT_CODE: i32 = 3;
 
 /// Run an interactive scan, picking folder and output location via file dialogs.
-pub fn run_with_dialogs(options: &ScanOptions, filter: &ScanFilter) {
+pub fn run_with_dialogs(options: &ScanOptions, filter: ScanFilter) {
     let folder = match rfd::FileDialog::new()
         .set_title("Select Folder to Scan")
         .pick_folder()
@@ -20,7 +29,8 @@ pub fn run_with_dialogs(options: &ScanOptions, filter: &ScanFilter) {
         }
     };
 
-    let default_name = cli::default_filename(&folder, options.format.extension());
+    let roots = Roots::single(folder);
+    let default_name = cli::default_filename(&roots, &options.file_extension());
     let output_path = match rfd::FileDialog::new()
         .set_title("Select Output Location")
         .set_file_name(&default_name)
@@ -33,58 +43,485 @@ pub fn run_with_dialogs(options: &ScanOptions, filter: &ScanFilter) {
         }
     };
 
-    run(&folder, &output_path, options, filter);
+    run(&roots, &output_path, options, filter);
 }
 
-/// Validate the target, scan it, and write the catalog in the chosen format.
-pub fn run(folder: &Path, output_path: &Path, options: &ScanOptions, filter: &ScanFilter) {
-    if !folder.exists() {
-        eprintln!("Error: Folder '{}' does not exist", folder.display());
-        if folder.is_relative()
-            && let Ok(cwd) = std::env::current_dir()
-        {
-            eprintln!("Current directory: {}", cwd.display());
-            eprintln!("Tried to resolve: {}", cwd.join(folder).display());
+/// Validate the targets, scan them, and write the catalog in each chosen format.
+pub fn run(roots: &Roots, output_path: &Path, options: &ScanOptions, mut filter: ScanFilter) {
+    for folder in &roots.folders {
+        if !folder.exists() {
+            eprintln!("Error: Folder '{}' does not exist", folder.display());
+            if folder.is_relative()
+                && let Ok(cwd) = std::env::current_dir()
+            {
+                eprintln!("Current directory: {}", cwd.display());
+                eprintln!("Tried to resolve: {}", cwd.join(folder).display());
+            }
+            std::process::exit(1);
+        }
+        if !folder.is_dir() && !folder.is_file() {
+            exit_error(&format!("'{}' is neither a directory nor a regular file", folder.display()));
+        }
+    }
+    let folder = roots.base.as_path();
+
+    let to_stdout = output::is_stdout(output_path);
+    let compress = options.compress || output::has_gz_suffix(output_path);
+    let formats = &options.formats;
+    if formats.contains(&OutputFormat::Sqlite) {
+        if to_stdout {
+            exit_error("--format sqlite cannot write to stdout");
+        }
+        if compress {
+            exit_error("--format sqlite cannot be compressed");
+        }
+        if options.encoding != Encoding::Utf8 {
+            exit_error("--encoding does not apply to --format sqlite");
+        }
+    }
+    if to_stdout && formats.len() > 1 {
+        exit_error("only one --format can be written to stdout");
+    }
+    if to_stdout && options.watch {
+        exit_error("--watch rewrites the catalog in place, so it needs an output file, not stdout");
+    }
+    if options.split {
+        if to_stdout {
+            exit_error("--split needs an output directory, not stdout");
+        }
+        if formats.len() > 1 {
+            exit_error("--split supports a single --format");
+        }
+        if matches!(formats[0], OutputFormat::Sqlite | OutputFormat::Ndjson) {
+            exit_error("--split does not support --format sqlite or ndjson");
+        }
+        if options.duplicates {
+            exit_error("--duplicates cannot be combined with --split");
+        }
+    }
+    if options.dry_run {
+        let targets = if to_stdout { Vec::new() } else { output_paths(output_path, formats, compress) };
+        filter.exclude_outputs(folder, &targets);
+        preview(roots, output_path, &targets, options, &filter);
+        fail_on_missing(roots);
+        return;
+    }
+    if options.summary_only {
+        let stats = scan::walk(roots, options, &filter, |_, _| {});
+        fail_fast(&stats, options);
+        if !options.quiet {
+            println!("{}", summary_line(&stats, roots, options));
+        }
+        log_timing(&stats, options);
+        exit_if_partial(&stats);
+        fail_on_missing(roots);
+        return;
+    }
+    let targets = if to_stdout {
+        Vec::new()
+    } else {
+        ensure_parent_dir(output_path);
+        output_paths(output_path, formats, compress)
+    };
+    // Rescanning a folder that holds earlier output (or the checkpoint) must not
+    // catalog it.
+    let own: Vec<PathBuf> = targets.iter().cloned().chain(options.checkpoint.clone()).collect();
+    filter.exclude_outputs(folder, &own);
+
+    if !options.watch {
+        let stats = scan_and_write(roots, output_path, options, &filter, compress);
+        exit_if_partial(&stats);
+        fail_on_missing(roots);
+        return;
+    }
+    interrupt::watching();
+    scan_and_write(roots, output_path, options, &filter, compress);
+    // The catalog (and cache) being rewritten must not count as a change.
+    let own: Vec<PathBuf> = targets.into_iter().chain(options.cache.clone()).collect();
+    watch::run(&roots.folders, &own, options.debounce, options.quiet, || {
+        scan_and_write(roots, output_path, options, &filter, compress);
+    });
+    fail_on_missing(roots);
+}
+
+/// `--dry-run`: say which folders would be scanned, which files written and with
+/// which options, and with `--sample` list some of the files found at the top
+/// level. Nothing is created.
+fn preview(roots: &Roots, output_path: &Path, targets: &[PathBuf], options: &ScanOptions, filter: &ScanFilter) {
+    println!("Dry run: nothing is scanned or written");
+    for folder in &roots.folders {
+        println!("Folder: {}", std::path::absolute(folder).unwrap_or_else(|_| folder.clone()).display());
+    }
+    if roots.folders.len() > 1 {
+        println!("Paths relative to: {}", roots.base.display());
+    }
+    if let Some(files) = &roots.files {
+        println!("Files listed: {}", files.len());
+    }
+    for folder in &roots.missing {
+        println!("Missing: {} (skipped)", folder.display());
+    }
+    let names: Vec<String> = options
+        .formats
+        .iter()
+        .map(|format| format.to_possible_value().map_or_else(String::new, |value| value.get_name().to_string()))
+        .collect();
+    if output::is_stdout(output_path) {
+        println!("Output: stdout ({})", names.join(", "));
+    } else if options.split {
+        println!("Output: a {} file per extension in {}", names.join(", "), output_path.display());
+    } else {
+        for (target, name) in targets.iter().zip(&names) {
+            let replaces = if target.exists() { ", replacing the existing file" } else { "" };
+            println!("Output: {} ({name}{replaces})", target.display());
+        }
+    }
+    if let Some(parent) = output_path.parent()
+        && !output::is_stdout(output_path)
+        && !parent.as_os_str().is_empty()
+        && !parent.exists()
+    {
+        println!("Creates directory: {}", parent.display());
+    }
+    if options.given.is_empty() {
+        println!("Options: none (defaults)");
+    } else {
+        println!("Options: {}", options.given.join(" "));
+    }
+    let Some(count) = options.sample else {
+        return;
+    };
+    // The real walk, one level deep and cut short, so every filter applies.
+    let mut sampling = options.clone();
+    sampling.max_depth = Some(1);
+    sampling.limit = Some(count as u64);
+    sampling.quiet = true;
+    sampling.verbose = 0;
+    sampling.timeout = None;
+    sampling.cache = None;
+    let mut found = Vec::new();
+    scan::walk(roots, &sampling, filter, |_, entry| found.push(entry.path));
+    println!("Sample ({} files from the top level):", found.len());
+    for path in found.iter().take(count) {
+        println!("  {}", path.display());
+    }
+}
+
+/// Scan the folders and write the catalog in each chosen format, reporting the
+/// outcome.
+fn scan_and_write(
+    roots: &Roots,
+    output_path: &Path,
+    options: &ScanOptions,
+    filter: &ScanFilter,
+    compress: bool,
+) -> ScanStats {
+    let folder = roots.base.as_path();
+    let formats = &options.formats;
+    // A lone NDJSON output streams straight to disk and plain text spools through
+    // a temporary file (flat memory); everything else groups in memory first so
+    // each format can be rendered from one scan.
+    if formats[..] == [OutputFormat::Ndjson] {
+        if options.min_group.is_some() {
+            exit_error("--min-group does not apply to --format ndjson, which is not grouped");
+        }
+        if options.duplicates {
+            exit_error("--duplicates needs the whole catalog, so it cannot stream --format ndjson");
         }
-        std::process::exit(1);
     }
-    if !folder.is_dir() {
-        exit_error(&format!("'{}' is not a directory", folder.display()));
+    // A checkpoint saves the catalog as it grows, so it is built in memory and
+    // written at the end instead.
+    if formats[..] == [OutputFormat::Ndjson] && options.checkpoint.is_none() {
+        let stats = Output::create(output_path, compress, options.encoding, options.line_ending)
+            .and_then(|mut out| {
+                let stats = output::ndjson::write_streaming(&mut out, roots, options, filter)?;
+                out.finish()?;
+                Ok(stats)
+            })
+            .unwrap_or_else(|e| write_failed(e));
+        fail_fast(&stats, options);
+        report(&stats, roots, options, vec![(output_path.to_path_buf(), Ok(()))], compress);
+        return stats;
     }
 
-    ensure_parent_dir(output_path);
+    if streams_text(options) {
+        let mut spool = output::text::Spool::new().unwrap_or_else(|e| write_failed(e));
+        let stats = scan::walk(roots, options, filter, |ext, entry| spool.add(ext, &entry, options));
+        fail_fast(&stats, options);
+        Output::create(output_path, compress, options.encoding, options.line_ending)
+            .and_then(|mut out| {
+                spool.finish(&mut out, &stats, options, palette(output_path, compress, options))?;
+                out.finish()
+            })
+            .unwrap_or_else(|e| write_failed(e));
+        report(&stats, roots, options, vec![(output_path.to_path_buf(), Ok(()))], compress);
+        return stats;
+    }
 
-    // CSV streams straight to disk (flat memory); text/JSON group in memory first.
-    let stats = match options.format {
-        OutputFormat::Csv => output::csv::write_streaming(folder, output_path, options, filter)
-            .unwrap_or_else(|e| exit_error(&format!("writing output file: {e}"))),
-        OutputFormat::Text | OutputFormat::Json => {
-            let mut catalog: Catalog = Catalog::new();
-            let stats = scan::walk(folder, options, filter, |ext, path, size| {
-                catalog.entry(ext).or_default().push(FileEntry { path, size });
-            });
-            write_grouped(&catalog, &stats, folder, output_path, options)
-                .unwrap_or_else(|e| exit_error(&format!("writing output file: {e}")));
-            stats
+    let (checkpoint, resume) = match &options.checkpoint {
+        Some(path) => {
+            let (checkpoint, resume) = open_checkpoint(path, roots, options, filter);
+            (Some(checkpoint), resume)
         }
+        None => (None, None),
+    };
+    let mut catalog: Catalog = Catalog::new();
+    // Digests are worked out by a pool of threads while the walk goes on.
+    let mut hasher = options
+        .hash
+        .map(|algorithm| hash::CatalogHasher::new(folder, algorithm, options, checkpoint.as_ref()));
+    let add = |ext, entry| match &mut hasher {
+        Some(hasher) => hasher.add(&mut catalog, ext, entry),
+        None => catalog.entry(ext).or_default().push(entry),
     };
+    let mut stats = match &checkpoint {
+        Some(checkpoint) => scan::walk_checkpointed(roots, options, filter, checkpoint, resume, add),
+        None => scan::walk(roots, options, filter, add),
+    };
+    fail_fast(&stats, options);
+    if let Some(hasher) = hasher {
+        hasher.finish(&mut catalog, &mut stats);
+    }
+    if let Some(min) = options.min_group {
+        stats.dropped = scan::fold_small_groups(&mut catalog, min, options.drop_small);
+    }
+    if options.lines {
+        lines::count_catalog(&mut catalog, folder, options);
+    }
+    if options.detect_encoding {
+        charset::detect_catalog(&mut catalog, folder, options);
+        charset::summarize(&catalog, &mut stats);
+    }
+    if options.duplicates {
+        let (groups, too_large) = duplicates::find(&catalog, folder, options);
+        stats.duplicates = groups;
+        // With --hash, the same files were already counted as they were hashed.
+        if options.hash.is_none() {
+            stats.hash_skipped = too_large;
+        }
+    }
+
+    if options.split {
+        let written = output::split::write(catalog, &stats, folder, output_path, options)
+            .unwrap_or_else(|e| write_failed(e));
+        if !options.quiet {
+            println!("{}", summary_line(&stats, roots, options));
+            println!("Saved {} files to: {}", written.len(), output_path.display());
+        }
+        log_timing(&stats, options);
+        finish_checkpoint(checkpoint, &stats);
+        return stats;
+    }
+
+    // Write every format even if one fails, so each outcome gets reported.
+    let written = formats
+        .iter()
+        .zip(output_paths(output_path, formats, compress))
+        .map(|(&format, path)| {
+            let result = write_catalog(format, &catalog, &stats, folder, &path, compress, options);
+            (path, result)
+        })
+        .collect();
+    report(&stats, roots, options, written, compress);
+    finish_checkpoint(checkpoint, &stats);
+    stats
+}
+
+/// Open the `--checkpoint` at `path` for this scan, and say so if an earlier
+/// run is being resumed.
+fn open_checkpoint(path: &Path, roots: &Roots, options: &ScanOptions, filter: &ScanFilter) -> (Checkpoint, Option<Resume>) {
+    let mut key = format!("{}\n{}", options.scan_key(), filter.scan_key());
+    for folder in &roots.folders {
+        let folder = std::path::absolute(folder).unwrap_or_else(|_| folder.clone());
+        key.push_str(&format!("\nfolder={}", folder.display()));
+    }
+    let (checkpoint, resume) = Checkpoint::open(path, &key, options.quiet)
+        .unwrap_or_else(|e| exit_error(&format!("checkpoint '{}': {e}", path.display())));
+    if let Some(resume) = &resume
+        && !options.quiet
+    {
+        eprintln!(
+            "Resuming from checkpoint '{}' ({} entries already catalogued)",
+            path.display(),
+            resume.entries.len()
+        );
+    }
+    (checkpoint, resume)
+}
 
-    print_summary(&stats, options);
-    println!("Saved to: {}", output_path.display());
+/// Delete the checkpoint once the catalog of a complete scan is written; after
+/// Ctrl-C or `--timeout` it is kept, to carry on from next time.
+fn finish_checkpoint(checkpoint: Option<Checkpoint>, stats: &ScanStats) {
+    if let Some(checkpoint) = checkpoint
+        && stats.interrupted.is_none()
+    {
+        checkpoint.remove();
+    }
 }
 
-/// Write a grouped catalog as text or JSON.
-fn write_grouped(
+/// Whether the catalog is plain text alone, with nothing that needs every file
+/// at once (hashing, line counts, encodings, duplicates, folding, splitting or
+/// a checkpoint), so it can be spooled to disk as the scan goes instead of held
+/// in memory.
+fn streams_text(options: &ScanOptions) -> bool {
+    options.formats[..] == [OutputFormat::Text]
+        && options.checkpoint.is_none()
+        && !options.split
+        && options.min_group.is_none()
+        && options.hash.is_none()
+        && !options.lines
+        && !options.detect_encoding
+        && !options.duplicates
+}
+
+/// Once a partial catalog is written, exit with the status that says Ctrl-C,
+/// `--timeout` or `--limit` cut the scan short.
+fn exit_if_partial(stats: &ScanStats) {
+    if stats.timed_out {
+        std::process::exit(interrupt::TIMEOUT_EXIT_CODE);
+    }
+    if stats.interrupted.is_some() {
+        std::process::exit(interrupt::EXIT_CODE);
+    }
+    if stats.truncated.is_some() {
+        std::process::exit(TRUNCATED_EXIT_CODE);
+    }
+}
+
+/// Once the catalog is written, exit non-zero if `--paths-from` listed folders
+/// that weren't there.
+fn fail_on_missing(roots: &Roots) {
+    match roots.missing.len() {
+        0 => {}
+        1 => exit_error(&format!("'{}' was not found and was skipped", roots.missing[0].display())),
+        n => exit_error(&format!("{n} listed folders were not found and were skipped")),
+    }
+}
+
+/// With `--fail-fast`, exit on the error that stopped the walk.
+fn fail_fast(stats: &ScanStats, options: &ScanOptions) {
+    if options.fail_fast
+        && let Some(error) = stats.errors.first()
+    {
+        match &error.path {
+            Some(path) => exit_error(&format!("cannot read '{}': {}", path.display(), error.message)),
+            None => exit_error(&error.message),
+        }
+    }
+}
+
+/// The file each format is written to: `output_path` itself for a single format
+/// (or the `--split` directory), else one sibling per format.
+fn output_paths(output_path: &Path, formats: &[OutputFormat], compress: bool) -> Vec<PathBuf> {
+    if formats.len() == 1 {
+        vec![output_path.to_path_buf()]
+    } else {
+        formats
+            .iter()
+            .map(|&format| cli::sibling_path(output_path, format, compress))
+            .collect()
+    }
+}
+
+/// Print the summary and where each output went (only failures with `--quiet`);
+/// exit non-zero if any write failed.
+fn report(
+    stats: &ScanStats,
+    roots: &Roots,
+    options: &ScanOptions,
+    written: Vec<(PathBuf, io::Result<()>)>,
+    compress: bool,
+) {
+    log_timing(stats, options);
+    if options.quiet {
+        let mut failed = false;
+        for (path, result) in written {
+            match result {
+                Err(e) if output::is_stdout(&path) => write_failed(e),
+                Err(e) => {
+                    eprintln!("Error: writing '{}': {e}", path.display());
+                    failed = true;
+                }
+                Ok(()) => {}
+            }
+        }
+        if failed {
+            std::process::exit(1);
+        }
+        return;
+    }
+    let summary = summary_line(stats, roots, options);
+
+    // Keep stdout clean for the catalog itself when it is being piped. Stderr may
+    // share the closed pipe (`2>&1 | head`), so failures there are ignored too.
+    if let [(path, _)] = &written[..]
+        && output::is_stdout(path)
+    {
+        if let Some((_, Err(e))) = written.into_iter().next() {
+            write_failed(e);
+        }
+        let mut stderr = io::stderr();
+        let _ = writeln!(stderr, "{summary}");
+        let _ = writeln!(stderr, "Saved to: <stdout>");
+        return;
+    }
+
+    println!("{summary}");
+    let mut failed = false;
+    for (path, result) in written {
+        match result {
+            Ok(()) if compress => {
+                let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                println!(
+                    "Saved to: {} ({} compressed)",
+                    path.display(),
+                    output::human_size(size)
+                );
+            }
+            Ok(()) => println!("Saved to: {}", path.display()),
+            Err(e) => {
+                eprintln!("Error: writing '{}': {e}", path.display());
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Write the catalog to `path` in one format.
+fn write_catalog(
+    format: OutputFormat,
     catalog: &Catalog,
     stats: &ScanStats,
     folder: &Path,
-    output_path: &Path,
+    path: &Path,
+    compress: bool,
     options: &ScanOptions,
-) -> std::io::Result<()> {
-    match options.format {
-        OutputFormat::Json => output::json::write(catalog, stats, folder, output_path, options),
-        _ => output::text::write(catalog, output_path, options),
+) -> io::Result<()> {
+    if format == OutputFormat::Sqlite {
+        return output::sqlite::write(catalog, stats, folder, path, options);
+    }
+
+    let line_ending = output::line_ending(format, options);
+    let mut out = Output::create(path, compress, options.encoding, line_ending)?;
+    output::render(&mut out, format, catalog, stats, folder, options, palette(path, compress, options))?;
+    out.finish()
+}
+
+/// How the catalog written to `path` is coloured: only ever when it is printed,
+/// uncompressed, to stdout, so no escape codes end up in a file.
+fn palette(path: &Path, compress: bool, options: &ScanOptions) -> Palette {
+    if output::is_stdout(path) && !compress { Palette::stdout(options.color) } else { Palette::PLAIN }
+}
+
+/// Exit after a failed write. A closed pipe (e.g. `| head`) ends the run quietly.
+fn write_failed(e: io::Error) -> ! {
+    if e.kind() == io::ErrorKind::BrokenPipe {
+        std::process::exit(0);
     }
+    exit_error(&format!("writing output file: {e}"))
 }
 
 /// Create the output file's parent directory if needed.
@@ -97,21 +534,272 @@ fn ensure_parent_dir(output_path: &Path) {
     }
 }
 
-/// Print the post-scan summary line to stdout.
-fn print_summary(stats: &ScanStats, options: &ScanOptions) {
+/// With `-v`, how long the walk and the whole run took, to stderr.
+fn log_timing(stats: &ScanStats, options: &ScanOptions) {
+    if options.verbose > 0 {
+        let total = stats.started.elapsed().unwrap_or_default();
+        eprintln!("Walked in {:.2?}; {:.2?} in all", stats.walk_time, total.max(stats.walk_time));
+    }
+}
+
+/// The post-scan summary line.
+fn summary_line(stats: &ScanStats, roots: &Roots, options: &ScanOptions) -> String {
+    let mut line = scan_line(stats, roots, options);
+    if let Some(notice) = output::partial_notice(stats) {
+        line = format!("{notice}. {line}");
+    }
+    match stats.outputs_excluded {
+        0 => {}
+        1 => line.push_str(" Output file excluded from results."),
+        n => line.push_str(&format!(" {n} output files excluded from results.")),
+    }
+    if !options.recursive {
+        line.push_str(" Non-recursive: subfolders were not scanned.");
+    } else if let Some(depth) = options.max_depth {
+        line.push_str(&format!(" Depth limited to {depth}."));
+    }
+    if stats.dropped > 0 {
+        line.push_str(&format!(" {} files in small groups omitted.", stats.dropped));
+    }
+    if stats.empty > 0 {
+        line.push_str(&format!(" {} empty files skipped.", stats.empty));
+    }
+    if stats.filtered > 0 {
+        line.push_str(&format!(" {} files filtered out.", stats.filtered));
+    }
+    if stats.junk_pruned > 0 {
+        line.push_str(&format!(" {} junk directories pruned.", stats.junk_pruned));
+    }
+    if stats.pruned > 0 {
+        line.push_str(&format!(" {} directories pruned.", stats.pruned));
+    }
+    if stats.ignored > 0 {
+        line.push_str(&format!(" {} entries ignored by ignore-file rules.", stats.ignored));
+    }
+    if !stats.errors.is_empty() {
+        line.push_str(&format!(" Skipped {} entries due to errors.", stats.errors.len()));
+    }
+    if !stats.mount_points.is_empty() {
+        // Only the first few; a scan of `/` can pass dozens.
+        let n = stats.mount_points.len();
+        let mut shown: Vec<String> =
+            stats.mount_points.iter().take(5).map(|p| p.display().to_string()).collect();
+        if n > shown.len() {
+            shown.push(format!("and {} more", n - shown.len()));
+        }
+        line.push_str(&format!(" {n} mount points not crossed: {}.", shown.join(", ")));
+    }
+    if !stats.virtual_mounts.is_empty() {
+        let n = stats.virtual_mounts.len();
+        let mut shown: Vec<String> =
+            stats.virtual_mounts.iter().take(5).map(|p| p.display().to_string()).collect();
+        if n > shown.len() {
+            shown.push(format!("and {} more", n - shown.len()));
+        }
+        line.push_str(&format!(
+            " {n} virtual filesystems skipped (--include-virtual to scan them): {}.",
+            shown.join(", ")
+        ));
+    }
+    if !stats.merged_roots.is_empty() {
+        let n = stats.merged_roots.len();
+        let mut shown: Vec<String> = stats
+            .merged_roots
+            .iter()
+            .take(5)
+            .map(|(folder, cover)| format!("{} into {}", folder.display(), cover.display()))
+            .collect();
+        if n > shown.len() {
+            shown.push(format!("and {} more", n - shown.len()));
+        }
+        line.push_str(&format!(" {n} overlapping folders merged: {}.", shown.join(", ")));
+    }
+    if options.cache.is_some() {
+        line.push_str(&format!(" {} of {} directories served from cache.", stats.cached_dirs, stats.dirs));
+    }
+    if options.retries > 0 {
+        line.push_str(&format!(" {} operations needed retries.", stats.retried));
+    }
+    if stats.link_loops > 0 {
+        line.push_str(&format!(" {} symlink loops not followed.", stats.link_loops));
+    }
+    if stats.broken_links > 0 {
+        line.push_str(&format!(" {} broken symlinks.", stats.broken_links));
+    }
+    if options.duplicates {
+        line.push_str(&format!(
+            " {} duplicate groups, {} reclaimable.",
+            stats.duplicates.len(),
+            output::display_size(duplicates::reclaimable(&stats.duplicates), options.exact_bytes)
+        ));
+    }
+    if stats.hardlinked > 0 {
+        line.push_str(&format!(" {} additional hardlinks counted once", stats.hardlinked));
+        if options.sizes {
+            line.push_str(&format!(
+                " ({} on disk)",
+                output::display_size(stats.total_size - stats.hardlinked_size, options.exact_bytes)
+            ));
+        }
+        line.push('.');
+    }
+    if options.verify_types {
+        line.push_str(&format!(" {} files do not match their extension.", stats.mismatched.len()));
+    }
+    if options.empty_dirs.is_some() {
+        line.push_str(&format!(" {} empty directories.", stats.empty_dirs.len()));
+    }
+    if !stats.case_variants.is_empty() {
+        line.push_str(&format!(
+            " {} extensions appear in more than one casing.",
+            stats.case_variants.len()
+        ));
+    }
+    if options.disk_usage {
+        line.push_str(&format!(
+            " {} on disk",
+            output::display_size(stats.allocated_size, options.exact_bytes)
+        ));
+        if stats.allocation_estimated > 0 {
+            line.push_str(&format!(
+                " ({} files at apparent size, allocation unknown)",
+                stats.allocation_estimated
+            ));
+        }
+        line.push('.');
+    }
+    if options.tracks_executables() {
+        line.push_str(&format!(" {} executables.", stats.executables));
+    }
+    if options.detect_encoding {
+        line.push_str(&format!(" {} text files are not UTF-8.", stats.non_utf8.len()));
+    }
+    if let Some(counts) = &stats.git {
+        let counts: Vec<String> = GitStatus::ALL
+            .iter()
+            .zip(counts)
+            .map(|(status, files)| format!("{files} {}", status.name()))
+            .collect();
+        line.push_str(&format!(" Git: {}.", counts.join(", ")));
+    }
+    if stats.hash_failures > 0 {
+        line.push_str(&format!(" {} files could not be hashed.", stats.hash_failures));
+    }
+    if stats.hash_skipped > 0 {
+        line.push_str(&format!(" {} files larger than --hash-max-size not hashed.", stats.hash_skipped));
+    }
+    if stats.xattr_failures > 0 {
+        line.push_str(&format!(
+            " Extended attributes of {} files could not be read.",
+            stats.xattr_failures
+        ));
+    }
+    if stats.perms_unknown > 0 {
+        line.push_str(&format!(
+            " Permissions of {} files could not be read.",
+            stats.perms_unknown
+        ));
+    }
+    line.push('\n');
+    line.push_str(&totals_line(stats, options));
+    line
+}
+
+/// The summary's second line: how many extensions (or groups) the files fell
+/// into and the five biggest, then how long the run took.
+fn totals_line(stats: &ScanStats, options: &ScanOptions) -> String {
+    let mut groups: Vec<(&String, u64)> = stats
+        .groups
+        .iter()
+        .filter(|(group, _)| scan::is_regular_group(group))
+        .map(|(group, &n)| (group, n))
+        .collect();
+    groups.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let kind = if options.group_by == GroupBy::Ext { "extensions" } else { "groups" };
+    let mut line = format!("{} {kind}", groups.len());
+    if !groups.is_empty() {
+        let top: Vec<String> = groups
+            .iter()
+            .take(5)
+            .map(|(group, n)| format!("{} {n}", if group.is_empty() { "(no extension)" } else { group.as_str() }))
+            .collect();
+        line.push_str(&format!(", most files: {}", top.join(", ")));
+    }
+    let elapsed = stats.started.elapsed().unwrap_or_default().max(stats.walk_time);
+    line.push_str(&format!(". Took {}.", output::human_duration(elapsed)));
+    line
+}
+
+/// The files found, with the `--archives` members listed alongside them, so the
+/// count matches the catalog's `Total:` line.
+fn files_phrase(stats: &ScanStats) -> String {
+    match stats.archive_entries {
+        0 => format!("{} files", stats.files),
+        members => format!("{} files + {members} archive members", stats.files),
+    }
+}
+
+fn scan_line(stats: &ScanStats, roots: &Roots, options: &ScanOptions) -> String {
+    if roots.only_files() {
+        let size = if options.sizes {
+            format!(" ({})", output::display_size(stats.total_size, options.exact_bytes))
+        } else {
+            String::new()
+        };
+        return format!("Catalogued {}{size} given as paths; no directories were walked.", files_phrase(stats));
+    }
     if options.sizes {
-        println!(
-            "Scanned {} files ({}) in {} directories ({} skipped, {} symlinks).",
-            stats.files,
-            output::human_size(stats.total_size),
+        format!(
+            "Scanned {} ({}) in {} directories ({} skipped, {} symlinks).",
+            files_phrase(stats),
+            output::display_size(stats.total_size, options.exact_bytes),
             stats.dirs,
             stats.skipped,
             stats.symlinks
-        );
+        )
     } else {
-        println!(
-            "Scanned {} files in {} directories ({} skipped, {} symlinks).",
-            stats.files, stats.dirs, stats.skipped, stats.symlinks
+        format!(
+            "Scanned {} in {} directories ({} skipped, {} symlinks).",
+            files_phrase(stats),
+            stats.dirs,
+            stats.skipped,
+            stats.symlinks
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+
+    #[test]
+    fn archive_members_are_counted_beside_the_files() {
+        let (options, _) = cli::parse_for_test(&["--archives", "zip"]);
+        let dir = tempfile::tempdir().unwrap();
+        let roots = Roots::single(dir.path().to_path_buf());
+        let mut stats = ScanStats::new();
+        (stats.files, stats.dirs) = (6, 1);
+        assert_eq!(scan_line(&stats, &roots, &options), "Scanned 6 files in 1 directories (0 skipped, 0 symlinks).");
+        stats.archive_entries = 6;
+        assert_eq!(
+            scan_line(&stats, &roots, &options),
+            "Scanned 6 files + 6 archive members in 1 directories (0 skipped, 0 symlinks)."
         );
     }
+
+    #[test]
+    fn only_files_given_as_paths_are_reported_as_not_walked() {
+        let (options, _) = cli::parse_for_test(&[]);
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "x").unwrap();
+        let mut stats = ScanStats::new();
+        stats.files = 1;
+        let files = Roots::single(file.clone());
+        assert_eq!(scan_line(&stats, &files, &options), "Catalogued 1 files given as paths; no directories were walked.");
+        // A folder is walked even if no directory ended up counted.
+        let folder = Roots::new(vec![dir.path().to_path_buf(), file]).unwrap();
+        assert_eq!(scan_line(&stats, &folder, &options), "Scanned 1 files in 0 directories (0 skipped, 0 symlinks).");
+    }
 }
@@ -0,0 +1,419 @@
+//! `--archives zip,tar`: catalog the entries inside archives as well as the
+//! archives themselves.
+//!
+//! For a zip only the central directory is read; nothing is decompressed. A tar
+//! is read from start to end, skipping over each file's data, and one compressed
+//! with gzip, xz or zstd decompressed on the way. Entries are listed one level deep: an archive inside
+//! an archive is catalogued as an entry but not opened.
+
+use crate::config::ArchiveFormat;
+use flate2::read::MultiGzDecoder;
+use liblzma::read::XzDecoder;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Separates an archive's path from an entry's path inside it.
+pub const SEPARATOR: &str = "!/";
+
+/// Names of the files `--archives tar` opens, by their lowercase endings.
+const TAR_SUFFIXES: &[&str] = &[".tar", ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.zst", ".tzst"];
+
+/// Tar archives are read in blocks of this many bytes.
+const BLOCK: usize = 512;
+
+/// A file stored in an archive.
+pub struct Member {
+    /// Path inside the archive, with `/` separators.
+    pub name: String,
+    /// Uncompressed size in bytes.
+    pub size: u64,
+}
+
+/// How the entries of an archive are listed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Zip,
+    Tar,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Zip => "zip",
+            Kind::Tar => "tar",
+        }
+    }
+}
+
+/// Which kind of archive the file at `path` is, by its name, if it is one of
+/// the `formats` to open.
+pub fn kind(path: &Path, formats: &[ArchiveFormat]) -> Option<Kind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if formats.contains(&ArchiveFormat::Zip) && name.ends_with(".zip") && name.len() > ".zip".len() {
+        return Some(Kind::Zip);
+    }
+    let tar = TAR_SUFFIXES.iter().any(|suffix| name.ends_with(suffix) && name.len() > suffix.len());
+    (formats.contains(&ArchiveFormat::Tar) && tar).then_some(Kind::Tar)
+}
+
+/// Add the files (not directories or links) in the archive at `path` to
+/// `found`. On an error, what was read before it is kept: a truncated tar still
+/// lists the files before the cut. An encrypted zip is an error rather than a
+/// partial listing.
+pub fn members(path: &Path, kind: Kind, found: &mut Vec<Member>) -> Result<(), String> {
+    match kind {
+        Kind::Zip => zip_members(path, found),
+        Kind::Tar => tar_members(path, found),
+    }
+}
+
+fn zip_members(path: &Path, found: &mut Vec<Member>) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    found.reserve(archive.len());
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index).map_err(|e| e.to_string())?;
+        if entry.encrypted() {
+            found.clear();
+            return Err("archive is encrypted".to_string());
+        }
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().map_err(|e| e.to_string())?.into_owned();
+        found.push(Member { name, size: entry.size() });
+    }
+    Ok(())
+}
+
+/// Compressed tars are recognised by their first bytes rather than their names,
+/// so a `.tar.gz` that was never gzipped still opens.
+fn tar_members(path: &Path, found: &mut Vec<Member>) -> Result<(), String> {
+    let mut file = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+    let mut magic = [0; 6];
+    let len = read_full(&mut file, &mut magic).map_err(|e| e.to_string())?;
+    let file = io::Cursor::new(magic[..len].to_vec()).chain(file);
+    let read = match magic {
+        [0x1f, 0x8b, ..] => read_tar(MultiGzDecoder::new(file), found),
+        [0xfd, b'7', b'z', b'X', b'Z', 0] => read_tar(XzDecoder::new_multi_decoder(file), found),
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => zstd::Decoder::new(file).and_then(|decoder| read_tar(decoder, found)),
+        _ => read_tar(file, found),
+    };
+    read.map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => "archive is truncated".to_string(),
+        _ => e.to_string(),
+    })
+}
+
+/// List the files in a tar stream: POSIX ustar and pax, and the GNU format's
+/// long names. The data of each entry is read past, not kept.
+fn read_tar(mut reader: impl Read, found: &mut Vec<Member>) -> io::Result<()> {
+    let mut block = [0u8; BLOCK];
+    // Names (and sizes) that a GNU long-name or pax header sets for the entry
+    // after it.
+    let mut long_name: Option<String> = None;
+    let mut pax_name: Option<String> = None;
+    let mut pax_size: Option<u64> = None;
+    let mut first = true;
+    loop {
+        let read = reader.read_exact(&mut block);
+        if first && (read.is_err() || !checksum_matches(&block)) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a tar archive"));
+        }
+        first = false;
+        read?;
+        // The archive ends with blocks of zeros.
+        if block.iter().all(|&b| b == 0) {
+            return Ok(());
+        }
+        if !checksum_matches(&block) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "corrupt tar header"));
+        }
+        let stored = number(&block[124..136])?;
+        let size = pax_size.take().unwrap_or(stored);
+        match block[156] {
+            // A regular file, an old-style one, or a contiguous one.
+            b'0' | 0 | b'7' => {
+                let name = pax_name.take().or(long_name.take()).unwrap_or_else(|| header_name(&block));
+                let name = clean(&name);
+                if !name.is_empty() && !name.ends_with('/') {
+                    found.push(Member { name: name.to_string(), size });
+                }
+            }
+            // A GNU sparse file, whose full size is kept further on in the header.
+            b'S' => {
+                let name = pax_name.take().or(long_name.take()).unwrap_or_else(|| header_name(&block));
+                let name = clean(&name).to_string();
+                found.push(Member { name, size: number(&block[483..495]).unwrap_or(size) });
+            }
+            b'L' => {
+                long_name = Some(text(&read_data(&mut reader, stored)?));
+                continue;
+            }
+            b'x' => {
+                let (name, size) = pax_fields(&read_data(&mut reader, stored)?);
+                pax_name = name;
+                pax_size = size;
+                continue;
+            }
+            // Headers that only concern metadata: GNU long link names, pax globals.
+            b'K' | b'g' => {}
+            // Directories, links and devices.
+            _ => {
+                long_name = None;
+                pax_name = None;
+            }
+        }
+        skip(&mut reader, padded(size))?;
+    }
+}
+
+/// Whether a header block's checksum fits: the sum of its bytes, counting the
+/// checksum field itself as spaces.
+fn checksum_matches(block: &[u8; BLOCK]) -> bool {
+    let Ok(expected) = number(&block[148..156]) else {
+        return false;
+    };
+    let sum: u64 = block
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { u64::from(b' ') } else { u64::from(b) })
+        .sum();
+    sum == expected
+}
+
+/// A numeric header field: octal text, or binary with the top bit set, as GNU
+/// tar writes sizes too big for octal.
+fn number(field: &[u8]) -> io::Result<u64> {
+    if field.first().is_some_and(|&b| b & 0x80 != 0) {
+        let value = field[1..].iter().fold(u64::from(field[0] & 0x7f), |n, &b| (n << 8) | u64::from(b));
+        return Ok(value);
+    }
+    let digits = text(field);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "corrupt tar header"))
+}
+
+/// A ustar header's name, with its prefix field in front when there is one.
+fn header_name(block: &[u8; BLOCK]) -> String {
+    let name = text(&block[0..100]);
+    let prefix = if &block[257..262] == b"ustar" { text(&block[345..500]) } else { String::new() };
+    if prefix.is_empty() { name } else { format!("{prefix}/{name}") }
+}
+
+/// The `path` and `size` records of a pax extended header.
+fn pax_fields(data: &[u8]) -> (Option<String>, Option<u64>) {
+    let (mut name, mut size) = (None, None);
+    // Each record is "<length> <key>=<value>\n".
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|&b| b == b' ') {
+        let Some(len) = std::str::from_utf8(&rest[..space]).ok().and_then(|n| n.parse::<usize>().ok()) else {
+            break;
+        };
+        if len <= space || len > rest.len() {
+            break;
+        }
+        let record = String::from_utf8_lossy(&rest[space + 1..len]);
+        if let Some((key, value)) = record.trim_end_matches('\n').split_once('=') {
+            match key {
+                "path" => name = Some(value.to_string()),
+                "size" => size = value.parse().ok(),
+                _ => {}
+            }
+        }
+        rest = &rest[len..];
+    }
+    (name, size)
+}
+
+/// A NUL-padded header field as text.
+fn text(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// `name` without the leading `./` or `/` that tar keeps from how it was made.
+fn clean(name: &str) -> &str {
+    let mut name = name;
+    loop {
+        let rest = name.trim_start_matches('/');
+        let rest = rest.strip_prefix("./").unwrap_or(rest);
+        if rest == name {
+            return name;
+        }
+        name = rest;
+    }
+}
+
+/// Read an entry's `size` bytes of data, and the padding after them.
+fn read_data(reader: &mut impl Read, size: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.by_ref().take(size).read_to_end(&mut data)?;
+    if (data.len() as u64) < size {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    skip(reader, padded(size) - size)?;
+    Ok(data)
+}
+
+/// Read past `len` bytes.
+fn skip(reader: &mut impl Read, len: u64) -> io::Result<()> {
+    if io::copy(&mut reader.by_ref().take(len), &mut io::sink())? < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// `size` rounded up to whole blocks.
+fn padded(size: u64) -> u64 {
+    size.div_ceil(BLOCK as u64) * BLOCK as u64
+}
+
+/// Fill as much of `buf` as the reader has, returning how much that was.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+/// The catalogue path of `member` inside the archive at `archive` (relative to
+/// the root), e.g. `photos.zip!/2024/beach.jpg`.
+pub fn member_path(archive: &Path, member: &str) -> PathBuf {
+    PathBuf::from(format!("{}{SEPARATOR}{member}", archive.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use crate::scan::{self, Roots};
+    use std::fs;
+    use std::io::Write;
+
+    /// A ustar archive of regular files, each holding `size` bytes of `x`.
+    fn tar(files: &[(&str, usize)]) -> Vec<u8> {
+        let mut tar = Vec::new();
+        for &(name, size) in files {
+            let mut header = [0u8; BLOCK];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[100..107].copy_from_slice(b"0000644");
+            header[124..135].copy_from_slice(format!("{size:011o}").as_bytes());
+            header[156] = b'0';
+            header[257..263].copy_from_slice(b"ustar\0");
+            header[263..265].copy_from_slice(b"00");
+            header[148..156].fill(b' ');
+            let sum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+            header[148..155].copy_from_slice(format!("{sum:06o}\0").as_bytes());
+            tar.extend_from_slice(&header);
+            tar.extend(std::iter::repeat_n(b'x', size));
+            tar.resize(tar.len() + padded(size as u64) as usize - size, 0);
+        }
+        tar.resize(tar.len() + 2 * BLOCK, 0);
+        tar
+    }
+
+    const FILES: &[(&str, usize)] = &[
+        ("./inner/path.txt", 10),
+        ("inner/big.bin", 64 * 1024),
+        ("inner/deeper/last.rs", 3),
+    ];
+
+    fn xz(data: &[u8]) -> Vec<u8> {
+        let mut encoder = liblzma::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zst(data: &[u8]) -> Vec<u8> {
+        zstd::encode_all(data, 3).unwrap()
+    }
+
+    fn listed(path: &Path) -> (Vec<(String, u64)>, Result<(), String>) {
+        let mut found = Vec::new();
+        let result = members(path, Kind::Tar, &mut found);
+        (found.into_iter().map(|m| (m.name, m.size)).collect(), result)
+    }
+
+    #[test]
+    fn xz_and_zstd_tars_are_decompressed_and_listed() {
+        let dir = tempfile::tempdir().unwrap();
+        let expected = [
+            ("inner/path.txt".to_string(), 10),
+            ("inner/big.bin".to_string(), 64 * 1024),
+            ("inner/deeper/last.rs".to_string(), 3),
+        ];
+        for (name, compress) in [("x.tar.xz", xz as fn(&[u8]) -> Vec<u8>), ("x.tar.zst", zst)] {
+            let path = dir.path().join(name);
+            fs::write(&path, compress(&tar(FILES))).unwrap();
+            assert_eq!(listed(&path), (expected.to_vec(), Ok(())), "{name}");
+        }
+    }
+
+    #[test]
+    fn members_are_catalogued_under_the_archive_path() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("x.tar.zst"), zst(&tar(FILES))).unwrap();
+        fs::write(dir.path().join("x.tar.xz"), xz(&tar(FILES))).unwrap();
+        let (options, filter) = cli::parse_for_test(&["--archives", "tar"]);
+        let mut found = Vec::new();
+        scan::walk(&Roots::single(dir.path().to_path_buf()), &options, &filter, |_, entry| {
+            found.push(entry.path.to_string_lossy().replace('\\', "/"));
+        });
+        found.sort();
+        let expected = [
+            "x.tar.xz",
+            "x.tar.xz!/inner/big.bin",
+            "x.tar.xz!/inner/deeper/last.rs",
+            "x.tar.xz!/inner/path.txt",
+            "x.tar.zst",
+            "x.tar.zst!/inner/big.bin",
+            "x.tar.zst!/inner/deeper/last.rs",
+            "x.tar.zst!/inner/path.txt",
+        ];
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn a_truncated_archive_keeps_the_members_before_the_cut_and_the_scan_goes_on() {
+        let dir = tempfile::tempdir().unwrap();
+        // Cut inside the big file's data: its header is before the cut, the last
+        // member's after it.
+        let plain = tar(FILES);
+        let cut = &plain[..plain.len() / 2];
+        fs::write(dir.path().join("cut.tar.xz"), xz(cut)).unwrap();
+        fs::write(dir.path().join("cut.tar.zst"), zst(cut)).unwrap();
+        // And one cut in the compressed stream instead.
+        let mut compressed = zst(&plain);
+        compressed.truncate(compressed.len() / 2);
+        fs::write(dir.path().join("short.tar.zst"), compressed).unwrap();
+        fs::write(dir.path().join("after.txt"), "x").unwrap();
+
+        for name in ["cut.tar.xz", "cut.tar.zst"] {
+            let (found, result) = listed(&dir.path().join(name));
+            let before = [("inner/path.txt".to_string(), 10), ("inner/big.bin".to_string(), 64 * 1024)];
+            assert_eq!(found, before, "{name}");
+            assert_eq!(result, Err("archive is truncated".to_string()), "{name}");
+        }
+        assert!(listed(&dir.path().join("short.tar.zst")).1.is_err());
+
+        let (options, filter) = cli::parse_for_test(&["--archives", "tar", "--quiet"]);
+        let mut found = Vec::new();
+        let stats = scan::walk(&Roots::single(dir.path().to_path_buf()), &options, &filter, |_, entry| {
+            found.push(entry.path.to_string_lossy().replace('\\', "/"));
+        });
+        assert_eq!(stats.errors.len(), 3);
+        assert!(found.contains(&"after.txt".to_string()));
+        assert!(found.contains(&"cut.tar.zst!/inner/path.txt".to_string()));
+    }
+}
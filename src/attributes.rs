@@ -0,0 +1,71 @@
+//! `--attributes`, `--only-attr` and `--skip-attr`: Windows file attribute
+//! flags (hidden, system, archive, reparse point). Other platforms have no such
+//! flags, so there the options are ignored with a warning.
+
+use crate::config::FileAttribute;
+use std::path::Path;
+
+/// Attribute letters in column order, as shown by [`label`].
+const LETTERS: [(FileAttribute, char); 4] = [
+    (FileAttribute::Hidden, 'H'),
+    (FileAttribute::System, 'S'),
+    (FileAttribute::Archive, 'A'),
+    (FileAttribute::Reparse, 'R'),
+];
+
+/// The raw attribute bits of the entry at `path` (not following links).
+#[cfg(windows)]
+pub fn read(path: &Path) -> Option<u32> {
+    use std::os::windows::fs::MetadataExt;
+    std::fs::symlink_metadata(path).ok().map(|m| m.file_attributes())
+}
+
+#[cfg(not(windows))]
+pub fn read(_path: &Path) -> Option<u32> {
+    None
+}
+
+/// Whether `bits` has `attribute` set.
+pub fn has(bits: u32, attribute: FileAttribute) -> bool {
+    bits & attribute.bit() != 0
+}
+
+/// A compact column such as `H-A-`: each flag's letter if set, `-` if not.
+pub fn label(bits: u32) -> String {
+    LETTERS
+        .iter()
+        .map(|&(attribute, letter)| if has(bits, attribute) { letter } else { '-' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_shows_a_letter_for_each_flag_set() {
+        assert_eq!(label(0), "----");
+        assert_eq!(label(FileAttribute::Hidden.bit() | FileAttribute::Archive.bit()), "H-A-");
+        assert_eq!(label(FileAttribute::System.bit() | FileAttribute::Reparse.bit() | 0x1), "-S-R");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn hidden_and_system_attributes_are_read_from_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("desktop.ini");
+        std::fs::write(&path, "x").unwrap();
+        assert!(!has(read(&path).unwrap(), FileAttribute::Hidden));
+        let status = std::process::Command::new("attrib").arg("+h").arg("+s").arg(&path).status().unwrap();
+        assert!(status.success());
+        let bits = read(&path).unwrap();
+        assert!(has(bits, FileAttribute::Hidden) && has(bits, FileAttribute::System));
+        assert_eq!(&label(bits)[..2], "HS");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn other_platforms_have_no_attributes() {
+        assert_eq!(read(Path::new(".")), None);
+    }
+}
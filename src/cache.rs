@@ -0,0 +1,193 @@
+//! `--cache FILE`: remember every directory's listing between runs, so a rescan
+//! only lists the directories whose modification time has changed since.
+//!
+//! A directory's modification time changes when entries are added, removed or
+//! renamed in it; edits to the files themselves don't touch it, so only names and
+//! kinds are cached, and sizes and times are still read from the files. The file
+//! is plain text: a version line, then for each directory a `D` line (its time
+//! and path) followed by one line per entry. Tabs, newlines and backslashes in
+//! names are escaped.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// First line of a cache file; the number after it is bumped whenever the
+/// format changes, so older caches are rebuilt rather than misread.
+const HEADER: &str = "CreepDir cache";
+const VERSION: u32 = 1;
+
+/// What a cached entry is, as the directory listing reported it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+impl Kind {
+    fn letter(self) -> char {
+        match self {
+            Kind::File => 'f',
+            Kind::Dir => 'd',
+            Kind::Symlink => 'l',
+            Kind::Other => 'o',
+        }
+    }
+
+    fn from_letter(letter: &str) -> Option<Self> {
+        match letter {
+            "f" => Some(Kind::File),
+            "d" => Some(Kind::Dir),
+            "l" => Some(Kind::Symlink),
+            "o" => Some(Kind::Other),
+            _ => None,
+        }
+    }
+}
+
+/// One directory's entries, as of its modification time.
+#[derive(Clone)]
+pub struct Listing {
+    pub modified: SystemTime,
+    /// Entry names (not paths) in the order they were listed.
+    pub entries: Vec<(String, Kind)>,
+}
+
+/// Listings keyed by absolute directory path.
+#[derive(Default)]
+pub struct Cache {
+    dirs: HashMap<PathBuf, Listing>,
+}
+
+impl Cache {
+    /// Read the cache at `path`. A missing file is an empty cache; one that can't
+    /// be read or parsed, or was written by another version, is an error.
+    pub fn load(path: &Path) -> Result<Cache, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Cache::default()),
+            Err(e) => return Err(e.to_string()),
+        };
+        let mut lines = text.lines().enumerate();
+        match lines.next().and_then(|(_, line)| line.strip_prefix(HEADER)) {
+            Some(version) if version.trim().parse() == Ok(VERSION) => {}
+            Some(_) => return Err("written by an incompatible version".to_string()),
+            None => return Err("not a CreepDir cache".to_string()),
+        }
+        let mut cache = Cache::default();
+        let mut current: Option<(PathBuf, Listing)> = None;
+        for (index, line) in lines {
+            let malformed = || format!("line {} is malformed", index + 1);
+            let mut fields = line.split('\t');
+            let (Some(tag), Some(first)) = (fields.next(), fields.next()) else {
+                return Err(malformed());
+            };
+            if tag == "D" {
+                let (Some(nanos), Some(dir), None) = (fields.next(), fields.next(), fields.next()) else {
+                    return Err(malformed());
+                };
+                let secs = first.parse().map_err(|_| malformed())?;
+                let nanos = nanos.parse().map_err(|_| malformed())?;
+                let modified = UNIX_EPOCH + Duration::new(secs, nanos);
+                let dir = PathBuf::from(unescape(dir).ok_or_else(malformed)?);
+                if let Some((dir, listing)) = current.replace((dir, Listing { modified, entries: Vec::new() })) {
+                    cache.dirs.insert(dir, listing);
+                }
+                continue;
+            }
+            let (Some(kind), None, Some((_, listing))) = (Kind::from_letter(tag), fields.next(), &mut current)
+            else {
+                return Err(malformed());
+            };
+            listing.entries.push((unescape(first).ok_or_else(malformed)?, kind));
+        }
+        if let Some((dir, listing)) = current {
+            cache.dirs.insert(dir, listing);
+        }
+        Ok(cache)
+    }
+
+    pub fn get(&self, dir: &Path) -> Option<&Listing> {
+        self.dirs.get(&absolute(dir))
+    }
+
+    /// The listing of `dir`, if it was cached at exactly `modified`.
+    pub fn fresh(&self, dir: &Path, modified: SystemTime) -> Option<&Listing> {
+        self.get(dir).filter(|listing| listing.modified == modified)
+    }
+
+    pub fn insert(&mut self, dir: &Path, listing: Listing) {
+        self.dirs.insert(absolute(dir), listing);
+    }
+
+    /// Write the cache to `path`, replacing the old file only once the new one
+    /// is complete.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".tmp");
+        let partial = PathBuf::from(partial);
+        let mut w = BufWriter::new(File::create(&partial)?);
+        writeln!(w, "{HEADER} {VERSION}")?;
+        let mut dirs: Vec<_> = self.dirs.iter().collect();
+        dirs.sort_by(|a, b| a.0.cmp(b.0));
+        for (dir, listing) in dirs {
+            // Listings from before 1970 or with unprintable paths aren't worth a
+            // format of their own; they are simply listed again next time.
+            let (Ok(since), Some(dir)) = (listing.modified.duration_since(UNIX_EPOCH), dir.to_str()) else {
+                continue;
+            };
+            writeln!(w, "D\t{}\t{}\t{}", since.as_secs(), since.subsec_nanos(), escape(dir))?;
+            for (name, kind) in &listing.entries {
+                writeln!(w, "{}\t{}", kind.letter(), escape(name))?;
+            }
+        }
+        w.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+        fs::rename(&partial, path)
+    }
+}
+
+/// `dir` made absolute against the working directory, so the same folder hits
+/// the cache however it was written on the command line.
+fn absolute(dir: &Path) -> PathBuf {
+    std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// `s` with backslashes, tabs and line breaks escaped, so it fits in one field
+/// of a tab-separated line.
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The inverse of [`escape`], or `None` for an unknown escape.
+pub fn unescape(s: &str) -> Option<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            '\\' => '\\',
+            't' => '\t',
+            'n' => '\n',
+            'r' => '\r',
+            _ => return None,
+        });
+    }
+    Some(out)
+}
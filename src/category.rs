@@ -0,0 +1,89 @@
+//! Built-in presets: `--category` extension lists that feed the `--ext` filter,
+//! and the `--no-junk` list of directories that are rarely worth cataloguing.
+
+use clap::ValueEnum;
+
+/// A named group of related extensions.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Category {
+    Images,
+    Video,
+    Audio,
+    Documents,
+    Code,
+    Archives,
+}
+
+impl Category {
+    /// The extensions (without dots) the category expands to.
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Category::Images => &[
+                "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "heif", "avif",
+                "svg", "ico", "raw", "cr2", "cr3", "nef", "arw", "dng", "orf", "rw2", "psd",
+            ],
+            Category::Video => &[
+                "mp4", "m4v", "mkv", "mov", "avi", "wmv", "flv", "webm", "mpg", "mpeg", "3gp",
+                "ts", "mts", "m2ts", "vob", "ogv",
+            ],
+            Category::Audio => &[
+                "mp3", "wav", "flac", "aac", "m4a", "ogg", "oga", "opus", "wma", "aiff", "aif",
+                "alac", "mid", "midi",
+            ],
+            Category::Documents => &[
+                "pdf", "doc", "docx", "odt", "rtf", "txt", "md", "xls", "xlsx", "ods", "csv",
+                "ppt", "pptx", "odp", "epub", "pages", "numbers", "key", "tex",
+            ],
+            Category::Code => &[
+                "rs", "c", "h", "cc", "cpp", "cxx", "hpp", "cs", "go", "java", "kt", "kts",
+                "scala", "swift", "m", "py", "rb", "php", "pl", "lua", "js", "mjs", "cjs", "ts",
+                "tsx", "jsx", "html", "css", "scss", "sh", "bash", "zsh", "ps1", "sql", "r",
+                "dart", "zig", "hs", "ex", "exs", "erl", "clj", "toml", "yaml", "yml", "json",
+                "xml",
+            ],
+            Category::Archives => &[
+                "zip", "tar", "gz", "tgz", "bz2", "tbz2", "xz", "txz", "zst", "7z", "rar", "lz",
+                "lzma", "cab", "iso", "dmg", "tar.gz", "tar.bz2", "tar.xz", "tar.zst",
+            ],
+        }
+    }
+
+    /// The name used on the command line.
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Print every category and its extensions, for `--list-categories`.
+pub fn print_list() {
+    for &category in Category::value_variants() {
+        println!("{}: {}", category.name(), category.extensions().join(", "));
+    }
+}
+
+/// Directories pruned by `--no-junk`: dependency caches, build output, VCS and
+/// IDE metadata, and OS bookkeeping folders.
+pub const JUNK_DIRS: &[&str] = &[
+    "node_modules",
+    ".git",
+    ".svn",
+    ".hg",
+    "target",
+    "__pycache__",
+    ".venv",
+    "build",
+    "dist",
+    ".idea",
+    ".vs",
+    "$RECYCLE.BIN",
+    "System Volume Information",
+];
+
+/// Print the `--no-junk` directory names, for `--list-junk`.
+pub fn print_junk() {
+    for name in JUNK_DIRS {
+        println!("{name}");
+    }
+}
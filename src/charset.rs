@@ -0,0 +1,215 @@
+//! `--detect-encoding`: classify each file's text encoding from a sample of its
+//! first bytes, to find everything that isn't UTF-8.
+
+use crate::config::ScanOptions;
+use crate::hash;
+use crate::scan::{Catalog, FileEntry, ScanStats, is_regular_group};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Default `--encoding-sample`: enough to see past a long ASCII header.
+pub const DEFAULT_SAMPLE: u64 = 64 * 1024;
+
+/// A file's text encoding, as far as its sample shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TextEncoding {
+    /// Valid UTF-8 without a byte-order mark (including plain ASCII and empty files).
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// Not valid UTF-8 and no NUL bytes: Latin-1, Windows-1252 or another 8-bit code page.
+    EightBit,
+    /// NUL bytes without a UTF-16 pattern: not text at all.
+    Binary,
+}
+
+impl TextEncoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "utf-8",
+            TextEncoding::Utf8Bom => "utf-8-bom",
+            TextEncoding::Utf16Le => "utf-16le",
+            TextEncoding::Utf16Be => "utf-16be",
+            TextEncoding::EightBit => "8-bit",
+            TextEncoding::Binary => "binary",
+        }
+    }
+
+    /// Text that isn't UTF-8, for the `--detect-encoding` list.
+    pub fn is_legacy(self) -> bool {
+        matches!(self, TextEncoding::Utf16Le | TextEncoding::Utf16Be | TextEncoding::EightBit)
+    }
+}
+
+/// Classify the first `sample` bytes of the file at `path`.
+pub fn detect_file(path: &Path, sample: u64) -> io::Result<TextEncoding> {
+    let mut start = Vec::new();
+    let len = File::open(path)?.take(sample).read_to_end(&mut start)?;
+    // A sample shorter than asked for is the whole file.
+    Ok(detect(&start, (len as u64) < sample))
+}
+
+/// Classify `start`, the beginning of a file (`complete` if it is the whole file).
+pub fn detect(start: &[u8], complete: bool) -> TextEncoding {
+    if start.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return TextEncoding::Utf8Bom;
+    }
+    if start.starts_with(&[0xFF, 0xFE]) {
+        return TextEncoding::Utf16Le;
+    }
+    if start.starts_with(&[0xFE, 0xFF]) {
+        return TextEncoding::Utf16Be;
+    }
+    if start.contains(&0) {
+        return utf16_without_bom(start).unwrap_or(TextEncoding::Binary);
+    }
+    match std::str::from_utf8(start) {
+        Ok(_) => TextEncoding::Utf8,
+        // A multi-byte character cut off by the end of the sample is still UTF-8.
+        Err(e) if e.error_len().is_none() && !complete => TextEncoding::Utf8,
+        Err(_) => TextEncoding::EightBit,
+    }
+}
+
+/// Mostly-ASCII UTF-16 puts a NUL in every other byte: the odd ones for
+/// little-endian, the even ones for big-endian.
+fn utf16_without_bom(start: &[u8]) -> Option<TextEncoding> {
+    let pairs = start.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+    let zero_at = |offset: usize| start.iter().skip(offset).step_by(2).filter(|&&b| b == 0).count();
+    let (even, odd) = (zero_at(0), zero_at(1));
+    let mostly = |zeros: usize| zeros * 10 >= pairs * 7;
+    if even == 0 && mostly(odd) {
+        Some(TextEncoding::Utf16Le)
+    } else if odd == 0 && mostly(even) {
+        Some(TextEncoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Detect the encoding of every regular file in the catalog, in parallel,
+/// storing it on the entries. Unreadable files keep `encoding: None`, with a
+/// warning unless `--quiet`.
+pub fn detect_catalog(catalog: &mut Catalog, root: &Path, options: &ScanOptions) {
+    let detect_all = |catalog: &mut Catalog| {
+        catalog
+            .par_iter_mut()
+            .filter(|(group, _)| is_regular_group(group))
+            .flat_map(|(_, entries)| entries.par_iter_mut())
+            .filter(|entry| !entry.archived)
+            .for_each(|entry| detect_entry(entry, root, options));
+    };
+    match hash::thread_pool(options) {
+        Some(pool) => pool.install(|| detect_all(catalog)),
+        None => detect_all(catalog),
+    }
+}
+
+/// Detect the encoding of one entry, warning if it can't be read.
+pub fn detect_entry(entry: &mut FileEntry, root: &Path, options: &ScanOptions) {
+    match detect_file(&root.join(&entry.path), options.encoding_sample) {
+        Ok(encoding) => entry.encoding = Some(encoding),
+        Err(e) => {
+            if !options.quiet {
+                eprintln!("Warning: cannot detect the encoding of '{}': {e}", entry.path.display());
+            }
+        }
+    }
+}
+
+/// Per-encoding counts and the non-UTF-8 files, gathered one entry at a time so
+/// streamed output can keep a summary too.
+#[derive(Default)]
+pub struct Tally {
+    counts: HashMap<TextEncoding, u64>,
+    legacy: Vec<(PathBuf, TextEncoding)>,
+}
+
+impl Tally {
+    pub fn add(&mut self, entry: &FileEntry) {
+        if let Some(encoding) = entry.encoding {
+            *self.counts.entry(encoding).or_default() += 1;
+            if encoding.is_legacy() {
+                self.legacy.push((entry.path.clone(), encoding));
+            }
+        }
+    }
+
+    /// Store the counts (most common first) and the sorted non-UTF-8 list in `stats`.
+    pub fn finish(self, stats: &mut ScanStats) {
+        let mut counts: Vec<(TextEncoding, u64)> = self.counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let mut legacy = self.legacy;
+        legacy.sort();
+        stats.encodings = counts;
+        stats.non_utf8 = legacy;
+    }
+}
+
+/// Tally the encodings recorded across the whole catalog into `stats`.
+pub fn summarize(catalog: &Catalog, stats: &mut ScanStats) {
+    let mut tally = Tally::default();
+    catalog.values().flatten().for_each(|entry| tally.add(entry));
+    tally.finish(stats);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        let unit = |u: u16| if big_endian { u.to_be_bytes() } else { u.to_le_bytes() };
+        text.encode_utf16().flat_map(unit).collect()
+    }
+
+    #[test]
+    fn utf8_with_and_without_a_bom() {
+        assert_eq!(detect(b"", true), TextEncoding::Utf8);
+        assert_eq!(detect("plain ascii\n".as_bytes(), true), TextEncoding::Utf8);
+        assert_eq!(detect("naïve café — ☕\n".as_bytes(), true), TextEncoding::Utf8);
+        assert_eq!(detect(b"\xef\xbb\xbfwith a mark", true), TextEncoding::Utf8Bom);
+    }
+
+    #[test]
+    fn a_character_cut_off_by_the_sample_is_still_utf8() {
+        let cut = &"café".as_bytes()[..4];
+        assert_eq!(detect(cut, false), TextEncoding::Utf8);
+        assert_eq!(detect(cut, true), TextEncoding::EightBit);
+    }
+
+    #[test]
+    fn utf16_with_a_bom() {
+        let mut le = vec![0xff, 0xfe];
+        le.extend(utf16("héllo", false));
+        let mut be = vec![0xfe, 0xff];
+        be.extend(utf16("héllo", true));
+        assert_eq!(detect(&le, true), TextEncoding::Utf16Le);
+        assert_eq!(detect(&be, true), TextEncoding::Utf16Be);
+    }
+
+    #[test]
+    fn utf16_without_a_bom_by_its_nul_bytes() {
+        assert_eq!(detect(&utf16("mostly ascii text", false), true), TextEncoding::Utf16Le);
+        assert_eq!(detect(&utf16("mostly ascii text", true), true), TextEncoding::Utf16Be);
+    }
+
+    #[test]
+    fn latin1_is_eight_bit() {
+        assert_eq!(detect(b"caf\xe9 cr\xe8me", true), TextEncoding::EightBit);
+    }
+
+    #[test]
+    fn nul_bytes_without_a_utf16_pattern_are_binary() {
+        let elf = b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x03\x00";
+        let png = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0, 0, 0, 0x0d];
+        assert_eq!(detect(elf, true), TextEncoding::Binary);
+        assert_eq!(detect(&png, true), TextEncoding::Binary);
+    }
+}
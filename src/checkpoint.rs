@@ -0,0 +1,920 @@
+//! `--checkpoint FILE`: save a long scan's progress as it goes, so a run that
+//! dies (or is stopped with Ctrl-C) can be resumed rather than started over.
+//!
+//! The walk visits entries in a fixed order (by path), so once it has finished
+//! with an entry, every directory before it is done, apart from the ones holding
+//! it. The file is a journal: each catalogued entry, digest, error and hardlink is
+//! appended as it is found, and every [`INTERVAL`] (or [`DIRS`] directories) a
+//! mark records the last entry finished and the tallies so far. A resumed run
+//! reads back everything up to the last mark, skips the directories done by then
+//! and carries on after that entry, so nothing is listed, read or hashed twice.
+//!
+//! The first lines name the format version and the scan: its folders and the
+//! options that decide what is found and recorded. A checkpoint left by another
+//! scan is started over. Once the catalog has been written the file is deleted.
+
+use crate::cache::{escape, unescape};
+use crate::cli;
+use crate::gitstatus::{GitCounts, GitStatus};
+use crate::owner::Owner;
+use crate::scan::{FileEntry, LargeFile, Mismatch, ScanError, ScanStats};
+use crate::xattrs::Xattr;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// First line of a checkpoint; the number after it is bumped whenever the
+/// format changes, so an older checkpoint is started over rather than misread.
+const HEADER: &str = "CreepDir checkpoint";
+const VERSION: u32 = 2;
+
+/// Longest time between marks.
+const INTERVAL: Duration = Duration::from_secs(30);
+
+/// Most directories walked between marks.
+const DIRS: u64 = 1000;
+
+/// The journal of one scan, shared by the walk and the hashing threads' results.
+pub struct Checkpoint {
+    path: PathBuf,
+    journal: Mutex<Journal>,
+    quiet: bool,
+}
+
+struct Journal {
+    out: BufWriter<File>,
+    /// When the last mark was written, and how many directories had been walked.
+    marked: Instant,
+    marked_dirs: u64,
+    /// Errors and type mismatches of the scan already in the journal.
+    errors: usize,
+    mismatched: usize,
+    /// Set once a write fails: the journal stops there, so what it holds up to
+    /// its last mark is still whole.
+    failed: bool,
+}
+
+/// Where a resumed walk picks up, and what the interrupted run had gathered.
+pub struct Resume {
+    /// Index of the folder the walk was in (the number of folders once the walk
+    /// had finished).
+    pub folder: usize,
+    /// The last entry finished in that folder, relative to it.
+    pub position: PathBuf,
+    /// Whether that entry is a directory, which the walk takes up after the
+    /// names sorting before its own followed by a `/`.
+    pub dir: bool,
+    /// The catalogued entries with their groups, in the order found.
+    pub entries: Vec<(String, FileEntry)>,
+    pub stats: ScanStats,
+    pub tallies: Tallies,
+    /// Paths recorded for multiply-linked files, in order: the file's identity,
+    /// its relative path and its size.
+    pub hardlinks: Vec<((u64, u64), PathBuf, u64)>,
+}
+
+/// The walk's running totals other than [`ScanStats`], as saved at each mark.
+#[derive(Default)]
+pub struct Tallies {
+    pub largest: Vec<LargeFile>,
+    /// Lowercase extension -> literal spelling -> files.
+    pub casings: HashMap<String, HashMap<String, u64>>,
+    /// User -> (files, bytes).
+    pub owned: HashMap<String, (u64, u64)>,
+    pub xattrs: HashMap<String, u64>,
+    pub git: GitCounts,
+    /// The walker threads' counts: ignored entries, pruned and junk directories,
+    /// and symlink loops.
+    pub counters: [u64; 4],
+    pub mount_points: Vec<PathBuf>,
+    pub virtual_mounts: Vec<PathBuf>,
+}
+
+/// What a mark records, borrowed from the walk.
+pub struct Mark<'a> {
+    pub folder: usize,
+    pub position: &'a Path,
+    pub dir: bool,
+    pub stats: &'a ScanStats,
+    pub largest: &'a BinaryHeap<Reverse<LargeFile>>,
+    pub casings: &'a HashMap<String, HashMap<String, u64>>,
+    pub owned: &'a HashMap<String, (u64, u64)>,
+    pub xattrs: &'a HashMap<String, u64>,
+    pub git: &'a GitCounts,
+    pub counters: [u64; 4],
+    pub mount_points: &'a [PathBuf],
+    pub virtual_mounts: &'a [PathBuf],
+}
+
+impl Checkpoint {
+    /// Open the checkpoint at `path` for the scan described by `key`, reading
+    /// back how far an earlier run of the same scan got. A missing file starts a
+    /// new checkpoint, and so (with a warning unless `quiet`) does one left by
+    /// another scan or version; a file that isn't a checkpoint at all is an error
+    /// rather than overwritten.
+    pub fn open(path: &Path, key: &str, quiet: bool) -> Result<(Checkpoint, Option<Resume>), String> {
+        let (resume, kept) = match fs::read(path) {
+            Ok(bytes) if bytes.is_empty() => (None, 0),
+            Ok(bytes) => match read(&String::from_utf8_lossy(&bytes), key)? {
+                Ok(found) => found,
+                Err(reason) => {
+                    if !quiet {
+                        eprintln!("Warning: checkpoint '{}' {reason}; starting over", path.display());
+                    }
+                    (None, 0)
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (None, 0),
+            Err(e) => return Err(e.to_string()),
+        };
+        let start = || -> io::Result<BufWriter<File>> {
+            let mut file = OpenOptions::new().create(true).write(true).truncate(false).open(path)?;
+            if kept == 0 {
+                file.set_len(0)?;
+                writeln!(file, "{HEADER} {VERSION}")?;
+                writeln!(file, "K\t{}", escape(key))?;
+            } else {
+                file.set_len(kept)?;
+                file.seek(SeekFrom::End(0))?;
+            }
+            Ok(BufWriter::new(file))
+        };
+        let out = start().map_err(|e| e.to_string())?;
+        let journal = Journal {
+            out,
+            marked: Instant::now(),
+            marked_dirs: resume.as_ref().map_or(0, |resume| resume.stats.dirs),
+            errors: resume.as_ref().map_or(0, |resume| resume.stats.errors.len()),
+            mismatched: resume.as_ref().map_or(0, |resume| resume.stats.mismatched.len()),
+            failed: false,
+        };
+        let checkpoint = Checkpoint { path: path.to_path_buf(), journal: Mutex::new(journal), quiet };
+        Ok((checkpoint, resume))
+    }
+
+    /// Journal a catalogued entry.
+    pub fn entry(&self, group: &str, entry: &FileEntry) {
+        self.write(|out| {
+            writeln!(out, "F\t{}\t{}", escape(group), entry_fields(entry))?;
+            for attr in &entry.xattrs {
+                writeln!(out, "A\t{}\t{}", escape(&attr.name), text(attr.value.as_deref()))?;
+            }
+            Ok(())
+        });
+    }
+
+    /// Journal the digest of the entry at `path`, or (`None`) that it was too
+    /// large to hash.
+    pub fn digest(&self, path: &Path, digest: Option<&str>) {
+        self.write(|out| writeln!(out, "Z\t{}\t{}", path_field(path), digest.map_or("!".to_string(), |d| format!("={d}"))));
+    }
+
+    /// Journal a path recorded for a multiply-linked file.
+    pub fn hardlink(&self, (device, inode): (u64, u64), path: &Path, size: u64) {
+        self.write(|out| writeln!(out, "N\t{device}\t{inode}\t{size}\t{}", path_field(path)));
+    }
+
+    /// Whether a mark is due, `dirs` directories into the walk.
+    pub fn due(&self, dirs: u64) -> bool {
+        self.journal
+            .lock()
+            .is_ok_and(|journal| journal.marked.elapsed() >= INTERVAL || dirs >= journal.marked_dirs + DIRS)
+    }
+
+    /// Record where the walk is and what it has gathered, and commit everything
+    /// journaled so far to disk.
+    pub fn mark(&self, mark: &Mark) {
+        let Ok(mut journal) = self.journal.lock() else {
+            return;
+        };
+        let (errors, mismatched) = (journal.errors, journal.mismatched);
+        let written = write_mark(&mut journal.out, mark, errors, mismatched)
+            .and_then(|()| journal.out.flush())
+            .and_then(|()| journal.out.get_ref().sync_data());
+        journal.marked = Instant::now();
+        journal.marked_dirs = mark.stats.dirs;
+        journal.errors = mark.stats.errors.len();
+        journal.mismatched = mark.stats.mismatched.len();
+        if let Err(e) = written {
+            self.fail(&mut journal, &e);
+        }
+    }
+
+    /// Once the walk is done, commit the digests journaled since its last mark
+    /// if that was a while ago.
+    pub fn commit(&self) {
+        let Ok(mut journal) = self.journal.lock() else {
+            return;
+        };
+        if journal.failed || journal.marked.elapsed() < INTERVAL {
+            return;
+        }
+        let written = writeln!(journal.out, ".")
+            .and_then(|()| journal.out.flush())
+            .and_then(|()| journal.out.get_ref().sync_data());
+        journal.marked = Instant::now();
+        if let Err(e) = written {
+            self.fail(&mut journal, &e);
+        }
+    }
+
+    /// Delete the checkpoint once the scan it was for is complete and written.
+    pub fn remove(self) {
+        drop(self.journal);
+        if let Err(e) = fs::remove_file(&self.path)
+            && !self.quiet
+        {
+            eprintln!("Warning: cannot delete checkpoint '{}': {e}", self.path.display());
+        }
+    }
+
+    fn write(&self, record: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>) {
+        let Ok(mut journal) = self.journal.lock() else {
+            return;
+        };
+        if journal.failed {
+            return;
+        }
+        if let Err(e) = record(&mut journal.out) {
+            self.fail(&mut journal, &e);
+        }
+    }
+
+    fn fail(&self, journal: &mut Journal, e: &io::Error) {
+        if !journal.failed && !self.quiet {
+            eprintln!("Warning: cannot write checkpoint '{}': {e}; no longer saving progress", self.path.display());
+        }
+        journal.failed = true;
+    }
+}
+
+/// The errors and mismatches found since the last mark, then the mark itself,
+/// closed by the `.` line that commits it.
+fn write_mark(out: &mut impl Write, mark: &Mark, errors: usize, mismatched: usize) -> io::Result<()> {
+    for error in mark.stats.errors.iter().skip(errors) {
+        writeln!(out, "E\t{}\t{}", opt_path_field(error.path.as_deref()), escape(&error.message))?;
+    }
+    for mismatch in mark.stats.mismatched.iter().skip(mismatched) {
+        writeln!(
+            out,
+            "V\t{}\t{}\t{}",
+            path_field(&mismatch.path),
+            escape(&mismatch.ext),
+            escape(mismatch.detected)
+        )?;
+    }
+    writeln!(out, "M\t{}\t{}\t{}", mark.folder, bit(mark.dir), path_field(mark.position))?;
+    for (name, value) in scalars(mark.stats) {
+        writeln!(out, "S\t{name}\t{value}")?;
+    }
+    let [ignored, pruned, junk, loops] = mark.counters;
+    writeln!(out, "W\t{ignored}\t{pruned}\t{junk}\t{loops}")?;
+    for Reverse(file) in mark.largest {
+        writeln!(out, "L\t{}\t{}\t{}", file.size, escape(&file.ext), path_field(&file.path.0))?;
+    }
+    for (lower, spellings) in mark.casings {
+        for (literal, files) in spellings {
+            writeln!(out, "C\t{files}\t{}\t{}", escape(lower), escape(literal))?;
+        }
+    }
+    for (user, (files, bytes)) in mark.owned {
+        writeln!(out, "O\t{files}\t{bytes}\t{}", escape(user))?;
+    }
+    for (name, files) in mark.xattrs {
+        writeln!(out, "X\t{files}\t{}", escape(name))?;
+    }
+    let [tracked, modified, untracked, ignored] = mark.git;
+    writeln!(out, "G\t{tracked}\t{modified}\t{untracked}\t{ignored}")?;
+    for mount_point in mark.mount_points {
+        writeln!(out, "P\t{}", path_field(mount_point))?;
+    }
+    for mount_point in mark.virtual_mounts {
+        writeln!(out, "Y\t{}", path_field(mount_point))?;
+    }
+    writeln!(out, ".")
+}
+
+/// The counters of `stats` a mark saves, by name.
+fn scalars(stats: &ScanStats) -> [(&'static str, u64); 19] {
+    [
+        ("files", stats.files),
+        ("dirs", stats.dirs),
+        ("skipped", stats.skipped),
+        ("symlinks", stats.symlinks),
+        ("link_loops", stats.link_loops),
+        ("broken_links", stats.broken_links),
+        ("total_size", stats.total_size),
+        ("hardlinked", stats.hardlinked),
+        ("hardlinked_size", stats.hardlinked_size),
+        ("filtered", stats.filtered),
+        ("empty", stats.empty),
+        ("outputs_excluded", stats.outputs_excluded),
+        ("perms_unknown", stats.perms_unknown),
+        ("xattr_failures", stats.xattr_failures),
+        ("archive_entries", stats.archive_entries),
+        ("retried", stats.retried),
+        ("executables", stats.executables),
+        ("allocated_size", stats.allocated_size),
+        ("allocation_estimated", stats.allocation_estimated),
+    ]
+}
+
+fn set_scalar(stats: &mut ScanStats, name: &str, value: u64) -> Option<()> {
+    let field = match name {
+        "files" => &mut stats.files,
+        "dirs" => &mut stats.dirs,
+        "skipped" => &mut stats.skipped,
+        "symlinks" => &mut stats.symlinks,
+        "link_loops" => &mut stats.link_loops,
+        "broken_links" => &mut stats.broken_links,
+        "total_size" => &mut stats.total_size,
+        "hardlinked" => &mut stats.hardlinked,
+        "hardlinked_size" => &mut stats.hardlinked_size,
+        "filtered" => &mut stats.filtered,
+        "empty" => &mut stats.empty,
+        "outputs_excluded" => &mut stats.outputs_excluded,
+        "perms_unknown" => &mut stats.perms_unknown,
+        "xattr_failures" => &mut stats.xattr_failures,
+        "archive_entries" => &mut stats.archive_entries,
+        "retried" => &mut stats.retried,
+        "executables" => &mut stats.executables,
+        "allocated_size" => &mut stats.allocated_size,
+        "allocation_estimated" => &mut stats.allocation_estimated,
+        _ => return None,
+    };
+    *field = value;
+    Some(())
+}
+
+/// What the lines of a checkpoint add up to, as it is read back.
+#[derive(Default)]
+struct Reading {
+    entries: Vec<(String, FileEntry)>,
+    /// Index into `entries` by path, for digests.
+    by_path: HashMap<PathBuf, usize>,
+    errors: Vec<ScanError>,
+    mismatched: Vec<Mismatch>,
+    hardlinks: Vec<((u64, u64), PathBuf, u64)>,
+    /// The latest mark: its folder, position and whether that is a directory.
+    mark: Option<(usize, (PathBuf, bool), ScanStats, Tallies)>,
+    /// Detected MIME types, each kept once for the rest of the run.
+    types: HashMap<String, &'static str>,
+}
+
+/// The state as of a checkpoint's last mark, if it has one, and its length in
+/// bytes up to there; or why it can't be resumed.
+type Found = Result<(Option<Resume>, u64), String>;
+
+/// Read back the checkpoint `text` for the scan `key`. The error means it isn't
+/// a checkpoint at all.
+fn read(text: &str, key: &str) -> Result<Found, String> {
+    let mut lines = text.split_inclusive('\n');
+    let Some(version) = lines.next().and_then(|line| line.trim_end().strip_prefix(HEADER)) else {
+        return Err("not a CreepDir checkpoint".to_string());
+    };
+    if version.trim().parse() != Ok(VERSION) {
+        return Ok(Err("was written by an incompatible version".to_string()));
+    }
+    match lines.next().and_then(|line| line.strip_suffix('\n')?.strip_prefix("K\t")) {
+        Some(found) if unescape(found).as_deref() == Some(key) => {}
+        Some(_) => return Ok(Err("is from a different scan (the folders or options changed)".to_string())),
+        None => return Ok(Ok((None, 0))),
+    }
+    // Only what a `.` line committed counts; anything after the last one was cut
+    // short.
+    let mut end = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        offset += line.len();
+        if line == ".\n" {
+            end = Some(offset);
+        }
+    }
+    let Some(end) = end else {
+        return Ok(Ok((None, 0)));
+    };
+    let mut reading = Reading::default();
+    for line in text[..end].lines().skip(2) {
+        if line != "." && reading.line(line).is_none() {
+            return Ok(Err("is damaged".to_string()));
+        }
+    }
+    Ok(Ok((Some(reading.finish()), end as u64)))
+}
+
+impl Reading {
+    /// Take in one journal line, or `None` if it is malformed.
+    fn line(&mut self, line: &str) -> Option<()> {
+        let mut fields = line.split('\t');
+        match fields.next()? {
+            "F" => {
+                let group = unescape(fields.next()?)?;
+                let entry = self.entry(&mut fields)?;
+                self.by_path.insert(entry.path.clone(), self.entries.len());
+                self.entries.push((group, entry));
+            }
+            "A" => {
+                let name = unescape(fields.next()?)?;
+                let (_, entry) = self.entries.last_mut()?;
+                entry.xattrs.push(Xattr { name, value: opt_text(fields.next()?)? });
+            }
+            "Z" => {
+                let path = path(fields.next()?)?;
+                let digest = fields.next()?;
+                if let Some(&index) = self.by_path.get(&path) {
+                    let entry = &mut self.entries[index].1;
+                    match digest.strip_prefix('=') {
+                        Some(digest) => entry.digest = Some(digest.to_string()),
+                        None => entry.too_large = true,
+                    }
+                }
+            }
+            "N" => {
+                let id = (fields.next()?.parse().ok()?, fields.next()?.parse().ok()?);
+                let size = fields.next()?.parse().ok()?;
+                self.hardlinks.push((id, path(fields.next()?)?, size));
+            }
+            "E" => {
+                let path = opt_path(fields.next()?)?;
+                self.errors.push(ScanError { path, message: unescape(fields.next()?)? });
+            }
+            "V" => {
+                let path = path(fields.next()?)?;
+                let ext = unescape(fields.next()?)?;
+                let detected = self.intern(unescape(fields.next()?)?);
+                self.mismatched.push(Mismatch { path, ext, detected });
+            }
+            "M" => {
+                let folder = fields.next()?.parse().ok()?;
+                let dir = flag(fields.next()?)?;
+                let position = path(fields.next()?)?;
+                self.mark = Some((folder, (position, dir), ScanStats::new(), Tallies::default()));
+            }
+            tag => {
+                let (_, _, stats, tallies) = self.mark.as_mut()?;
+                match tag {
+                    "S" => set_scalar(stats, fields.next()?, fields.next()?.parse().ok()?)?,
+                    "W" => {
+                        for counter in &mut tallies.counters {
+                            *counter = fields.next()?.parse().ok()?;
+                        }
+                    }
+                    "L" => {
+                        let size = fields.next()?.parse().ok()?;
+                        let ext = unescape(fields.next()?)?;
+                        let path = Reverse(path(fields.next()?)?);
+                        tallies.largest.push(LargeFile { size, path, ext });
+                    }
+                    "C" => {
+                        let files = fields.next()?.parse().ok()?;
+                        let lower = unescape(fields.next()?)?;
+                        let literal = unescape(fields.next()?)?;
+                        tallies.casings.entry(lower).or_default().insert(literal, files);
+                    }
+                    "O" => {
+                        let totals = (fields.next()?.parse().ok()?, fields.next()?.parse().ok()?);
+                        tallies.owned.insert(unescape(fields.next()?)?, totals);
+                    }
+                    "X" => {
+                        let files = fields.next()?.parse().ok()?;
+                        tallies.xattrs.insert(unescape(fields.next()?)?, files);
+                    }
+                    "G" => {
+                        for count in &mut tallies.git {
+                            *count = fields.next()?.parse().ok()?;
+                        }
+                    }
+                    "P" => tallies.mount_points.push(path(fields.next()?)?),
+                    "Y" => tallies.virtual_mounts.push(path(fields.next()?)?),
+                    _ => return None,
+                }
+            }
+        }
+        Some(())
+    }
+
+    /// The state as of the last mark, with every entry, error and hardlink
+    /// journaled up to it.
+    fn finish(self) -> Resume {
+        let (folder, (position, dir), mut stats, tallies) =
+            self.mark.unwrap_or_else(|| (0, (PathBuf::new(), false), ScanStats::new(), Tallies::default()));
+        stats.errors = self.errors;
+        stats.mismatched = self.mismatched;
+        Resume { folder, position, dir, entries: self.entries, stats, tallies, hardlinks: self.hardlinks }
+    }
+
+    /// `name` as a string that lives for the rest of the run, as detected types
+    /// do; each distinct one is kept once.
+    fn intern(&mut self, name: String) -> &'static str {
+        if let Some(&known) = self.types.get(&name) {
+            return known;
+        }
+        let known: &'static str = Box::leak(name.clone().into_boxed_str());
+        self.types.insert(name, known);
+        known
+    }
+
+    fn entry<'a>(&mut self, fields: &mut impl Iterator<Item = &'a str>) -> Option<FileEntry> {
+        let path = path(fields.next()?)?;
+        let size = number(fields.next()?)?;
+        let mut entry = FileEntry::new(path, size);
+        entry.allocated = number(fields.next()?)?;
+        entry.target = opt_path(fields.next()?)?;
+        entry.broken_link = flag(fields.next()?)?;
+        entry.resolved = opt_path(fields.next()?)?;
+        entry.mime = opt_text(fields.next()?)?.map(|mime| self.intern(mime));
+        entry.modified = time(fields.next()?)?;
+        entry.created = time(fields.next()?)?;
+        entry.permissions = number(fields.next()?)?.and_then(from_mode);
+        let uid = number(fields.next()?)?;
+        let user = opt_text(fields.next()?)?;
+        let gid = number(fields.next()?)?;
+        let group = opt_text(fields.next()?)?;
+        if let (Some(uid), Some(user), Some(gid), Some(group)) = (uid, user, gid, group) {
+            entry.owner = Some(Owner { uid: u32::try_from(uid).ok()?, user, gid: u32::try_from(gid).ok()?, group });
+        }
+        entry.attributes = number(fields.next()?)?.and_then(|bits| u32::try_from(bits).ok());
+        entry.executable = match fields.next()? {
+            "-" => None,
+            other => Some(flag(other)?),
+        };
+        entry.hardlink = flag(fields.next()?)?;
+        entry.archived = flag(fields.next()?)?;
+        entry.git = match fields.next()? {
+            "-" => None,
+            name => Some(*GitStatus::ALL.iter().find(|status| status.name() == name)?),
+        };
+        Some(entry)
+    }
+}
+
+/// The fields of an `F` line after the group.
+fn entry_fields(entry: &FileEntry) -> String {
+    let owner = entry.owner.as_ref();
+    [
+        path_field(&entry.path),
+        num(entry.size),
+        num(entry.allocated),
+        opt_path_field(entry.target.as_deref()),
+        bit(entry.broken_link),
+        opt_path_field(entry.resolved.as_deref()),
+        text(entry.mime),
+        time_field(entry.modified),
+        time_field(entry.created),
+        num(entry.permissions.as_ref().and_then(mode)),
+        num(owner.map(|o| u64::from(o.uid))),
+        text(owner.map(|o| o.user.as_str())),
+        num(owner.map(|o| u64::from(o.gid))),
+        text(owner.map(|o| o.group.as_str())),
+        num(entry.attributes.map(u64::from)),
+        entry.executable.map_or("-".to_string(), bit),
+        bit(entry.hardlink),
+        bit(entry.archived),
+        entry.git.map_or("-", GitStatus::name).to_string(),
+    ]
+    .join("\t")
+}
+
+/// Paths are saved as their bytes: [`escape`]d, with any that aren't valid
+/// UTF-8 (as a name on Unix may hold) written as `\xHH`.
+fn path_field(path: &Path) -> String {
+    let mut field = String::new();
+    for chunk in path.as_os_str().as_encoded_bytes().utf8_chunks() {
+        field.push_str(&escape(chunk.valid()));
+        for byte in chunk.invalid() {
+            field.push_str(&format!("\\x{byte:02x}"));
+        }
+    }
+    field
+}
+
+fn path(field: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut rest = field.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        rest = after;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        let (&escaped, after) = rest.split_first()?;
+        rest = after;
+        bytes.push(match escaped {
+            b'\\' => b'\\',
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b'x' => {
+                let (hex, after) = rest.split_at_checked(2)?;
+                rest = after;
+                u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?
+            }
+            _ => return None,
+        });
+    }
+    Some(cli::path_from_bytes(&bytes))
+}
+
+/// An optional path: `-` for none, else `=` and the [`path_field`].
+fn opt_path_field(path: Option<&Path>) -> String {
+    path.map_or("-".to_string(), |path| format!("={}", path_field(path)))
+}
+
+fn opt_path(field: &str) -> Option<Option<PathBuf>> {
+    match field.strip_prefix('=') {
+        Some(value) => path(value).map(Some),
+        None if field == "-" => Some(None),
+        None => None,
+    }
+}
+
+/// An optional string: `-` for none, else `=` and the escaped text.
+fn text(value: Option<&str>) -> String {
+    value.map_or("-".to_string(), |value| format!("={}", escape(value)))
+}
+
+fn opt_text(field: &str) -> Option<Option<String>> {
+    match field.strip_prefix('=') {
+        Some(value) => unescape(value).map(Some),
+        None if field == "-" => Some(None),
+        None => None,
+    }
+}
+
+fn num(value: Option<u64>) -> String {
+    value.map_or("-".to_string(), |n| n.to_string())
+}
+
+fn number(field: &str) -> Option<Option<u64>> {
+    if field == "-" { Some(None) } else { field.parse().ok().map(Some) }
+}
+
+fn bit(value: bool) -> String {
+    if value { "1" } else { "0" }.to_string()
+}
+
+fn flag(field: &str) -> Option<bool> {
+    match field {
+        "1" => Some(true),
+        "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// A time as seconds and nanoseconds since 1970; earlier ones aren't worth a
+/// format of their own and are saved as unknown.
+fn time_field(time: Option<SystemTime>) -> String {
+    match time.and_then(|time| time.duration_since(UNIX_EPOCH).ok()) {
+        Some(since) => format!("{}.{}", since.as_secs(), since.subsec_nanos()),
+        None => "-".to_string(),
+    }
+}
+
+fn time(field: &str) -> Option<Option<SystemTime>> {
+    if field == "-" {
+        return Some(None);
+    }
+    let (secs, nanos) = field.split_once('.')?;
+    Some(Some(UNIX_EPOCH + Duration::new(secs.parse().ok()?, nanos.parse().ok()?)))
+}
+
+#[cfg(unix)]
+fn mode(permissions: &Permissions) -> Option<u64> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(u64::from(permissions.mode()))
+}
+
+#[cfg(unix)]
+fn from_mode(mode: u64) -> Option<Permissions> {
+    use std::os::unix::fs::PermissionsExt;
+    u32::try_from(mode).ok().map(Permissions::from_mode)
+}
+
+// Elsewhere `--checkpoint` refuses `--perms`, since permissions can't be made
+// from saved bits.
+#[cfg(not(unix))]
+fn mode(_permissions: &Permissions) -> Option<u64> {
+    None
+}
+
+#[cfg(not(unix))]
+fn from_mode(_mode: u64) -> Option<Permissions> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::{self, DIR_GROUP, Roots};
+
+    const KEY: &str = "hidden=include\nfolder=/scanned";
+
+    /// The opening lines of a checkpoint for the scan `key`.
+    fn head(key: &str) -> String {
+        format!("{HEADER} {VERSION}\nK\t{}\n", escape(key))
+    }
+
+    fn entry_line(path: &str) -> String {
+        format!("F\t.txt\t{}\n", entry_fields(&FileEntry::new(PathBuf::from(path), Some(1))))
+    }
+
+    /// Hand `record` a mark at `position` in the first folder, with `stats` and
+    /// `counters` and nothing else gathered.
+    fn with_mark(position: &str, dir: bool, stats: &ScanStats, counters: [u64; 4], record: impl FnOnce(&Mark)) {
+        record(&Mark {
+            folder: 0,
+            position: Path::new(position),
+            dir,
+            stats,
+            largest: &BinaryHeap::new(),
+            casings: &HashMap::new(),
+            owned: &HashMap::new(),
+            xattrs: &HashMap::new(),
+            git: &[0; 4],
+            counters,
+            mount_points: &[],
+            virtual_mounts: &[],
+        });
+    }
+
+    /// The lines of a mark at the file `position`, one file in.
+    fn mark_lines(position: &str) -> String {
+        let mut stats = ScanStats::new();
+        stats.files = 1;
+        let mut out = Vec::new();
+        with_mark(position, false, &stats, [0; 4], |mark| write_mark(&mut out, mark, 0, 0).unwrap());
+        String::from_utf8(out).unwrap()
+    }
+
+    /// What reading `text` back for the scan [`KEY`] resumes from, and how much
+    /// of it counts.
+    fn resume(text: &str) -> (Option<Resume>, u64) {
+        read(text, KEY).unwrap().unwrap()
+    }
+
+    #[test]
+    fn a_journal_reads_back_everything_up_to_its_last_mark() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint");
+        let (checkpoint, resume) = Checkpoint::open(&path, KEY, true).unwrap();
+        assert!(resume.is_none());
+        let mut entry = FileEntry::new(PathBuf::from("sub/a.txt"), Some(3));
+        entry.xattrs.push(Xattr { name: "user.tag".to_string(), value: Some("blue".to_string()) });
+        checkpoint.entry(".txt", &entry);
+        checkpoint.digest(&entry.path, Some("ab12"));
+        checkpoint.hardlink((1, 2), &entry.path, 3);
+        let mut stats = ScanStats::new();
+        stats.files = 1;
+        stats.dirs = 1;
+        with_mark("sub", true, &stats, [2, 0, 0, 0], |mark| checkpoint.mark(mark));
+        // Journaled after the mark, so lost with the run.
+        checkpoint.entry(".txt", &FileEntry::new(PathBuf::from("sub/b.txt"), Some(1)));
+        drop(checkpoint);
+
+        let (checkpoint, resume) = Checkpoint::open(&path, KEY, true).unwrap();
+        drop(checkpoint);
+        let resume = resume.unwrap();
+        assert_eq!((resume.folder, resume.position.as_path(), resume.dir), (0, Path::new("sub"), true));
+        assert_eq!(resume.entries.len(), 1);
+        let (group, entry) = &resume.entries[0];
+        assert_eq!((group.as_str(), entry.path.as_path(), entry.size), (".txt", Path::new("sub/a.txt"), Some(3)));
+        assert_eq!(entry.digest.as_deref(), Some("ab12"));
+        assert_eq!(entry.xattrs[0].value.as_deref(), Some("blue"));
+        assert_eq!(resume.hardlinks, [((1, 2), PathBuf::from("sub/a.txt"), 3)]);
+        assert_eq!((resume.stats.files, resume.stats.dirs, resume.tallies.counters), (1, 1, [2, 0, 0, 0]));
+        // Reopening cut the journal back to its last mark.
+        assert!(!fs::read_to_string(&path).unwrap().contains("b.txt"));
+    }
+
+    #[test]
+    fn a_dot_line_commits_what_was_journaled_after_the_mark() {
+        let marked = head(KEY) + &entry_line("a.txt") + &mark_lines("a.txt");
+        let digest = "Z\ta.txt\t=ab12\n";
+        let (found, kept) = resume(&(marked.clone() + digest));
+        assert_eq!(found.unwrap().entries[0].1.digest, None);
+        assert_eq!(kept, marked.len() as u64);
+        let committed = marked + digest + ".\n";
+        let (found, kept) = resume(&committed);
+        assert_eq!(found.unwrap().entries[0].1.digest.as_deref(), Some("ab12"));
+        assert_eq!(kept, committed.len() as u64);
+    }
+
+    #[test]
+    fn a_journal_cut_off_mid_record_resumes_from_its_last_mark() {
+        let marked = head(KEY) + &entry_line("a.txt") + &mark_lines("a.txt");
+        let cut = entry_line("b.txt");
+        let (found, kept) = resume(&(marked.clone() + &cut[..cut.len() / 2]));
+        let found = found.unwrap();
+        assert_eq!(found.entries.len(), 1);
+        assert_eq!(found.position, Path::new("a.txt"));
+        assert_eq!(kept, marked.len() as u64);
+
+        // The next run carries on from the mark, not from the broken line.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint");
+        fs::write(&path, marked + &cut[..cut.len() / 2]).unwrap();
+        let (checkpoint, _) = Checkpoint::open(&path, KEY, true).unwrap();
+        checkpoint.entry(".txt", &FileEntry::new(PathBuf::from("c.txt"), Some(1)));
+        with_mark("c.txt", false, &ScanStats::new(), [0; 4], |mark| checkpoint.mark(mark));
+        drop(checkpoint);
+        let (_, resume) = Checkpoint::open(&path, KEY, true).unwrap();
+        let paths: Vec<_> = resume.unwrap().entries.into_iter().map(|(_, entry)| entry.path).collect();
+        assert_eq!(paths, [PathBuf::from("a.txt"), PathBuf::from("c.txt")]);
+    }
+
+    #[test]
+    fn a_walk_resumed_after_any_entry_catalogs_everything_else_once() {
+        let tree = tempfile::tempdir().unwrap();
+        for name in ["a/c/d.txt", "a/b.txt", "a.txt", "a-b/e.txt", "B.txt", "z/y/x.txt"] {
+            let path = tree.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "x").unwrap();
+        }
+        let roots = Roots::single(tree.path().to_path_buf());
+        let (options, filter) = cli::parse_for_test(&["--entry-types", "file,dir"]);
+        let mut full = Vec::new();
+        scan::walk(&roots, &options, &filter, |group, entry| full.push((group, entry.path)));
+        let journals = tempfile::tempdir().unwrap();
+        for done in 1..full.len() {
+            let path = journals.path().join(done.to_string());
+            let (checkpoint, _) = Checkpoint::open(&path, KEY, true).unwrap();
+            for (group, entry) in &full[..done] {
+                checkpoint.entry(group, &FileEntry::new(entry.clone(), None));
+            }
+            let (group, at) = &full[done - 1];
+            let at = at.to_str().unwrap();
+            with_mark(at, group == DIR_GROUP, &ScanStats::new(), [0; 4], |mark| checkpoint.mark(mark));
+            drop(checkpoint);
+            let (checkpoint, resume) = Checkpoint::open(&path, KEY, true).unwrap();
+            let mut found = Vec::new();
+            scan::walk_checkpointed(&roots, &options, &filter, &checkpoint, resume, |group, entry| {
+                found.push((group, entry.path));
+            });
+            assert_eq!(found, full, "resumed after {at}");
+        }
+    }
+
+    #[test]
+    fn a_journal_without_a_mark_starts_over() {
+        assert!(resume(&head(KEY)).0.is_none());
+        assert!(resume(&(head(KEY) + &entry_line("a.txt"))).0.is_none());
+    }
+
+    #[test]
+    fn a_malformed_committed_line_makes_the_checkpoint_damaged() {
+        let text = head(KEY) + "F\t.txt\tnot an entry\n" + &mark_lines("a.txt");
+        assert_eq!(read(&text, KEY).unwrap().err().as_deref(), Some("is damaged"));
+        let text = head(KEY) + &mark_lines("a.txt").replace("S\tfiles", "S\tno such counter");
+        assert_eq!(read(&text, KEY).unwrap().err().as_deref(), Some("is damaged"));
+    }
+
+    #[test]
+    fn another_version_or_scan_is_started_over_and_other_files_are_refused() {
+        let body = entry_line("a.txt") + &mark_lines("a.txt");
+        let older = format!("{HEADER} 1\nK\t{}\n{body}", escape(KEY));
+        assert_eq!(read(&older, KEY).unwrap().err().as_deref(), Some("was written by an incompatible version"));
+        let other = head("hidden=exclude\nfolder=/scanned") + &body;
+        assert_eq!(
+            read(&other, KEY).unwrap().err().as_deref(),
+            Some("is from a different scan (the folders or options changed)")
+        );
+        assert!(read("some notes\n", KEY).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint");
+        fs::write(&path, &other).unwrap();
+        let (checkpoint, resume) = Checkpoint::open(&path, KEY, true).unwrap();
+        drop(checkpoint);
+        assert!(resume.is_none());
+        assert_eq!(fs::read_to_string(&path).unwrap(), head(KEY));
+        fs::write(&path, "some notes\n").unwrap();
+        assert!(Checkpoint::open(&path, KEY, true).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "some notes\n");
+    }
+
+    #[test]
+    fn paths_are_saved_byte_for_byte() {
+        for name in ["tab\there", "line\nbreak\r", r"back\slash", r"looks\x41escaped", "ä.txt"] {
+            let field = path_field(Path::new(name));
+            assert!(!field.contains(['\t', '\n', '\r']), "{field}");
+            assert_eq!(path(&field), Some(PathBuf::from(name)));
+        }
+        assert_eq!(path(r"bad\x4"), None);
+        assert_eq!(path(r"bad\q"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_name_that_is_not_utf8_round_trips() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let name = Path::new(OsStr::from_bytes(b"bad\xff\xfe.txt"));
+        assert_eq!(path_field(name), r"bad\xff\xfe.txt");
+        assert_eq!(path(&path_field(name)).as_deref(), Some(name));
+        assert_ne!(path(&path_field(name)).as_deref(), Some(Path::new("bad\u{fffd}\u{fffd}.txt")));
+    }
+}
@@ -1,124 +1,1185 @@
 //! Command-line interface definition and path resolution helpers.
 
-use crate::config::{OutputFormat, ScanFilter, ScanOptions};
-use clap::Parser;
+use crate::category::{self, Category};
+use crate::charset;
+use crate::config::{ArchiveFormat, ColorChoice, ContentKind, DateGranularity, Encoding, EmptyDirMode, EntryType, ExtAliases, FileAttribute, GroupBy, HashAlgorithm, Hidden, LineEnding, OutputFormat, ScanFilter, ScanOptions, TimeBound};
+use crate::output::{self, template::Template};
+use crate::scan::{self, Roots};
+use chrono::{NaiveDate, NaiveTime};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, Parser, ValueHint};
 use std::env;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Parser)]
 #[command(name = "CreepDir", version)]
 #[command(
     about = "Scan folders and catalog files by extension",
-    long_about = "CreepDir recursively scans a folder and catalogs files grouped by extension.
+    long_about = "CreepDir recursively scans folders and catalogs files grouped by extension.
 
-Output formats: text (default), --json, or --csv.
+Output formats: --format text (default), json, csv, markdown, html, yaml, xml, sqlite,
+toml, ndjson (streamed while scanning, for very large trees), tree, flat
+(one sorted path per line), sha256sum (a manifest for `sha256sum -c`), or a
+graph of extensions for Graphviz (dot) or Mermaid (mermaid).
+Use --template FILE for a custom layout.
+Repeat --format (or pass a list like --format text,json) to write several
+formats from one scan; they are saved side by side (inventory.txt,
+inventory.json, ...).
+SQLite output appends each run to the database, keeping earlier scans.
 Scanning runs in parallel and skips folders it can't access instead of aborting.
 
 Usage modes:
   - Run without arguments to show this help message
   - Use --select to open file dialogs for interactive folder/output selection
   - Provide a folder path to scan it (output saved in the scanned folder)
-  - Provide both folder and output paths to specify exact output location"
+  - Provide both folder and output paths to specify exact output location (an
+    output directory needs -o)
+  - Provide several folders with -o OUTPUT for one combined catalog, where each
+    path starts with its folder's place under their common directory
+  - Use `-` as the output path to write the catalog to stdout"
 )]
 pub struct Cli {
-    /// Path to the folder to scan
-    #[arg(value_name = "FOLDER")]
-    pub folder: Option<PathBuf>,
+    /// Folders (or single files) to scan; without -o, a second path is the output
+    /// file instead (an existing directory there is refused; use -o for that)
+    #[arg(value_name = "FOLDER", value_hint = ValueHint::AnyPath)]
+    pub paths: Vec<PathBuf>,
 
-    /// Path where the output file should be saved
-    #[arg(value_name = "OUTPUT")]
+    /// Path where the output file should be saved (`-` for stdout); required
+    /// to scan more than one folder
+    #[arg(long, short = 'o', value_name = "OUTPUT", value_hint = ValueHint::AnyPath)]
     pub output: Option<PathBuf>,
 
     /// Open file explorer to select folder and output location
     #[arg(long, short = 's')]
     pub select: bool,
 
-    /// Suppress per-folder "skipping" warnings for inaccessible paths
-    #[arg(long, short = 'q')]
+    /// Read the folders to scan from FILE (`-` for stdin), one per line; blank
+    /// lines and `#` comments are skipped, and folders that don't exist are
+    /// reported and skipped
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, conflicts_with_all = ["paths", "select"])]
+    pub paths_from: Option<PathBuf>,
+
+    /// Catalog the files listed in FILE (`-` for stdin), one per line, instead of
+    /// walking a folder, as from `find`. Listed directories are counted but not
+    /// descended; paths that don't exist are listed under "errors"
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["paths", "select", "paths_from", "watch", "cache", "checkpoint", "empty_dirs"]
+    )]
+    pub files_from: Option<PathBuf>,
+
+    /// With --files-from, the paths are separated by NUL bytes rather than
+    /// newlines (as `find -print0` writes them)
+    #[arg(long, requires = "files_from")]
+    pub null: bool,
+
+    /// With --files-from, the directory the catalogued paths are relative to
+    /// (default: the current directory); files outside it keep their full path
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, requires = "files_from")]
+    pub root: Option<PathBuf>,
+
+    /// Print nothing but errors: no warnings, progress line, summary or "Saved
+    /// to" line
+    #[arg(long, short = 'q', conflicts_with = "verbose")]
     pub quiet: bool,
 
-    /// Number of threads to use for scanning (default: number of CPU cores)
+    /// Log each directory entered and how long the scan took, to stderr; -vv
+    /// also logs why each skipped entry was left out
+    #[arg(long, short = 'v', action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Abort the scan at the first unreadable entry instead of skipping it and
+    /// listing it under "errors"
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Number of threads to use for scanning (default: number of CPU cores;
+    /// 1 scans on a single thread)
     #[arg(long, short = 'j', value_name = "N")]
     pub threads: Option<usize>,
 
-    /// Follow symlinks and junctions (loops are reported and skipped)
+    /// Follow symlinks and junctions into directories; links back to a directory
+    /// already being walked, and broken links, are reported and skipped
     #[arg(long)]
     pub follow_symlinks: bool,
 
-    /// Maximum directory depth to descend (0 = only the root folder)
+    /// Stay on the filesystem each folder is on: don't descend into mount points
+    /// (or, on Windows, junctions and mount points onto another volume)
+    #[arg(long, short = 'x')]
+    pub one_file_system: bool,
+
+    /// Also walk virtual filesystems such as /proc, /sys and /dev on Linux, which
+    /// are skipped (and listed in the summary) by default
+    #[arg(long)]
+    pub include_virtual: bool,
+
+    /// Retry directory listings and metadata reads up to N times when they fail
+    /// with an error that looks transient (timeouts, interrupted calls, dropped
+    /// network connections); useful on flaky network shares
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+
+    /// With --retries, how long to wait before the first retry; the wait doubles
+    /// with each further attempt (e.g. 500ms or 2s)
+    #[arg(long, value_name = "DURATION", default_value = "500ms", value_parser = parse_duration, requires = "retries")]
+    pub retry_delay: Duration,
+
+    /// Keep each directory's listing in FILE, and on later runs reuse it for
+    /// directories whose modification time hasn't changed instead of listing
+    /// them again
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, conflicts_with = "follow_symlinks")]
+    pub cache: Option<PathBuf>,
+
+    /// With --cache, list every directory again (and rewrite the cache) rather
+    /// than trusting unchanged modification times
+    #[arg(long, requires = "cache")]
+    pub no_cache_trust: bool,
+
+    /// Save the scan's progress to FILE as it goes; if the run is interrupted or
+    /// killed, the same command picks up where it stopped. The file is deleted
+    /// once the catalog is written, and started over if the folders or scan
+    /// options change
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["watch", "cache", "limit", "empty_dirs"]
+    )]
+    pub checkpoint: Option<PathBuf>,
+
+    /// After the first scan, keep running and rewrite the output whenever files
+    /// under the folders are created, deleted, renamed or changed; Ctrl-C writes
+    /// any pending changes and exits
+    #[arg(long)]
+    pub watch: bool,
+
+    /// With --watch, how long changes must settle before the output is
+    /// rewritten (e.g. 2s or 1m)
+    #[arg(long, value_name = "DURATION", default_value = "2s", value_parser = parse_duration, requires = "watch")]
+    pub debounce: Duration,
+
+    /// Maximum directory depth to descend (1 = only files directly inside FOLDER)
     #[arg(long, value_name = "N")]
     pub max_depth: Option<usize>,
 
-    /// Only include these extensions, comma-separated (e.g. --ext rs,txt,md)
-    #[arg(long, value_name = "EXTS", value_delimiter = ',')]
+    /// Only catalog files at least N levels deep (1 = files directly inside FOLDER)
+    #[arg(long, value_name = "N")]
+    pub min_depth: Option<usize>,
+
+    /// Only scan the entries directly inside FOLDER (same as --max-depth 1)
+    #[arg(long, conflicts_with = "max_depth")]
+    pub no_recurse: bool,
+
+    /// Stop the walk after cataloguing N entries; the output is marked as truncated
+    /// and the run exits with status 3
+    #[arg(long, value_name = "N")]
+    pub limit: Option<u64>,
+
+    /// Stop the walk once DURATION has passed since the scan began (e.g. 30s or
+    /// 2h); what was found is written, headed TIMED OUT, and the run exits with
+    /// status 124
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with = "watch")]
+    pub timeout: Option<Duration>,
+
+    /// Show what the scan would do (the folders, the files it would write and
+    /// the options given) without scanning or writing anything, not even the
+    /// output directory; exits non-zero if the options are invalid
+    #[arg(long, conflicts_with = "select")]
+    pub dry_run: bool,
+
+    /// Scan and print the summary (counts, top extensions, size, time taken)
+    /// without writing a catalog
+    #[arg(long, conflicts_with_all = ["output", "split", "watch", "checkpoint", "dry_run"])]
+    pub summary_only: bool,
+
+    /// With --dry-run, also list up to N files the scan would catalog from the
+    /// top level of the folders
+    #[arg(long, value_name = "N", requires = "dry_run")]
+    pub sample: Option<usize>,
+
+    /// Only include these extensions; repeat or comma-separate (e.g. --ext rs,txt,md)
+    #[arg(long, visible_alias = "include-ext", value_name = "EXTS", value_delimiter = ',')]
     pub ext: Vec<String>,
 
+    /// Only include a preset group of extensions (repeatable; see --list-categories)
+    #[arg(long, value_name = "NAME", value_enum, value_delimiter = ',')]
+    pub category: Vec<Category>,
+
+    /// Print the --category presets and their extensions, then exit
+    #[arg(long)]
+    pub list_categories: bool,
+
+    /// Print a completion script for SHELL to stdout, then exit (e.g.
+    /// `--completions zsh > ~/.zfunc/_CreepDir`)
+    #[arg(long, value_name = "SHELL", value_enum)]
+    pub completions: Option<clap_complete::Shell>,
+
+    /// Only include files without an extension (including dotfiles like .bashrc)
+    #[arg(long, conflicts_with_all = ["ext", "category"])]
+    pub no_ext_only: bool,
+
+    /// Also group this multi-part extension as one, like the built-in tar.gz,
+    /// tar.bz2, tar.xz and tar.zst (repeatable or comma-separated)
+    #[arg(long, value_name = "EXT", value_delimiter = ',')]
+    pub compound_ext: Vec<String>,
+
+    /// Group by the last extension only, so backup.tar.gz is under .gz
+    #[arg(long, conflicts_with = "compound_ext")]
+    pub no_compound_ext: bool,
+
+    /// Group extension FROM under TO, e.g. --alias jpeg=jpg (repeatable; adds to the
+    /// built-ins jpeg/jpe=jpg, tiff=tif, htm=html, yml=yaml, markdown=md, midi=mid, aif=aiff)
+    #[arg(long, value_name = "FROM=TO", value_parser = parse_alias)]
+    pub alias: Vec<(String, String)>,
+
+    /// Read more aliases from a file, one FROM=TO per line (# starts a comment)
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub alias_file: Vec<PathBuf>,
+
+    /// Group every extension exactly as spelled, without the built-in aliases
+    #[arg(long, conflicts_with_all = ["alias", "alias_file"])]
+    pub no_alias: bool,
+
+    /// Group by the extension's literal casing, so .JPG and .jpg are listed apart
+    /// (implies --no-alias)
+    #[arg(long, conflicts_with_all = ["alias", "alias_file"])]
+    pub preserve_case: bool,
+
+    /// Don't report extensions that appear in several casings (.jpg and .JPG)
+    #[arg(long)]
+    pub no_case_variants: bool,
+
+    /// Skip these extensions; repeat or comma-separate (e.g. --exclude-ext o,tmp)
+    #[arg(long, value_name = "EXTS", value_delimiter = ',')]
+    pub exclude_ext: Vec<String>,
+
+    /// Only include files matching a glob (repeatable, e.g. --include "src/**/*.rs")
+    #[arg(long, short = 'i', value_name = "GLOB")]
+    pub include: Vec<String>,
+
     /// Exclude paths matching a glob (repeatable, e.g. --exclude "*.tmp" --exclude node_modules)
     #[arg(long, short = 'e', value_name = "GLOB")]
     pub exclude: Vec<String>,
 
+    /// Kinds of entries to catalog: file, dir, symlink (comma-separated)
+    #[arg(
+        long,
+        value_name = "TYPES",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "file"
+    )]
+    pub entry_types: Vec<EntryType>,
+
+    /// Also catalog the files inside archives of these formats (zip, tar), as
+    /// `archive.zip!/inner/path`; nested archives are listed but not opened
+    #[arg(long, value_name = "FORMATS", value_enum, value_delimiter = ',')]
+    pub archives: Vec<ArchiveFormat>,
+
+    /// Also show each listed symlink's canonical (fully resolved) target
+    #[arg(long)]
+    pub resolve_links: bool,
+
+    /// Include or exclude hidden entries (dotfiles; also the hidden attribute on Windows)
+    #[arg(long, value_name = "MODE", value_enum, default_value_t)]
+    pub hidden: Hidden,
+
+    /// Only include files whose relative path (with `/` separators) matches a regex (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    pub regex: Vec<String>,
+
+    /// Skip files whose relative path (with `/` separators) matches a regex (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    pub regex_exclude: Vec<String>,
+
+    /// Match --regex and --regex-exclude case-insensitively
+    #[arg(long)]
+    pub ignore_case: bool,
+
+    /// Only include files whose relative path contains TEXT (repeatable; all must match)
+    #[arg(long, value_name = "TEXT")]
+    pub contains: Vec<String>,
+
+    /// Skip paths whose relative path contains TEXT (repeatable)
+    #[arg(long, value_name = "TEXT")]
+    pub not_contains: Vec<String>,
+
+    /// Match --contains and --not-contains case-sensitively
+    #[arg(long)]
+    pub case_sensitive: bool,
+
+    /// Don't descend into directories with this name (repeatable; globs like "*.cache" allowed)
+    #[arg(long, value_name = "NAME")]
+    pub exclude_dir: Vec<String>,
+
+    /// Leave out empty (zero-length) files
+    #[arg(long)]
+    pub skip_empty: bool,
+
+    /// Only include text or binary files, judged by sampling each file's contents
+    #[arg(long, value_name = "KIND", value_enum)]
+    pub only: Option<ContentKind>,
+
+    /// Don't apply .creepignore files found in the scanned tree
+    #[arg(long)]
+    pub no_creepignore: bool,
+
+    /// Don't descend into common junk directories (node_modules, target, .git, ...; see --list-junk)
+    #[arg(long)]
+    pub no_junk: bool,
+
+    /// Print the directories skipped by --no-junk, then exit
+    #[arg(long)]
+    pub list_junk: bool,
+
+    /// Scan this directory even though --no-junk lists it (repeatable)
+    #[arg(long, value_name = "NAME", requires = "no_junk")]
+    pub keep_dir: Vec<String>,
+
+    /// Only include files whose sniffed type matches (repeatable, e.g. image/* or application/pdf)
+    #[arg(long, value_name = "PATTERN")]
+    pub mime: Vec<String>,
+
+    /// Detect each file's MIME type from its contents and include it in the output
+    #[arg(long)]
+    pub show_mime: bool,
+
+    /// Group files by extension, by MIME type detected from their contents, by
+    /// EXIF date, or into executable and not executable
+    #[arg(long, value_name = "KEY", value_enum, default_value_t)]
+    pub group_by: GroupBy,
+
+    /// Group extensionless scripts by the interpreter on their `#!` line
+    /// (e.g. `#!python3`, `#!bash`) instead of with other extensionless files
+    #[arg(long)]
+    pub shebang: bool,
+
+    /// With --group-by exif-date, group by year or by year and month
+    #[arg(long, value_name = "UNIT", value_enum, default_value_t)]
+    pub date_granularity: DateGranularity,
+
+    /// Only include files of at least this size (e.g. 500K, 100M, 2G, or bytes)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub min_size: Option<u64>,
+
+    /// Only include files of at most this size (e.g. 500K, 100M, 2G, or bytes)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_size: Option<u64>,
+
+    /// Only include files modified after a date (2024-01-01) or within a duration (7d, 12h, 3w)
+    #[arg(long, value_name = "WHEN", value_parser = parse_bound)]
+    pub newer_than: Option<TimeBound>,
+
+    /// Only include files modified before a date (2024-01-01) or longer ago than a duration (2y)
+    #[arg(long, value_name = "WHEN", value_parser = parse_bound)]
+    pub older_than: Option<TimeBound>,
+
+    /// Read more --exclude globs from FILE, one per line (`#` comments; repeatable)
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub exclude_from: Vec<PathBuf>,
+
+    /// Skip files and directories ignored by .gitignore (nested files and
+    /// .git/info/exclude included), and the .git directory itself
+    #[arg(long)]
+    pub gitignore: bool,
+
     /// Include file sizes in the output and a size total in the summary
     #[arg(long)]
     pub sizes: bool,
 
-    /// Write output as JSON
-    #[arg(long, conflicts_with = "csv")]
-    pub json: bool,
+    /// With --sizes, show exact byte counts instead of KiB/MiB
+    #[arg(long, requires = "sizes")]
+    pub bytes: bool,
 
-    /// Write output as CSV (streamed, low memory)
+    /// With --sizes, also show the space each file occupies on disk (allocated
+    /// blocks), with apparent and on-disk totals
+    #[arg(long, requires = "sizes")]
+    pub disk_usage: bool,
+
+    /// Include a content digest of every file (lowercase hex); crc32 and xxh3 are fast
+    /// change-detection checksums, not integrity hashes
+    #[arg(long, value_name = "ALGORITHM", value_enum)]
+    pub hash: Option<HashAlgorithm>,
+
+    /// With --hash (or --format sha256sum), number of threads hashing files while
+    /// the walk goes on (default: as many as --threads, or one per CPU core)
+    #[arg(long, value_name = "N")]
+    pub hash_threads: Option<usize>,
+
+    /// With --hash or --duplicates, leave files larger than SIZE unhashed: they
+    /// are listed with `<skipped: too large>` for a digest and never reported as
+    /// duplicates (e.g. 1G; 0 hashes everything)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub hash_max_size: Option<u64>,
+
+    /// Append a section listing groups of identical files and the space they waste
+    #[arg(long)]
+    pub duplicates: bool,
+
+    /// With --duplicates, also group zero-byte files (all trivially identical)
+    #[arg(long, requires = "duplicates")]
+    pub duplicates_empty: bool,
+
+    /// Append a section listing the N biggest matched files
+    #[arg(long, value_name = "N")]
+    pub largest: Option<usize>,
+
+    /// Append a section listing files whose magic bytes don't match their
+    /// extension (e.g. a ZIP named .jpg); reads the start of every file
+    #[arg(long)]
+    pub verify_types: bool,
+
+    /// Append a section listing empty directories: `direct` (no entries, the
+    /// default) or `recursive` (no files anywhere beneath)
+    #[arg(
+        long,
+        value_name = "MODE",
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "direct"
+    )]
+    pub empty_dirs: Option<EmptyDirMode>,
+
+    /// Count lines in text files (binary files show `-`), with per-extension totals
+    #[arg(long)]
+    pub lines: bool,
+
+    /// Classify each file's text encoding (UTF-8, UTF-8 with BOM, UTF-16LE/BE,
+    /// 8-bit or binary) from a sample of it, and list the files that aren't UTF-8
     #[arg(long)]
+    pub detect_encoding: bool,
+
+    /// How much of each file --detect-encoding reads (e.g. 4K, 1M)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "detect_encoding")]
+    pub encoding_sample: Option<u64>,
+
+    /// Include each file's permissions: the read-only flag, plus mode bits
+    /// like `rw-r--r--` on Unix. Windows has no mode bits, so there the column
+    /// reads `read-only` or `read-write`
+    #[arg(long)]
+    pub perms: bool,
+
+    /// List files that are hardlinks to the same content, grouped together
+    #[arg(long)]
+    pub hardlinks: bool,
+
+    /// Show each file's Windows attributes as a compact `HSAR` column (hidden,
+    /// system, archive, reparse point; Windows only, ignored with a warning elsewhere)
+    #[arg(long)]
+    pub attributes: bool,
+
+    /// Only include files with this Windows attribute (repeatable: any of them)
+    #[arg(long, value_name = "ATTR", value_enum)]
+    pub only_attr: Vec<FileAttribute>,
+
+    /// Leave out files with this Windows attribute and don't descend into such
+    /// directories (repeatable), e.g. `--skip-attr system`
+    #[arg(long, value_name = "ATTR", value_enum)]
+    pub skip_attr: Vec<FileAttribute>,
+
+    /// Mark executable files: any execute bit on Unix, or an .exe, .bat, .cmd,
+    /// .ps1 or .com extension on Windows
+    #[arg(long)]
+    pub executables: bool,
+
+    /// Include each file's owning user and group, with a per-owner summary (Unix only)
+    #[arg(long)]
+    pub owner: bool,
+
+    /// List each file's extended attribute names, with a per-attribute summary
+    /// (Unix only; ignored with a warning elsewhere)
+    #[arg(long)]
+    pub xattrs: bool,
+
+    /// With --xattrs, also show attribute values (truncated)
+    #[arg(long, requires = "xattrs")]
+    pub xattr_values: bool,
+
+    /// Mark each file as tracked, modified, untracked or ignored in the git
+    /// repository containing FOLDER, with per-status counts (needs git on PATH)
+    #[arg(long)]
+    pub git_status: bool,
+
+    /// Include each file's modification time (ISO 8601, local time)
+    #[arg(long)]
+    pub mtime: bool,
+
+    /// Include each file's creation time where the platform and filesystem record
+    /// it ("unavailable" otherwise)
+    #[arg(long, visible_alias = "ctime")]
+    pub created: bool,
+
+    /// With --mtime or --created, show timestamps in UTC instead of local time
+    #[arg(long)]
+    pub utc: bool,
+
+    /// Output format; repeat or comma-separate to write several from one scan
+    #[arg(long, short = 'f', value_name = "FORMAT", value_enum, value_delimiter = ',')]
+    pub format: Vec<OutputFormat>,
+
+    /// Draw --format tree with ASCII instead of box-drawing characters
+    #[arg(long)]
+    pub ascii: bool,
+
+    /// Prefix each --format flat line with the extension and a tab
+    #[arg(long)]
+    pub with_ext: bool,
+
+    /// Fold extensions with fewer than N files into a trailing "other" group
+    #[arg(long, value_name = "N")]
+    pub min_group: Option<usize>,
+
+    /// With --min-group, omit the small groups instead of folding them
+    #[arg(long, requires = "min_group")]
+    pub drop_small: bool,
+
+    /// Show at most N extension nodes in --format dot/mermaid (the rest become "other")
+    #[arg(long, value_name = "N")]
+    pub graph_limit: Option<usize>,
+
+    /// Shorthand for --format json
+    #[arg(long, conflicts_with_all = ["csv", "format"])]
+    pub json: bool,
+
+    /// Shorthand for --format csv
+    #[arg(long, conflicts_with = "format")]
     pub csv: bool,
+
+    /// Gzip-compress the output (implied when OUTPUT ends in .gz)
+    #[arg(long, short = 'z')]
+    pub compress: bool,
+
+    /// Character encoding of the output file
+    #[arg(long, value_name = "ENC", value_enum, default_value_t)]
+    pub encoding: Encoding,
+
+    /// Line endings in the output file
+    #[arg(long, value_name = "STYLE", value_enum, default_value_t)]
+    pub line_endings: LineEnding,
+
+    /// Colour the text catalog printed to stdout: auto (on a terminal, unless NO_COLOR is set), always or never
+    #[arg(long, value_name = "WHEN", value_enum, default_value_t)]
+    pub color: ColorChoice,
+
+    /// Write one file per extension (plus index.txt) into OUTPUT as a directory
+    #[arg(long)]
+    pub split: bool,
+
+    /// Render output through a template file instead of a built-in format
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath, conflicts_with_all = ["format", "json", "csv"])]
+    pub template: Option<PathBuf>,
 }
 
 impl Cli {
-    /// Resolve the scan options selected on the command line.
-    pub fn scan_options(&self) -> ScanOptions {
-        ScanOptions {
+    /// Resolve the scan options selected on the command line, failing if a
+    /// `--template` file can't be read or parsed or the depth limits are invalid.
+    pub fn scan_options(&self) -> Result<ScanOptions, String> {
+        let template = self.template.as_deref().map(Template::load).transpose()?;
+        let formats = if template.is_some() {
+            vec![OutputFormat::Template]
+        } else {
+            OutputFormat::from_flags(&self.format, self.json, self.csv)
+        };
+        let hash = if formats.iter().any(|f| f.needs_hashes()) {
+            match self.hash {
+                None | Some(HashAlgorithm::Sha256) => Some(HashAlgorithm::Sha256),
+                Some(other) => {
+                    return Err(format!(
+                        "--format sha256sum needs SHA-256 digests, not --hash {}",
+                        other.name()
+                    ));
+                }
+            }
+        } else {
+            self.hash
+        };
+        if self.hash_threads.is_some() && hash.is_none() {
+            return Err("--hash-threads only applies with --hash".to_string());
+        }
+        if self.hash_max_size.is_some() && hash.is_none() && !self.duplicates {
+            return Err("--hash-max-size only applies with --hash or --duplicates".to_string());
+        }
+        if self.hash_threads == Some(0) {
+            return Err("--hash-threads must be at least 1".to_string());
+        }
+        let compound_exts = if self.no_compound_ext {
+            Vec::new()
+        } else {
+            let mut exts: Vec<String> = scan::COMPOUND_EXTENSIONS.iter().map(|e| e.to_string()).collect();
+            for arg in &self.compound_ext {
+                let ext = arg.trim().trim_start_matches('.').to_lowercase();
+                if !ext.contains('.') || ext.starts_with('.') || ext.ends_with('.') {
+                    return Err(format!(
+                        "--compound-ext '{arg}' is not a multi-part extension like tar.lz"
+                    ));
+                }
+                exts.push(format!(".{ext}"));
+            }
+            exts
+        };
+        let mut aliases = ExtAliases::default();
+        if !self.no_alias && !self.preserve_case {
+            aliases = ExtAliases::builtin();
+            for path in &self.alias_file {
+                for (from, to) in read_aliases(path)? {
+                    aliases.insert(&from, &to);
+                }
+            }
+            for (from, to) in &self.alias {
+                aliases.insert(from, to);
+            }
+        }
+        if self.resolve_links && !self.entry_types.contains(&EntryType::Symlink) {
+            return Err("--resolve-links needs symlinks listed with --entry-types symlink".to_string());
+        }
+        if self.shebang && self.group_by != GroupBy::Ext {
+            return Err("--shebang only applies when grouping by extension".to_string());
+        }
+        if self.utc && !self.mtime && !self.created {
+            return Err("--utc only applies to --mtime or --created timestamps".to_string());
+        }
+        let xattrs = self.xattrs && cfg!(unix);
+        if self.xattrs && !xattrs && !self.quiet {
+            eprintln!("Warning: --xattrs is only supported on Unix; ignoring it");
+        }
+        let windows_only = [
+            ("--attributes", self.attributes),
+            ("--only-attr", !self.only_attr.is_empty()),
+            ("--skip-attr", !self.skip_attr.is_empty()),
+        ];
+        if !cfg!(windows) && !self.quiet {
+            for (flag, _) in windows_only.iter().filter(|(_, given)| *given) {
+                eprintln!("Warning: {flag} is only supported on Windows; ignoring it");
+            }
+        }
+        if self.owner && !cfg!(unix) {
+            return Err("--owner is only supported on Unix".to_string());
+        }
+        if self.largest == Some(0) {
+            return Err("--largest must be at least 1".to_string());
+        }
+        if self.limit == Some(0) {
+            return Err("--limit must be at least 1".to_string());
+        }
+        if self.timeout.is_some_and(|timeout| timeout.is_zero()) {
+            return Err("--timeout must be longer than 0".to_string());
+        }
+        if self.max_depth == Some(0) {
+            return Err(
+                "--max-depth must be at least 1 (1 = only files directly inside FOLDER)".to_string(),
+            );
+        }
+        if self.min_depth == Some(0) {
+            return Err("--min-depth must be at least 1 (1 = files directly inside FOLDER)".to_string());
+        }
+        let max_depth = if self.no_recurse { Some(1) } else { self.max_depth };
+        if let (Some(min), Some(max)) = (self.min_depth, max_depth)
+            && min > max
+        {
+            return Err(format!(
+                "--min-depth {min} is greater than the maximum depth {max}, so nothing can match"
+            ));
+        }
+        if self.checkpoint.is_some() && self.perms && !cfg!(unix) {
+            return Err("--checkpoint cannot save --perms on this platform".to_string());
+        }
+        let sizes = self.sizes || template.as_ref().is_some_and(Template::uses_sizes);
+
+        Ok(ScanOptions {
             quiet: self.quiet,
+            verbose: self.verbose,
+            fail_fast: self.fail_fast,
             threads: self.threads,
             follow_symlinks: self.follow_symlinks,
-            max_depth: self.max_depth,
-            sizes: self.sizes,
-            format: OutputFormat::from_flags(self.json, self.csv),
+            one_file_system: self.one_file_system,
+            include_virtual: self.include_virtual,
+            retries: self.retries,
+            retry_delay: self.retry_delay,
+            cache: self.cache.clone(),
+            cache_trust: !self.no_cache_trust,
+            checkpoint: self.checkpoint.clone(),
+            watch: self.watch,
+            debounce: self.debounce,
+            max_depth,
+            min_depth: self.min_depth,
+            limit: self.limit,
+            timeout: self.timeout,
+            dry_run: self.dry_run,
+            summary_only: self.summary_only,
+            sample: self.sample,
+            given: Vec::new(),
+            recursive: !self.no_recurse,
+            show_mime: self.show_mime,
+            group_by: self.group_by,
+            shebang: self.shebang,
+            date_granularity: self.date_granularity,
+            compound_exts,
+            aliases,
+            preserve_case: self.preserve_case,
+            case_variants: !self.no_case_variants,
+            entry_types: self.entry_types.clone(),
+            archives: self.archives.clone(),
+            resolve_links: self.resolve_links,
+            hidden: self.hidden,
+            gitignore: self.gitignore,
+            creepignore: !self.no_creepignore,
+            sizes,
+            disk_usage: self.disk_usage,
+            attributes: self.attributes && cfg!(windows),
+            exact_bytes: self.bytes,
+            hash,
+            hash_threads: self.hash_threads,
+            hash_max_size: self.hash_max_size.filter(|&max| max > 0),
+            duplicates: self.duplicates,
+            duplicates_empty: self.duplicates_empty,
+            lines: self.lines,
+            detect_encoding: self.detect_encoding,
+            encoding_sample: self.encoding_sample.unwrap_or(charset::DEFAULT_SAMPLE).max(1),
+            empty_dirs: self.empty_dirs,
+            largest: self.largest,
+            hardlinks: self.hardlinks,
+            verify_types: self.verify_types,
+            perms: self.perms,
+            owner: self.owner,
+            executables: self.executables,
+            xattrs,
+            xattr_values: xattrs && self.xattr_values,
+            git_status: self.git_status,
+            mtime: self.mtime,
+            created: self.created,
+            utc: self.utc,
+            formats,
+            compress: self.compress,
+            encoding: self.encoding,
+            line_ending: self.line_endings,
+            color: self.color,
+            ascii_tree: self.ascii,
+            flat_ext: self.with_ext,
+            min_group: self.min_group,
+            drop_small: self.drop_small,
+            graph_limit: self.graph_limit,
+            split: self.split,
+            template,
+        })
+    }
+
+    /// Build the include/exclude filter, failing on an unreadable --exclude-from
+    /// file, an invalid glob or regex, an extension
+    /// that is both included and excluded, or an empty size or date range.
+    /// Extensions are matched in the aliased form `options` groups them under.
+    pub fn scan_filter(&self, options: &ScanOptions) -> Result<ScanFilter, String> {
+        let aliases = &options.aliases;
+        let junk = if self.no_junk { category::JUNK_DIRS } else { &[] };
+        let mut exclude = self.exclude.clone();
+        for path in &self.exclude_from {
+            exclude.extend(read_patterns(path)?);
+        }
+        // The empty extension key is the "(no extension)" group.
+        let ext = if self.no_ext_only {
+            vec![String::new()]
+        } else {
+            // A preset minus --exclude-ext is a valid request, not a conflict.
+            let excluded = |e: &str| {
+                let e = aliases.canonical_arg(e);
+                self.exclude_ext.iter().any(|x| aliases.canonical_arg(x) == e)
+            };
+            let presets = self
+                .category
+                .iter()
+                .flat_map(|c| c.extensions())
+                .filter(|e| !excluded(e));
+            self.ext
+                .iter()
+                .map(String::as_str)
+                .chain(presets.copied())
+                .map(|e| aliases.canonical_arg(e))
+                .collect()
+        };
+        let exclude_ext: Vec<String> =
+            self.exclude_ext.iter().map(|e| aliases.canonical_arg(e)).collect();
+        ScanFilter::new(&ext, &exclude_ext, &self.include, &exclude)?
+            .with_skip_empty(self.skip_empty)
+            .with_only(self.only)
+            .with_mime(&self.mime)?
+            .with_excluded_dirs(&self.exclude_dir)?
+            .with_junk_dirs(junk, &self.keep_dir)
+            .with_regexes(&self.regex, &self.regex_exclude, self.ignore_case)?
+            .with_substrings(&self.contains, &self.not_contains, self.case_sensitive)
+            .with_size_range(self.min_size, self.max_size)?
+            .with_time_range(self.newer_than.clone(), self.older_than.clone())
+            .map(|filter| {
+                // Other platforms have no attribute flags to filter on.
+                if cfg!(windows) {
+                    filter.with_attributes(&self.only_attr, &self.skip_attr)
+                } else {
+                    filter
+                }
+            })
+    }
+}
+
+/// The options given on the command line, as typed (`--ext rs,txt`), for
+/// `--dry-run` to show; the folders, the output and the dry run's own options
+/// are left out.
+pub fn given_options(matches: &ArgMatches) -> Vec<String> {
+    Cli::command()
+        .get_arguments()
+        .filter(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .filter_map(|arg| {
+            let long = arg.get_long().filter(|long| !["output", "dry-run", "sample"].contains(long))?;
+            if !arg.get_action().takes_values() {
+                return Some(format!("--{long}"));
+            }
+            let values: Vec<String> = matches
+                .get_raw(arg.get_id().as_str())
+                .into_iter()
+                .flatten()
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect();
+            Some(format!("--{long} {}", values.join(",")))
+        })
+        .collect()
+}
+
+/// Read an `--exclude-from` file: one glob per line, skipping blank lines and `#`
+/// comments. A malformed pattern is reported as `file:line`.
+fn read_patterns(path: &Path) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read --exclude-from file '{}': {e}", path.display()))?;
+    let mut patterns = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let pattern = line.trim();
+        if pattern.is_empty() || pattern.starts_with('#') {
+            continue;
+        }
+        globset::Glob::new(pattern).map_err(|e| {
+            format!("{}:{}: invalid pattern '{pattern}': {e}", path.display(), number + 1)
+        })?;
+        patterns.push(pattern.to_string());
+    }
+    Ok(patterns)
+}
+
+/// Read a `--paths-from` list (`-` for stdin): one folder per line, skipping
+/// blank lines and `#` comments.
+fn read_folders(path: &Path) -> Result<Vec<PathBuf>, String> {
+    let text = if path.as_os_str() == "-" {
+        io::read_to_string(io::stdin())
+    } else {
+        std::fs::read_to_string(path)
+    }
+    .map_err(|e| format!("cannot read --paths-from '{}': {e}", path.display()))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Read a `--files-from` list: one path per line, or per NUL-terminated record
+/// with `--null`. Paths are kept as given, spaces and all; only empty ones are
+/// skipped.
+pub fn read_files(path: &Path, null: bool) -> Result<Vec<PathBuf>, String> {
+    let mut bytes = Vec::new();
+    if path.as_os_str() == "-" {
+        io::stdin().read_to_end(&mut bytes)
+    } else {
+        std::fs::File::open(path).and_then(|mut file| file.read_to_end(&mut bytes))
+    }
+    .map_err(|e| format!("cannot read --files-from '{}': {e}", path.display()))?;
+    let separator = if null { b'\0' } else { b'\n' };
+    Ok(bytes
+        .split(|&b| b == separator)
+        .map(|record| if null { record } else { record.strip_suffix(b"\r").unwrap_or(record) })
+        .filter(|record| !record.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+/// A path from its bytes, as read from a file: exactly on Unix, elsewhere as
+/// UTF-8 (any invalid sequence replaced).
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Read an `--alias-file`: one `FROM=TO` per line, skipping blank lines and `#`
+/// comments. A malformed line is reported as `file:line`.
+fn read_aliases(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read --alias-file '{}': {e}", path.display()))?;
+    let mut aliases = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let alias = parse_alias(line)
+            .map_err(|e| format!("{}:{}: {e}", path.display(), number + 1))?;
+        aliases.push(alias);
+    }
+    Ok(aliases)
+}
+
+/// Parse an alias such as `jpeg=jpg` (dots and case don't matter).
+fn parse_alias(arg: &str) -> Result<(String, String), String> {
+    let Some((from, to)) = arg.split_once('=') else {
+        return Err(format!("invalid alias '{arg}': expected FROM=TO, e.g. jpeg=jpg"));
+    };
+    let (from, to) = (from.trim(), to.trim());
+    let bare = |ext: &str| ext.trim_start_matches('.').to_lowercase();
+    if bare(from).is_empty() || bare(to).is_empty() {
+        return Err(format!("invalid alias '{arg}': both sides need an extension"));
+    }
+    if bare(from) == bare(to) {
+        return Err(format!("invalid alias '{arg}': an extension can't alias itself"));
+    }
+    Ok((from.to_string(), to.to_string()))
+}
+
+/// Parse a size such as `4096`, `500K`, `1.5M` or `2GiB`. Units are binary
+/// (K = 1024 bytes) and case-insensitive, with an optional `B`/`iB`; a lowercase
+/// `b` alone (bits, as in `10Mb`) is rejected as ambiguous.
+fn parse_size(arg: &str) -> Result<u64, String> {
+    let arg = arg.trim();
+    let split = arg
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(arg.len());
+    let (number, unit) = arg.split_at(split);
+
+    let exponent = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" if unit != "b" => 0,
+        "K" | "KB" | "KIB" => 1,
+        "M" | "MB" | "MIB" => 2,
+        "G" | "GB" | "GIB" => 3,
+        "T" | "TB" | "TIB" => 4,
+        _ => {
+            return Err(format!(
+                "unknown size unit '{unit}' (use bytes or K, M, G, T, e.g. 500K or 2G)"
+            ));
         }
+    };
+    if unit.len() > 1 && unit.ends_with('b') {
+        return Err(format!("ambiguous size unit '{unit}': use '{}B' for bytes", &unit[..1]));
     }
 
-    /// Build the include/exclude filter, failing on an invalid glob.
-    pub fn scan_filter(&self) -> Result<ScanFilter, String> {
-        ScanFilter::new(&self.ext, &self.exclude)
+    let scale = 1024u64.pow(exponent);
+    if exponent == 0 || !number.contains('.') {
+        return number
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(scale))
+            .ok_or_else(|| format!("invalid size '{arg}' (expected e.g. 4096, 500K or 2G)"));
     }
+    match number.parse::<f64>() {
+        Ok(n) if n.is_finite() && n * (scale as f64) < u64::MAX as f64 => {
+            Ok((n * scale as f64).round() as u64)
+        }
+        _ => Err(format!("invalid size '{arg}' (expected e.g. 4096, 500K or 1.5G)")),
+    }
+}
+
+/// Parse a `--newer-than`/`--older-than` value, keeping the argument.
+fn parse_bound(arg: &str) -> Result<TimeBound, String> {
+    Ok(TimeBound { time: parse_time(arg)?, arg: arg.trim().to_string() })
 }
 
-/// Resolve the final output file path given the scanned folder and chosen format.
+/// Parse a point in time: a UTC date (`2024-01-01`, optionally `T13:45[:00]`) or
+/// a duration before now in seconds, minutes, hours, days, weeks or 365-day years
+/// (`90s`, `30m`, `12h`, `7d`, `3w`, `2y`).
+fn parse_time(arg: &str) -> Result<SystemTime, String> {
+    let arg = arg.trim();
+    if let Some(duration) = duration_arg(arg) {
+        return SystemTime::now()
+            .checked_sub(duration?)
+            .ok_or_else(|| format!("duration '{arg}' is too large"));
+    }
+
+    let invalid = || format!("invalid date '{arg}' (expected YYYY-MM-DD[THH:MM[:SS]] or e.g. 7d)");
+    let (date, time) = arg.split_once(['T', ' ']).unwrap_or((arg, ""));
+    let mut date_parts = date.splitn(3, '-');
+    let (Some(y), Some(m), Some(d)) = (date_parts.next(), date_parts.next(), date_parts.next())
+    else {
+        return Err(invalid());
+    };
+    let year: i32 = y.parse().map_err(|_| invalid())?;
+    let month: u32 = m.parse().map_err(|_| invalid())?;
+    let day: u32 = d.parse().map_err(|_| invalid())?;
+    let midnight = NaiveDate::from_ymd_opt(year, month, day).ok_or_else(invalid)?.and_time(NaiveTime::MIN);
+
+    let mut secs_of_day = 0;
+    if !time.is_empty() {
+        let fields: Vec<&str> = time.trim_end_matches('Z').split(':').collect();
+        if !(2..=3).contains(&fields.len()) {
+            return Err(invalid());
+        }
+        let limits = [24, 60, 60];
+        for (field, limit) in fields.iter().zip(limits) {
+            let value: i64 = field.parse().map_err(|_| invalid())?;
+            if !(0..limit).contains(&value) {
+                return Err(invalid());
+            }
+            secs_of_day = secs_of_day * 60 + value;
+        }
+        if fields.len() == 2 {
+            secs_of_day *= 60;
+        }
+    }
+
+    let secs = midnight.and_utc().timestamp() + secs_of_day;
+    let offset = Duration::from_secs(secs.unsigned_abs());
+    if secs >= 0 {
+        UNIX_EPOCH.checked_add(offset)
+    } else {
+        UNIX_EPOCH.checked_sub(offset)
+    }
+    .ok_or_else(invalid)
+}
+
+/// Parse a duration in milliseconds, seconds, minutes, hours, days, weeks or
+/// 365-day years (`500ms`, `2s`, `30m`, `12h`, `7d`, `3w`, `2y`).
+fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let arg = arg.trim();
+    duration_arg(arg).unwrap_or_else(|| Err(format!("invalid duration '{arg}' (expected e.g. 2s or 5m)")))
+}
+
+/// `arg` read as a duration, if it is shaped like one: digits, then `ms` or a
+/// unit letter.
+fn duration_arg(arg: &str) -> Option<Result<Duration, String>> {
+    if let Some(millis) = arg.strip_suffix("ms")
+        && !millis.is_empty()
+        && millis.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Some(millis.parse().map(Duration::from_millis).map_err(|_| format!("duration '{arg}' is too large")));
+    }
+    let unit = arg.chars().last().filter(|c| c.is_ascii_alphabetic())?;
+    if arg.len() < 2 || !arg[..arg.len() - 1].bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let too_large = || format!("duration '{arg}' is too large");
+    let Ok(n) = arg[..arg.len() - 1].parse::<u64>() else {
+        return Some(Err(too_large()));
+    };
+    let unit_secs = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86_400,
+        'w' => 7 * 86_400,
+        'y' => 365 * 86_400,
+        _ => {
+            return Some(Err(format!(
+                "unknown duration unit '{unit}' (use ms, s, m, h, d, w or y, e.g. 7d)"
+            )));
+        }
+    };
+    Some(n.checked_mul(unit_secs).map(Duration::from_secs).ok_or_else(too_large))
+}
+
+impl Cli {
+    /// The folders to scan and the output path, if any: every positional path is
+    /// a folder when `-o` is given, otherwise `FOLDER [OUTPUT]`, where `OUTPUT`
+    /// can't be an existing directory (nor, for a file `FOLDER`, an existing file).
+    pub fn folders_and_output(&self) -> Result<(Vec<PathBuf>, Option<PathBuf>), String> {
+        let several = || "give the output with -o/--output to scan more than one folder".to_string();
+        if self.files_from.is_some() {
+            let root = self.root.clone().unwrap_or_else(|| PathBuf::from("."));
+            return Ok((vec![root], self.output.clone()));
+        }
+        if let Some(list) = &self.paths_from {
+            let folders = read_folders(list)?;
+            if folders.is_empty() {
+                return Err(format!("no folders listed in --paths-from '{}'", list.display()));
+            }
+            if folders.len() > 1 && self.output.is_none() {
+                return Err(several());
+            }
+            return Ok((folders, self.output.clone()));
+        }
+        if self.output.is_some() {
+            return Ok((self.paths.clone(), self.output.clone()));
+        }
+        match &self.paths[..] {
+            [] | [_] => Ok((self.paths.clone(), None)),
+            // Most likely a shell glob of files rather than FILE OUTPUT; don't
+            // overwrite the second one with the catalog of the first.
+            [folder, output] if folder.is_file() && output.is_file() => Err(format!(
+                "'{}' and '{}' are both files; give the output with -o/--output to catalog both, \
+                 or to write the catalog over an existing file",
+                folder.display(),
+                output.display()
+            )),
+            // Two folders, not FOLDER OUTPUT: writing one's catalog into the other
+            // is never what was meant.
+            [folder, output] if output.is_dir() => Err(format!(
+                "'{}' and '{}' would be the folder and the output; give the output with \
+                 -o/--output to scan both, or to write the catalog into a directory",
+                folder.display(),
+                output.display()
+            )),
+            [folder, output] => Ok((vec![folder.clone()], Some(output.clone()))),
+            _ => Err(several()),
+        }
+    }
+}
+
+/// Resolve the final output file path given the scanned folders and output options.
 ///
-/// If `output` is an existing directory, a `<folder>.<ext>` file is placed inside it.
-/// If `output` is `None`, the file is written into the scanned folder.
+/// If `output` is an existing directory, a `<name>.<ext>` file is placed inside it
+/// (see [`Roots::name`]). If `output` is `None`, the file is written into the
+/// scanned folder. `-` is kept as-is and means stdout. With `--split` the result
+/// is a directory instead: `output` itself, or `<folder>/<name>-catalog` by default.
 pub fn resolve_output_path(
     output: Option<PathBuf>,
-    folder: &Path,
-    format: OutputFormat,
+    roots: &Roots,
+    options: &ScanOptions,
 ) -> PathBuf {
-    let ext = format.extension();
+    let ext = &options.file_extension();
+    if options.split {
+        return match output {
+            Some(output) => normalize_path(output),
+            None => roots.base.join(format!("{}-catalog", roots.name())),
+        };
+    }
     match output {
+        Some(output) if output::is_stdout(&output) => output,
         Some(output) => {
             let normalized = normalize_path(output);
             if normalized.is_dir() {
-                normalized.join(default_filename(folder, ext))
+                normalized.join(default_filename(roots, ext))
             } else {
                 normalized
             }
         }
-        None => folder.join(default_filename(folder, ext)),
+        None => roots.base.join(default_filename(roots, ext)),
+    }
+}
+
+/// Path for one of several formats written from one scan: `output` with its
+/// extension (and any `.gz`) replaced, e.g. `inventory.txt` -> `inventory.json`.
+pub fn sibling_path(output: &Path, format: OutputFormat, compress: bool) -> PathBuf {
+    let mut path = output.to_path_buf();
+    if output::has_gz_suffix(&path) {
+        path.set_extension("");
     }
+    path.set_extension(format.file_extension(compress));
+    path
 }
 
-/// Build the default output filename (`<folder>.<ext>`) for a scanned folder.
-pub fn default_filename(folder: &Path, ext: &str) -> String {
-    let name = folder
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("output");
-    format!("{name}.{ext}")
+/// Build the default output filename (`<name>.<ext>`) for the scanned folders.
+pub fn default_filename(roots: &Roots, ext: &str) -> String {
+    format!("{}.{ext}", roots.name())
 }
 
 /// Normalize and resolve a path to work cross-platform.
@@ -150,3 +1211,83 @@ pub fn normalize_path(path: PathBuf) -> PathBuf {
     }
     path
 }
+
+/// The options and filter that `args` (flags only, no program name) select, for
+/// tests.
+#[cfg(test)]
+pub fn parse_for_test(args: &[&str]) -> (ScanOptions, ScanFilter) {
+    let cli = Cli::try_parse_from(std::iter::once("CreepDir").chain(args.iter().copied()))
+        .unwrap_or_else(|e| panic!("{e}"));
+    let options = cli.scan_options().unwrap();
+    let filter = cli.scan_filter(&options).unwrap();
+    (options, filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(arg: &str) -> Result<i64, String> {
+        parse_time(arg).map(|time| match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => after.as_secs() as i64,
+            Err(before) => -(before.duration().as_secs() as i64),
+        })
+    }
+
+    #[test]
+    fn dates_are_utc_midnights_unless_a_time_is_given() {
+        assert_eq!(secs("1970-01-01"), Ok(0));
+        assert_eq!(secs("2024-02-29"), Ok(1_709_164_800));
+        assert_eq!(secs("2024-02-29T13:45"), Ok(1_709_164_800 + 13 * 3600 + 45 * 60));
+        assert_eq!(secs("2024-02-29 13:45:30Z"), Ok(1_709_164_800 + 13 * 3600 + 45 * 60 + 30));
+        assert_eq!(secs("1969-12-31T23:59:59"), Ok(-1));
+    }
+
+    #[test]
+    fn impossible_dates_and_times_are_rejected() {
+        for arg in ["2023-02-29", "2024-13-01", "2024-04-31", "2024-01-01T24:00", "2024-01-01T12", "soon"] {
+            assert!(secs(arg).is_err(), "{arg}");
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn a_leading_slash_on_windows_is_relative_to_the_current_directory() {
+        let cwd = env::current_dir().unwrap();
+        assert_eq!(normalize_path(PathBuf::from("/photos")), cwd.join("photos"));
+        assert_eq!(normalize_path(PathBuf::from(r"C:\photos")), PathBuf::from(r"C:\photos"));
+        assert_eq!(normalize_path(PathBuf::from(r"\\server\share")), PathBuf::from(r"\\server\share"));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn relative_paths_are_resolved_against_the_current_directory() {
+        let cwd = env::current_dir().unwrap();
+        assert_eq!(normalize_path(PathBuf::from("photos")), cwd.join("photos"));
+        assert_eq!(normalize_path(PathBuf::from("/photos")), PathBuf::from("/photos"));
+    }
+
+    fn folders_and_output(args: &[&Path]) -> Result<(Vec<PathBuf>, Option<PathBuf>), String> {
+        let args = std::iter::once(Path::new("CreepDir").as_os_str()).chain(args.iter().map(|p| p.as_os_str()));
+        Cli::try_parse_from(args).unwrap().folders_and_output()
+    }
+
+    #[test]
+    fn a_second_folder_is_not_taken_as_the_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        std::fs::create_dir(&a).unwrap();
+        std::fs::create_dir(&b).unwrap();
+        assert!(folders_and_output(&[&a, &b]).is_err());
+        let out = dir.path().join("out.txt");
+        assert_eq!(folders_and_output(&[&a, &out]), Ok((vec![a.clone()], Some(out.clone()))));
+        assert_eq!(folders_and_output(&[&a, &b, "-o".as_ref(), &out]), Ok((vec![a, b], Some(out))));
+    }
+
+    #[test]
+    fn a_dry_run_shows_the_options_as_given() {
+        let args = ["CreepDir", "src", "--ext", "rs,txt", "--mtime", "--dry-run", "-o", "out.txt", "--ext", "md"];
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        assert_eq!(given_options(&matches), ["--ext rs,txt,md", "--mtime"]);
+    }
+}
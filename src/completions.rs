@@ -0,0 +1,17 @@
+//! `--completions SHELL`: print a completion script for bash, zsh, fish,
+//! PowerShell or elvish, generated by `clap_complete` from the command-line
+//! definition so it never falls behind the flags. Options naming a file or
+//! folder complete paths (by their value hints), options with a fixed set of
+//! values (such as `--format` and `--group-by`) complete those values.
+
+use crate::cli::Cli;
+use clap::CommandFactory;
+use clap_complete::Shell;
+use std::io;
+
+/// Print the completion script for `shell` to stdout.
+pub fn print(shell: Shell) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+}
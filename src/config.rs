@@ -1,25 +1,62 @@
 //! Configuration types shared across the scanner and output writers.
 
+use crate::hash::hex;
+use crate::output::{iso8601_utc, template::Template};
+use clap::ValueEnum;
 use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::{RegexSet, RegexSetBuilder};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 /// Output format for the generated catalog.
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum OutputFormat {
     Text,
     Json,
     Csv,
+    Markdown,
+    Html,
+    Yaml,
+    Xml,
+    Sqlite,
+    Toml,
+    Ndjson,
+    Tree,
+    Flat,
+    Sha256sum,
+    Dot,
+    Mermaid,
+    /// Rendered through a `--template` file; not selectable via `--format`.
+    #[value(skip)]
+    Template,
 }
 
 impl OutputFormat {
-    /// Choose a format from the mutually-exclusive `--json` / `--csv` flags.
-    pub fn from_flags(json: bool, csv: bool) -> Self {
-        if json {
-            OutputFormat::Json
+    /// Whether rendering this format needs a content digest for every file.
+    pub fn needs_hashes(self) -> bool {
+        self == OutputFormat::Sha256sum
+    }
+
+    /// Choose formats from `--format` (repeatable, first use wins on duplicates),
+    /// falling back to the `--json` / `--csv` shorthands.
+    pub fn from_flags(formats: &[OutputFormat], json: bool, csv: bool) -> Vec<Self> {
+        if !formats.is_empty() {
+            let mut unique = Vec::with_capacity(formats.len());
+            for &format in formats {
+                if !unique.contains(&format) {
+                    unique.push(format);
+                }
+            }
+            unique
+        } else if json {
+            vec![OutputFormat::Json]
         } else if csv {
-            OutputFormat::Csv
+            vec![OutputFormat::Csv]
         } else {
-            OutputFormat::Text
+            vec![OutputFormat::Text]
         }
     }
 
@@ -29,79 +66,1269 @@ impl OutputFormat {
             OutputFormat::Text => "txt",
             OutputFormat::Json => "json",
             OutputFormat::Csv => "csv",
+            OutputFormat::Markdown => "md",
+            OutputFormat::Html => "html",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Xml => "xml",
+            OutputFormat::Sqlite => "sqlite",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Tree | OutputFormat::Flat | OutputFormat::Template => "txt",
+            OutputFormat::Sha256sum => "sha256",
+            OutputFormat::Dot => "dot",
+            OutputFormat::Mermaid => "mmd",
+        }
+    }
+
+    /// Extension for auto-named output files, e.g. `json` or `json.gz`.
+    pub fn file_extension(self, compress: bool) -> String {
+        if compress {
+            format!("{}.gz", self.extension())
+        } else {
+            self.extension().to_string()
+        }
+    }
+}
+
+/// Character encoding of text output.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Encoding {
+    /// Plain UTF-8.
+    #[default]
+    Utf8,
+    /// UTF-8 with a leading byte-order mark (helps Excel and older Windows tools).
+    #[value(name = "utf8-bom")]
+    Utf8Bom,
+    /// UTF-16 little-endian with a byte-order mark.
+    Utf16le,
+}
+
+/// Kind of filesystem entry listed in the catalog.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EntryType {
+    File,
+    /// Directories, grouped under `<dir>`.
+    Dir,
+    /// Symlinks (including dangling ones), grouped under `<symlink>` with their target.
+    Symlink,
+}
+
+/// Archive format whose contents `--archives` catalogs.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ArchiveFormat {
+    Zip,
+    /// Tar archives, plain or compressed with gzip, xz or zstd (`.tar`, `.tar.gz`,
+    /// `.tgz`, `.tar.xz`, `.txz`, `.tar.zst`, `.tzst`).
+    Tar,
+}
+
+/// Content digest algorithm selected by `--hash`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha1,
+    Md5,
+    Blake3,
+    /// Fast 32-bit checksum for change detection; not an integrity hash.
+    Crc32,
+    /// Fast 64-bit XXH3 fingerprint for change detection; not an integrity hash.
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    /// Lowercase name, as written to structured output.
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Crc32 => "crc32",
+            HashAlgorithm::Xxh3 => "xxh3",
         }
     }
+
+    /// Whether equal digests can be trusted to mean equal contents. The fast
+    /// checksums collide far too easily for that; they only flag changes.
+    pub fn is_cryptographic(self) -> bool {
+        !matches!(self, HashAlgorithm::Crc32 | HashAlgorithm::Xxh3)
+    }
+}
+
+/// File content class selected by `--only`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ContentKind {
+    /// No NUL bytes near the start (or a UTF-16/32 byte-order mark).
+    Text,
+    Binary,
+}
+
+/// How catalogued files are grouped, selected by `--group-by`.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum GroupBy {
+    /// File name extension.
+    #[default]
+    Ext,
+    /// MIME type sniffed from the file's magic bytes (reads every file).
+    Mime,
+    /// Images by EXIF capture date (modification date, marked, without one);
+    /// everything else under "other".
+    ExifDate,
+    /// Two groups, executable and not (by extension on Windows).
+    Exec,
+}
+
+/// How finely `--group-by exif-date` buckets dates.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum DateGranularity {
+    Year,
+    #[default]
+    Month,
+}
+
+/// Which directories `--empty-dirs` reports.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EmptyDirMode {
+    /// Directories with no entries at all.
+    Direct,
+    /// Directories with no files anywhere beneath them (only empty subdirectories).
+    Recursive,
+}
+
+/// Whether hidden entries are catalogued.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Hidden {
+    #[default]
+    Include,
+    /// Skip dot-prefixed entries (and, on Windows, those with the hidden attribute).
+    Exclude,
+}
+
+/// A Windows file attribute, for `--only-attr` and `--skip-attr`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FileAttribute {
+    Hidden,
+    System,
+    Archive,
+    /// A reparse point: a symlink, junction, or cloud placeholder.
+    Reparse,
+}
+
+impl FileAttribute {
+    /// The `FILE_ATTRIBUTE_*` flag for this attribute.
+    pub fn bit(self) -> u32 {
+        match self {
+            FileAttribute::Hidden => 0x2,
+            FileAttribute::System => 0x4,
+            FileAttribute::Archive => 0x20,
+            FileAttribute::Reparse => 0x400,
+        }
+    }
+}
+
+/// Line terminator used in text output.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    /// CRLF on Windows, LF elsewhere.
+    #[default]
+    Native,
+}
+
+impl LineEnding {
+    /// Whether lines end in `\r\n` on this platform.
+    pub fn is_crlf(self) -> bool {
+        match self {
+            LineEnding::Lf => false,
+            LineEnding::Crlf => true,
+            LineEnding::Native => cfg!(windows),
+        }
+    }
+
+    /// The bytes that end a line.
+    pub fn terminator(self) -> &'static str {
+        if self.is_crlf() { "\r\n" } else { "\n" }
+    }
+}
+
+/// When the text catalog is coloured, selected by `--color`.
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// When printed to a terminal and `NO_COLOR` isn't set.
+    #[default]
+    Auto,
+    Always,
+    Never,
 }
 
 /// Options that control how a scan is performed.
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct ScanOptions {
-    /// Suppress per-folder warnings for inaccessible paths.
+    /// Print nothing but errors (`--quiet`).
     pub quiet: bool,
+    /// How much to log to stderr: 1 for each directory entered and timings, 2
+    /// for every entry left out too (`-v`, `-vv`).
+    pub verbose: u8,
+    /// Stop at the first unreadable entry instead of skipping it.
+    pub fail_fast: bool,
     /// Number of worker threads (`None` = one per CPU core).
     pub threads: Option<usize>,
-    /// Follow symlinks/junctions (jwalk reports loops as errors).
+    /// Follow symlinks/junctions, skipping links that lead back up the tree.
     pub follow_symlinks: bool,
-    /// Maximum recursion depth (`None` = unlimited). Depth 0 is the root itself.
+    /// Don't descend into directories on another device or volume than the folder.
+    pub one_file_system: bool,
+    /// Walk virtual filesystems (`/proc`, `/sys`, ...) too (`--include-virtual`).
+    pub include_virtual: bool,
+    /// Extra attempts for directory listings and metadata reads that fail with a
+    /// transient error (`--retries`).
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each one after it.
+    pub retry_delay: Duration,
+    /// Directory listings kept between runs, with `--cache`.
+    pub cache: Option<PathBuf>,
+    /// Serve directories whose modification time is unchanged from the cache
+    /// (off with `--no-cache-trust`).
+    pub cache_trust: bool,
+    /// Where the scan's progress is saved, with `--checkpoint`.
+    pub checkpoint: Option<PathBuf>,
+    /// Keep rescanning and rewriting the output on changes, with `--watch`.
+    pub watch: bool,
+    /// How long changes must settle before a `--watch` rescan.
+    pub debounce: Duration,
+    /// Maximum recursion depth (`None` = unlimited). Depth 1 is the files directly
+    /// inside the root; a followed symlinked directory counts as one level like any other.
     pub max_depth: Option<usize>,
+    /// Whether hidden files and directories are scanned.
+    pub hidden: Hidden,
+    /// Honor `.gitignore` files and `.git/info/exclude`, and skip `.git` itself.
+    pub gitignore: bool,
+    /// Record each file's sniffed MIME type in the catalog.
+    pub show_mime: bool,
+    /// Which kinds of entries are catalogued (never empty).
+    pub entry_types: Vec<EntryType>,
+    /// Archive formats opened to catalog their entries too, with `--archives`.
+    pub archives: Vec<ArchiveFormat>,
+    /// Only catalog files at least this deep (1 = directly inside the root); shallower
+    /// directories are still walked.
+    pub min_depth: Option<usize>,
+    /// Stop the walk once this many entries are catalogued (`--limit`).
+    pub limit: Option<u64>,
+    /// Stop the walk once this long has passed since it began (`--timeout`).
+    pub timeout: Option<Duration>,
+    /// Say what the scan would do instead of scanning (`--dry-run`).
+    pub dry_run: bool,
+    /// Print the summary without writing a catalog (`--summary-only`).
+    pub summary_only: bool,
+    /// With `--dry-run`, how many top-level files to list as a sample.
+    pub sample: Option<usize>,
+    /// The options given on the command line, as typed, for `--dry-run` to show.
+    pub given: Vec<String>,
+    /// False for `--no-recurse` (which sets `max_depth` to 1), so the summary can say so.
+    pub recursive: bool,
+    /// Honor `.creepignore` files in the scanned tree (off with `--no-creepignore`).
+    pub creepignore: bool,
     /// Collect file sizes (costs one metadata call per file).
     pub sizes: bool,
-    /// Output format.
-    pub format: OutputFormat,
+    /// Also collect allocated (on-disk) sizes (`--disk-usage`).
+    pub disk_usage: bool,
+    /// Record each file's Windows attribute flags (`--attributes`, Windows only).
+    pub attributes: bool,
+    /// Also record each listed symlink's canonical target (`--resolve-links`).
+    pub resolve_links: bool,
+    /// Multi-part extensions such as `.tar.gz` that group as one (lowercase,
+    /// dot-prefixed); empty with `--no-compound-ext`.
+    pub compound_exts: Vec<String>,
+    /// Extension variants grouped under one canonical key (`.jpeg` under `.jpg`);
+    /// empty with `--no-alias`.
+    pub aliases: ExtAliases,
+    /// Group by the extension as spelled (`.JPG` apart from `.jpg`), without aliases.
+    pub preserve_case: bool,
+    /// Report extensions found in more than one casing (off with `--no-case-variants`).
+    pub case_variants: bool,
+    /// Grouping key for the catalog.
+    pub group_by: GroupBy,
+    /// Group extensionless scripts by interpreter (`--shebang`).
+    pub shebang: bool,
+    /// Date buckets for [`GroupBy::ExifDate`].
+    pub date_granularity: DateGranularity,
+    /// Digest every catalogued file (`--hash`, or SHA-256 for `--format sha256sum`).
+    pub hash: Option<HashAlgorithm>,
+    /// Threads hashing files during the walk (`--hash-threads`; `None` = `threads`).
+    pub hash_threads: Option<usize>,
+    /// Files larger than this many bytes are neither hashed nor checked for
+    /// duplicates (`--hash-max-size`; `None`, or 0 on the command line, for no limit).
+    pub hash_max_size: Option<u64>,
+    /// Report groups of identical files (`--duplicates`).
+    pub duplicates: bool,
+    /// Let zero-byte files form duplicate groups too.
+    pub duplicates_empty: bool,
+    /// List the paths that share content through hardlinks (`--hardlinks`).
+    pub hardlinks: bool,
+    /// Report the N biggest matched files (`--largest`).
+    pub largest: Option<usize>,
+    /// Report files whose content doesn't match their extension (`--verify-types`).
+    pub verify_types: bool,
+    /// Report empty directories (`--empty-dirs`).
+    pub empty_dirs: Option<EmptyDirMode>,
+    /// Count lines in text files (`--lines`).
+    pub lines: bool,
+    /// Classify each text file's encoding (`--detect-encoding`).
+    pub detect_encoding: bool,
+    /// Bytes sampled from each file for `--detect-encoding` (`--encoding-sample`).
+    pub encoding_sample: u64,
+    /// Record each file's permissions (`--perms`).
+    pub perms: bool,
+    /// Mark executable files (`--executables`; by extension on Windows).
+    pub executables: bool,
+    /// Record each file's owning user and group (`--owner`, Unix only).
+    pub owner: bool,
+    /// Record each file's extended attribute names (`--xattrs`, Unix only).
+    pub xattrs: bool,
+    /// Also record the attribute values, truncated (`--xattr-values`).
+    pub xattr_values: bool,
+    /// Record each file's status in the enclosing git repository (`--git-status`).
+    pub git_status: bool,
+    /// Record each file's modification time (`--mtime`).
+    pub mtime: bool,
+    /// Record each file's creation time where available (`--created`).
+    pub created: bool,
+    /// Show `--mtime` and `--created` timestamps in UTC rather than local time.
+    pub utc: bool,
+    /// Show sizes as exact byte counts (`--bytes`) rather than KiB/MiB.
+    pub exact_bytes: bool,
+    /// Output formats, rendered from a single scan (never empty).
+    pub formats: Vec<OutputFormat>,
+    /// Gzip the output (also implied by a `.gz` output path).
+    pub compress: bool,
+    /// Character encoding applied to the rendered output.
+    pub encoding: Encoding,
+    /// Line terminator applied to the rendered output.
+    pub line_ending: LineEnding,
+    /// Colour the text catalog printed to stdout (`--color`).
+    pub color: ColorChoice,
+    /// Draw `--format tree` with ASCII instead of box-drawing characters.
+    pub ascii_tree: bool,
+    /// Prefix each `--format flat` line with its extension and a tab.
+    pub flat_ext: bool,
+    /// Fold extension groups with fewer files than this into one "other" group.
+    pub min_group: Option<usize>,
+    /// Omit the small groups instead of folding them.
+    pub drop_small: bool,
+    /// Cap on extension nodes in `--format dot`/`mermaid`; the rest become "other".
+    pub graph_limit: Option<usize>,
+    /// Write one file per extension into a directory instead of a single file.
+    pub split: bool,
+    /// Custom layout from `--template` (implies [`OutputFormat::Template`]).
+    pub template: Option<Template>,
+}
+
+impl ScanOptions {
+    /// The first requested format, which names auto-generated output paths.
+    pub fn primary_format(&self) -> OutputFormat {
+        self.formats[0]
+    }
+
+    /// Extension for auto-named output files of the primary format.
+    pub fn file_extension(&self) -> String {
+        self.primary_format().file_extension(self.compress)
+    }
+
+    /// Whether symlinks are listed as entries (`--entry-types symlink`).
+    pub fn lists_links(&self) -> bool {
+        self.entry_types.contains(&EntryType::Symlink)
+    }
+
+    /// Whether hardlinks are recognized, so sizes count shared content once.
+    pub fn tracks_hardlinks(&self) -> bool {
+        self.sizes || self.hardlinks
+    }
+
+    /// Whether the requested output needs each file's metadata (one extra call
+    /// per file).
+    pub fn needs_metadata(&self) -> bool {
+        self.sizes
+            || self.mtime
+            || self.created
+            || self.perms
+            || self.owner
+            || self.hardlinks
+            || self.largest.is_some()
+            || self.group_by == GroupBy::ExifDate
+            || self.tracks_executables()
+    }
+
+    /// Whether each file is checked for being executable.
+    pub fn tracks_executables(&self) -> bool {
+        self.executables || self.group_by == GroupBy::Exec
+    }
+
+    /// The options that decide which entries the scan finds and what it records
+    /// about each, one `name=value` per line, to tell whether a `--checkpoint`
+    /// belongs to it. Where and how the catalog is written, how the walk runs
+    /// and what is worked out after it may all change between runs.
+    pub fn scan_key(&self) -> String {
+        // Every field is named, so a new one has to be placed on one side or the
+        // other. `--cache`, `--watch`, `--limit` and `--empty-dirs` can't be
+        // given with `--checkpoint`; `--template` only matters through `sizes`.
+        let ScanOptions {
+            quiet: _,
+            verbose: _,
+            fail_fast,
+            threads: _,
+            follow_symlinks,
+            one_file_system,
+            include_virtual,
+            retries: _,
+            retry_delay: _,
+            cache: _,
+            cache_trust: _,
+            checkpoint: _,
+            watch: _,
+            debounce: _,
+            max_depth,
+            hidden,
+            gitignore,
+            show_mime,
+            entry_types,
+            archives,
+            min_depth,
+            limit: _,
+            timeout: _,
+            dry_run: _,
+            summary_only: _,
+            sample: _,
+            given: _,
+            recursive: _,
+            creepignore,
+            sizes,
+            disk_usage,
+            attributes,
+            resolve_links,
+            compound_exts,
+            aliases,
+            preserve_case,
+            case_variants,
+            group_by,
+            shebang,
+            date_granularity,
+            hash,
+            hash_threads: _,
+            hash_max_size,
+            duplicates: _,
+            duplicates_empty: _,
+            hardlinks,
+            largest,
+            verify_types,
+            empty_dirs: _,
+            lines: _,
+            detect_encoding: _,
+            encoding_sample: _,
+            perms,
+            executables,
+            owner,
+            xattrs,
+            xattr_values,
+            git_status,
+            mtime,
+            created,
+            utc: _,
+            exact_bytes: _,
+            formats: _,
+            compress: _,
+            encoding: _,
+            line_ending: _,
+            color: _,
+            ascii_tree: _,
+            flat_ext: _,
+            min_group: _,
+            drop_small: _,
+            graph_limit: _,
+            split: _,
+            template: _,
+        } = self;
+        let aliases = aliases.0.iter().map(|(from, to)| format!("{from}={to}"));
+        [
+            ("fail_fast", fail_fast.to_string()),
+            ("follow_symlinks", follow_symlinks.to_string()),
+            ("one_file_system", one_file_system.to_string()),
+            ("include_virtual", include_virtual.to_string()),
+            ("max_depth", key_value(*max_depth)),
+            ("hidden", key_name(hidden)),
+            ("gitignore", gitignore.to_string()),
+            ("show_mime", show_mime.to_string()),
+            ("entry_types", key_names(entry_types)),
+            ("archives", key_names(archives)),
+            ("min_depth", key_value(*min_depth)),
+            ("creepignore", creepignore.to_string()),
+            ("sizes", sizes.to_string()),
+            ("disk_usage", disk_usage.to_string()),
+            ("attributes", attributes.to_string()),
+            ("resolve_links", resolve_links.to_string()),
+            ("compound_exts", key_list(compound_exts.iter().cloned())),
+            ("aliases", key_digest(aliases)),
+            ("preserve_case", preserve_case.to_string()),
+            ("case_variants", case_variants.to_string()),
+            ("group_by", key_name(group_by)),
+            ("shebang", shebang.to_string()),
+            ("date_granularity", key_name(date_granularity)),
+            ("hash", hash.as_ref().map_or_else(|| "none".to_string(), key_name)),
+            ("hash_max_size", key_value(*hash_max_size)),
+            ("hardlinks", hardlinks.to_string()),
+            ("largest", key_value(*largest)),
+            ("verify_types", verify_types.to_string()),
+            ("perms", perms.to_string()),
+            ("executables", executables.to_string()),
+            ("owner", owner.to_string()),
+            ("xattrs", xattrs.to_string()),
+            ("xattr_values", xattr_values.to_string()),
+            ("git_status", git_status.to_string()),
+            ("mtime", mtime.to_string()),
+            ("created", created.to_string()),
+        ]
+        .map(|(name, value)| format!("{name}={value}"))
+        .join("\n")
+    }
+}
+
+/// An optional value in a scan key.
+fn key_value(value: Option<impl std::fmt::Display>) -> String {
+    value.map_or_else(|| "none".to_string(), |value| value.to_string())
+}
+
+/// An option's value as spelled on the command line, in a scan key.
+fn key_name(value: &impl ValueEnum) -> String {
+    value.to_possible_value().map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+/// A set of option values in a scan key, however they were ordered.
+fn key_names(values: &[impl ValueEnum]) -> String {
+    key_list(values.iter().map(key_name))
+}
+
+fn key_list(values: impl Iterator<Item = String>) -> String {
+    let mut values: Vec<String> = values.collect();
+    values.sort();
+    values.dedup();
+    values.join(",")
+}
+
+/// A digest standing for a set of values too long to spell out in a scan key,
+/// such as the patterns of an `--exclude-from` file.
+fn key_digest(values: impl Iterator<Item = String>) -> String {
+    let mut values: Vec<String> = values.collect();
+    values.sort();
+    let mut digest = Sha256::new();
+    for value in values {
+        digest.update(value.as_bytes());
+        digest.update(b"\n");
+    }
+    hex(&digest.finalize())
+}
+
+/// Extension aliases applied unless `--no-alias`, as `(variant, canonical)`.
+pub const DEFAULT_ALIASES: &[(&str, &str)] = &[
+    ("jpeg", "jpg"),
+    ("jpe", "jpg"),
+    ("tiff", "tif"),
+    ("htm", "html"),
+    ("yml", "yaml"),
+    ("markdown", "md"),
+    ("midi", "mid"),
+    ("aif", "aiff"),
+];
+
+/// Maps variant extension keys to the canonical key their files are grouped
+/// under, both in catalog form (`.jpeg` -> `.jpg`). A canonical key is never
+/// itself a variant, so one lookup always resolves fully.
+#[derive(Clone, Default)]
+pub struct ExtAliases(HashMap<String, String>);
+
+impl ExtAliases {
+    /// The [`DEFAULT_ALIASES`].
+    pub fn builtin() -> Self {
+        let mut aliases = ExtAliases::default();
+        for (from, to) in DEFAULT_ALIASES {
+            aliases.insert(from, to);
+        }
+        aliases
+    }
+
+    /// Group `from` under `to` (CLI spellings such as `JPEG` or `.jpeg`). A later
+    /// alias wins: `jpg=jpeg` reverses the built-in `jpeg=jpg`, and variants that
+    /// pointed at `from` follow it to `to`.
+    pub fn insert(&mut self, from: &str, to: &str) {
+        let (from, to) = (ext_key(from), ext_key(to));
+        if from == to {
+            return;
+        }
+        self.0.remove(&to);
+        for canonical in self.0.values_mut() {
+            if *canonical == from {
+                canonical.clone_from(&to);
+            }
+        }
+        self.0.insert(from, to);
+    }
+
+    /// The canonical form of the extension key `ext`.
+    pub fn canonical(&self, ext: String) -> String {
+        match self.0.get(&ext) {
+            Some(canonical) => canonical.clone(),
+            None => ext,
+        }
+    }
+
+    /// The canonical catalog key for an extension argument such as `JPEG`.
+    pub fn canonical_arg(&self, arg: &str) -> String {
+        self.canonical(ext_key(arg))
+    }
 }
 
 /// Decides which files are included in the catalog.
 pub struct ScanFilter {
     /// Allow-list of dot-prefixed lowercase extensions (e.g. `.rs`). `None` = all.
     ext: Option<Vec<String>>,
+    /// Extensions that are never catalogued, in the same form as `ext`.
+    exclude_ext: Vec<String>,
+    /// Glob patterns; only files matching one of them are included.
+    include: Option<GlobSet>,
     /// Glob patterns; matching files/dirs are excluded (dirs are pruned).
     exclude: Option<Arc<GlobSet>>,
+    /// Directory names (or name globs) that are never descended into.
+    exclude_dir: Option<Arc<GlobSet>>,
+    /// Directory names pruned by the `--no-junk` preset.
+    junk_dirs: Arc<Vec<String>>,
+    /// Regexes over the `/`-separated relative path: files must match one of
+    /// `regex` (if given) and none of `regex_exclude`.
+    regex: Option<RegexSet>,
+    regex_exclude: Option<RegexSet>,
+    /// `--contains`/`--not-contains` terms.
+    substrings: Substrings,
+    /// Leave out zero-length files.
+    skip_empty: bool,
+    /// Only text or only binary files, judged by sampling their contents.
+    only: Option<ContentKind>,
+    /// Lowercase MIME patterns (`image/png`, `image/*`); files must match one.
+    mime: Vec<String>,
+    /// Windows attributes a file must have one of (`--only-attr`), and ones that
+    /// exclude a file or prune a directory (`--skip-attr`).
+    only_attr: Vec<FileAttribute>,
+    skip_attr: Vec<FileAttribute>,
+    /// Inclusive file size bounds in bytes.
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    /// Only files modified after `newer_than` and before `older_than`.
+    newer_than: Option<TimeBound>,
+    older_than: Option<TimeBound>,
+    /// This run's own output files (or `--split` directory), relative to the root.
+    own_outputs: Vec<PathBuf>,
+    /// The glob and regex patterns as given, by flag (`exclude=*.o`), for
+    /// [`scan_key`](Self::scan_key); the compiled sets don't keep them.
+    patterns: Vec<String>,
+}
+
+/// A `--newer-than` or `--older-than` bound, with the argument it was worked
+/// out from: `7d` is a different time on every run, but the same scan.
+#[derive(Clone)]
+pub struct TimeBound {
+    pub time: SystemTime,
+    pub arg: String,
 }
 
 impl ScanFilter {
-    /// Build a filter from raw `--ext` and `--exclude` CLI values.
+    /// Build a filter from raw `--ext`, `--exclude-ext`, `--include` and `--exclude`
+    /// CLI values.
     ///
-    /// Returns `Err` with a human-readable message if an exclude glob is invalid.
-    pub fn new(ext_args: &[String], exclude_args: &[String]) -> Result<Self, String> {
+    /// Returns `Err` with a human-readable message if a glob is invalid or an
+    /// extension is both included and excluded.
+    pub fn new(
+        ext_args: &[String],
+        exclude_ext_args: &[String],
+        include_args: &[String],
+        exclude_args: &[String],
+    ) -> Result<Self, String> {
         let ext = if ext_args.is_empty() {
             None
         } else {
-            Some(
-                ext_args
-                    .iter()
-                    .map(|e| format!(".{}", e.trim().trim_start_matches('.').to_lowercase()))
-                    .collect(),
-            )
+            Some(ext_args.iter().map(|e| ext_key(e)).collect::<Vec<_>>())
         };
+        let exclude_ext: Vec<String> = exclude_ext_args.iter().map(|e| ext_key(e)).collect();
+        if let Some(both) = ext.iter().flatten().find(|e| exclude_ext.contains(e)) {
+            return Err(format!(
+                "extension '{both}' is given to both --ext and --exclude-ext"
+            ));
+        }
 
-        let exclude = if exclude_args.is_empty() {
-            None
-        } else {
-            let mut builder = GlobSetBuilder::new();
-            for pattern in exclude_args {
-                let glob = Glob::new(pattern)
-                    .map_err(|e| format!("invalid --exclude pattern '{pattern}': {e}"))?;
-                builder.add(glob);
+        let include = glob_set(include_args, "include")?;
+        let exclude = glob_set(exclude_args, "exclude")?.map(Arc::new);
+        let patterns = include_args
+            .iter()
+            .map(|pattern| format!("include={pattern}"))
+            .chain(exclude_args.iter().map(|pattern| format!("exclude={pattern}")))
+            .collect();
+
+        Ok(ScanFilter {
+            ext,
+            exclude_ext,
+            include,
+            exclude,
+            exclude_dir: None,
+            junk_dirs: Arc::default(),
+            regex: None,
+            regex_exclude: None,
+            substrings: Substrings::default(),
+            skip_empty: false,
+            only: None,
+            mime: Vec::new(),
+            only_attr: Vec::new(),
+            skip_attr: Vec::new(),
+            min_size: None,
+            max_size: None,
+            newer_than: None,
+            older_than: None,
+            own_outputs: Vec::new(),
+            patterns,
+        })
+    }
+
+    /// Never descend into directories named one of `names`. A name is matched
+    /// exactly unless it contains glob characters (`*`, `?`, `[`, `{`).
+    pub fn with_excluded_dirs(mut self, names: &[String]) -> Result<Self, String> {
+        let patterns: Vec<String> = names
+            .iter()
+            .map(|name| {
+                if name.contains(['*', '?', '[', '{']) {
+                    name.clone()
+                } else {
+                    globset::escape(name)
+                }
+            })
+            .collect();
+        self.exclude_dir = glob_set(&patterns, "exclude-dir")?.map(Arc::new);
+        self.patterns.extend(patterns.iter().map(|pattern| format!("exclude-dir={pattern}")));
+        Ok(self)
+    }
+
+    /// Filter files by regexes over their relative path, optionally ignoring case.
+    /// Fails with the regex engine's message if a pattern is invalid.
+    pub fn with_regexes(
+        mut self,
+        include: &[String],
+        exclude: &[String],
+        ignore_case: bool,
+    ) -> Result<Self, String> {
+        self.regex = regex_set(include, "regex", ignore_case)?;
+        self.regex_exclude = regex_set(exclude, "regex-exclude", ignore_case)?;
+        // Spelled the way the regex crate takes ignoring case inline.
+        let flags = if ignore_case { "(?i)" } else { "" };
+        self.patterns.extend(include.iter().map(|pattern| format!("regex={flags}{pattern}")));
+        self.patterns.extend(exclude.iter().map(|pattern| format!("regex-exclude={flags}{pattern}")));
+        Ok(self)
+    }
+
+    /// Filter by plain substrings of the relative path: every `contains` term must
+    /// appear and no `not_contains` term may. Case-insensitive unless `case_sensitive`.
+    pub fn with_substrings(
+        mut self,
+        contains: &[String],
+        not_contains: &[String],
+        case_sensitive: bool,
+    ) -> Self {
+        let fold = |terms: &[String]| -> Vec<String> {
+            terms
+                .iter()
+                .filter(|t| !t.is_empty())
+                .map(|t| if case_sensitive { t.clone() } else { t.to_lowercase() })
+                .collect()
+        };
+        self.substrings = Substrings {
+            contains: fold(contains),
+            not_contains: fold(not_contains),
+            case_sensitive,
+        };
+        self
+    }
+
+    /// The `--contains`/`--not-contains` terms.
+    pub fn substrings(&self) -> &Substrings {
+        &self.substrings
+    }
+
+    /// Whether a regex filter was given (so relative paths must be checked).
+    pub fn has_regex(&self) -> bool {
+        self.regex.is_some() || self.regex_exclude.is_some()
+    }
+
+    /// Whether a file's `/`-separated relative path passes the regex filters.
+    pub fn regex_allowed(&self, relative_path: &str) -> bool {
+        self.regex.as_ref().is_none_or(|set| set.is_match(relative_path))
+            && !self
+                .regex_exclude
+                .as_ref()
+                .is_some_and(|set| set.is_match(relative_path))
+    }
+
+    /// Leave out zero-length files. Unlike `--min-size 1`, files whose size can't
+    /// be read are kept.
+    pub fn with_skip_empty(mut self, skip_empty: bool) -> Self {
+        self.skip_empty = skip_empty;
+        self
+    }
+
+    /// Whether `--skip-empty` was given.
+    pub fn skip_empty(&self) -> bool {
+        self.skip_empty
+    }
+
+    /// Only include text or binary files. Each candidate's first few KB are read,
+    /// so this is noticeably slower on large trees.
+    pub fn with_only(mut self, only: Option<ContentKind>) -> Self {
+        self.only = only;
+        self
+    }
+
+    /// The `--only` content class, if given.
+    pub fn only(&self) -> Option<ContentKind> {
+        self.only
+    }
+
+    /// Prune the `--no-junk` directories, minus any named in `keep`.
+    pub fn with_junk_dirs(mut self, junk: &[&str], keep: &[String]) -> Self {
+        let names = junk
+            .iter()
+            .filter(|name| !keep.iter().any(|k| k == *name))
+            .map(|name| name.to_string())
+            .collect();
+        self.junk_dirs = Arc::new(names);
+        self
+    }
+
+    /// The `--no-junk` directory names (empty unless requested).
+    pub fn junk_dirs(&self) -> &Arc<Vec<String>> {
+        &self.junk_dirs
+    }
+
+    /// Only include files whose sniffed MIME type matches one of `patterns`, each
+    /// `type/subtype` or `type/*`.
+    pub fn with_mime(mut self, patterns: &[String]) -> Result<Self, String> {
+        for pattern in patterns {
+            let valid = pattern
+                .split_once('/')
+                .is_some_and(|(kind, sub)| !kind.is_empty() && !sub.is_empty() && !sub.contains('/'));
+            if !valid {
+                return Err(format!(
+                    "invalid --mime pattern '{pattern}' (expected e.g. image/png or image/*)"
+                ));
             }
-            let set = builder
-                .build()
-                .map_err(|e| format!("failed to build exclude patterns: {e}"))?;
-            Some(Arc::new(set))
+        }
+        self.mime = patterns.iter().map(|p| p.to_ascii_lowercase()).collect();
+        Ok(self)
+    }
+
+    /// Whether a `--mime` filter was given.
+    pub fn has_mime(&self) -> bool {
+        !self.mime.is_empty()
+    }
+
+    /// Whether a detected MIME type passes the `--mime` filter.
+    pub fn mime_allowed(&self, mime: &str) -> bool {
+        if self.mime.is_empty() {
+            return true;
+        }
+        let kind = mime.split('/').next().unwrap_or(mime);
+        self.mime.iter().any(|pattern| match pattern.strip_suffix("/*") {
+            Some(prefix) => prefix == "*" || prefix == kind,
+            None => pattern == mime,
+        })
+    }
+
+    /// Only include files whose size is within `min..=max` bytes (either bound
+    /// optional). Fails if `min` is greater than `max`.
+    pub fn with_size_range(mut self, min: Option<u64>, max: Option<u64>) -> Result<Self, String> {
+        if let (Some(min), Some(max)) = (min, max)
+            && min > max
+        {
+            return Err(format!(
+                "--min-size ({min} bytes) is greater than --max-size ({max} bytes)"
+            ));
+        }
+        self.min_size = min;
+        self.max_size = max;
+        Ok(self)
+    }
+
+    /// Only include files modified after `newer` and before `older` (either bound
+    /// optional). Fails if the range is empty.
+    pub fn with_time_range(
+        mut self,
+        newer: Option<TimeBound>,
+        older: Option<TimeBound>,
+    ) -> Result<Self, String> {
+        if let (Some(newer), Some(older)) = (&newer, &older)
+            && newer.time >= older.time
+        {
+            return Err(format!(
+                "--newer-than ({}) is not before --older-than ({}), so no file can match",
+                iso8601_utc(newer.time),
+                iso8601_utc(older.time)
+            ));
+        }
+        self.newer_than = newer;
+        self.older_than = older;
+        Ok(self)
+    }
+
+    /// Only include files with one of the `only` attributes, and leave out files
+    /// and directories with any of the `skip` ones.
+    pub fn with_attributes(mut self, only: &[FileAttribute], skip: &[FileAttribute]) -> Self {
+        self.only_attr = only.to_vec();
+        self.skip_attr = skip.to_vec();
+        self
+    }
+
+    /// Whether `--only-attr` or `--skip-attr` was given.
+    pub fn has_attr_filter(&self) -> bool {
+        !self.only_attr.is_empty() || !self.skip_attr.is_empty()
+    }
+
+    /// The `--skip-attr` attributes, which also prune directories.
+    pub fn skip_attr(&self) -> &[FileAttribute] {
+        &self.skip_attr
+    }
+
+    /// Whether a file with attribute `bits` passes `--only-attr` and `--skip-attr`.
+    pub fn attributes_allowed(&self, bits: u32) -> bool {
+        (self.only_attr.is_empty() || self.only_attr.iter().any(|a| bits & a.bit() != 0))
+            && !self.skip_attr.iter().any(|a| bits & a.bit() != 0)
+    }
+
+    /// Leave the given output paths out of a scan of `root`. Paths are compared
+    /// canonicalized, so `./out.txt`, symlinked parents and the like are all
+    /// recognized; paths outside `root` are ignored. Parents must already exist.
+    pub fn exclude_outputs(&mut self, root: &Path, outputs: &[PathBuf]) {
+        let Ok(root) = root.canonicalize() else {
+            return;
         };
+        for output in outputs {
+            let (Some(parent), Some(name)) = (output.parent(), output.file_name()) else {
+                continue;
+            };
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            if let Ok(parent) = parent.canonicalize()
+                && let Ok(relative) = parent.join(name).strip_prefix(&root)
+            {
+                self.own_outputs.push(relative.to_path_buf());
+            }
+        }
+    }
+
+    /// Whether a file (by path relative to the root) is one of this run's outputs.
+    pub fn is_own_output(&self, relative_path: &Path) -> bool {
+        self.own_outputs.iter().any(|own| relative_path.starts_with(own))
+    }
+
+    /// The whole filter as lines of a scan key, for `--checkpoint` (see
+    /// [`ScanOptions::scan_key`]), this run's own outputs included. The
+    /// patterns, which may come from an `--exclude-from` file of any length, are
+    /// one digest.
+    pub fn scan_key(&self) -> String {
+        let ScanFilter {
+            ext,
+            exclude_ext,
+            include: _,
+            exclude: _,
+            exclude_dir: _,
+            junk_dirs,
+            regex: _,
+            regex_exclude: _,
+            substrings,
+            skip_empty,
+            only,
+            mime,
+            only_attr,
+            skip_attr,
+            min_size,
+            max_size,
+            newer_than,
+            older_than,
+            own_outputs,
+            patterns,
+        } = self;
+        let bound = |bound: &Option<TimeBound>| key_value(bound.as_ref().map(|bound| &bound.arg));
+        [
+            ("ext", ext.as_ref().map_or_else(|| "all".to_string(), |ext| key_list(ext.iter().cloned()))),
+            ("exclude_ext", key_list(exclude_ext.iter().cloned())),
+            ("patterns", key_digest(patterns.iter().cloned())),
+            ("junk_dirs", key_list(junk_dirs.iter().cloned())),
+            ("contains", key_list(substrings.contains.iter().cloned())),
+            ("not_contains", key_list(substrings.not_contains.iter().cloned())),
+            ("case_sensitive", substrings.case_sensitive.to_string()),
+            ("skip_empty", skip_empty.to_string()),
+            ("only", only.as_ref().map_or_else(|| "any".to_string(), key_name)),
+            ("mime", key_list(mime.iter().cloned())),
+            ("only_attr", key_names(only_attr)),
+            ("skip_attr", key_names(skip_attr)),
+            ("min_size", key_value(*min_size)),
+            ("max_size", key_value(*max_size)),
+            ("newer_than", bound(newer_than)),
+            ("older_than", bound(older_than)),
+            ("own_outputs", key_list(own_outputs.iter().map(|path| path.display().to_string()))),
+        ]
+        .map(|(name, value)| format!("{name}={value}"))
+        .join("\n")
+    }
+
+    /// Whether a size, emptiness or date filter needs each file's metadata.
+    pub fn needs_metadata(&self) -> bool {
+        self.skip_empty || self.has_size_range() || self.has_time_range()
+    }
+
+    /// Whether a modification time bound was given.
+    pub fn has_time_range(&self) -> bool {
+        self.newer_than.is_some() || self.older_than.is_some()
+    }
+
+    /// Whether a file modified at `mtime` is within the time bounds.
+    pub fn time_allowed(&self, mtime: SystemTime) -> bool {
+        self.newer_than.as_ref().is_none_or(|newer| mtime > newer.time)
+            && self.older_than.as_ref().is_none_or(|older| mtime < older.time)
+    }
+
+    /// Whether a size bound was given (so every file's size must be read).
+    pub fn has_size_range(&self) -> bool {
+        self.min_size.is_some() || self.max_size.is_some()
+    }
 
-        Ok(ScanFilter { ext, exclude })
+    /// Whether a file of `size` bytes is within the size bounds.
+    pub fn size_allowed(&self, size: u64) -> bool {
+        self.min_size.is_none_or(|min| size >= min) && self.max_size.is_none_or(|max| size <= max)
     }
 
     /// Whether a file with the given extension key should be included.
     pub fn ext_allowed(&self, ext: &str) -> bool {
+        if self.exclude_ext.iter().any(|e| e == ext) {
+            return false;
+        }
         match &self.ext {
             Some(allowed) => allowed.iter().any(|e| e == ext),
             None => true,
         }
     }
 
+    /// The compiled include set, if any include patterns were given.
+    pub fn include(&self) -> Option<&GlobSet> {
+        self.include.as_ref()
+    }
+
     /// The compiled exclude set, if any exclude patterns were given.
     pub fn exclude(&self) -> Option<&Arc<GlobSet>> {
         self.exclude.as_ref()
     }
+
+    /// The compiled `--exclude-dir` names, matched against directory names.
+    pub fn exclude_dir(&self) -> Option<&Arc<GlobSet>> {
+        self.exclude_dir.as_ref()
+    }
+}
+
+/// Plain substring tests against a `/`-separated relative path.
+#[derive(Clone, Default)]
+pub struct Substrings {
+    contains: Vec<String>,
+    not_contains: Vec<String>,
+    case_sensitive: bool,
+}
+
+impl Substrings {
+    /// Whether any term was given.
+    pub fn is_empty(&self) -> bool {
+        self.contains.is_empty() && self.not_contains.is_empty()
+    }
+
+    /// Whether `--not-contains` terms were given (so directories can be pruned).
+    pub fn has_exclusions(&self) -> bool {
+        !self.not_contains.is_empty()
+    }
+
+    /// Whether a file path has every `--contains` term and no `--not-contains` term.
+    pub fn allows(&self, relative_path: &str) -> bool {
+        let path = self.fold(relative_path);
+        self.contains.iter().all(|t| path.contains(t.as_str()))
+            && !self.not_contains.iter().any(|t| path.contains(t.as_str()))
+    }
+
+    /// Whether a path has a `--not-contains` term; a directory that does can be
+    /// pruned, since every path below it does too.
+    pub fn rejects(&self, relative_path: &str) -> bool {
+        let path = self.fold(relative_path);
+        self.not_contains.iter().any(|t| path.contains(t.as_str()))
+    }
+
+    fn fold<'a>(&self, s: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.case_sensitive {
+            s.into()
+        } else {
+            s.to_lowercase().into()
+        }
+    }
+}
+
+/// Compile the patterns given to `--<flag>`; `None` if there are none.
+fn glob_set(patterns: &[String], flag: &str) -> Result<Option<GlobSet>, String> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob =
+            Glob::new(pattern).map_err(|e| format!("invalid --{flag} pattern '{pattern}': {e}"))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| format!("failed to build {flag} patterns: {e}"))
+}
+
+/// Compile the regexes given to `--<flag>`; `None` if there are none.
+fn regex_set(
+    patterns: &[String],
+    flag: &str,
+    ignore_case: bool,
+) -> Result<Option<RegexSet>, String> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    // Compile one by one first so the error names the offending pattern.
+    for pattern in patterns {
+        RegexSetBuilder::new([pattern])
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| format!("invalid --{flag} pattern '{pattern}': {e}"))?;
+    }
+    RegexSetBuilder::new(patterns)
+        .case_insensitive(ignore_case)
+        .build()
+        .map(Some)
+        .map_err(|e| format!("failed to build {flag} patterns: {e}"))
+}
+
+/// Normalize an extension argument (`JPG`, `.jpg`) to its catalog key (`.jpg`).
+/// An empty argument stands for files without an extension.
+fn ext_key(arg: &str) -> String {
+    let ext = arg.trim().trim_start_matches('.');
+    if ext.is_empty() {
+        String::new()
+    } else {
+        format!(".{}", ext.to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+
+    #[test]
+    fn extension_arguments_become_lowercase_dotted_keys() {
+        assert_eq!(ext_key("JPG"), ".jpg");
+        assert_eq!(ext_key(".Jpg"), ".jpg");
+        assert_eq!(ext_key(" tar.GZ "), ".tar.gz");
+        assert_eq!(ext_key("."), "");
+        assert_eq!(ext_key(""), "");
+    }
+
+    #[test]
+    fn ext_allowed_compares_catalog_keys() {
+        let (_, filter) = cli::parse_for_test(&["--ext", "JPG,Png"]);
+        assert!(filter.ext_allowed(".jpg"));
+        assert!(filter.ext_allowed(".png"));
+        assert!(!filter.ext_allowed(".gif"));
+        assert!(!filter.ext_allowed(".JPG"));
+        let (_, filter) = cli::parse_for_test(&["--exclude-ext", ".Tmp"]);
+        assert!(filter.ext_allowed(".jpg"));
+        assert!(!filter.ext_allowed(".tmp"));
+    }
+
+    #[test]
+    fn an_anchored_regex_matches_from_the_start_of_the_relative_path() {
+        let (_, filter) = cli::parse_for_test(&["--regex", "^sub/"]);
+        assert!(filter.regex_allowed("sub/a.txt"));
+        assert!(filter.regex_allowed("sub/deeper/b.rs"));
+        assert!(!filter.regex_allowed("other/sub/a.txt"));
+        assert!(!filter.regex_allowed("subway/a.txt"));
+    }
+
+    #[test]
+    fn alternation_matches_either_side() {
+        let (_, filter) = cli::parse_for_test(&["--regex", r"\.(rs|toml)$|^docs/"]);
+        assert!(filter.regex_allowed("src/main.rs"));
+        assert!(filter.regex_allowed("Cargo.toml"));
+        assert!(filter.regex_allowed("docs/guide.md"));
+        assert!(!filter.regex_allowed("src/notes.md"));
+    }
+
+    #[test]
+    fn repeated_regexes_include_a_match_of_any_and_exclude_a_match_of_any() {
+        let (_, filter) =
+            cli::parse_for_test(&["--regex", "^a/", "--regex", "^b/", "--regex-exclude", "tmp", "--regex-exclude", "~$"]);
+        assert!(filter.regex_allowed("a/x"));
+        assert!(filter.regex_allowed("b/y"));
+        assert!(!filter.regex_allowed("c/z"));
+        assert!(!filter.regex_allowed("a/tmp/x"));
+        assert!(!filter.regex_allowed("b/y~"));
+    }
+
+    #[test]
+    fn ignore_case_applies_to_every_regex() {
+        let (_, filter) = cli::parse_for_test(&["--regex", "^Sub/", "--regex-exclude", "SKIP", "--ignore-case"]);
+        assert!(filter.regex_allowed("sub/a.txt"));
+        assert!(filter.regex_allowed("SUB/a.txt"));
+        assert!(!filter.regex_allowed("sub/skip.txt"));
+    }
+
+    fn scan_key(args: &[&str]) -> String {
+        let (options, filter) = cli::parse_for_test(args);
+        format!("{}\n{}", options.scan_key(), filter.scan_key())
+    }
+
+    #[test]
+    fn the_scan_key_ignores_the_order_options_were_given_in_and_how_the_catalog_is_written() {
+        let key = scan_key(&["--ext", "rs,txt", "--mtime"]);
+        assert_eq!(key, scan_key(&["--ext", "txt,rs", "--mtime"]));
+        assert_eq!(key, scan_key(&["--ext", "txt", "--ext", "rs", "--mtime", "--format", "json", "--threads", "2"]));
+        assert_ne!(key, scan_key(&["--ext", "rs", "--mtime"]));
+        assert_ne!(key, scan_key(&["--ext", "rs,txt"]));
+        assert_ne!(scan_key(&["--regex", "a"]), scan_key(&["--regex", "a", "--ignore-case"]));
+    }
+
+    #[test]
+    fn the_scan_key_follows_the_contents_of_pattern_and_alias_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let patterns = dir.path().join("patterns");
+        let aliases = dir.path().join("aliases");
+        let files = ["--exclude-from", patterns.to_str().unwrap(), "--alias-file", aliases.to_str().unwrap()];
+        std::fs::write(&patterns, "*.o\n").unwrap();
+        std::fs::write(&aliases, "cc=cpp\n").unwrap();
+        let key = scan_key(&files);
+        std::fs::write(&patterns, "*.o\n# a comment\n").unwrap();
+        assert_eq!(key, scan_key(&files));
+        std::fs::write(&patterns, "*.o\n*.a\n").unwrap();
+        let more_patterns = scan_key(&files);
+        assert_ne!(key, more_patterns);
+        std::fs::write(&aliases, "cc=cpp\nhh=hpp\n").unwrap();
+        assert_ne!(more_patterns, scan_key(&files));
+    }
+
+    #[test]
+    fn a_relative_time_bound_keeps_its_scan_key_as_the_clock_moves_on() {
+        let (_, filter) = cli::parse_for_test(&["--newer-than", "7d"]);
+        let (_, later) = cli::parse_for_test(&["--newer-than", "7d"]);
+        assert_eq!(filter.scan_key(), later.scan_key());
+    }
 }
@@ -0,0 +1,108 @@
+//! `--duplicates`: find catalogued files with identical contents.
+//!
+//! Files are bucketed by size first, so only files sharing a size with another
+//! are ever read; those are then hashed and grouped by digest.
+
+use crate::config::{HashAlgorithm, ScanOptions};
+use crate::hash;
+use crate::scan::{Catalog, is_regular_group, sort_key};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files with identical contents.
+#[derive(Clone)]
+pub struct DuplicateGroup {
+    /// Size of each copy in bytes.
+    pub size: u64,
+    /// Lowercase hex digest shared by every copy.
+    pub digest: String,
+    /// Relative paths of the copies, sorted (always at least two).
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    /// Bytes that deleting all but one copy would free.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+/// Bytes reclaimable across all `groups`.
+pub fn reclaimable(groups: &[DuplicateGroup]) -> u64 {
+    groups.iter().map(DuplicateGroup::wasted).sum()
+}
+
+/// Find groups of identical files in `catalog`, largest waste first, and count
+/// the files left out for being larger than `--hash-max-size`.
+///
+/// Digests already computed by a cryptographic `--hash` are reused; otherwise
+/// (including for the collision-prone `crc32` and `xxh3`) candidates are hashed
+/// with SHA-256. Zero-byte files are left out unless `--duplicates-empty`.
+/// Additional hardlinks are left out, since removing one frees nothing. Files
+/// whose size or contents can't be read are skipped.
+pub fn find(catalog: &Catalog, root: &Path, options: &ScanOptions) -> (Vec<DuplicateGroup>, u64) {
+    let algorithm = options
+        .hash
+        .filter(|algorithm| algorithm.is_cryptographic())
+        .unwrap_or(HashAlgorithm::Sha256);
+    let reuse = options.hash == Some(algorithm);
+    let files: Vec<_> = catalog
+        .iter()
+        .filter(|(group, _)| is_regular_group(group))
+        .flat_map(|(_, entries)| entries)
+        .filter(|entry| !entry.hardlink && !entry.archived)
+        .collect();
+
+    let sized: Vec<(u64, &_)> = files
+        .par_iter()
+        .filter_map(|entry| {
+            let size = match entry.size {
+                Some(size) => size,
+                None => fs::metadata(root.join(&entry.path)).ok()?.len(),
+            };
+            Some((size, *entry))
+        })
+        .collect();
+    let mut by_size: HashMap<u64, Vec<_>> = HashMap::new();
+    let mut too_large = 0;
+    for (size, entry) in sized {
+        if options.hash_max_size.is_some_and(|max| size > max) {
+            too_large += 1;
+        } else if size > 0 || options.duplicates_empty {
+            by_size.entry(size).or_default().push(entry);
+        }
+    }
+
+    let candidates: Vec<_> = by_size
+        .into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .flat_map(|(size, entries)| entries.into_iter().map(move |entry| (size, entry)))
+        .collect();
+    let digested: Vec<(u64, String, PathBuf)> = candidates
+        .par_iter()
+        .filter_map(|&(size, entry)| {
+            let digest = match &entry.digest {
+                Some(digest) if reuse => digest.clone(),
+                _ => hash::hash_file(&root.join(&entry.path), algorithm).ok()?,
+            };
+            Some((size, digest, entry.path.clone()))
+        })
+        .collect();
+
+    let mut by_digest: HashMap<(u64, String), Vec<PathBuf>> = HashMap::new();
+    for (size, digest, path) in digested {
+        by_digest.entry((size, digest)).or_default().push(path);
+    }
+    let mut groups: Vec<DuplicateGroup> = by_digest
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((size, digest), mut paths)| {
+            paths.sort_by_cached_key(|path| sort_key(path));
+            DuplicateGroup { size, digest, paths }
+        })
+        .collect();
+    groups.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.paths.cmp(&b.paths)));
+    (groups, too_large)
+}
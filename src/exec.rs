@@ -0,0 +1,77 @@
+//! `--executables` and `--group-by exec`: which files can be run.
+//!
+//! On Unix a file is executable when any of its execute bits is set. Windows has
+//! no execute bit, so there the answer comes from the extension instead
+//! ([`WINDOWS_EXTENSIONS`]), whatever the file's permissions.
+
+use std::fs::Metadata;
+use std::path::Path;
+
+/// Group for runnable files with `--group-by exec`.
+pub const EXECUTABLE_GROUP: &str = "executable";
+/// Group for everything else (including files whose permissions couldn't be read).
+pub const NOT_EXECUTABLE_GROUP: &str = "not executable";
+
+/// Extensions treated as executable on Windows.
+#[cfg(not(unix))]
+pub const WINDOWS_EXTENSIONS: &[&str] = &["exe", "bat", "cmd", "ps1", "com"];
+
+/// Whether the file at `path` is executable, or `None` if its metadata is needed
+/// but couldn't be read.
+#[cfg(unix)]
+pub fn is_executable(_path: &Path, metadata: Option<&Metadata>) -> Option<bool> {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.map(|m| m.permissions().mode() & 0o111 != 0)
+}
+
+/// Whether the file at `path` is executable, judged by its extension.
+#[cfg(not(unix))]
+pub fn is_executable(path: &Path, _metadata: Option<&Metadata>) -> Option<bool> {
+    let ext = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    Some(ext.is_some_and(|ext| WINDOWS_EXTENSIONS.contains(&ext.as_str())))
+}
+
+/// The `--group-by exec` group for a file.
+pub fn group(executable: Option<bool>) -> String {
+    let group = if executable == Some(true) { EXECUTABLE_GROUP } else { NOT_EXECUTABLE_GROUP };
+    group.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn any_execute_bit_makes_a_file_executable_on_unix() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        // The name doesn't matter on Unix, only the mode.
+        let path = dir.path().join("tool.exe");
+        std::fs::write(&path, "#!/bin/sh\n").unwrap();
+        for (mode, executable) in [(0o644, false), (0o744, true), (0o654, true), (0o645, true), (0o600, false)] {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+            let metadata = std::fs::metadata(&path).unwrap();
+            assert_eq!(is_executable(&path, Some(&metadata)), Some(executable), "{mode:o}");
+        }
+        assert_eq!(is_executable(&path, None), None);
+    }
+
+    #[cfg(not(unix))]
+    #[test]
+    fn the_extension_decides_on_windows() {
+        for name in ["setup.exe", "RUN.BAT", "build.cmd", "deploy.ps1", "old.com"] {
+            assert_eq!(is_executable(Path::new(name), None), Some(true), "{name}");
+        }
+        for name in ["script.sh", "notes.txt", "exe", "archive.exe.bak"] {
+            assert_eq!(is_executable(Path::new(name), None), Some(false), "{name}");
+        }
+    }
+
+    #[test]
+    fn unknown_permissions_group_as_not_executable() {
+        assert_eq!(group(Some(true)), EXECUTABLE_GROUP);
+        assert_eq!(group(Some(false)), NOT_EXECUTABLE_GROUP);
+        assert_eq!(group(None), NOT_EXECUTABLE_GROUP);
+    }
+}
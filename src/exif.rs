@@ -0,0 +1,171 @@
+//! `--group-by exif-date`: group images by when they were taken.
+//!
+//! A minimal EXIF reader: it finds the TIFF structure at the start of a TIFF-based
+//! file (TIFF, DNG and most camera raws) or in a JPEG's APP1 segment, and reads
+//! `DateTimeOriginal` (falling back to `DateTimeDigitized`, then `DateTime`). Only
+//! the first [`HEADER_LIMIT`] bytes are read, and anything malformed simply
+//! means "no date".
+
+use crate::category::Category;
+use crate::config::{DateGranularity, ScanOptions};
+use crate::scan::OTHER_GROUP;
+use std::fs::{File, Metadata};
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Bytes read from the start of each image. A JPEG's EXIF segment is capped at
+/// 64 KiB and follows at most a few small segments; TIFF-based raws keep their
+/// first IFDs near the start.
+const HEADER_LIMIT: u64 = 256 * 1024;
+
+/// Marks a group whose date came from the filesystem, not the image.
+const MTIME_SUFFIX: &str = " (mtime)";
+
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_DATE_TIME_DIGITIZED: u16 = 0x9004;
+
+/// The catalog group for a file with extension key `ext`: its capture date
+/// (`2023-07`, or `2023` by year), its modification date marked ` (mtime)` when
+/// the image has no readable EXIF date, or [`OTHER_GROUP`] for non-images.
+pub fn date_group(path: &Path, ext: &str, metadata: Option<&Metadata>, options: &ScanOptions) -> String {
+    let ext = ext.trim_start_matches('.');
+    if !Category::Images.extensions().contains(&ext) {
+        return OTHER_GROUP.to_string();
+    }
+    if let Some((year, month)) = read_date(path) {
+        return label(year, month, options.date_granularity);
+    }
+    match metadata.and_then(|m| m.modified().ok()) {
+        Some(mtime) => {
+            let local = chrono::DateTime::<chrono::Local>::from(mtime);
+            let (year, month) = (local.format("%Y"), local.format("%m"));
+            let date = match options.date_granularity {
+                DateGranularity::Year => year.to_string(),
+                DateGranularity::Month => format!("{year}-{month}"),
+            };
+            format!("{date}{MTIME_SUFFIX}")
+        }
+        None => "unknown date".to_string(),
+    }
+}
+
+fn label(year: u16, month: u8, granularity: DateGranularity) -> String {
+    match granularity {
+        DateGranularity::Year => format!("{year:04}"),
+        DateGranularity::Month => format!("{year:04}-{month:02}"),
+    }
+}
+
+/// The year and month an image was taken, from its EXIF data.
+fn read_date(path: &Path) -> Option<(u16, u8)> {
+    let header = read_header(path).ok()?;
+    let tiff = if header.starts_with(&[0xFF, 0xD8]) {
+        jpeg_exif(&header)?
+    } else {
+        &header[..]
+    };
+    Tiff::new(tiff)?.date()
+}
+
+fn read_header(path: &Path) -> io::Result<Vec<u8>> {
+    let mut header = Vec::new();
+    File::open(path)?.take(HEADER_LIMIT).read_to_end(&mut header)?;
+    Ok(header)
+}
+
+/// The TIFF structure inside a JPEG's `Exif` APP1 segment.
+fn jpeg_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+        let marker = data[pos + 1];
+        // Start of scan or end of image: the metadata segments are over.
+        if marker == 0xDA || marker == 0xD9 {
+            return None;
+        }
+        let length = usize::from(u16::from_be_bytes([data[pos + 2], data[pos + 3]]));
+        if length < 2 {
+            return None;
+        }
+        let segment = data.get(pos + 4..pos + 2 + length)?;
+        if marker == 0xE1
+            && let Some(tiff) = segment.strip_prefix(b"Exif\0\0")
+        {
+            return Some(tiff);
+        }
+        pos += 2 + length;
+    }
+    None
+}
+
+/// A TIFF byte structure with its byte order.
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..4)? {
+            b"II*\0" => true,
+            b"MM\0*" => false,
+            _ => return None,
+        };
+        Some(Tiff { data, little_endian })
+    }
+
+    fn u16_at(&self, pos: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(pos..pos + 2)?.try_into().ok()?;
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32_at(&self, pos: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    /// The best capture date: the EXIF IFD's original or digitized time, else
+    /// IFD0's `DateTime`.
+    fn date(&self) -> Option<(u16, u8)> {
+        let ifd0 = usize::try_from(self.u32_at(4)?).ok()?;
+        let exif = self
+            .find(ifd0, TAG_EXIF_IFD)
+            .and_then(|entry| usize::try_from(self.u32_at(entry + 8)?).ok());
+        exif.and_then(|ifd| {
+            self.date_tag(ifd, TAG_DATE_TIME_ORIGINAL)
+                .or_else(|| self.date_tag(ifd, TAG_DATE_TIME_DIGITIZED))
+        })
+        .or_else(|| self.date_tag(ifd0, TAG_DATE_TIME))
+    }
+
+    /// The offset of the 12-byte entry for `tag` in the IFD at `ifd`.
+    fn find(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let count = usize::from(self.u16_at(ifd)?);
+        (0..count)
+            .map(|i| ifd + 2 + i * 12)
+            .take_while(|&entry| entry + 12 <= self.data.len())
+            .find(|&entry| self.u16_at(entry) == Some(tag))
+    }
+
+    /// Year and month from an ASCII `YYYY:MM:DD HH:MM:SS` value.
+    fn date_tag(&self, ifd: usize, tag: u16) -> Option<(u16, u8)> {
+        let entry = self.find(ifd, tag)?;
+        let count = usize::try_from(self.u32_at(entry + 4)?).ok()?;
+        // Values over four bytes live elsewhere, at the offset the entry holds.
+        let start = if count > 4 { usize::try_from(self.u32_at(entry + 8)?).ok()? } else { entry + 8 };
+        let value = self.data.get(start..start.checked_add(count)?)?;
+        parse_date(value)
+    }
+}
+
+fn parse_date(value: &[u8]) -> Option<(u16, u8)> {
+    let text = std::str::from_utf8(value.get(..7)?).ok()?;
+    let year: u16 = text.get(..4)?.parse().ok()?;
+    let month: u8 = text.get(5..7)?.parse().ok()?;
+    // Cameras with an unset clock write `0000:00:00 00:00:00`.
+    ((1800..=9999).contains(&year) && (1..=12).contains(&month)).then_some((year, month))
+}
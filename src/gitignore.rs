@@ -0,0 +1,98 @@
+//! Ignore-file rules: `.gitignore` for `--gitignore` (nested files, the enclosing
+//! repository's `.git/info/exclude`) and CreepDir's own `.creepignore`. Both use
+//! gitignore syntax including `!negation`, with deeper files taking precedence.
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Per-directory ignore file read unless `--no-creepignore`.
+pub const CREEPIGNORE: &str = ".creepignore";
+
+/// The ignore rules in effect for one directory. jwalk clones it into every child
+/// directory, so it is a shared linked list (innermost layer first).
+#[derive(Clone, Default, Debug)]
+pub struct IgnoreStack(Option<Arc<Layer>>);
+
+#[derive(Debug)]
+struct Layer {
+    rules: Gitignore,
+    parent: Option<Arc<Layer>>,
+}
+
+impl IgnoreStack {
+    /// Rules that apply to `root` from above it: when `root` lies inside a git
+    /// repository, the `.gitignore` files between the repository top and `root`,
+    /// plus the repository's `.git/info/exclude`.
+    pub fn for_root(root: &Path, quiet: bool) -> Self {
+        let ancestors: Vec<&Path> = root.ancestors().skip(1).collect();
+        let Some(top) = ancestors.iter().position(|dir| dir.join(".git").exists()) else {
+            return IgnoreStack::default();
+        };
+
+        let mut stack = IgnoreStack::default();
+        for (i, dir) in ancestors[..=top].iter().enumerate().rev() {
+            let files: &[&str] = if dir.join(".gitignore").is_file() {
+                &[".gitignore"]
+            } else {
+                &[]
+            };
+            stack.push_dir(dir, files, i == top, quiet);
+        }
+        stack
+    }
+
+    /// Add the rules defined in `dir`: `.git/info/exclude` when `dir` is a
+    /// repository root, then each of `ignore_files` (names of files present in
+    /// `dir`), later ones taking precedence.
+    pub fn push_dir(&mut self, dir: &Path, ignore_files: &[&str], is_repo: bool, quiet: bool) {
+        if is_repo {
+            let exclude = dir.join(".git").join("info").join("exclude");
+            if exclude.is_file() {
+                self.push(dir, &exclude, quiet);
+            }
+        }
+        for name in ignore_files {
+            self.push(dir, &dir.join(name), quiet);
+        }
+    }
+
+    fn push(&mut self, dir: &Path, file: &Path, quiet: bool) {
+        let mut builder = GitignoreBuilder::new(dir);
+        // The error already names the file and line (`path: line 3: ...`).
+        if let Some(e) = builder.add(file)
+            && !quiet
+        {
+            eprintln!("Warning: {e}");
+        }
+        match builder.build() {
+            Ok(rules) if !rules.is_empty() => {
+                self.0 = Some(Arc::new(Layer {
+                    rules,
+                    parent: self.0.take(),
+                }));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                if !quiet {
+                    eprintln!("Warning: ignoring '{}': {e}", file.display());
+                }
+            }
+        }
+    }
+
+    /// Whether `path` is ignored. The innermost file with a matching pattern
+    /// decides, so a nested `!keep.log` re-includes what a parent ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut layer = self.0.as_deref();
+        while let Some(current) = layer {
+            match current.rules.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => layer = current.parent.as_deref(),
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,148 @@
+//! `--git-status`: whether each file is tracked, modified, untracked or ignored
+//! in the git repository containing the scan root.
+//!
+//! Statuses come from `git status --porcelain -z`, run once before the walk, so
+//! git must be on `PATH`. Files inside a submodule take the status git reports
+//! for the submodule itself.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A file's state in the repository.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GitStatus {
+    /// Committed and unchanged.
+    Tracked,
+    /// Tracked with staged or unstaged changes (including renames and conflicts).
+    Modified,
+    Untracked,
+    Ignored,
+}
+
+impl GitStatus {
+    /// Every status, in summary order.
+    pub const ALL: [GitStatus; 4] =
+        [GitStatus::Tracked, GitStatus::Modified, GitStatus::Untracked, GitStatus::Ignored];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GitStatus::Tracked => "tracked",
+            GitStatus::Modified => "modified",
+            GitStatus::Untracked => "untracked",
+            GitStatus::Ignored => "ignored",
+        }
+    }
+}
+
+/// Files per status, indexed like [`GitStatus::ALL`].
+pub type GitCounts = [u64; 4];
+
+/// The statuses git reports under the scan root, keyed by root-relative path.
+pub struct Statuses {
+    files: HashMap<PathBuf, GitStatus>,
+    /// Directories reported as a whole (ignored directories and submodules), so
+    /// everything below them shares their status.
+    dirs: Vec<(PathBuf, GitStatus)>,
+}
+
+impl Statuses {
+    /// Ask git about the repository containing `root`. Fails when git can't be
+    /// run or `root` isn't inside a work tree.
+    pub fn load(root: &Path) -> Result<Self, String> {
+        let prefix = git(root, &["rev-parse", "--show-prefix"])?;
+        let prefix = String::from_utf8_lossy(&prefix).trim_end_matches('\n').to_string();
+        let output = git(
+            root,
+            &["status", "--porcelain=v1", "-z", "--ignored", "--untracked-files=all", "--", "."],
+        )?;
+
+        let mut statuses = Statuses { files: HashMap::new(), dirs: Vec::new() };
+        let mut records = output.split(|&b| b == 0).filter(|r| !r.is_empty());
+        while let Some(record) = records.next() {
+            if record.len() < 4 {
+                continue;
+            }
+            let (code, path) = (&record[..2], String::from_utf8_lossy(&record[3..]));
+            // Renames and copies are followed by their source path.
+            if code.contains(&b'R') || code.contains(&b'C') {
+                records.next();
+            }
+            let status = match code {
+                b"??" => GitStatus::Untracked,
+                b"!!" => GitStatus::Ignored,
+                _ => GitStatus::Modified,
+            };
+            // Paths are relative to the top of the work tree.
+            let Some(relative) = path.strip_prefix(prefix.as_str()) else {
+                continue;
+            };
+            match relative.strip_suffix('/') {
+                Some(dir) => statuses.dirs.push((PathBuf::from(dir), status)),
+                None => {
+                    statuses.files.insert(PathBuf::from(relative), status);
+                }
+            }
+        }
+        for submodule in submodules(root, &prefix) {
+            let status = statuses.files.remove(&submodule).unwrap_or(GitStatus::Tracked);
+            statuses.dirs.push((submodule, status));
+        }
+        Ok(statuses)
+    }
+
+    /// The status of the file at `path`, relative to the scan root. Anything git
+    /// didn't mention is tracked and clean; files in a `.git` directory have none.
+    pub fn status(&self, path: &Path) -> Option<GitStatus> {
+        if path.components().any(|c| c.as_os_str() == ".git") {
+            return None;
+        }
+        if let Some(&status) = self.files.get(path) {
+            return Some(status);
+        }
+        let dir = self.dirs.iter().find(|(dir, _)| path.starts_with(dir));
+        Some(dir.map_or(GitStatus::Tracked, |&(_, status)| status))
+    }
+}
+
+/// Submodule paths under the scan root, from the repository's `.gitmodules`.
+/// A repository without submodules (or an unreadable file) gives none.
+fn submodules(root: &Path, prefix: &str) -> Vec<PathBuf> {
+    let Ok(top) = git(root, &["rev-parse", "--show-toplevel"]) else {
+        return Vec::new();
+    };
+    let top = PathBuf::from(String::from_utf8_lossy(&top).trim_end_matches('\n'));
+    let gitmodules = top.join(".gitmodules");
+    if !gitmodules.is_file() {
+        return Vec::new();
+    }
+    let file = gitmodules.to_string_lossy();
+    let Ok(output) = git(root, &["config", "--file", &file, "--null", "--get-regexp", r"\.path$"])
+    else {
+        return Vec::new();
+    };
+    // Each record is `submodule.<name>.path\n<path>`.
+    output
+        .split(|&b| b == 0)
+        .filter_map(|record| {
+            let record = String::from_utf8_lossy(record);
+            let (_, path) = record.split_once('\n')?;
+            path.strip_prefix(prefix).map(PathBuf::from)
+        })
+        .collect()
+}
+
+/// Run `git -C root args...`, returning its standard output.
+fn git(root: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .map_err(|e| format!("cannot run git: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().next().unwrap_or("git failed").trim().to_string());
+    }
+    Ok(output.stdout)
+}
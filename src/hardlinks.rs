@@ -0,0 +1,74 @@
+//! Hardlink detection, so content reachable through several paths is counted once.
+//!
+//! A file with more than one link is identified by `(device, inode)` on Unix and
+//! `(volume serial, file index)` on Windows. The first path seen for an identity
+//! owns its bytes; later ones are marked as additional links.
+
+use crate::scan::sort_key;
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+
+/// Where a file's content lives on disk.
+type FileId = (u64, u64);
+
+/// Paths that share one file's content.
+#[derive(Clone)]
+pub struct HardlinkGroup {
+    /// Size of the shared content in bytes.
+    pub size: u64,
+    /// Relative paths of every link found, sorted (always at least two).
+    pub paths: Vec<PathBuf>,
+}
+
+/// The identity of the file at `path` if it has more than one link.
+#[cfg(unix)]
+pub fn file_id(_path: &Path, metadata: &Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+/// The identity of the file at `path` if it has more than one link. Windows only
+/// reports link counts through an open handle, so this opens the file.
+#[cfg(windows)]
+pub fn file_id(path: &Path, _metadata: &Metadata) -> Option<FileId> {
+    let handle = winapi_util::Handle::from_path_any(path).ok()?;
+    let info = winapi_util::file::information(&handle).ok()?;
+    (info.number_of_links() > 1).then(|| (info.volume_serial_number(), info.file_index()))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn file_id(_path: &Path, _metadata: &Metadata) -> Option<FileId> {
+    None
+}
+
+/// The multiply-linked files seen so far in a walk.
+#[derive(Default)]
+pub struct Tracker {
+    seen: HashMap<FileId, (u64, Vec<PathBuf>)>,
+}
+
+impl Tracker {
+    /// Record `path` (`size` bytes) under `id`, returning whether its content was
+    /// already seen through another path.
+    pub fn record(&mut self, id: FileId, path: PathBuf, size: u64) -> bool {
+        let (_, paths) = self.seen.entry(id).or_insert_with(|| (size, Vec::new()));
+        paths.push(path);
+        paths.len() > 1
+    }
+
+    /// Every identity reached through more than one catalogued path, largest first.
+    pub fn groups(self) -> Vec<HardlinkGroup> {
+        let mut groups: Vec<HardlinkGroup> = self
+            .seen
+            .into_values()
+            .filter(|(_, paths)| paths.len() > 1)
+            .map(|(size, mut paths)| {
+                paths.sort_by_cached_key(|path| sort_key(path));
+                HardlinkGroup { size, paths }
+            })
+            .collect();
+        groups.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.paths.cmp(&b.paths)));
+        groups
+    }
+}
@@ -0,0 +1,452 @@
+//! Streaming content hashing for catalogued files.
+//!
+//! With `--hash`, files are hashed by a pool of worker threads while the walk
+//! goes on, fed through a bounded queue so the walk waits rather than running
+//! far ahead of the disk.
+
+use crate::checkpoint::Checkpoint;
+use crate::config::{HashAlgorithm, ScanOptions};
+use crate::scan::{Catalog, FileEntry, ScanStats, is_regular_group};
+use sha2::Digest;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Read size for hashing; files are streamed, never loaded whole.
+const CHUNK: usize = 64 * 1024;
+
+/// Files queued per hashing worker before the walk has to wait for them, so a
+/// walk that outpaces the disk doesn't queue up the whole tree.
+const QUEUE_PER_WORKER: usize = 32;
+
+/// Entries held back per hashing worker behind a file that is still being
+/// hashed, with [`InOrder`].
+const WINDOW_PER_WORKER: usize = 256;
+
+/// How often the progress line on stderr is refreshed.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Shown in place of a digest when a file could not be read.
+pub const UNREADABLE: &str = "<unreadable>";
+
+/// Shown in place of a digest for a file larger than `--hash-max-size`.
+pub const TOO_LARGE: &str = "<skipped: too large>";
+
+/// Shown in place of a digest for an entry inside an archive, which isn't hashed.
+pub const NOT_HASHED: &str = "-";
+
+/// The digest to display for a hashed file: its hex digest, [`NOT_HASHED`] for an
+/// archive entry, [`TOO_LARGE`], or [`UNREADABLE`].
+pub fn display_digest(entry: &FileEntry) -> &str {
+    match &entry.digest {
+        Some(digest) => digest,
+        None if entry.archived => NOT_HASHED,
+        None if entry.too_large => TOO_LARGE,
+        None => UNREADABLE,
+    }
+}
+
+/// Digest of the file at `path` with `algorithm`, as lowercase hex.
+pub fn hash_file(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    digest_file(File::open(path)?, algorithm)
+}
+
+/// Like [`hash_file`], but `None` without reading it if the file is larger than
+/// `max_size` bytes.
+fn hash_within(path: &Path, algorithm: HashAlgorithm, max_size: Option<u64>) -> io::Result<Option<String>> {
+    let file = File::open(path)?;
+    if let Some(max) = max_size
+        && file.metadata()?.len() > max
+    {
+        return Ok(None);
+    }
+    digest_file(file, algorithm).map(Some)
+}
+
+fn digest_file(file: File, algorithm: HashAlgorithm) -> io::Result<String> {
+    match algorithm {
+        HashAlgorithm::Sha256 => digest_reader::<sha2::Sha256>(file),
+        HashAlgorithm::Sha1 => digest_reader::<sha1::Sha1>(file),
+        HashAlgorithm::Md5 => digest_reader::<md5::Md5>(file),
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            for_each_chunk(file, |chunk| {
+                hasher.update(chunk);
+            })?;
+            Ok(hex(hasher.finalize().as_bytes()))
+        }
+        HashAlgorithm::Crc32 => {
+            let mut hasher = crc32fast::Hasher::new();
+            for_each_chunk(file, |chunk| hasher.update(chunk))?;
+            Ok(hex(&hasher.finalize().to_be_bytes()))
+        }
+        HashAlgorithm::Xxh3 => {
+            let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+            for_each_chunk(file, |chunk| hasher.update(chunk))?;
+            Ok(hex(&hasher.digest().to_be_bytes()))
+        }
+    }
+}
+
+fn digest_reader<D: Digest>(file: File) -> io::Result<String> {
+    let mut hasher = D::new();
+    for_each_chunk(file, |chunk| hasher.update(chunk))?;
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Feed `file` to `consume` in [`CHUNK`]-sized pieces.
+fn for_each_chunk(mut file: File, mut consume: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut buf = vec![0u8; CHUNK];
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => consume(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Lowercase hex encoding, the one digest representation used everywhere.
+pub fn hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for &b in bytes {
+        out.push(DIGITS[usize::from(b >> 4)] as char);
+        out.push(DIGITS[usize::from(b & 0xf)] as char);
+    }
+    out
+}
+
+/// A pool of hashing threads fed through a bounded queue, so files are hashed
+/// while the walk is still finding more. Each result comes back with the ticket
+/// its file was queued under, in whatever order the workers finish.
+struct Pipeline {
+    jobs: Option<SyncSender<(u64, PathBuf)>>,
+    results: Receiver<(u64, io::Result<Option<String>>)>,
+    workers: Vec<JoinHandle<()>>,
+    /// Files queued whose result hasn't been taken yet.
+    outstanding: u64,
+}
+
+impl Pipeline {
+    /// Start `workers` threads hashing files under `root` with `algorithm`,
+    /// skipping those larger than `max_size`.
+    fn start(root: &Path, algorithm: HashAlgorithm, max_size: Option<u64>, workers: usize) -> Self {
+        let (jobs, queue) = mpsc::sync_channel::<(u64, PathBuf)>(workers * QUEUE_PER_WORKER);
+        let (done, results) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        let workers = (0..workers)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let done = done.clone();
+                let root = root.to_path_buf();
+                std::thread::spawn(move || {
+                    loop {
+                        let job = queue.lock().ok().and_then(|queue| queue.recv().ok());
+                        let Some((ticket, path)) = job else {
+                            return;
+                        };
+                        let result = hash_within(&root.join(path), algorithm, max_size);
+                        if done.send((ticket, result)).is_err() {
+                            return;
+                        }
+                    }
+                })
+            })
+            .collect();
+        Pipeline { jobs: Some(jobs), results, workers, outstanding: 0 }
+    }
+
+    /// Queue the file at `path` (relative to the root) under `ticket`, waiting
+    /// while the queue is full.
+    fn submit(&mut self, ticket: u64, path: &Path) {
+        if let Some(jobs) = &self.jobs
+            && jobs.send((ticket, path.to_path_buf())).is_ok()
+        {
+            self.outstanding += 1;
+        }
+    }
+
+    /// A result that is already in, if any.
+    fn try_next(&mut self) -> Option<(u64, io::Result<Option<String>>)> {
+        let result = self.results.try_recv().ok()?;
+        self.outstanding -= 1;
+        Some(result)
+    }
+
+    /// The next result, waiting for it; `None` once every file queued is done.
+    fn next(&mut self) -> Option<(u64, io::Result<Option<String>>)> {
+        if self.outstanding == 0 {
+            return None;
+        }
+        let result = self.results.recv().ok()?;
+        self.outstanding -= 1;
+        Some(result)
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Number of hashing workers: `--hash-threads`, else `--threads`, else one per
+/// CPU core.
+fn workers(options: &ScanOptions) -> usize {
+    options
+        .hash_threads
+        .or(options.threads)
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, usize::from))
+        .max(1)
+}
+
+/// Whether `entry`, listed under `group`, is hashed: it has content and isn't
+/// inside an archive.
+fn hashes(group: &str, entry: &FileEntry) -> bool {
+    is_regular_group(group) && !entry.archived && entry.digest.is_none() && !entry.too_large
+}
+
+/// Warn about a file that could not be hashed, unless `quiet`.
+fn warn(entry: &FileEntry, e: &io::Error, quiet: bool) {
+    if !quiet {
+        eprintln!("Warning: cannot hash '{}': {e}", entry.path.display());
+    }
+}
+
+/// Hashes files as the walk adds them to an in-memory catalog, storing each
+/// digest on its entry once a worker is done with it.
+///
+/// A file that can't be read keeps `digest: None` and is warned about unless
+/// `--quiet`. Digests are stored by the entry's place in the catalog, so which
+/// worker finishes first doesn't change the output. With `--checkpoint`, each
+/// one is also saved there, and entries a resumed scan brings back with their
+/// digests aren't hashed again.
+pub struct CatalogHasher<'a> {
+    pipeline: Pipeline,
+    checkpoint: Option<&'a Checkpoint>,
+    /// Where each file still being hashed sits in the catalog, by ticket.
+    pending: HashMap<u64, (String, usize)>,
+    next_ticket: u64,
+    failures: u64,
+    skipped: u64,
+    quiet: bool,
+}
+
+impl<'a> CatalogHasher<'a> {
+    pub fn new(root: &Path, algorithm: HashAlgorithm, options: &ScanOptions, checkpoint: Option<&'a Checkpoint>) -> Self {
+        CatalogHasher {
+            pipeline: Pipeline::start(root, algorithm, options.hash_max_size, workers(options)),
+            checkpoint,
+            pending: HashMap::new(),
+            next_ticket: 0,
+            failures: 0,
+            skipped: 0,
+            quiet: options.quiet,
+        }
+    }
+
+    /// Add `entry` to `catalog` under `group`, queueing it for hashing if it has
+    /// content, and store whatever digests are ready.
+    pub fn add(&mut self, catalog: &mut Catalog, group: String, entry: FileEntry) {
+        let hashed = hashes(&group, &entry);
+        if entry.too_large {
+            self.skipped += 1;
+        }
+        let entries = catalog.entry(group.clone()).or_default();
+        if hashed {
+            let ticket = self.next_ticket;
+            self.next_ticket += 1;
+            self.pipeline.submit(ticket, &entry.path);
+            self.pending.insert(ticket, (group, entries.len()));
+        }
+        entries.push(entry);
+        while let Some(result) = self.pipeline.try_next() {
+            self.store(catalog, result);
+        }
+    }
+
+    /// Wait for the files still being hashed and store their digests, counting
+    /// in `stats` the files that could not be read or were too large. Unless
+    /// `--quiet`, a `Hashing N/M files` line on an interactive stderr shows how
+    /// the rest is going.
+    pub fn finish(mut self, catalog: &mut Catalog, stats: &mut ScanStats) {
+        let total = self.pending.len() as u64;
+        let done = AtomicU64::new(0);
+        let finished = AtomicBool::new(false);
+        let show_progress = total > 0 && !self.quiet && io::stderr().is_terminal();
+        std::thread::scope(|scope| {
+            if show_progress {
+                scope.spawn(|| report_progress(&done, total, &finished));
+            }
+            while let Some(result) = self.pipeline.next() {
+                self.store(catalog, result);
+                done.fetch_add(1, Ordering::Relaxed);
+                if let Some(checkpoint) = self.checkpoint {
+                    checkpoint.commit();
+                }
+            }
+            finished.store(true, Ordering::Relaxed);
+        });
+        stats.hash_failures = self.failures;
+        stats.hash_skipped = self.skipped;
+    }
+
+    fn store(&mut self, catalog: &mut Catalog, (ticket, result): (u64, io::Result<Option<String>>)) {
+        let Some((group, index)) = self.pending.remove(&ticket) else {
+            return;
+        };
+        let Some(entry) = catalog.get_mut(&group).and_then(|entries| entries.get_mut(index)) else {
+            return;
+        };
+        match result {
+            Ok(Some(digest)) => {
+                if let Some(checkpoint) = self.checkpoint {
+                    checkpoint.digest(&entry.path, Some(&digest));
+                }
+                entry.digest = Some(digest);
+            }
+            Ok(None) => {
+                if let Some(checkpoint) = self.checkpoint {
+                    checkpoint.digest(&entry.path, None);
+                }
+                entry.too_large = true;
+                self.skipped += 1;
+            }
+            Err(e) => {
+                warn(entry, &e, self.quiet);
+                self.failures += 1;
+            }
+        }
+    }
+}
+
+/// Hashes files for output written as the walk goes, handing entries back in
+/// the order they were added once their digests are in.
+///
+/// Entries behind a file still being hashed are held back, up to a window of
+/// [`WINDOW_PER_WORKER`] per worker; past that the walk waits for it, so memory
+/// stays flat however far the walk gets ahead.
+pub struct InOrder {
+    pipeline: Pipeline,
+    /// Entries not handed back yet, oldest first, with whether each is still
+    /// being hashed.
+    window: VecDeque<(String, FileEntry, bool)>,
+    /// Ticket of the entry at the front of `window`.
+    front: u64,
+    limit: usize,
+    failures: u64,
+    skipped: u64,
+    quiet: bool,
+}
+
+impl InOrder {
+    pub fn new(root: &Path, algorithm: HashAlgorithm, options: &ScanOptions) -> Self {
+        let workers = workers(options);
+        InOrder {
+            pipeline: Pipeline::start(root, algorithm, options.hash_max_size, workers),
+            window: VecDeque::new(),
+            front: 0,
+            limit: workers * WINDOW_PER_WORKER,
+            failures: 0,
+            skipped: 0,
+            quiet: options.quiet,
+        }
+    }
+
+    /// Queue `entry` (listed under `group`) for hashing if it has content, and
+    /// pass every entry that is ready, in order, to `emit`.
+    pub fn add(&mut self, group: String, entry: FileEntry, emit: &mut impl FnMut(String, FileEntry)) {
+        let hashed = hashes(&group, &entry);
+        if hashed {
+            let ticket = self.front + self.window.len() as u64;
+            self.pipeline.submit(ticket, &entry.path);
+        }
+        self.window.push_back((group, entry, hashed));
+        while let Some(result) = self.pipeline.try_next() {
+            self.store(result);
+        }
+        self.release(emit);
+        while self.window.len() >= self.limit {
+            let Some(result) = self.pipeline.next() else {
+                break;
+            };
+            self.store(result);
+            self.release(emit);
+        }
+    }
+
+    /// Wait for the files still being hashed and pass the remaining entries to
+    /// `emit`, counting in `stats` the files that could not be read or were too
+    /// large.
+    pub fn finish(mut self, emit: &mut impl FnMut(String, FileEntry), stats: &mut ScanStats) {
+        while let Some(result) = self.pipeline.next() {
+            self.store(result);
+            self.release(emit);
+        }
+        for (group, entry, _) in self.window.drain(..) {
+            emit(group, entry);
+        }
+        stats.hash_failures = self.failures;
+        stats.hash_skipped = self.skipped;
+    }
+
+    fn store(&mut self, (ticket, result): (u64, io::Result<Option<String>>)) {
+        let Some((_, entry, waiting)) = self.window.get_mut((ticket - self.front) as usize) else {
+            return;
+        };
+        *waiting = false;
+        match result {
+            Ok(Some(digest)) => entry.digest = Some(digest),
+            Ok(None) => {
+                entry.too_large = true;
+                self.skipped += 1;
+            }
+            Err(e) => {
+                warn(entry, &e, self.quiet);
+                self.failures += 1;
+            }
+        }
+    }
+
+    /// Hand the entries at the front that are done to `emit`.
+    fn release(&mut self, emit: &mut impl FnMut(String, FileEntry)) {
+        while self.window.front().is_some_and(|(_, _, waiting)| !waiting) {
+            if let Some((group, entry, _)) = self.window.pop_front() {
+                self.front += 1;
+                emit(group, entry);
+            }
+        }
+    }
+}
+
+/// A rayon pool sized by `--threads`, or `None` to use the global pool.
+pub fn thread_pool(options: &ScanOptions) -> Option<rayon::ThreadPool> {
+    options.threads.and_then(|n| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(n.max(1))
+            .build()
+            .ok()
+    })
+}
+
+/// Redraw a progress line on stderr until `finished`, then clear it.
+fn report_progress(done: &AtomicU64, total: u64, finished: &AtomicBool) {
+    let mut stderr = io::stderr();
+    while !finished.load(Ordering::Relaxed) {
+        let _ = write!(stderr, "\rHashing {}/{total} files", done.load(Ordering::Relaxed));
+        let _ = stderr.flush();
+        std::thread::sleep(PROGRESS_INTERVAL);
+    }
+    let _ = write!(stderr, "\r\x1b[K");
+}
@@ -0,0 +1,109 @@
+//! Ctrl-C handling. A first Ctrl-C during the walk stops it, and whatever was
+//! found so far is still written, marked as a partial scan. A second one, or one
+//! outside the walk, quits at once. With `--watch`, a first Ctrl-C instead lets
+//! the current scan finish and asks the watch loop to stop after its last write.
+//!
+//! `--timeout` stops the walk the same way once its deadline passes.
+
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Exit status after an interrupted scan (128 + SIGINT, as shells report it).
+pub const EXIT_CODE: i32 = 130;
+
+/// Exit status after `--timeout` stopped the scan, as `timeout(1)` reports it.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// How long past the deadline a walk stuck in one filesystem call (a hung
+/// network share) is waited for before the process quits without output.
+const GRACE: Duration = Duration::from_secs(10);
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+static WALKING: AtomicBool = AtomicBool::new(false);
+static WATCHING: AtomicBool = AtomicBool::new(false);
+static STOP: AtomicBool = AtomicBool::new(false);
+static DEADLINE: OnceLock<Instant> = OnceLock::new();
+
+/// Install the Ctrl-C handler. Failing to is not fatal: Ctrl-C then just kills
+/// the process as usual.
+pub fn install() {
+    let _ = ctrlc::set_handler(|| {
+        if WATCHING.load(Ordering::SeqCst) {
+            if STOP.swap(true, Ordering::SeqCst) {
+                std::process::exit(EXIT_CODE);
+            }
+            eprintln!("\nStopping: writing any pending changes (press Ctrl-C again to quit now)");
+            return;
+        }
+        if !WALKING.load(Ordering::SeqCst) || REQUESTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(EXIT_CODE);
+        }
+        eprintln!("\nInterrupted: writing what was found so far (press Ctrl-C again to quit now)");
+    });
+}
+
+/// Whether the walk has been asked to stop.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Start the `--timeout` clock: the walk stops once `timeout` has passed. A
+/// walk that can't get back to check, blocked in a directory listing that never
+/// returns, is ended [`GRACE`] later.
+pub fn start_deadline(timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    if DEADLINE.set(deadline).is_err() {
+        return;
+    }
+    std::thread::spawn(move || {
+        std::thread::sleep(deadline.saturating_duration_since(Instant::now()) + GRACE);
+        if WALKING.load(Ordering::SeqCst) {
+            eprintln!("Timed out: the walk is stuck and did not stop; quitting without output");
+            std::process::exit(TIMEOUT_EXIT_CODE);
+        }
+    });
+}
+
+/// Whether the `--timeout` deadline has passed.
+pub fn timed_out() -> bool {
+    DEADLINE.get().is_some_and(|deadline| Instant::now() >= *deadline)
+}
+
+/// Whether the walk should stop, for Ctrl-C or `--timeout`.
+pub fn stopping() -> bool {
+    requested() || timed_out()
+}
+
+/// How long is left before the `--timeout` deadline, if there is one.
+pub fn time_left() -> Option<Duration> {
+    DEADLINE.get().map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+/// From now on, Ctrl-C asks `--watch` to stop (see [`stop_requested`]) rather
+/// than cutting a scan short.
+pub fn watching() {
+    WATCHING.store(true, Ordering::SeqCst);
+}
+
+/// Whether `--watch` has been asked to stop.
+pub fn stop_requested() -> bool {
+    STOP.load(Ordering::Relaxed)
+}
+
+/// Marks the walk as running for as long as it is held, so a Ctrl-C stops it
+/// instead of quitting.
+pub struct Walking(());
+
+impl Walking {
+    pub fn start() -> Self {
+        WALKING.store(true, Ordering::SeqCst);
+        Walking(())
+    }
+}
+
+impl Drop for Walking {
+    fn drop(&mut self) {
+        WALKING.store(false, Ordering::SeqCst);
+    }
+}
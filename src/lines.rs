@@ -0,0 +1,63 @@
+//! `--lines`: line counts for text files, a lightweight `cloc`.
+
+use crate::config::ScanOptions;
+use crate::scan::{Catalog, is_regular_group};
+use crate::{hash, sniff};
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Read size for counting; files are streamed, never loaded whole.
+const CHUNK: usize = 64 * 1024;
+
+/// Count the lines in the file at `path`, or `None` if its start looks binary
+/// (the `--only` NUL-byte sniff). A final line without a trailing newline counts.
+pub fn count_lines(path: &Path) -> io::Result<Option<u64>> {
+    let mut file = File::open(path)?;
+    let mut buf = vec![0u8; CHUNK];
+    let mut lines = 0;
+    let mut first = true;
+    let mut last = b'\n';
+    loop {
+        let n = match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let chunk = &buf[..n];
+        if first && sniff::looks_binary(chunk) {
+            return Ok(None);
+        }
+        first = false;
+        lines += chunk.iter().filter(|&&b| b == b'\n').count() as u64;
+        last = chunk[n - 1];
+    }
+    Ok(Some(lines + u64::from(last != b'\n')))
+}
+
+/// Count lines in every catalogued file under `root`, in parallel, storing the
+/// counts on the entries. Binary and unreadable files keep `lines: None`; read
+/// failures are warned about unless `--quiet`.
+pub fn count_catalog(catalog: &mut Catalog, root: &Path, options: &ScanOptions) {
+    let count_all = |catalog: &mut Catalog| {
+        catalog
+            .par_iter_mut()
+            .filter(|(group, _)| is_regular_group(group))
+            .flat_map(|(_, entries)| entries.par_iter_mut())
+            .filter(|entry| !entry.archived)
+            .for_each(|entry| match count_lines(&root.join(&entry.path)) {
+                Ok(lines) => entry.lines = lines,
+                Err(e) => {
+                    if !options.quiet {
+                        eprintln!("Warning: cannot count lines in '{}': {e}", entry.path.display());
+                    }
+                }
+            });
+    };
+    match hash::thread_pool(options) {
+        Some(pool) => pool.install(|| count_all(catalog)),
+        None => count_all(catalog),
+    }
+}
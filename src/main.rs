@@ -1,37 +1,114 @@
 mod app;
+mod archive;
+mod attributes;
+mod cache;
+mod category;
+mod charset;
+mod checkpoint;
 mod cli;
+mod completions;
 mod config;
+mod diskusage;
+mod duplicates;
+mod exec;
+mod exif;
+mod gitignore;
+mod gitstatus;
+mod hardlinks;
+mod hash;
+mod interrupt;
+mod lines;
+mod mounts;
 mod output;
+mod owner;
+mod progress;
+mod retry;
 mod scan;
+mod shebang;
+mod sniff;
+mod watch;
+mod xattrs;
 
-use clap::{CommandFactory, Parser};
+use clap::{CommandFactory, FromArgMatches};
 use cli::Cli;
+use scan::Roots;
 
 fn main() {
-    let args = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.format(&mut Cli::command()).exit());
 
-    let options = args.scan_options();
+    if args.list_categories {
+        category::print_list();
+        return;
+    }
+    if args.list_junk {
+        category::print_junk();
+        return;
+    }
+    if let Some(shell) = args.completions {
+        completions::print(shell);
+        return;
+    }
+
+    let mut options = args.scan_options().unwrap_or_else(|e| exit_error(&e));
+    options.given = cli::given_options(&matches);
     let filter = args
-        .scan_filter()
+        .scan_filter(&options)
         .unwrap_or_else(|e| exit_error(&e));
+    interrupt::install();
 
     if args.select {
-        if args.folder.is_some() || args.output.is_some() {
+        if !args.paths.is_empty() || args.output.is_some() {
             exit_error("--select cannot be used with path arguments");
         }
-        app::run_with_dialogs(&options, &filter);
+        app::run_with_dialogs(&options, filter);
         return;
     }
 
-    let Some(folder) = args.folder.clone() else {
+    let (folders, output) = args.folders_and_output().unwrap_or_else(|e| exit_error(&e));
+    if folders.is_empty() {
         Cli::command().print_help().unwrap();
         return;
-    };
+    }
 
-    let folder = cli::normalize_path(folder);
-    let output_path = cli::resolve_output_path(args.output.clone(), &folder, options.format);
+    let mut folders: Vec<_> = folders.into_iter().map(cli::normalize_path).collect();
+    // A stale entry in a --paths-from list shouldn't sink the rest of the batch.
+    let mut missing = Vec::new();
+    if args.paths_from.is_some() {
+        let (found, listed): (Vec<_>, Vec<_>) =
+            folders.into_iter().partition(|folder| folder.is_dir() || folder.is_file());
+        folders = found;
+        if !options.quiet {
+            for folder in &listed {
+                eprintln!("Warning: skipping '{}': not an existing folder or file", folder.display());
+            }
+        }
+        if folders.is_empty() {
+            exit_error("none of the folders from --paths-from exist");
+        }
+        missing = listed;
+    }
+    let mut roots = Roots::new(folders).unwrap_or_else(|e| exit_error(&e));
+    roots.missing = missing;
+    if let Some(list) = &args.files_from {
+        if !roots.folders[0].is_dir() {
+            exit_error(&format!("--root '{}' is not an existing directory", roots.folders[0].display()));
+        }
+        let files = cli::read_files(list, args.null).unwrap_or_else(|e| exit_error(&e));
+        roots.files = Some(files.into_iter().map(cli::normalize_path).collect());
+    }
+    if !options.quiet {
+        for (folder, cover) in &roots.merged {
+            eprintln!(
+                "Warning: '{}' is inside or the same as '{}'; scanning it once, as part of that",
+                folder.display(),
+                cover.display()
+            );
+        }
+    }
+    let output_path = cli::resolve_output_path(output, &roots, &options);
 
-    app::run(&folder, &output_path, &options, &filter);
+    app::run(&roots, &output_path, &options, filter);
 }
 
 /// Print an error to stderr and exit with a non-zero status.
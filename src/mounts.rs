@@ -0,0 +1,148 @@
+//! `--one-file-system`: keep the walk on the filesystem each folder starts on,
+//! like `find -xdev`. Unix compares device IDs; Windows compares volume serial
+//! numbers, so a junction or mount point onto another volume is a boundary too.
+//!
+//! Also the virtual filesystems a scan skips unless `--include-virtual`: on Linux,
+//! the kernel's pseudo filesystems (`/proc`, `/sys`, `/dev` and the like), whose
+//! "files" are generated on demand and can block when read.
+
+use std::path::{Path, PathBuf};
+
+/// Filesystem types that hold no stored data.
+#[cfg(target_os = "linux")]
+const VIRTUAL_TYPES: &[&str] = &[
+    "proc", "sysfs", "devtmpfs", "devpts", "cgroup", "cgroup2", "securityfs", "debugfs", "tracefs", "pstore",
+    "bpf", "mqueue", "hugetlbfs", "configfs", "fusectl", "binfmt_misc", "autofs", "efivarfs", "selinuxfs",
+    "rpc_pipefs", "nsfs",
+];
+
+/// Where a `tmpfs` is runtime state rather than somewhere files are kept.
+#[cfg(target_os = "linux")]
+const RUNTIME_DIRS: &[&str] = &["/run", "/dev", "/sys", "/proc"];
+
+/// The device (or volume) the directory at `path` lives on, following links.
+#[cfg(unix)]
+pub fn device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| m.dev())
+}
+
+/// The device (or volume) the directory at `path` lives on, following links.
+#[cfg(windows)]
+pub fn device(path: &Path) -> Option<u64> {
+    let handle = winapi_util::Handle::from_path_any(path).ok()?;
+    winapi_util::file::information(&handle).ok().map(|info| info.volume_serial_number())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn device(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Whether a directory on `device` is somewhere else than a walk that started on
+/// `root`. Unknown devices never count as a boundary.
+pub fn crosses(root: Option<u64>, device: Option<u64>) -> bool {
+    matches!((root, device), (Some(root), Some(device)) if root != device)
+}
+
+/// Whether the directory at `path` is on another filesystem than `root`, the
+/// device a walk started on; `path` isn't looked at when `root` is unknown.
+pub fn leaves(root: Option<u64>, path: &Path) -> bool {
+    root.is_some() && crosses(root, device(path))
+}
+
+/// The mount points of virtual filesystems, from `/proc/mounts`: the types in
+/// [`VIRTUAL_TYPES`], and `tmpfs` under [`RUNTIME_DIRS`]. Empty if the table
+/// can't be read.
+#[cfg(target_os = "linux")]
+pub fn virtual_mounts() -> Vec<PathBuf> {
+    let Ok(table) = std::fs::read_to_string("/proc/mounts") else {
+        return Vec::new();
+    };
+    let mut mounts: Vec<PathBuf> = table
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let (_, point, kind) = (fields.next()?, unescape(fields.next()?), fields.next()?);
+            let runtime = kind == "tmpfs" && RUNTIME_DIRS.iter().any(|dir| Path::new(&point).starts_with(dir));
+            (VIRTUAL_TYPES.contains(&kind) || runtime).then(|| PathBuf::from(point))
+        })
+        .collect();
+    mounts.sort();
+    mounts.dedup();
+    mounts
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn virtual_mounts() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// A mount point as `/proc/mounts` writes it, with spaces, tabs, newlines and
+/// backslashes as octal escapes (`\040`).
+#[cfg(target_os = "linux")]
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 4).and_then(|digits| {
+            let digits = std::str::from_utf8(digits).ok()?;
+            u8::from_str_radix(digits, 8).ok()
+        });
+        match escaped {
+            Some(byte) if bytes[i] == b'\\' => {
+                out.push(byte);
+                i += 4;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_two_known_different_devices_are_a_boundary() {
+        assert!(!crosses(Some(7), Some(7)));
+        assert!(crosses(Some(7), Some(8)));
+        assert!(!crosses(None, Some(8)));
+        assert!(!crosses(Some(7), None));
+        assert!(!crosses(None, None));
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn a_subdirectory_is_on_its_parents_device() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("sub");
+        std::fs::create_dir(&sub).unwrap();
+        let root = device(dir.path());
+        assert!(root.is_some());
+        assert!(!crosses(root, device(&sub)));
+        assert!(!leaves(root, &sub));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn proc_is_a_filesystem_of_its_own() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(crosses(device(dir.path()), device(Path::new("/proc"))));
+        assert!(leaves(device(dir.path()), Path::new("/proc")));
+        assert!(!leaves(None, Path::new("/proc")));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn octal_escapes_in_mount_points_are_decoded() {
+        assert_eq!(unescape(r"/mnt/my\040disk"), "/mnt/my disk");
+        assert_eq!(unescape(r"/mnt/back\134slash"), r"/mnt/back\slash");
+        assert_eq!(unescape(r"/mnt/odd\04"), r"/mnt/odd\04");
+    }
+}
@@ -0,0 +1,80 @@
+//! ANSI colour for the text catalog when it is printed to a terminal: group
+//! headers in bold, their counts in yellow and error lines in red. A file is
+//! never coloured, so a [`Palette`] is chosen for where the output goes.
+
+use crate::config::ColorChoice;
+use std::io::{self, IsTerminal};
+
+const BOLD_CYAN: &str = "\x1b[1;36m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether, and so how, text is coloured.
+#[derive(Clone, Copy)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    /// No colour at all, for anything written to a file.
+    pub const PLAIN: Palette = Palette { enabled: false };
+
+    /// The palette for text printed to stdout, by `--color`: `auto` colours
+    /// only a terminal, and not when `NO_COLOR` is set.
+    pub fn stdout(choice: ColorChoice) -> Palette {
+        let enabled = match choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+        };
+        Palette { enabled: enabled && enable_ansi() }
+    }
+
+    /// A group or section heading.
+    pub fn heading(self, text: &str) -> String {
+        self.paint(BOLD_CYAN, text)
+    }
+
+    /// The counts in a heading.
+    pub fn count(self, text: &str) -> String {
+        self.paint(YELLOW, text)
+    }
+
+    /// An error, or the notice heading a partial catalog.
+    pub fn error(self, text: &str) -> String {
+        self.paint(RED, text)
+    }
+
+    fn paint(self, code: &str, text: &str) -> String {
+        if self.enabled { format!("{code}{text}{RESET}") } else { text.to_string() }
+    }
+}
+
+/// Windows consoles show escape codes as text until asked to interpret them;
+/// if that fails, nothing is coloured.
+#[cfg(windows)]
+fn enable_ansi() -> bool {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::System::Console::{
+        CONSOLE_MODE, ENABLE_VIRTUAL_TERMINAL_PROCESSING, GetConsoleMode, SetConsoleMode,
+    };
+    let handle = io::stdout().as_raw_handle();
+    let mut mode: CONSOLE_MODE = 0;
+    // SAFETY: `handle` is this process's stdout; `mode` is a valid out-pointer.
+    if unsafe { GetConsoleMode(handle, &mut mode) } == 0 {
+        // Not a console but a pipe, a file or a terminal emulator's pty, any of
+        // which passes escape codes through.
+        return true;
+    }
+    // SAFETY: as above; the mode only gains a flag.
+    mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+        || unsafe { SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) } != 0
+}
+
+#[cfg(not(windows))]
+fn enable_ansi() -> bool {
+    true
+}
@@ -1,42 +1,182 @@
-//! CSV output, streamed as files are discovered so memory stays flat.
+//! CSV output: one row per file, ordered by extension then path.
 
-use super::buffered_writer;
-use crate::config::{ScanFilter, ScanOptions};
-use crate::scan::{self, ScanStats};
+use super::{modified_time, permissions_label, sorted_entries, sorted_extensions};
+use crate::attributes;
+use crate::config::{GroupBy, ScanOptions};
+use crate::{diskusage, hash, xattrs};
+use crate::scan::{self, Catalog, SYMLINK_GROUP, is_regular_group};
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::PathBuf;
 
-/// Scan `root` and stream rows straight to a CSV file, returning the scan stats.
-pub fn write_streaming(
-    root: &Path,
-    output_path: &Path,
-    options: &ScanOptions,
-    filter: &ScanFilter,
-) -> io::Result<ScanStats> {
-    let mut writer = buffered_writer(output_path)?;
-    writeln!(writer, "extension,path,size")?;
-
-    let mut write_err: Option<io::Error> = None;
-    let stats = scan::walk(root, options, filter, |ext, path, size| {
-        if write_err.is_some() {
-            return;
-        }
-        let size_field = size.map(|s| s.to_string()).unwrap_or_default();
-        if let Err(e) = writeln!(
-            writer,
-            "{},{},{size_field}",
-            field(&ext),
-            field(&path.to_string_lossy()),
-        ) {
-            write_err = Some(e);
+/// Write the catalog as CSV with an `extension,relative_path` header, plus `size`,
+/// `allocated,allocated_estimated` (with `--disk-usage`),
+/// a digest column named after the `--hash` algorithm, `lines`, `readonly,permissions`,
+/// `uid,user,gid,group`, `attributes`, `file_extension` (with `--group-by exec`), `executable`, `hardlink`, `xattrs` (`name=value` pairs joined by `;`),
+/// `encoding`, `git_status`,
+/// `target,broken` (and `resolved`) for listed symlinks,
+/// `modified` and `created` when those are collected. Rows are sorted so identical trees give identical files.
+/// Each record ends in the `--line-endings` terminator, written here rather than
+/// translated on the way out, as a quoted field may hold a line break of its own.
+pub fn write(writer: &mut dyn Write, catalog: &Catalog, options: &ScanOptions) -> io::Result<()> {
+    let end = options.line_ending.terminator();
+    write!(writer, "extension,relative_path")?;
+    if options.sizes {
+        write!(writer, ",size")?;
+    }
+    if options.disk_usage {
+        write!(writer, ",allocated,allocated_estimated")?;
+    }
+    if let Some(algorithm) = options.hash {
+        write!(writer, ",{}", algorithm.name())?;
+    }
+    if options.lines {
+        write!(writer, ",lines")?;
+    }
+    if options.perms {
+        write!(writer, ",readonly,permissions")?;
+    }
+    if options.owner {
+        write!(writer, ",uid,user,gid,group")?;
+    }
+    if options.attributes {
+        write!(writer, ",attributes")?;
+    }
+    if options.group_by == GroupBy::Exec {
+        write!(writer, ",file_extension")?;
+    }
+    if options.executables {
+        write!(writer, ",executable")?;
+    }
+    if options.hardlinks {
+        write!(writer, ",hardlink")?;
+    }
+    if options.xattrs {
+        write!(writer, ",xattrs")?;
+    }
+    if options.detect_encoding {
+        write!(writer, ",encoding")?;
+    }
+    if options.git_status {
+        write!(writer, ",git_status")?;
+    }
+    if options.lists_links() {
+        write!(writer, ",target,broken")?;
+        if options.resolve_links {
+            write!(writer, ",resolved")?;
         }
-    });
+    }
+    if options.mtime {
+        write!(writer, ",modified")?;
+    }
+    if options.created {
+        write!(writer, ",created")?;
+    }
+    writer.write_all(end.as_bytes())?;
 
-    if let Some(e) = write_err {
-        return Err(e);
+    for ext in sorted_extensions(catalog) {
+        let entries = sorted_entries(&catalog[ext]);
+        for entry in entries {
+            write!(writer, "{},{}", field(ext), field(&entry.path.to_string_lossy()))?;
+            if options.sizes {
+                let size = entry.size.map(|s| s.to_string()).unwrap_or_default();
+                write!(writer, ",{size}")?;
+            }
+            if options.disk_usage {
+                // Empty for directories and symlinks.
+                match diskusage::on_disk(entry.allocated, entry.size).filter(|_| is_regular_group(ext)) {
+                    Some((bytes, estimated)) => write!(writer, ",{bytes},{estimated}")?,
+                    None => write!(writer, ",,")?,
+                }
+            }
+            if options.hash.is_some() {
+                // Empty for directories, symlinks and archive entries, `<unreadable>`
+                // for read failures.
+                let digest = match &entry.digest {
+                    Some(digest) => digest,
+                    None if entry.too_large => hash::TOO_LARGE,
+                    None if is_regular_group(ext) && !entry.archived => hash::UNREADABLE,
+                    None => "",
+                };
+                write!(writer, ",{digest}")?;
+            }
+            if options.lines {
+                // Empty for binary files, directories and symlinks.
+                let lines = entry.lines.map(|n| n.to_string()).unwrap_or_default();
+                write!(writer, ",{lines}")?;
+            }
+            if options.perms {
+                match &entry.permissions {
+                    Some(p) => write!(writer, ",{},{}", p.readonly(), permissions_label(p))?,
+                    None if is_regular_group(ext) => write!(writer, ",?,?")?,
+                    None => write!(writer, ",,")?,
+                }
+            }
+            if options.owner {
+                // Empty for directories and symlinks, `?` when the metadata was unreadable.
+                match &entry.owner {
+                    Some(o) => write!(
+                        writer,
+                        ",{},{},{},{}",
+                        o.uid,
+                        field(&o.user),
+                        o.gid,
+                        field(&o.group)
+                    )?,
+                    None if is_regular_group(ext) => write!(writer, ",?,?,?,?")?,
+                    None => write!(writer, ",,,,")?,
+                }
+            }
+            if options.attributes {
+                write!(writer, ",{}", entry.attributes.map(attributes::label).unwrap_or_default())?;
+            }
+            if options.group_by == GroupBy::Exec {
+                let own = if is_regular_group(ext) { scan::group_key(&entry.path, options) } else { String::new() };
+                write!(writer, ",{}", field(&own))?;
+            }
+            if options.executables {
+                write!(writer, ",{}", entry.executable.map(|e| e.to_string()).unwrap_or_default())?;
+            }
+            if options.hardlinks {
+                // Empty for directories and symlinks.
+                if is_regular_group(ext) {
+                    write!(writer, ",{}", entry.hardlink)?;
+                } else {
+                    write!(writer, ",")?;
+                }
+            }
+            if options.xattrs {
+                write!(writer, ",{}", field(&xattrs::labels(&entry.xattrs).join(";")))?;
+            }
+            if options.detect_encoding {
+                write!(writer, ",{}", entry.encoding.map_or("", |e| e.name()))?;
+            }
+            if options.git_status {
+                write!(writer, ",{}", entry.git.map_or("", |s| s.name()))?;
+            }
+            if options.lists_links() {
+                // Empty for everything but symlinks.
+                let path =
+                    |p: Option<&PathBuf>| p.map(|p| field(&p.to_string_lossy())).unwrap_or_default();
+                let link = ext == SYMLINK_GROUP;
+                let broken = if link { entry.broken_link.to_string() } else { String::new() };
+                write!(writer, ",{},{broken}", path(entry.target.as_ref()))?;
+                if options.resolve_links {
+                    write!(writer, ",{}", path(entry.resolved.as_ref()))?;
+                }
+            }
+            if options.mtime {
+                let modified = entry.modified.map(|t| modified_time(t, options)).unwrap_or_default();
+                write!(writer, ",{modified}")?;
+            }
+            if options.created {
+                let created = entry.created.map(|t| modified_time(t, options)).unwrap_or_default();
+                write!(writer, ",{created}")?;
+            }
+            writer.write_all(end.as_bytes())?;
+        }
     }
-    writer.flush()?;
-    Ok(stats)
+
+    Ok(())
 }
 
 /// Quote a CSV field if it contains a comma, quote, or newline (RFC 4180).
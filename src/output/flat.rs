@@ -0,0 +1,76 @@
+//! Flat output: every relative path on its own line, sorted, with no grouping.
+//! Handy for diffing two runs or piping into other tools.
+
+use crate::config::ScanOptions;
+use crate::scan::{Catalog, sort_key};
+use std::io::{self, Write};
+use std::path::Path;
+
+pub fn write(writer: &mut dyn Write, catalog: &Catalog, options: &ScanOptions) -> io::Result<()> {
+    let mut rows: Vec<(&Path, &str)> = catalog
+        .iter()
+        .flat_map(|(ext, entries)| entries.iter().map(move |e| (e.path.as_path(), ext.as_str())))
+        .collect();
+    rows.sort_by_cached_key(|(path, _)| sort_key(path));
+
+    for (path, ext) in rows {
+        let path = escape(&path.to_string_lossy());
+        if options.flat_ext {
+            writeln!(writer, "{}\t{path}", escape(ext))?;
+        } else {
+            writeln!(writer, "{path}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Escape line breaks and tabs so each path stays on one line (and one column),
+/// and backslashes so an escape can't be mistaken for a name that has `\n` in
+/// it. On Windows a backslash is the path separator, which no name contains, so
+/// it is left as it is.
+fn escape(s: &str) -> String {
+    let backslash = cfg!(not(windows)) && s.contains('\\');
+    if !backslash && !s.contains(['\n', '\r', '\t']) {
+        return s.to_string();
+    }
+    let mut escaped = String::with_capacity(s.len() + 8);
+    for c in s.chars() {
+        match c {
+            '\\' if backslash => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use crate::scan::FileEntry;
+    use std::path::PathBuf;
+
+    fn lines(names: &[&str]) -> String {
+        let (options, _) = cli::parse_for_test(&[]);
+        let entries = names.iter().map(|name| FileEntry::new(PathBuf::from(name), Some(0))).collect();
+        let catalog = Catalog::from([(".txt".to_string(), entries)]);
+        let mut out = Vec::new();
+        write(&mut out, &catalog, &options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn line_breaks_and_tabs_are_escaped() {
+        assert_eq!(lines(&["new\nline.txt", "tab\tbed.txt"]), "new\\nline.txt\ntab\\tbed.txt\n");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn a_backslash_in_a_name_stays_distinct_from_an_escaped_newline() {
+        let out = lines(&["new\\nline.txt", "new\nline.txt"]);
+        assert_eq!(out, "new\\nline.txt\nnew\\\\nline.txt\n");
+    }
+}
@@ -0,0 +1,126 @@
+//! Graph exports: the scanned root linked to one node per extension, labelled with
+//! its file count, as Graphviz DOT (`dot -Tpng`) or a Mermaid flowchart.
+
+use super::human_size;
+use crate::config::ScanOptions;
+use crate::scan::Catalog;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// One extension node; `ext` is `None` for the folded "other" node.
+struct Node {
+    ext: Option<String>,
+    count: usize,
+    size: u64,
+}
+
+/// Extension nodes ordered by file count (largest first, ties by name). With
+/// `--graph-limit N`, everything past the first N is folded into one "other" node.
+fn nodes(catalog: &Catalog, limit: Option<usize>) -> Vec<Node> {
+    let mut nodes: Vec<Node> = catalog
+        .iter()
+        .map(|(ext, entries)| Node {
+            ext: Some(ext.clone()),
+            count: entries.len(),
+            size: entries.iter().filter_map(|e| e.size).sum(),
+        })
+        .collect();
+    nodes.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.ext.cmp(&b.ext)));
+
+    if let Some(limit) = limit
+        && nodes.len() > limit
+    {
+        let tail = nodes.split_off(limit);
+        nodes.push(Node {
+            ext: None,
+            count: tail.iter().map(|n| n.count).sum(),
+            size: tail.iter().map(|n| n.size).sum(),
+        });
+    }
+    nodes
+}
+
+fn label(node: &Node, sizes: bool) -> (String, String) {
+    let name = match node.ext.as_deref() {
+        None => "other".to_string(),
+        Some("") => "(no extension)".to_string(),
+        Some(ext) => ext.to_string(),
+    };
+    let detail = if sizes {
+        format!("{} files, {}", node.count, human_size(node.size))
+    } else {
+        format!("{} files", node.count)
+    };
+    (name, detail)
+}
+
+pub fn write_dot(
+    w: &mut dyn Write,
+    catalog: &Catalog,
+    root: &Path,
+    options: &ScanOptions,
+) -> io::Result<()> {
+    writeln!(w, "digraph catalog {{")?;
+    writeln!(w, "  rankdir=LR;")?;
+    writeln!(w, "  node [shape=box, style=rounded];")?;
+    writeln!(
+        w,
+        "  root [label=\"{}\", shape=folder];",
+        dot_escape(&root.to_string_lossy())
+    )?;
+    for (i, node) in nodes(catalog, options.graph_limit).iter().enumerate() {
+        let (name, detail) = label(node, options.sizes);
+        writeln!(w, "  n{i} [label=\"{}\\n{detail}\"];", dot_escape(&name))?;
+        writeln!(w, "  root -> n{i};")?;
+    }
+    writeln!(w, "}}")
+}
+
+pub fn write_mermaid(
+    w: &mut dyn Write,
+    catalog: &Catalog,
+    root: &Path,
+    options: &ScanOptions,
+) -> io::Result<()> {
+    writeln!(w, "flowchart LR")?;
+    writeln!(w, "  root[\"{}\"]", mermaid_escape(&root.to_string_lossy()))?;
+    for (i, node) in nodes(catalog, options.graph_limit).iter().enumerate() {
+        let (name, detail) = label(node, options.sizes);
+        writeln!(
+            w,
+            "  root --> n{i}[\"{}<br>{detail}\"]",
+            mermaid_escape(&name)
+        )?;
+    }
+    Ok(())
+}
+
+/// Escape a DOT double-quoted string; line breaks become spaces.
+fn dot_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escape text inside a Mermaid `["..."]` label using its `#code;` entities.
+fn mermaid_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("#quot;"),
+            '#' => out.push_str("#35;"),
+            '<' => out.push_str("#lt;"),
+            '>' => out.push_str("#gt;"),
+            c if c.is_control() => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,129 @@
+//! Self-contained HTML report: a summary table plus a collapsible `<details>`
+//! section per extension. Styles are inlined so the file works offline.
+
+use super::{count_label, display_size, iso8601_utc, partial_notice, sorted_entries, sorted_extensions};
+use crate::config::ScanOptions;
+use crate::hash;
+use crate::scan::{Catalog, ScanStats, is_regular_group};
+use std::io::{self, Write};
+use std::path::Path;
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.4em; margin-bottom: 0.2em; }
+.meta { color: #666; margin-top: 0; }
+.partial { color: #b00020; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }
+td.num, th.num { text-align: right; }
+tfoot td { font-weight: bold; }
+details { margin: 0.3em 0; }
+summary { cursor: pointer; font-weight: bold; }
+ul { font-family: ui-monospace, monospace; font-size: 0.9em; }
+";
+
+pub fn write(
+    w: &mut dyn Write,
+    catalog: &Catalog,
+    stats: &ScanStats,
+    root: &Path,
+    options: &ScanOptions,
+) -> io::Result<()> {
+    let extensions = sorted_extensions(catalog);
+    let root = escape(&root.to_string_lossy());
+
+    writeln!(w, "<!DOCTYPE html>")?;
+    writeln!(w, "<html lang=\"en\">")?;
+    writeln!(w, "<head>")?;
+    writeln!(w, "<meta charset=\"utf-8\">")?;
+    writeln!(w, "<title>CreepDir: {root}</title>")?;
+    writeln!(w, "<style>\n{STYLE}</style>")?;
+    writeln!(w, "</head>")?;
+    writeln!(w, "<body>")?;
+    writeln!(w, "<h1>{root}</h1>")?;
+    writeln!(
+        w,
+        "<p class=\"meta\">Scanned {} by CreepDir {}</p>",
+        iso8601_utc(stats.started),
+        env!("CARGO_PKG_VERSION")
+    )?;
+    if let Some(notice) = partial_notice(stats) {
+        writeln!(w, "<p class=\"partial\"><strong>{}</strong></p>", escape(&notice))?;
+    }
+
+    writeln!(w, "<table>")?;
+    write!(w, "<thead><tr><th>Extension</th><th class=\"num\">Files</th>")?;
+    if options.sizes {
+        write!(w, "<th class=\"num\">Size</th>")?;
+    }
+    writeln!(w, "</tr></thead>")?;
+    writeln!(w, "<tbody>")?;
+    for ext in &extensions {
+        let entries = &catalog[*ext];
+        write!(w, "<tr><td>{}</td><td class=\"num\">{}</td>", label(ext), entries.len())?;
+        if options.sizes {
+            let total: u64 = entries.iter().filter_map(|e| e.size).sum();
+            write!(w, "<td class=\"num\">{}</td>", display_size(total, options.exact_bytes))?;
+        }
+        writeln!(w, "</tr>")?;
+    }
+    writeln!(w, "</tbody>")?;
+    write!(w, "<tfoot><tr><td>Total</td><td class=\"num\">{}</td>", stats.files)?;
+    if options.sizes {
+        let total = display_size(stats.total_size, options.exact_bytes);
+        write!(w, "<td class=\"num\">{total}</td>")?;
+    }
+    writeln!(w, "</tr></tfoot>")?;
+    writeln!(w, "</table>")?;
+
+    for ext in extensions {
+        let entries = sorted_entries(&catalog[ext]);
+
+        writeln!(w, "<details>")?;
+        writeln!(w, "<summary>{} ({})</summary>", label(ext), count_label(ext, entries.len()))?;
+        writeln!(w, "<ul>")?;
+        for entry in entries {
+            let path = escape(&entry.path.to_string_lossy());
+            write!(w, "<li>{path}")?;
+            if options.sizes {
+                write!(w, " ({})", display_size(entry.size.unwrap_or(0), options.exact_bytes))?;
+            }
+            if options.hash.is_some() && is_regular_group(ext) {
+                let digest = escape(hash::display_digest(entry));
+                write!(w, " <code>{digest}</code>")?;
+            }
+            writeln!(w, "</li>")?;
+        }
+        writeln!(w, "</ul>")?;
+        writeln!(w, "</details>")?;
+    }
+
+    writeln!(w, "</body>")?;
+    writeln!(w, "</html>")?;
+    Ok(())
+}
+
+/// Display label for an extension key, escaped for HTML.
+fn label(ext: &str) -> String {
+    if ext.is_empty() {
+        "(no extension)".to_string()
+    } else {
+        escape(ext)
+    }
+}
+
+/// Escape text for use in HTML element content and attribute values.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 8);
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
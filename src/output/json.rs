@@ -1,36 +1,185 @@
 //! JSON output: a summary plus files grouped by extension.
 
-use super::{buffered_writer, sorted_extensions};
-use crate::config::ScanOptions;
-use crate::scan::{Catalog, ScanStats};
+use super::{iso8601_utc, modified_time, permissions_label, sorted_entries, sorted_extensions};
+use crate::attributes;
+use crate::config::{GroupBy, HashAlgorithm, ScanOptions};
+use crate::duplicates::{self, DuplicateGroup};
+use crate::gitstatus::{GitCounts, GitStatus};
+use crate::{diskusage, hash};
+use crate::owner::Owner;
+use crate::xattrs::Xattr;
+use crate::scan::{self, Catalog, FileEntry, SYMLINK_GROUP, ScanError, ScanStats, is_regular_group};
+use std::fs::Permissions;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+/// Write the catalog as a single JSON document.
+///
+/// Paths are converted lossily to UTF-8 and escaped, so the document stays valid
+/// even for names containing quotes, control characters, or non-UTF-8 bytes.
 pub fn write(
+    w: &mut dyn Write,
     catalog: &Catalog,
     stats: &ScanStats,
     root: &Path,
-    output_path: &Path,
     options: &ScanOptions,
 ) -> io::Result<()> {
-    let mut w = buffered_writer(output_path)?;
-
     writeln!(w, "{{")?;
     writeln!(w, "  \"generator\": \"CreepDir {}\",", env!("CARGO_PKG_VERSION"))?;
     writeln!(w, "  \"root\": \"{}\",", escape(&root.to_string_lossy()))?;
+    writeln!(w, "  \"scanned_at\": \"{}\",", iso8601_utc(stats.started))?;
+    if let Some(at) = &stats.interrupted {
+        let key = if stats.timed_out { "timed_out_at" } else { "interrupted_at" };
+        writeln!(w, "  \"{key}\": \"{}\",", escape(&at.to_string_lossy()))?;
+    }
+    if let Some(at) = &stats.truncated {
+        writeln!(w, "  \"truncated_at\": \"{}\",", escape(&at.to_string_lossy()))?;
+    }
     writeln!(w, "  \"summary\": {{")?;
     writeln!(w, "    \"files\": {},", stats.files)?;
     writeln!(w, "    \"directories\": {},", stats.dirs)?;
     writeln!(w, "    \"skipped\": {},", stats.skipped)?;
     writeln!(w, "    \"symlinks\": {},", stats.symlinks)?;
-    writeln!(w, "    \"total_size\": {}", num_opt(options.sizes, stats.total_size))?;
+    writeln!(w, "    \"symlink_loops\": {},", stats.link_loops)?;
+    writeln!(w, "    \"broken_symlinks\": {},", stats.broken_links)?;
+    writeln!(w, "    \"total_size\": {},", num_opt(options.sizes, stats.total_size))?;
+    let deduplicated = stats.total_size - stats.hardlinked_size;
+    writeln!(w, "    \"deduplicated_size\": {},", num_opt(options.sizes, deduplicated))?;
+    writeln!(w, "    \"allocated_size\": {}", num_opt(options.disk_usage, stats.allocated_size))?;
     writeln!(w, "  }},")?;
+    if options.duplicates {
+        write_duplicates(w, &stats.duplicates)?;
+    }
+    if options.hardlinks {
+        let groups: Vec<String> = stats
+            .hardlinks
+            .iter()
+            .map(|g| {
+                let paths: Vec<String> = g
+                    .paths
+                    .iter()
+                    .map(|p| format!("\"{}\"", escape(&p.to_string_lossy())))
+                    .collect();
+                format!("{{ \"size\": {}, \"paths\": [{}] }}", g.size, paths.join(", "))
+            })
+            .collect();
+        writeln!(w, "  \"hardlinks\": [{}],", groups.join(", "))?;
+    }
+    if options.largest.is_some() {
+        let files: Vec<String> = stats
+            .largest
+            .iter()
+            .map(|f| {
+                format!(
+                    "{{ \"path\": \"{}\", \"size\": {}, \"extension\": \"{}\" }}",
+                    escape(&f.path.0.to_string_lossy()),
+                    f.size,
+                    escape(&f.ext)
+                )
+            })
+            .collect();
+        writeln!(w, "  \"largest\": [{}],", files.join(", "))?;
+    }
+    if options.verify_types {
+        let files: Vec<String> = stats
+            .mismatched
+            .iter()
+            .map(|f| {
+                format!(
+                    "{{ \"path\": \"{}\", \"extension\": \"{}\", \"detected\": \"{}\" }}",
+                    escape(&f.path.to_string_lossy()),
+                    escape(&f.ext),
+                    f.detected
+                )
+            })
+            .collect();
+        writeln!(w, "  \"mismatched\": [{}],", files.join(", "))?;
+    }
+    if options.empty_dirs.is_some() {
+        let dirs: Vec<String> = stats
+            .empty_dirs
+            .iter()
+            .map(|d| format!("\"{}\"", escape(&d.to_string_lossy())))
+            .collect();
+        writeln!(w, "  \"empty_directories\": [{}],", dirs.join(", "))?;
+    }
+    if options.owner {
+        let owners: Vec<String> = stats
+            .owners
+            .iter()
+            .map(|o| {
+                format!(
+                    "{{ \"user\": \"{}\", \"files\": {}, \"size\": {} }}",
+                    escape(&o.user),
+                    o.files,
+                    o.size
+                )
+            })
+            .collect();
+        writeln!(w, "  \"owners\": [{}],", owners.join(", "))?;
+    }
+    if options.xattrs {
+        let counts: Vec<String> = stats
+            .xattrs
+            .iter()
+            .map(|(name, files)| format!("\"{}\": {files}", escape(name)))
+            .collect();
+        writeln!(w, "  \"xattrs\": {{ {} }},", counts.join(", "))?;
+    }
+    if options.detect_encoding {
+        let counts: Vec<String> = stats
+            .encodings
+            .iter()
+            .map(|(encoding, files)| format!("\"{}\": {files}", encoding.name()))
+            .collect();
+        writeln!(w, "  \"encodings\": {{ {} }},", counts.join(", "))?;
+        let files: Vec<String> = stats
+            .non_utf8
+            .iter()
+            .map(|(path, encoding)| {
+                format!(
+                    "{{ \"path\": \"{}\", \"encoding\": \"{}\" }}",
+                    escape(&path.to_string_lossy()),
+                    encoding.name()
+                )
+            })
+            .collect();
+        writeln!(w, "  \"non_utf8\": [{}],", files.join(", "))?;
+    }
+    if options.tracks_executables() {
+        writeln!(w, "  \"executables\": {},", stats.executables)?;
+    }
+    if let Some(counts) = &stats.git {
+        writeln!(w, "  \"git_status\": {{ {} }},", git_counts(counts).join(", "))?;
+    }
+    if !stats.errors.is_empty() {
+        let errors: Vec<String> = stats.errors.iter().map(error_object).collect();
+        writeln!(w, "  \"errors\": [{}],", errors.join(", "))?;
+    }
+    if !stats.case_variants.is_empty() {
+        let variants: Vec<String> = stats
+            .case_variants
+            .iter()
+            .map(|v| {
+                let spellings: Vec<String> = v
+                    .spellings
+                    .iter()
+                    .map(|(spelling, files)| format!("\"{}\": {files}", escape(spelling)))
+                    .collect();
+                format!(
+                    "{{ \"extension\": \"{}\", \"spellings\": {{ {} }} }}",
+                    escape(&v.ext),
+                    spellings.join(", ")
+                )
+            })
+            .collect();
+        writeln!(w, "  \"case_variants\": [{}],", variants.join(", "))?;
+    }
     writeln!(w, "  \"extensions\": [")?;
 
     let extensions = sorted_extensions(catalog);
     for (i, ext) in extensions.iter().enumerate() {
-        let mut entries: Vec<_> = catalog[*ext].iter().collect();
-        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let entries = sorted_entries(&catalog[*ext]);
         let total: u64 = entries.iter().filter_map(|e| e.size).sum();
 
         writeln!(w, "    {{")?;
@@ -41,9 +190,60 @@ pub fn write(
         for (j, entry) in entries.iter().enumerate() {
             let comma = if j + 1 < entries.len() { "," } else { "" };
             let size = entry.size.map_or_else(|| "null".to_string(), |s| s.to_string());
+            let mut extra = String::new();
+            if *ext == SYMLINK_GROUP {
+                extra.push_str(&link_fields(entry, options));
+            }
+            if options.disk_usage && is_regular_group(ext) {
+                extra.push_str(&allocated_fields(entry));
+            }
+            if let Some(algorithm) = options.hash
+                && is_regular_group(ext)
+            {
+                extra.push_str(&digest_field(entry, algorithm));
+            }
+            if options.lines && is_regular_group(ext) {
+                let lines = entry.lines.map_or_else(|| "null".to_string(), |n| n.to_string());
+                extra.push_str(&format!(", \"lines\": {lines}"));
+            }
+            if options.perms && is_regular_group(ext) {
+                extra.push_str(&perms_fields(entry.permissions.as_ref()));
+            }
+            if options.owner && is_regular_group(ext) {
+                extra.push_str(&owner_fields(entry.owner.as_ref()));
+            }
+            if options.hardlinks && is_regular_group(ext) {
+                extra.push_str(&format!(", \"hardlink\": {}", entry.hardlink));
+            }
+            if options.attributes && is_regular_group(ext) {
+                extra.push_str(&attributes_field(entry.attributes));
+            }
+            extra.push_str(&exec_fields(entry, ext, options));
+            extra.push_str(&xattr_field(&entry.xattrs, options));
+            extra.push_str(&git_field(entry.git));
+            if let Some(encoding) = entry.encoding {
+                extra.push_str(&format!(", \"encoding\": \"{}\"", encoding.name()));
+            }
+            if options.mtime {
+                let modified = entry.modified.map_or_else(
+                    || "null".to_string(),
+                    |t| format!("\"{}\"", modified_time(t, options)),
+                );
+                extra.push_str(&format!(", \"modified\": {modified}"));
+            }
+            if options.created {
+                let created = entry.created.map_or_else(
+                    || "null".to_string(),
+                    |t| format!("\"{}\"", modified_time(t, options)),
+                );
+                extra.push_str(&format!(", \"created\": {created}"));
+            }
+            if let Some(mime) = entry.mime {
+                extra.push_str(&format!(", \"mime\": \"{}\"", escape(mime)));
+            }
             writeln!(
                 w,
-                "        {{ \"path\": \"{}\", \"size\": {size} }}{comma}",
+                "        {{ \"path\": \"{}\", \"size\": {size}{extra} }}{comma}",
                 escape(&entry.path.to_string_lossy()),
             )?;
         }
@@ -54,7 +254,168 @@ pub fn write(
 
     writeln!(w, "  ]")?;
     writeln!(w, "}}")?;
-    w.flush()
+    Ok(())
+}
+
+/// The `"duplicates"` member: reclaimable bytes plus each group of identical files.
+fn write_duplicates(w: &mut dyn Write, groups: &[DuplicateGroup]) -> io::Result<()> {
+    writeln!(w, "  \"duplicates\": {{")?;
+    writeln!(w, "    \"reclaimable\": {},", duplicates::reclaimable(groups))?;
+    writeln!(w, "    \"groups\": [")?;
+    for (i, group) in groups.iter().enumerate() {
+        let paths: Vec<String> = group
+            .paths
+            .iter()
+            .map(|p| format!("\"{}\"", escape(&p.to_string_lossy())))
+            .collect();
+        let comma = if i + 1 < groups.len() { "," } else { "" };
+        writeln!(
+            w,
+            "      {{ \"size\": {}, \"digest\": \"{}\", \"wasted\": {}, \"paths\": [{}] }}{comma}",
+            group.size,
+            group.digest,
+            group.wasted(),
+            paths.join(", ")
+        )?;
+    }
+    writeln!(w, "    ]")?;
+    writeln!(w, "  }},")
+}
+
+/// `, "target": ..., "broken": ...` for a listed symlink, plus `"resolved"` with
+/// `--resolve-links`; a target that can't be read or resolved is `null`.
+pub(super) fn link_fields(entry: &FileEntry, options: &ScanOptions) -> String {
+    let path = |p: Option<&PathBuf>| {
+        p.map_or_else(|| "null".to_string(), |p| format!("\"{}\"", escape(&p.to_string_lossy())))
+    };
+    let mut fields = format!(
+        ", \"target\": {}, \"broken\": {}",
+        path(entry.target.as_ref()),
+        entry.broken_link
+    );
+    if options.resolve_links {
+        fields.push_str(&format!(", \"resolved\": {}", path(entry.resolved.as_ref())));
+    }
+    fields
+}
+
+/// `, "xattrs": [names]`, or `{name: value}` with `--xattr-values` (`null` for
+/// an unreadable value); nothing for a file without extended attributes.
+pub(super) fn xattr_field(attrs: &[Xattr], options: &ScanOptions) -> String {
+    if attrs.is_empty() {
+        return String::new();
+    }
+    if options.xattr_values {
+        let pairs: Vec<String> = attrs
+            .iter()
+            .map(|a| {
+                let value = a
+                    .value
+                    .as_ref()
+                    .map_or_else(|| "null".to_string(), |v| format!("\"{}\"", escape(v)));
+                format!("\"{}\": {value}", escape(&a.name))
+            })
+            .collect();
+        format!(", \"xattrs\": {{ {} }}", pairs.join(", "))
+    } else {
+        let names: Vec<String> = attrs.iter().map(|a| format!("\"{}\"", escape(&a.name))).collect();
+        format!(", \"xattrs\": [{}]", names.join(", "))
+    }
+}
+
+/// `, "attributes": "H-A-"` for `--attributes`, `null` when unknown.
+pub(super) fn attributes_field(bits: Option<u32>) -> String {
+    let label = bits.map_or_else(|| "null".to_string(), |b| format!("\"{}\"", attributes::label(b)));
+    format!(", \"attributes\": {label}")
+}
+
+/// `, "<algorithm>": ...` for `--hash`: the digest, the `<skipped: too large>`
+/// marker, or `null` if the file couldn't be read.
+pub(super) fn digest_field(entry: &FileEntry, algorithm: HashAlgorithm) -> String {
+    let digest = match &entry.digest {
+        Some(digest) => format!("\"{digest}\""),
+        None if entry.too_large => format!("\"{}\"", hash::TOO_LARGE),
+        None => "null".to_string(),
+    };
+    format!(", \"{}\": {digest}", algorithm.name())
+}
+
+/// `, "allocated": ...` for `--disk-usage`, plus `"allocated_estimated": true`
+/// when it is only the apparent size.
+pub(super) fn allocated_fields(entry: &FileEntry) -> String {
+    match diskusage::on_disk(entry.allocated, entry.size) {
+        Some((bytes, false)) => format!(", \"allocated\": {bytes}"),
+        Some((bytes, true)) => format!(", \"allocated\": {bytes}, \"allocated_estimated\": true"),
+        None => ", \"allocated\": null".to_string(),
+    }
+}
+
+/// `, "executable": ...` with `--executables`, and the file's own `"extension"`
+/// when grouped by `--group-by exec`.
+pub(super) fn exec_fields(entry: &FileEntry, group: &str, options: &ScanOptions) -> String {
+    let mut fields = String::new();
+    if !is_regular_group(group) {
+        return fields;
+    }
+    if options.group_by == GroupBy::Exec {
+        let ext = scan::group_key(&entry.path, options);
+        fields.push_str(&format!(", \"extension\": \"{}\"", escape(&ext)));
+    }
+    if options.executables {
+        let executable = entry.executable.map_or_else(|| "null".to_string(), |e| e.to_string());
+        fields.push_str(&format!(", \"executable\": {executable}"));
+    }
+    fields
+}
+
+/// `, "git_status": "..."` for a file annotated by `--git-status`.
+pub(super) fn git_field(status: Option<GitStatus>) -> String {
+    status.map_or_else(String::new, |s| format!(", \"git_status\": \"{}\"", s.name()))
+}
+
+/// `{ "path": ..., "message": ... }` for an entry skipped by the walk.
+pub(super) fn error_object(error: &ScanError) -> String {
+    let path = error
+        .path
+        .as_ref()
+        .map_or_else(|| "null".to_string(), |p| format!("\"{}\"", escape(&p.to_string_lossy())));
+    format!("{{ \"path\": {path}, \"message\": \"{}\" }}", escape(&error.message))
+}
+
+/// `"tracked": N` and so on, for each status in summary order.
+pub(super) fn git_counts(counts: &GitCounts) -> Vec<String> {
+    GitStatus::ALL
+        .iter()
+        .zip(counts)
+        .map(|(status, files)| format!("\"{}\": {files}", status.name()))
+        .collect()
+}
+
+/// `, "uid": ..., "user": ..., "gid": ..., "group": ...` for `--owner`, all `null`
+/// when unknown.
+pub(super) fn owner_fields(owner: Option<&Owner>) -> String {
+    match owner {
+        Some(o) => format!(
+            ", \"uid\": {}, \"user\": \"{}\", \"gid\": {}, \"group\": \"{}\"",
+            o.uid,
+            escape(&o.user),
+            o.gid,
+            escape(&o.group)
+        ),
+        None => ", \"uid\": null, \"user\": null, \"gid\": null, \"group\": null".to_string(),
+    }
+}
+
+/// `, "readonly": ..., "permissions": ...` for `--perms`, both `null` when unknown.
+pub(super) fn perms_fields(permissions: Option<&Permissions>) -> String {
+    match permissions {
+        Some(p) => format!(
+            ", \"readonly\": {}, \"permissions\": \"{}\"",
+            p.readonly(),
+            permissions_label(p)
+        ),
+        None => ", \"readonly\": null, \"permissions\": null".to_string(),
+    }
 }
 
 /// A numeric JSON value when `present`, otherwise `null`.
@@ -67,7 +428,7 @@ fn num_opt(present: bool, value: u64) -> String {
 }
 
 /// Escape a string for inclusion in a JSON string literal.
-fn escape(s: &str) -> String {
+pub(super) fn escape(s: &str) -> String {
     let mut out = String::with_capacity(s.len() + 2);
     for c in s.chars() {
         match c {
@@ -0,0 +1,117 @@
+//! Markdown output: a summary table followed by one `## .ext (N files)` section per
+//! extension, ready to paste into an issue or wiki page.
+
+use super::{count_label, display_size, partial_notice, sorted_entries, sorted_extensions};
+use crate::config::ScanOptions;
+use crate::hash;
+use crate::scan::{Catalog, ScanStats, is_regular_group};
+use std::io::{self, Write};
+use std::path::Path;
+
+pub fn write(
+    w: &mut dyn Write,
+    catalog: &Catalog,
+    stats: &ScanStats,
+    root: &Path,
+    options: &ScanOptions,
+) -> io::Result<()> {
+    let extensions = sorted_extensions(catalog);
+
+    writeln!(w, "# CreepDir catalog of {}", escape(&root.to_string_lossy()))?;
+    writeln!(w)?;
+    if let Some(notice) = partial_notice(stats) {
+        writeln!(w, "> **{}**", escape(&notice))?;
+        writeln!(w)?;
+    }
+    if options.sizes {
+        writeln!(w, "| Extension | Files | Size |")?;
+        writeln!(w, "|---|---:|---:|")?;
+    } else {
+        writeln!(w, "| Extension | Files |")?;
+        writeln!(w, "|---|---:|")?;
+    }
+    for ext in &extensions {
+        let entries = &catalog[*ext];
+        if options.sizes {
+            let total: u64 = entries.iter().filter_map(|e| e.size).sum();
+            let total = display_size(total, options.exact_bytes);
+            writeln!(w, "| {} | {} | {total} |", label(ext), entries.len())?;
+        } else {
+            writeln!(w, "| {} | {} |", label(ext), entries.len())?;
+        }
+    }
+    if options.sizes {
+        writeln!(
+            w,
+            "| **Total** | **{}** | **{}** |",
+            stats.files,
+            display_size(stats.total_size, options.exact_bytes)
+        )?;
+    } else {
+        writeln!(w, "| **Total** | **{}** |", stats.files)?;
+    }
+
+    for ext in extensions {
+        let entries = sorted_entries(&catalog[ext]);
+
+        writeln!(w)?;
+        writeln!(w, "## {} ({})", label(ext), count_label(ext, entries.len()))?;
+        writeln!(w)?;
+        for entry in entries {
+            let path = escape(&entry.path.to_string_lossy());
+            write!(w, "- {path}")?;
+            if options.sizes {
+                let size = entry
+                    .size
+                    .map_or_else(|| "size unknown".to_string(), |s| display_size(s, options.exact_bytes));
+                write!(w, " ({size})")?;
+            }
+            if options.hash.is_some() && is_regular_group(ext) {
+                write!(w, " `{}`", hash::display_digest(entry))?;
+            }
+            writeln!(w)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Display label for an extension key, escaped for Markdown.
+fn label(ext: &str) -> String {
+    if ext.is_empty() {
+        "(no extension)".to_string()
+    } else {
+        escape(ext)
+    }
+}
+
+/// Backslash-escape characters that Markdown would otherwise interpret.
+///
+/// Inline syntax characters are always escaped; block markers (`#`, `+`, `-`, `1.`)
+/// only matter at the start of a line and are escaped there. Line breaks are shown
+/// as a literal `\n` so a path never spills onto a second line.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 8);
+    let leading_digits = s.bytes().take_while(u8::is_ascii_digit).count();
+    for (i, c) in s.char_indices() {
+        match c {
+            '\\' | '`' | '*' | '_' | '{' | '}' | '[' | ']' | '<' | '>' | '|' | '~' | '#'
+            | '&' | '!' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '+' | '-' if i == 0 => {
+                out.push('\\');
+                out.push(c);
+            }
+            '.' | ')' if i > 0 && i == leading_digits => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
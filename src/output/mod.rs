@@ -1,32 +1,326 @@
 //! Catalog writers for the supported output formats.
 
+pub mod color;
 pub mod csv;
+pub mod flat;
+pub mod graph;
+pub mod html;
 pub mod json;
+pub mod markdown;
+pub mod ndjson;
+pub mod sha256sum;
+pub mod split;
+pub mod sqlite;
+pub mod template;
 pub mod text;
+pub mod toml;
+pub mod tree;
+pub mod xml;
+pub mod yaml;
 
-use crate::scan::Catalog;
-use std::fs::File;
-use std::io::{self, BufWriter};
+use crate::config::{Encoding, LineEnding, OutputFormat, ScanOptions};
+use color::Palette;
+use crate::scan::{Catalog, DIR_GROUP, FileEntry, OTHER_GROUP, SYMLINK_GROUP, ScanStats, sort_key};
+use chrono::{DateTime, Local, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs::{File, Permissions};
+use std::io::{self, BufWriter, Write};
 use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Buffer size for output writers; large enough to keep syscalls rare on big scans.
 const WRITER_CAPACITY: usize = 128 * 1024;
 
-/// Create a buffered writer over a freshly created output file.
-fn buffered_writer(path: &Path) -> io::Result<BufWriter<File>> {
-    Ok(BufWriter::with_capacity(WRITER_CAPACITY, File::create(path)?))
+/// Output path that means "write to stdout".
+pub const STDOUT_PATH: &str = "-";
+
+/// Whether `path` is the `-` stdout marker rather than a real file.
+pub fn is_stdout(path: &Path) -> bool {
+    path.as_os_str() == STDOUT_PATH
+}
+
+/// Whether `path` asks for gzip output by its `.gz` suffix.
+pub fn has_gz_suffix(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+}
+
+/// An open catalog destination: a file or stdout, with line endings and encoding
+/// applied and then optionally gzip-compressed.
+///
+/// Writers render UTF-8 into it through `&mut dyn Write`; [`Output::finish`] must
+/// be called afterwards to flush buffers and write the gzip trailer.
+pub struct Output {
+    writer: BufWriter<Transcoder>,
+}
+
+/// Converts the LF-terminated UTF-8 rendered by writers into the requested line
+/// endings and [`Encoding`].
+///
+/// Translation is byte-level, so it would turn a line break inside a quoted CSV
+/// field into CRLF too; CSV ends its own records instead (see [`line_ending`]).
+struct Transcoder {
+    encoding: Encoding,
+    crlf: bool,
+    /// Trailing bytes of a UTF-8 sequence split across two writes.
+    pending: Vec<u8>,
+    inner: Stage,
+}
+
+/// The byte sink behind the output buffer.
+enum Stage {
+    Plain(Box<dyn Write>),
+    Gzip(GzEncoder<Box<dyn Write>>),
+}
+
+impl Output {
+    /// Open stdout for `-`, else create (truncate) the file at `path`.
+    pub fn create(
+        path: &Path,
+        compress: bool,
+        encoding: Encoding,
+        line_ending: LineEnding,
+    ) -> io::Result<Self> {
+        let sink: Box<dyn Write> = if is_stdout(path) {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(path)?)
+        };
+        let mut stage = if compress {
+            Stage::Gzip(GzEncoder::new(sink, Compression::default()))
+        } else {
+            Stage::Plain(sink)
+        };
+        match encoding {
+            Encoding::Utf8 => {}
+            Encoding::Utf8Bom => stage.write_all("\u{feff}".as_bytes())?,
+            Encoding::Utf16le => stage.write_all(&[0xFF, 0xFE])?,
+        }
+        let transcoder = Transcoder {
+            encoding,
+            crlf: line_ending.is_crlf(),
+            pending: Vec::new(),
+            inner: stage,
+        };
+        Ok(Output {
+            writer: BufWriter::with_capacity(WRITER_CAPACITY, transcoder),
+        })
+    }
+
+    /// Flush everything through to the destination, completing any compression.
+    pub fn finish(self) -> io::Result<()> {
+        let mut transcoder = self.writer.into_inner().map_err(|e| e.into_error())?;
+        if !transcoder.pending.is_empty() {
+            // A sequence cut off at the very end can't be completed any more.
+            transcoder.pending.clear();
+            transcoder.write_utf16("\u{fffd}")?;
+        }
+        match transcoder.inner {
+            Stage::Plain(mut sink) => sink.flush(),
+            Stage::Gzip(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.writer.write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Transcoder {
+    /// Transcode UTF-8 bytes to UTF-16LE, holding back a sequence split across writes.
+    fn write_utf16_bytes(&mut self, buf: &[u8]) -> io::Result<()> {
+        let mut data = std::mem::take(&mut self.pending);
+        data.extend_from_slice(buf);
+        let mut rest = data.as_slice();
+        while !rest.is_empty() {
+            match std::str::from_utf8(rest) {
+                Ok(s) => {
+                    self.write_utf16(s)?;
+                    rest = &[];
+                }
+                Err(e) => {
+                    let (valid, tail) = rest.split_at(e.valid_up_to());
+                    self.write_utf16(std::str::from_utf8(valid).expect("validated prefix"))?;
+                    match e.error_len() {
+                        Some(len) => {
+                            self.write_utf16("\u{fffd}")?;
+                            rest = &tail[len..];
+                        }
+                        None => {
+                            // Incomplete sequence at the end: wait for the next write.
+                            self.pending = tail.to_vec();
+                            rest = &[];
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_utf16(&mut self, s: &str) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(s.len() * 2);
+        for unit in s.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        self.inner.write_all(&bytes)
+    }
+}
+
+impl Write for Transcoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let translated;
+        let bytes = if self.crlf && buf.contains(&b'\n') {
+            translated = to_crlf(buf);
+            translated.as_slice()
+        } else {
+            buf
+        };
+        if self.encoding == Encoding::Utf16le {
+            self.write_utf16_bytes(bytes)?;
+        } else {
+            self.inner.write_all(bytes)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Replace every `\n` with `\r\n`.
+fn to_crlf(buf: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(buf.len() + buf.len() / 16);
+    for &b in buf {
+        if b == b'\n' {
+            out.push(b'\r');
+        }
+        out.push(b);
+    }
+    out
+}
+
+impl Write for Stage {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stage::Plain(sink) => sink.write(buf),
+            Stage::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stage::Plain(sink) => sink.flush(),
+            Stage::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// The line endings an [`Output`] for `format` translates to: none for CSV,
+/// which writes `options.line_ending` after each record itself so that a line
+/// break inside a quoted field is kept as it is.
+pub fn line_ending(format: OutputFormat, options: &ScanOptions) -> LineEnding {
+    if format == OutputFormat::Csv { LineEnding::Lf } else { options.line_ending }
+}
+
+/// Render a grouped catalog into `w` in the given format, coloured by `palette`
+/// where the format allows (only plain text does).
+///
+/// SQLite is written straight to its database file and must be handled by the
+/// caller.
+pub fn render(
+    w: &mut dyn Write,
+    format: OutputFormat,
+    catalog: &Catalog,
+    stats: &ScanStats,
+    root: &Path,
+    options: &ScanOptions,
+    palette: Palette,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Text => text::write(w, catalog, stats, options, palette),
+        OutputFormat::Json => json::write(w, catalog, stats, root, options),
+        OutputFormat::Csv => csv::write(w, catalog, options),
+        OutputFormat::Markdown => markdown::write(w, catalog, stats, root, options),
+        OutputFormat::Html => html::write(w, catalog, stats, root, options),
+        OutputFormat::Yaml => yaml::write(w, catalog, stats, root, options),
+        OutputFormat::Xml => xml::write(w, catalog, stats, root, options),
+        OutputFormat::Toml => toml::write(w, catalog, stats, root, options),
+        OutputFormat::Tree => tree::write(w, catalog, root, options),
+        OutputFormat::Flat => flat::write(w, catalog, options),
+        OutputFormat::Sha256sum => sha256sum::write(w, catalog),
+        OutputFormat::Dot => graph::write_dot(w, catalog, root, options),
+        OutputFormat::Mermaid => graph::write_mermaid(w, catalog, root, options),
+        OutputFormat::Template => {
+            let template = options
+                .template
+                .as_ref()
+                .expect("template format without a template");
+            template::write(w, catalog, stats, root, template, options.sizes)
+        }
+        OutputFormat::Ndjson => ndjson::write(w, catalog, stats, options),
+        OutputFormat::Sqlite => unreachable!("SQLite is not rendered to a stream"),
+    }
 }
 
 /// Extension keys of a catalog, sorted for deterministic output.
 fn sorted_extensions(catalog: &Catalog) -> Vec<&String> {
-    let mut extensions: Vec<&String> = catalog.keys().collect();
-    extensions.sort();
+    sorted_groups(catalog.keys())
+}
+
+/// A group's entries in catalog order: by [`sort_key`], the relative path's
+/// bytes with `/` between components, so `a-b.txt` comes before `a/b.txt`.
+fn sorted_entries(entries: &[FileEntry]) -> Vec<&FileEntry> {
+    let mut entries: Vec<&FileEntry> = entries.iter().collect();
+    entries.sort_by_cached_key(|entry| sort_key(&entry.path));
+    entries
+}
+
+/// Group names in catalog order.
+fn sorted_groups<'a>(groups: impl Iterator<Item = &'a String>) -> Vec<&'a String> {
+    let mut extensions: Vec<&String> = groups.collect();
+    // Folded small groups come last, after every real extension.
+    extensions.sort_by_key(|ext| (ext.as_str() == OTHER_GROUP, *ext));
     extensions
 }
 
-/// Human-readable byte size (e.g. `1.5 MB`).
+/// How many entries a group heading counts: `N files`, or `N directories` and
+/// `N symlinks` for the groups `--entry-types` adds.
+pub fn count_label(group: &str, count: usize) -> String {
+    match group {
+        DIR_GROUP => format!("{count} directories"),
+        SYMLINK_GROUP => format!("{count} symlinks"),
+        _ => format!("{count} files"),
+    }
+}
+
+/// The line that heads a catalog cut short by Ctrl-C or `--limit`, or `None`
+/// for a full scan.
+pub fn partial_notice(stats: &ScanStats) -> Option<String> {
+    if let Some(at) = &stats.truncated {
+        return Some(format!("TRUNCATED — --limit reached at {}", at.display()));
+    }
+    let at = stats.interrupted.as_ref()?;
+    if stats.timed_out {
+        return Some(format!("TIMED OUT — --timeout reached at {}", at.display()));
+    }
+    Some(format!("PARTIAL SCAN — interrupted at {}", at.display()))
+}
+
+/// Human-readable byte size in binary units (e.g. `1.5 MiB`).
 pub fn human_size(bytes: u64) -> String {
-    const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
     if bytes < 1024 {
         return format!("{bytes} B");
     }
@@ -38,3 +332,209 @@ pub fn human_size(bytes: u64) -> String {
     }
     format!("{size:.1} {}", UNITS[unit])
 }
+
+/// A duration as shown to people: `0.42s`, `12.3s`, `4m 05s` or `2h 07m`.
+pub fn human_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..10 => format!("{:.2}s", duration.as_secs_f64()),
+        10..60 => format!("{:.1}s", duration.as_secs_f64()),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// A file size as shown to people: exact (`1536 bytes`) for `--bytes`, else
+/// [`human_size`].
+pub fn display_size(bytes: u64, exact: bool) -> String {
+    match (exact, bytes) {
+        (false, _) => human_size(bytes),
+        (true, 1) => "1 byte".to_string(),
+        (true, n) => format!("{n} bytes"),
+    }
+}
+
+/// Permissions as shown in output: mode bits like `rw-r--r--` on Unix; elsewhere,
+/// where only the read-only flag is meaningful, `read-only` or `read-write`.
+pub fn permissions_label(permissions: &Permissions) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = permissions.mode();
+        let mut label = String::with_capacity(9);
+        for shift in [6, 3, 0] {
+            let bits = mode >> shift;
+            label.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+            label.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+            label.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+        }
+        label
+    }
+    #[cfg(not(unix))]
+    {
+        if permissions.readonly() { "read-only" } else { "read-write" }.to_string()
+    }
+}
+
+/// Format a timestamp as ISO 8601 UTC (e.g. `2024-05-01T13:45:00Z`).
+pub fn iso8601_utc(time: SystemTime) -> String {
+    utc(time).format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Format a timestamp as ISO 8601 in the local time zone, with its UTC offset
+/// (e.g. `2024-05-01T15:45:00+02:00`). The layout is fixed, not locale-dependent.
+pub fn iso8601_local(time: SystemTime) -> String {
+    utc(time).with_timezone(&Local).format("%Y-%m-%dT%H:%M:%S%:z").to_string()
+}
+
+/// `time` to the whole second, clamped to what chrono can represent (a corrupt
+/// timestamp can lie hundreds of thousands of years out).
+fn utc(time: SystemTime) -> DateTime<Utc> {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_else(|e| -(e.duration().as_secs() as i64));
+    let clamped = if secs < 0 { DateTime::<Utc>::MIN_UTC } else { DateTime::<Utc>::MAX_UTC };
+    DateTime::from_timestamp(secs, 0).unwrap_or(clamped)
+}
+
+/// A `--mtime` or `--created` timestamp as configured: UTC with `--utc`, else local time.
+pub fn modified_time(time: SystemTime, options: &ScanOptions) -> String {
+    if options.utc {
+        iso8601_utc(time)
+    } else {
+        iso8601_local(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use crate::scan::FileEntry;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// The bytes `format` writes for `catalog` with the options `args` select.
+    fn rendered(format: OutputFormat, catalog: &Catalog, args: &[&str]) -> Vec<u8> {
+        let (options, _) = cli::parse_for_test(args);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog");
+        let line_ending = line_ending(format, &options);
+        let mut out = Output::create(&path, false, options.encoding, line_ending).unwrap();
+        render(&mut out, format, catalog, &ScanStats::new(), Path::new("."), &options, Palette::PLAIN).unwrap();
+        out.finish().unwrap();
+        fs::read(&path).unwrap()
+    }
+
+    fn one_file(path: &str) -> Catalog {
+        Catalog::from([(".txt".to_string(), vec![FileEntry::new(PathBuf::from(path), Some(1))])])
+    }
+
+    #[test]
+    fn utf8_is_written_as_is() {
+        let bytes = rendered(OutputFormat::Flat, &one_file("café/ñ.txt"), &["--encoding", "utf8"]);
+        assert_eq!(bytes, b"caf\xc3\xa9/\xc3\xb1.txt\n");
+    }
+
+    #[test]
+    fn utf8_bom_leads_with_the_mark() {
+        let bytes = rendered(OutputFormat::Flat, &one_file("café/ñ.txt"), &["--encoding", "utf8-bom"]);
+        assert_eq!(bytes, b"\xef\xbb\xbfcaf\xc3\xa9/\xc3\xb1.txt\n");
+    }
+
+    #[test]
+    fn utf16le_leads_with_the_mark_and_encodes_every_unit() {
+        let bytes = rendered(OutputFormat::Flat, &one_file("é/😀.txt"), &["--encoding", "utf16le"]);
+        let mut expected = vec![0xff, 0xfe];
+        for unit in "é/😀.txt\n".encode_utf16() {
+            expected.extend_from_slice(&unit.to_le_bytes());
+        }
+        assert_eq!(bytes, expected);
+        assert_eq!(&bytes[2..6], &[0xe9, 0x00, b'/', 0x00]);
+        // The emoji is a surrogate pair.
+        assert_eq!(&bytes[6..10], &[0x3d, 0xd8, 0x00, 0xde]);
+    }
+
+    #[test]
+    fn a_sequence_split_across_writes_is_transcoded_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog");
+        let mut out = Output::create(&path, false, Encoding::Utf16le, LineEnding::Lf).unwrap();
+        let e_acute = "é".as_bytes();
+        out.write_all(&e_acute[..1]).unwrap();
+        out.flush().unwrap();
+        out.write_all(&e_acute[1..]).unwrap();
+        out.finish().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), [0xff, 0xfe, 0xe9, 0x00]);
+    }
+
+    fn two_groups() -> Catalog {
+        Catalog::from([
+            (".md".to_string(), vec![FileEntry::new(PathBuf::from("b.md"), Some(1))]),
+            (".txt".to_string(), vec![FileEntry::new(PathBuf::from("line\nbreak.txt"), Some(1))]),
+        ])
+    }
+
+    const TEXT: &str = "--- .md (1 files) ---\nb.md\n\n--- .txt (1 files) ---\nline\nbreak.txt\n\nTotal: 2 files\n";
+
+    #[test]
+    fn lf_leaves_text_as_rendered() {
+        assert_eq!(rendered(OutputFormat::Text, &two_groups(), &["--line-endings", "lf"]), TEXT.as_bytes());
+    }
+
+    #[test]
+    fn crlf_ends_every_text_line_including_blank_ones() {
+        let bytes = rendered(OutputFormat::Text, &two_groups(), &["--line-endings", "crlf"]);
+        assert_eq!(bytes, TEXT.replace('\n', "\r\n").as_bytes());
+    }
+
+    #[test]
+    fn native_follows_the_platform() {
+        let bytes = rendered(OutputFormat::Text, &two_groups(), &["--line-endings", "native"]);
+        let expected = if cfg!(windows) { TEXT.replace('\n', "\r\n") } else { TEXT.to_string() };
+        assert_eq!(bytes, expected.as_bytes());
+    }
+
+    #[test]
+    fn csv_ends_records_but_keeps_a_line_break_inside_a_field() {
+        let lf = "extension,relative_path\n.md,b.md\n.txt,\"line\nbreak.txt\"\n";
+        let crlf = "extension,relative_path\r\n.md,b.md\r\n.txt,\"line\nbreak.txt\"\r\n";
+        let native = if cfg!(windows) { crlf } else { lf };
+        for (style, expected) in [("lf", lf), ("crlf", crlf), ("native", native)] {
+            let bytes = rendered(OutputFormat::Csv, &two_groups(), &["--line-endings", style]);
+            assert_eq!(String::from_utf8(bytes).unwrap(), expected, "--line-endings {style}");
+        }
+    }
+
+    #[test]
+    fn utc_timestamps_are_iso8601() {
+        let at = |secs: u64| iso8601_utc(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(at(0), "1970-01-01T00:00:00Z");
+        assert_eq!(at(1_709_210_730), "2024-02-29T12:45:30Z");
+        assert_eq!(iso8601_utc(UNIX_EPOCH - Duration::from_secs(1)), "1969-12-31T23:59:59Z");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_permissions_show_the_mode_bits() {
+        use std::os::unix::fs::PermissionsExt;
+        let label = |mode| permissions_label(&Permissions::from_mode(mode));
+        assert_eq!(label(0o644), "rw-r--r--");
+        assert_eq!(label(0o100755), "rwxr-xr-x");
+        assert_eq!(label(0o4710), "rwx--x---");
+        assert_eq!(label(0o400), "r--------");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_permissions_show_only_the_read_only_flag() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, "x").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        assert_eq!(permissions_label(&permissions), "read-write");
+        permissions.set_readonly(true);
+        assert_eq!(permissions_label(&permissions), "read-only");
+    }
+}
@@ -0,0 +1,189 @@
+//! NDJSON output, streamed as files are discovered so memory stays flat.
+//!
+//! Each file is one `{"ext": ..., "path": ...}` line, followed by a final
+//! `{"summary": {...}}` line. Every line is handed to the writer in one piece, so
+//! an interrupted scan still leaves a file that parses line by line.
+
+use super::json::{
+    allocated_fields, attributes_field, digest_field, error_object, escape, exec_fields, git_field, link_fields,
+    owner_fields, perms_fields, xattr_field,
+};
+use super::{modified_time, sorted_entries, sorted_extensions};
+use crate::config::{ScanFilter, ScanOptions};
+use crate::{charset, hash, lines};
+use crate::scan::{self, Catalog, FileEntry, Roots, ScanStats, is_regular_group};
+use std::io::{self, Write};
+
+/// Scan `roots` and stream one JSON object per file to `writer`, returning the
+/// scan stats.
+pub fn write_streaming(
+    writer: &mut dyn Write,
+    roots: &Roots,
+    options: &ScanOptions,
+    filter: &ScanFilter,
+) -> io::Result<ScanStats> {
+    let root = roots.base.as_path();
+    let mut write_err: Option<io::Error> = None;
+    let mut line = String::new();
+    let mut encodings = charset::Tally::default();
+    let mut emit = |ext: String, mut entry: FileEntry| {
+        if write_err.is_some() {
+            return;
+        }
+        let readable = is_regular_group(&ext) && !entry.archived;
+        if options.lines && readable {
+            match lines::count_lines(&root.join(&entry.path)) {
+                Ok(lines) => entry.lines = lines,
+                Err(e) if !options.quiet => {
+                    eprintln!("Warning: cannot count lines in '{}': {e}", entry.path.display());
+                }
+                Err(_) => {}
+            }
+        }
+        if options.detect_encoding && readable {
+            charset::detect_entry(&mut entry, root, options);
+            encodings.add(&entry);
+        }
+        file_line(&mut line, &ext, &entry, options);
+        if let Err(e) = writer.write_all(line.as_bytes()) {
+            write_err = Some(e);
+        }
+    };
+    // Hashed by a pool of threads as the walk goes; lines still come out in
+    // walk order, each once its digest is in.
+    let mut hasher = options.hash.map(|algorithm| hash::InOrder::new(root, algorithm, options));
+    let mut stats = scan::walk(roots, options, filter, |ext, entry| match &mut hasher {
+        Some(hasher) => hasher.add(ext, entry, &mut emit),
+        None => emit(ext, entry),
+    });
+    if let Some(hasher) = hasher {
+        hasher.finish(&mut emit, &mut stats);
+    }
+
+    if let Some(e) = write_err {
+        return Err(e);
+    }
+    encodings.finish(&mut stats);
+    write_errors(writer, &stats)?;
+    writer.write_all(summary_line(&stats, options).as_bytes())?;
+    Ok(stats)
+}
+
+/// Write an already-grouped catalog as NDJSON (used when several formats are
+/// rendered from one scan), ordered by extension then path.
+pub fn write(
+    writer: &mut dyn Write,
+    catalog: &Catalog,
+    stats: &ScanStats,
+    options: &ScanOptions,
+) -> io::Result<()> {
+    let mut line = String::new();
+    for ext in sorted_extensions(catalog) {
+        let entries = sorted_entries(&catalog[ext]);
+        for entry in entries {
+            file_line(&mut line, ext, entry, options);
+            writer.write_all(line.as_bytes())?;
+        }
+    }
+    write_errors(writer, stats)?;
+    writer.write_all(summary_line(stats, options).as_bytes())
+}
+
+/// One `{"error": ...}` line per entry the walk skipped, ahead of the summary.
+fn write_errors(writer: &mut dyn Write, stats: &ScanStats) -> io::Result<()> {
+    for error in &stats.errors {
+        writeln!(writer, "{{\"error\": {}}}", error_object(error))?;
+    }
+    Ok(())
+}
+
+/// Render one file's line (including the trailing newline) into `line`.
+fn file_line(line: &mut String, ext: &str, entry: &FileEntry, options: &ScanOptions) {
+    line.clear();
+    line.push_str("{\"ext\": \"");
+    line.push_str(&escape(ext));
+    line.push_str("\", \"path\": \"");
+    line.push_str(&escape(&entry.path.to_string_lossy()));
+    line.push('"');
+    if options.sizes {
+        match entry.size {
+            Some(s) => line.push_str(&format!(", \"size\": {s}")),
+            None => line.push_str(", \"size\": null"),
+        }
+    }
+    if options.disk_usage && is_regular_group(ext) {
+        line.push_str(&allocated_fields(entry));
+    }
+    if let Some(algorithm) = options.hash
+        && is_regular_group(ext)
+    {
+        line.push_str(&digest_field(entry, algorithm));
+    }
+    if options.lines && is_regular_group(ext) {
+        match entry.lines {
+            Some(n) => line.push_str(&format!(", \"lines\": {n}")),
+            None => line.push_str(", \"lines\": null"),
+        }
+    }
+    if ext == scan::SYMLINK_GROUP {
+        line.push_str(&link_fields(entry, options));
+    }
+    if options.perms && is_regular_group(ext) {
+        line.push_str(&perms_fields(entry.permissions.as_ref()));
+    }
+    if options.owner && is_regular_group(ext) {
+        line.push_str(&owner_fields(entry.owner.as_ref()));
+    }
+    if options.hardlinks && is_regular_group(ext) {
+        line.push_str(&format!(", \"hardlink\": {}", entry.hardlink));
+    }
+    if options.attributes && is_regular_group(ext) {
+        line.push_str(&attributes_field(entry.attributes));
+    }
+    line.push_str(&exec_fields(entry, ext, options));
+    line.push_str(&xattr_field(&entry.xattrs, options));
+    line.push_str(&git_field(entry.git));
+    if let Some(encoding) = entry.encoding {
+        line.push_str(&format!(", \"encoding\": \"{}\"", encoding.name()));
+    }
+    if options.mtime {
+        match entry.modified {
+            Some(t) => line.push_str(&format!(", \"modified\": \"{}\"", modified_time(t, options))),
+            None => line.push_str(", \"modified\": null"),
+        }
+    }
+    if options.created {
+        match entry.created {
+            Some(t) => line.push_str(&format!(", \"created\": \"{}\"", modified_time(t, options))),
+            None => line.push_str(", \"created\": null"),
+        }
+    }
+    line.push_str("}\n");
+}
+
+fn summary_line(stats: &ScanStats, options: &ScanOptions) -> String {
+    let (total_size, deduplicated_size) = if options.sizes {
+        let deduplicated = stats.total_size - stats.hardlinked_size;
+        (stats.total_size.to_string(), deduplicated.to_string())
+    } else {
+        ("null".to_string(), "null".to_string())
+    };
+    let allocated_size = if options.disk_usage {
+        stats.allocated_size.to_string()
+    } else {
+        "null".to_string()
+    };
+    let interrupted_at = match &stats.interrupted {
+        Some(at) if stats.timed_out => format!(", \"timed_out_at\": \"{}\"", escape(&at.to_string_lossy())),
+        Some(at) => format!(", \"interrupted_at\": \"{}\"", escape(&at.to_string_lossy())),
+        None => String::new(),
+    };
+    let truncated_at = match &stats.truncated {
+        Some(at) => format!(", \"truncated_at\": \"{}\"", escape(&at.to_string_lossy())),
+        None => String::new(),
+    };
+    format!(
+        "{{\"summary\": {{\"files\": {}, \"directories\": {}, \"skipped\": {}, \"symlinks\": {}, \"symlink_loops\": {}, \"broken_symlinks\": {}, \"total_size\": {total_size}, \"deduplicated_size\": {deduplicated_size}, \"allocated_size\": {allocated_size}{interrupted_at}{truncated_at}}}}}\n",
+        stats.files, stats.dirs, stats.skipped, stats.symlinks, stats.link_loops, stats.broken_links,
+    )
+}
@@ -0,0 +1,71 @@
+//! `sha256sum`-compatible manifest: `HEXDIGEST  relative/path` per file, sorted by
+//! path, so `sha256sum -c` run from the scanned folder verifies the tree.
+
+use crate::scan::{Catalog, sort_key};
+use std::io::{self, Write};
+use std::path::Path;
+
+pub fn write(writer: &mut dyn Write, catalog: &Catalog) -> io::Result<()> {
+    let mut rows: Vec<(&Path, &str)> = catalog
+        .values()
+        .flatten()
+        .filter_map(|e| e.digest.as_deref().map(|d| (e.path.as_path(), d)))
+        .collect();
+    rows.sort_by_cached_key(|(path, _)| sort_key(path));
+
+    for (path, digest) in rows {
+        // The name's own bytes, so one that isn't UTF-8 still names the file.
+        let path = path.as_os_str().as_encoded_bytes();
+        // GNU coreutils marks lines whose name needs escaping with a leading `\`.
+        if path.iter().any(|b| matches!(b, b'\\' | b'\n' | b'\r')) {
+            let mut escaped = Vec::with_capacity(path.len() + 8);
+            for &b in path {
+                match b {
+                    b'\\' => escaped.extend_from_slice(b"\\\\"),
+                    b'\n' => escaped.extend_from_slice(b"\\n"),
+                    b'\r' => escaped.extend_from_slice(b"\\r"),
+                    _ => escaped.push(b),
+                }
+            }
+            write!(writer, "\\{digest}  ")?;
+            writer.write_all(&escaped)?;
+        } else {
+            write!(writer, "{digest}  ")?;
+            writer.write_all(path)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::FileEntry;
+    use std::path::PathBuf;
+
+    fn manifest(paths: &[PathBuf]) -> Vec<u8> {
+        let entries = paths
+            .iter()
+            .map(|path| FileEntry { digest: Some("ab12".to_string()), ..FileEntry::new(path.clone(), None) })
+            .collect();
+        let mut out = Vec::new();
+        write(&mut out, &Catalog::from([(".txt".to_string(), entries)])).unwrap();
+        out
+    }
+
+    #[test]
+    fn names_needing_escapes_are_marked_and_escaped() {
+        let out = manifest(&[PathBuf::from("a\nb.txt"), PathBuf::from("plain.txt")]);
+        assert_eq!(out, b"\\ab12  a\\nb.txt\nab12  plain.txt\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_name_that_is_not_utf8_is_written_as_its_bytes() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let out = manifest(&[PathBuf::from(OsStr::from_bytes(b"bad\xff.txt")), PathBuf::from(r"back\slash.txt")]);
+        assert_eq!(out, b"\\ab12  back\\\\slash.txt\nab12  bad\xff.txt\n");
+    }
+}
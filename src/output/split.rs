@@ -0,0 +1,95 @@
+//! `--split`: one catalog file per extension plus an `index.txt`, written into an
+//! output directory.
+
+use super::color::Palette;
+use super::{Output, human_size, line_ending, render, sorted_extensions};
+use crate::config::{LineEnding, ScanOptions};
+use crate::scan::{Catalog, ScanStats};
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Write each extension group to its own file in `dir` (created if missing), using
+/// the chosen format, and return the paths written (index last).
+pub fn write(
+    mut catalog: Catalog,
+    stats: &ScanStats,
+    root: &Path,
+    dir: &Path,
+    options: &ScanOptions,
+) -> io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+
+    let suffix = options.file_extension();
+    let mut taken = HashSet::new();
+    let mut index = Vec::new();
+    let mut written = Vec::new();
+
+    let extensions: Vec<String> = sorted_extensions(&catalog).into_iter().cloned().collect();
+    for ext in extensions {
+        let entries = catalog.remove(&ext).unwrap_or_default();
+        let name = unique_name(&file_stem(&ext), &suffix, &mut taken);
+        let path = dir.join(&name);
+
+        let mut group_stats = stats.clone();
+        group_stats.files = entries.len() as u64;
+        group_stats.total_size = entries.iter().filter_map(|e| e.size).sum();
+        index.push((ext.clone(), group_stats.files, group_stats.total_size, name));
+
+        let group = Catalog::from([(ext, entries)]);
+        let mut out = open(&path, options, line_ending(options.primary_format(), options))?;
+        render(&mut out, options.primary_format(), &group, &group_stats, root, options, Palette::PLAIN)?;
+        out.finish()?;
+        written.push(path);
+    }
+
+    let index_suffix = if options.compress { "txt.gz" } else { "txt" };
+    let index_path = dir.join(unique_name("index", index_suffix, &mut taken));
+    let mut out = open(&index_path, options, options.line_ending)?;
+    for (ext, count, size, name) in &index {
+        let label = if ext.is_empty() { "(no extension)" } else { ext };
+        if options.sizes {
+            writeln!(out, "{label}\t{count}\t{}\t{name}", human_size(*size))?;
+        } else {
+            writeln!(out, "{label}\t{count}\t{name}")?;
+        }
+    }
+    writeln!(out, "total\t{}", stats.files)?;
+    out.finish()?;
+    written.push(index_path);
+
+    Ok(written)
+}
+
+fn open(path: &Path, options: &ScanOptions, line_ending: LineEnding) -> io::Result<Output> {
+    Output::create(path, options.compress, options.encoding, line_ending)
+}
+
+/// Turn an extension key into a filename stem that is valid on every platform.
+fn file_stem(ext: &str) -> String {
+    if ext.is_empty() {
+        return "no-extension".to_string();
+    }
+    let stem: String = ext
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    // Windows silently drops trailing dots and spaces.
+    stem.trim_end_matches(['.', ' ']).to_string()
+}
+
+/// Build `<stem>.<suffix>`, adding `-2`, `-3`, ... if the name is already used.
+/// Comparison is case-insensitive so case-folding filesystems can't collide.
+fn unique_name(stem: &str, suffix: &str, taken: &mut HashSet<String>) -> String {
+    let mut name = format!("{stem}.{suffix}");
+    let mut n = 2;
+    while !taken.insert(name.to_lowercase()) {
+        name = format!("{stem}-{n}.{suffix}");
+        n += 1;
+    }
+    name
+}
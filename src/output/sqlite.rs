@@ -0,0 +1,138 @@
+//! SQLite output: scans accumulate into a database instead of overwriting a file.
+//!
+//! Schema:
+//! - `scans(id, root, scanned_at, generator, files, total_size, hash)`, one row per
+//!   run; `hash` names the `--hash` algorithm.
+//! - `files(id, scan_id, extension, relative_path, size, mtime, digest, lines,
+//!   created)`, with `size`, `mtime` and `created` (seconds since the Unix epoch),
+//!   `digest` and `lines` left `NULL` when not collected or unavailable.
+//!
+//! Databases created before a column existed get it added on first use.
+
+use super::{iso8601_utc, sorted_entries, sorted_extensions};
+use crate::config::ScanOptions;
+use crate::scan::{Catalog, ScanStats};
+use rusqlite::{Connection, params};
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS scans (
+    id          INTEGER PRIMARY KEY,
+    root        TEXT NOT NULL,
+    scanned_at  TEXT NOT NULL,
+    generator   TEXT NOT NULL,
+    files       INTEGER NOT NULL,
+    total_size  INTEGER,
+    hash        TEXT
+);
+CREATE TABLE IF NOT EXISTS files (
+    id            INTEGER PRIMARY KEY,
+    scan_id       INTEGER NOT NULL REFERENCES scans(id),
+    extension     TEXT NOT NULL,
+    relative_path TEXT NOT NULL,
+    size          INTEGER,
+    mtime         INTEGER,
+    digest        TEXT,
+    lines         INTEGER,
+    created       INTEGER,
+    git_status    TEXT,
+    allocated     INTEGER
+);
+CREATE INDEX IF NOT EXISTS files_scan_ext ON files(scan_id, extension);
+";
+
+/// Append this scan and its files to the database at `output_path`, creating the
+/// schema on first use. All inserts for a scan run in a single transaction.
+pub fn write(
+    catalog: &Catalog,
+    stats: &ScanStats,
+    root: &Path,
+    output_path: &Path,
+    options: &ScanOptions,
+) -> io::Result<()> {
+    write_db(catalog, stats, root, output_path, options).map_err(io::Error::other)
+}
+
+fn write_db(
+    catalog: &Catalog,
+    stats: &ScanStats,
+    root: &Path,
+    output_path: &Path,
+    options: &ScanOptions,
+) -> rusqlite::Result<()> {
+    let mut conn = Connection::open(output_path)?;
+    conn.execute_batch(SCHEMA)?;
+    add_column(&conn, "scans", "hash", "TEXT")?;
+    add_column(&conn, "files", "digest", "TEXT")?;
+    add_column(&conn, "files", "lines", "INTEGER")?;
+    add_column(&conn, "files", "created", "INTEGER")?;
+    add_column(&conn, "files", "git_status", "TEXT")?;
+    add_column(&conn, "files", "allocated", "INTEGER")?;
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO scans (root, scanned_at, generator, files, total_size, hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            root.to_string_lossy(),
+            iso8601_utc(stats.started),
+            format!("CreepDir {}", env!("CARGO_PKG_VERSION")),
+            stats.files as i64,
+            options.sizes.then_some(stats.total_size as i64),
+            options.hash.map(|algorithm| algorithm.name()),
+        ],
+    )?;
+    let scan_id = tx.last_insert_rowid();
+
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO files
+                 (scan_id, extension, relative_path, size, mtime, digest, lines, created, git_status,
+                  allocated)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        )?;
+        // Inserted in catalog order, so row ids come out the same for the same tree.
+        for ext in sorted_extensions(catalog) {
+            let entries = sorted_entries(&catalog[ext]);
+            for entry in entries {
+                insert.execute(params![
+                    scan_id,
+                    ext,
+                    entry.path.to_string_lossy(),
+                    entry.size.map(|s| s as i64),
+                    entry.modified.map(unix_seconds),
+                    entry.digest,
+                    entry.lines.map(|n| n as i64),
+                    entry.created.map(unix_seconds),
+                    entry.git.map(|s| s.name()),
+                    entry.allocated.map(|n| n as i64),
+                ])?;
+            }
+        }
+    }
+
+    tx.commit()
+}
+
+/// Add `column` to `table` unless an older schema version already has it.
+fn add_column(conn: &Connection, table: &str, column: &str, ty: &str) -> rusqlite::Result<()> {
+    let mut columns = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let exists = columns
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {ty}"))?;
+    }
+    Ok(())
+}
+
+/// Seconds since the Unix epoch, negative for earlier times.
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_else(|e| -(e.duration().as_secs() as i64))
+}
@@ -0,0 +1,327 @@
+//! User-defined output layouts loaded with `--template FILE`.
+//!
+//! A template file has one `section=line` entry per line; repeating a section adds
+//! another output line. Blank lines and lines starting with `#` are ignored.
+//!
+//! | Section  | Rendered                | Placeholders                         |
+//! |----------|-------------------------|--------------------------------------|
+//! | `header` | once, before all groups | `{root}` `{date}` `{files}` `{size}` |
+//! | `group`  | once per extension      | `{ext}` `{count}` `{size}` + header  |
+//! | `file`   | once per file           | `{ext}` `{path}` `{size}` + header   |
+//! | `footer` | once, after all groups  | same as `header`                     |
+//!
+//! `{size}` is the file, group, or overall size depending on the section, and
+//! turns on size collection. Write `{{` and `}}` for literal braces.
+
+use super::{human_size, iso8601_utc, sorted_entries, sorted_extensions};
+use crate::scan::{Catalog, ScanStats};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// A parsed template, validated up front so rendering cannot fail on syntax.
+#[derive(Clone)]
+pub struct Template {
+    header: Vec<Line>,
+    group: Vec<Line>,
+    file: Vec<Line>,
+    footer: Vec<Line>,
+}
+
+type Line = Vec<Piece>;
+
+#[derive(Clone)]
+enum Piece {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Root,
+    Date,
+    Files,
+    Size,
+    Ext,
+    Count,
+    Path,
+}
+
+#[derive(Clone, Copy)]
+enum Section {
+    Header,
+    Group,
+    File,
+    Footer,
+}
+
+impl Section {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "header" => Some(Section::Header),
+            "group" => Some(Section::Group),
+            "file" => Some(Section::File),
+            "footer" => Some(Section::Footer),
+            _ => None,
+        }
+    }
+
+    /// Resolve a placeholder name, if it is valid in this section.
+    fn field(self, name: &str) -> Option<Field> {
+        let field = match name {
+            "root" => Field::Root,
+            "date" => Field::Date,
+            "files" => Field::Files,
+            "size" => Field::Size,
+            "ext" => Field::Ext,
+            "count" => Field::Count,
+            "path" => Field::Path,
+            _ => return None,
+        };
+        let allowed = match field {
+            Field::Root | Field::Date | Field::Files | Field::Size => true,
+            Field::Ext => matches!(self, Section::Group | Section::File),
+            Field::Count => matches!(self, Section::Group),
+            Field::Path => matches!(self, Section::File),
+        };
+        allowed.then_some(field)
+    }
+}
+
+/// Per-line values available while rendering.
+struct Context<'a> {
+    root: &'a str,
+    date: &'a str,
+    files: u64,
+    size: Option<u64>,
+    ext: &'a str,
+    count: usize,
+    path: &'a str,
+}
+
+impl Template {
+    /// Read and parse a template file, reporting problems as `file:line: message`.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("reading template '{}': {e}", path.display()))?;
+        Self::parse(&source).map_err(|(line, msg)| format!("{}:{line}: {msg}", path.display()))
+    }
+
+    fn parse(source: &str) -> Result<Self, (usize, String)> {
+        let mut template = Template {
+            header: Vec::new(),
+            group: Vec::new(),
+            file: Vec::new(),
+            footer: Vec::new(),
+        };
+
+        for (i, raw) in source.lines().enumerate() {
+            let lineno = i + 1;
+            if raw.trim().is_empty() || raw.trim_start().starts_with('#') {
+                continue;
+            }
+            let Some((name, value)) = raw.split_once('=') else {
+                return Err((lineno, "expected `section=text`".to_string()));
+            };
+            let section = Section::from_name(name.trim()).ok_or_else(|| {
+                (
+                    lineno,
+                    format!(
+                        "unknown section '{}' (expected header, group, file, or footer)",
+                        name.trim()
+                    ),
+                )
+            })?;
+            let line = parse_line(value, section).map_err(|msg| (lineno, msg))?;
+            match section {
+                Section::Header => template.header.push(line),
+                Section::Group => template.group.push(line),
+                Section::File => template.file.push(line),
+                Section::Footer => template.footer.push(line),
+            }
+        }
+
+        Ok(template)
+    }
+
+    /// Whether any line uses `{size}`, which requires collecting file sizes.
+    pub fn uses_sizes(&self) -> bool {
+        [&self.header, &self.group, &self.file, &self.footer]
+            .iter()
+            .flat_map(|lines| lines.iter().flatten())
+            .any(|piece| matches!(piece, Piece::Field(Field::Size)))
+    }
+}
+
+/// Split a template line into literal text and placeholders.
+fn parse_line(value: &str, section: Section) -> Result<Line, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err("unterminated '{' (use '{{' for a literal brace)".into()),
+                    }
+                }
+                let field = section
+                    .field(&name)
+                    .ok_or_else(|| format!("unknown placeholder '{{{name}}}' in this section"))?;
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Field(field));
+            }
+            '}' => return Err("unmatched '}' (use '}}' for a literal brace)".into()),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+pub fn write(
+    w: &mut dyn Write,
+    catalog: &Catalog,
+    stats: &ScanStats,
+    root: &Path,
+    template: &Template,
+    sizes: bool,
+) -> io::Result<()> {
+    let root = root.to_string_lossy();
+    let date = iso8601_utc(stats.started);
+    let mut ctx = Context {
+        root: &root,
+        date: &date,
+        files: stats.files,
+        size: sizes.then_some(stats.total_size),
+        ext: "",
+        count: 0,
+        path: "",
+    };
+
+    render(w, &template.header, &ctx)?;
+    for ext in sorted_extensions(catalog) {
+        let entries = sorted_entries(&catalog[ext]);
+
+        ctx.ext = ext;
+        ctx.count = entries.len();
+        ctx.size = sizes.then(|| entries.iter().filter_map(|e| e.size).sum());
+        render(w, &template.group, &ctx)?;
+
+        for entry in entries {
+            let path = entry.path.to_string_lossy();
+            let file_ctx = Context {
+                path: &path,
+                size: entry.size,
+                ..ctx
+            };
+            render(w, &template.file, &file_ctx)?;
+        }
+    }
+    ctx.ext = "";
+    ctx.size = sizes.then_some(stats.total_size);
+    render(w, &template.footer, &ctx)?;
+
+    Ok(())
+}
+
+fn render(w: &mut dyn Write, lines: &[Line], ctx: &Context) -> io::Result<()> {
+    for line in lines {
+        for piece in line {
+            match piece {
+                Piece::Text(text) => w.write_all(text.as_bytes())?,
+                Piece::Field(Field::Root) => w.write_all(ctx.root.as_bytes())?,
+                Piece::Field(Field::Date) => w.write_all(ctx.date.as_bytes())?,
+                Piece::Field(Field::Files) => write!(w, "{}", ctx.files)?,
+                Piece::Field(Field::Size) => match ctx.size {
+                    Some(size) => w.write_all(human_size(size).as_bytes())?,
+                    None => w.write_all(b"?")?,
+                },
+                Piece::Field(Field::Ext) => w.write_all(ctx.ext.as_bytes())?,
+                Piece::Field(Field::Count) => write!(w, "{}", ctx.count)?,
+                Piece::Field(Field::Path) => w.write_all(ctx.path.as_bytes())?,
+            }
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::FileEntry;
+    use std::path::PathBuf;
+
+    fn render_one(source: &str) -> String {
+        let template = Template::parse(source).unwrap();
+        let entry = FileEntry::new(PathBuf::from("a/b.txt"), Some(3));
+        let catalog = Catalog::from([(".txt".to_string(), vec![entry])]);
+        let mut out = Vec::new();
+        write(&mut out, &catalog, &ScanStats::new(), Path::new("/root"), &template, false).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn error(source: &str) -> (usize, String) {
+        Template::parse(source).err().expect("template should not parse")
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render_one("file={{path}} = {path}\nfile=}}{{"), "{path} = a/b.txt\n}{\n");
+    }
+
+    #[test]
+    fn placeholders_fill_in_per_section() {
+        assert_eq!(
+            render_one("# comment\n\ngroup=[{ext}: {count}]\nfile=  {path}\nfooter={files} files"),
+            "[.txt: 1]\n  a/b.txt\n0 files\n"
+        );
+    }
+
+    #[test]
+    fn an_unknown_placeholder_is_an_error() {
+        let (line, message) = error("header=ok\nfile={name}");
+        assert_eq!(line, 2);
+        assert_eq!(message, "unknown placeholder '{name}' in this section");
+    }
+
+    #[test]
+    fn a_placeholder_outside_its_section_is_an_error() {
+        assert_eq!(error("header={path}").1, "unknown placeholder '{path}' in this section");
+    }
+
+    #[test]
+    fn an_unterminated_brace_is_an_error() {
+        let (line, message) = error("file={path");
+        assert_eq!(line, 1);
+        assert_eq!(message, "unterminated '{' (use '{{' for a literal brace)");
+    }
+
+    #[test]
+    fn an_unmatched_closing_brace_is_an_error() {
+        assert_eq!(error("file=path}").1, "unmatched '}' (use '}}' for a literal brace)");
+    }
+
+    #[test]
+    fn an_unknown_section_is_an_error() {
+        assert!(error("body={path}").1.starts_with("unknown section 'body'"));
+    }
+}
@@ -1,45 +1,754 @@
-//! Plain-text output: files grouped under `--- .ext (N files) ---` headers.
+//! Plain-text output: files grouped under `--- .ext (N files) ---` headers. With
+//! `--sizes` each line ends in an aligned `(1.4 MiB)` and headers carry the group
+//! total. A grand-total line closes the file.
+//!
+//! A [`Spool`] renders the same output as the scan goes, parking each group's
+//! lines in a temporary file so memory stays flat however many files are listed.
 
-use super::{buffered_writer, human_size, sorted_extensions};
-use crate::config::ScanOptions;
-use crate::scan::Catalog;
-use std::io::{self, Write};
-use std::path::Path;
+use super::color::Palette;
+use super::{count_label, display_size, modified_time, partial_notice, permissions_label, sorted_entries, sorted_extensions, sorted_groups};
+use crate::attributes;
+use crate::config::{GroupBy, ScanOptions};
+use crate::duplicates::{self, DuplicateGroup};
+use crate::gitstatus::GitStatus;
+use crate::hardlinks::HardlinkGroup;
+use crate::{diskusage, hash, xattrs};
+use crate::scan::{
+    Catalog, DIR_GROUP, FileEntry, LargeFile, OTHER_GROUP, SYMLINK_GROUP, ScanStats, extension_key,
+    group_key, is_regular_group, sort_key,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-pub fn write(catalog: &Catalog, output_path: &Path, options: &ScanOptions) -> io::Result<()> {
-    let mut writer = buffered_writer(output_path)?;
+/// Bytes of lines a spooled group holds in memory before they go to the file.
+const CHUNK: usize = 16 * 1024;
+
+pub fn write(
+    writer: &mut dyn Write,
+    catalog: &Catalog,
+    stats: &ScanStats,
+    options: &ScanOptions,
+    palette: Palette,
+) -> io::Result<()> {
+    write_notice(writer, stats, palette)?;
+    let mut totals = Totals::default();
 
     for ext in sorted_extensions(catalog) {
-        let mut entries: Vec<_> = catalog[ext].iter().collect();
-        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        let entries = sorted_entries(&catalog[ext]);
 
-        let label = if ext.is_empty() { "(no extension)" } else { ext };
-        if options.sizes {
-            let total: u64 = entries.iter().filter_map(|e| e.size).sum();
-            writeln!(
-                writer,
-                "--- {label} ({} files, {}) ---",
-                entries.len(),
-                human_size(total)
-            )?;
-        } else {
-            writeln!(writer, "--- {label} ({} files) ---", entries.len())?;
+        let lines: Vec<(String, String)> = entries
+            .iter()
+            .map(|entry| (display_name(entry, options), details(ext, entry, options)))
+            .collect();
+        let mut group = Group::default();
+        for (entry, (name, _)) in entries.iter().zip(&lines) {
+            group.add(ext, entry, name, options);
+        }
+        group.write_header(writer, ext, &mut totals, options, palette)?;
+        if ext == OTHER_GROUP {
+            writeln!(writer, "(folded: {})", folded_extensions(&entries, options))?;
         }
+        let width = (options.sizes && is_regular_group(ext)).then_some(group.width);
+        for (name, details) in &lines {
+            write_line(writer, name, details, width)?;
+        }
+        writeln!(writer)?;
+    }
+    write_footer(writer, &totals, stats, options, palette)
+}
 
-        for entry in entries {
-            if options.sizes {
-                writeln!(
-                    writer,
-                    "{}\t{}",
-                    entry.path.to_string_lossy(),
-                    human_size(entry.size.unwrap_or(0))
-                )?;
+/// Text output built while the walk runs. Each file's line is rendered as it is
+/// found and buffered per group; full buffers are appended to an anonymous
+/// temporary file, and [`finish`](Spool::finish) reads the groups back in
+/// order. The walk hands each group its files in path order; a group that
+/// arrives out of order (such as entries from inside a zip) is sorted in memory
+/// when it is written.
+pub struct Spool {
+    file: File,
+    len: u64,
+    groups: HashMap<String, Spooled>,
+    /// The first write that failed; the rest of the scan isn't spooled.
+    error: Option<io::Error>,
+}
+
+/// One group's share of a [`Spool`].
+struct Spooled {
+    group: Group,
+    /// `(offset, length)` of each chunk already in the file.
+    chunks: Vec<(u64, usize)>,
+    buffer: Vec<u8>,
+    /// Sort key of the latest file, to notice one arriving out of order.
+    last: Vec<u8>,
+    sorted: bool,
+}
+
+impl Spool {
+    /// Create the temporary file.
+    pub fn new() -> io::Result<Spool> {
+        // Unique per run, as `--watch` spools every rescan.
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+        let run = RUNS.fetch_add(1, Ordering::Relaxed);
+        let name = format!("creepdir-{}-{run}.spool", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let mut open = File::options();
+        open.read(true).write(true).create_new(true);
+        // Windows deletes the file once it's closed, however the run ends.
+        #[cfg(windows)]
+        {
+            use std::os::windows::fs::OpenOptionsExt;
+            open.custom_flags(windows_sys::Win32::Storage::FileSystem::FILE_FLAG_DELETE_ON_CLOSE);
+        }
+        let file = open.open(&path)?;
+        // Unix lets the open file go nameless, with the same effect.
+        #[cfg(unix)]
+        std::fs::remove_file(&path)?;
+        Ok(Spool { file, len: 0, groups: HashMap::new(), error: None })
+    }
+
+    /// Render `entry` into group `ext`.
+    pub fn add(&mut self, ext: String, entry: &FileEntry, options: &ScanOptions) {
+        if self.error.is_some() {
+            return;
+        }
+        let name = display_name(entry, options);
+        let details = details(&ext, entry, options);
+        let key = sort_key(&entry.path);
+        if !self.groups.contains_key(&ext) {
+            let spooled = Spooled {
+                group: Group::default(),
+                chunks: Vec::new(),
+                buffer: Vec::new(),
+                last: Vec::new(),
+                sorted: true,
+            };
+            self.groups.insert(ext.clone(), spooled);
+        }
+        let spooled = self.groups.get_mut(&ext).expect("group just inserted");
+        spooled.group.add(&ext, entry, &name, options);
+        spooled.sorted &= key >= spooled.last;
+        for field in [&key[..], name.as_bytes(), details.as_bytes()] {
+            spooled.buffer.extend_from_slice(&(field.len() as u64).to_le_bytes());
+            spooled.buffer.extend_from_slice(field);
+        }
+        spooled.last = key;
+        if spooled.buffer.len() >= CHUNK {
+            if let Err(e) = self.file.write_all(&spooled.buffer) {
+                self.error = Some(e);
+                return;
+            }
+            spooled.chunks.push((self.len, spooled.buffer.len()));
+            self.len += spooled.buffer.len() as u64;
+            spooled.buffer.clear();
+        }
+    }
+
+    /// Write the whole catalog, exactly as [`write`] would have.
+    pub fn finish(
+        mut self,
+        writer: &mut dyn Write,
+        stats: &ScanStats,
+        options: &ScanOptions,
+        palette: Palette,
+    ) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        write_notice(writer, stats, palette)?;
+        let mut totals = Totals::default();
+        let groups = std::mem::take(&mut self.groups);
+        for ext in sorted_groups(groups.keys()) {
+            let spooled = &groups[ext];
+            spooled.group.write_header(writer, ext, &mut totals, options, palette)?;
+            let width = (options.sizes && is_regular_group(ext)).then_some(spooled.group.width);
+            if spooled.sorted {
+                let mut chunk = Vec::with_capacity(CHUNK);
+                for &(offset, len) in &spooled.chunks {
+                    self.read(offset, len, &mut chunk)?;
+                    for (_, name, details) in records(&chunk)? {
+                        write_line(writer, name, details, width)?;
+                    }
+                }
+                for (_, name, details) in records(&spooled.buffer)? {
+                    write_line(writer, name, details, width)?;
+                }
             } else {
-                writeln!(writer, "{}", entry.path.to_string_lossy())?;
+                let mut all = Vec::new();
+                for &(offset, len) in &spooled.chunks {
+                    let mut chunk = Vec::new();
+                    self.read(offset, len, &mut chunk)?;
+                    all.extend_from_slice(&chunk);
+                }
+                all.extend_from_slice(&spooled.buffer);
+                let mut lines = records(&all)?;
+                lines.sort_by(|a, b| a.0.cmp(b.0));
+                for (_, name, details) in lines {
+                    write_line(writer, name, details, width)?;
+                }
+            }
+            writeln!(writer)?;
+        }
+        write_footer(writer, &totals, stats, options, palette)
+    }
+
+    /// Read the spooled bytes at `offset` into `chunk`.
+    fn read(&mut self, offset: u64, len: usize, chunk: &mut Vec<u8>) -> io::Result<()> {
+        chunk.resize(len, 0);
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(chunk)
+    }
+}
+
+/// The `(sort key, name, details)` records in spooled bytes.
+fn records(mut bytes: &[u8]) -> io::Result<Vec<(&[u8], &str, &str)>> {
+    let mut records = Vec::new();
+    while !bytes.is_empty() {
+        let key = field(&mut bytes)?;
+        let name = std::str::from_utf8(field(&mut bytes)?).map_err(|_| corrupt())?;
+        let details = std::str::from_utf8(field(&mut bytes)?).map_err(|_| corrupt())?;
+        records.push((key, name, details));
+    }
+    Ok(records)
+}
+
+/// Split one length-prefixed field off the front of `bytes`.
+fn field<'a>(bytes: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+    let (len, rest) = bytes.split_first_chunk::<8>().ok_or_else(corrupt)?;
+    let len = usize::try_from(u64::from_le_bytes(*len)).map_err(|_| corrupt())?;
+    let (field, rest) = rest.split_at_checked(len).ok_or_else(corrupt)?;
+    *bytes = rest;
+    Ok(field)
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "spool file is corrupt")
+}
+
+/// The `PARTIAL SCAN` line heading a catalog cut short by Ctrl-C.
+fn write_notice(writer: &mut dyn Write, stats: &ScanStats, palette: Palette) -> io::Result<()> {
+    if let Some(notice) = partial_notice(stats) {
+        writeln!(writer, "{}", palette.error(&notice))?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// What a group's header sums up, gathered one file at a time.
+#[derive(Default)]
+struct Group {
+    files: usize,
+    size: SizeTotal,
+    lines: u64,
+    /// Longest listed name, so the sizes line up within the group.
+    width: usize,
+    /// Spellings `--alias` merged into the group.
+    variants: BTreeSet<String>,
+}
+
+impl Group {
+    fn add(&mut self, ext: &str, entry: &FileEntry, name: &str, options: &ScanOptions) {
+        self.files += 1;
+        self.size.bytes += entry.size.unwrap_or(0);
+        self.size.incomplete |= entry.size.is_none();
+        self.lines += entry.lines.unwrap_or(0);
+        self.width = self.width.max(name.chars().count());
+        if options.group_by == GroupBy::Ext && is_regular_group(ext) && ext != OTHER_GROUP {
+            let variant = extension_key(&entry.path, &options.compound_exts, options.preserve_case);
+            if variant != ext {
+                self.variants.insert(variant);
             }
         }
+    }
+
+    /// The `--- .ext (N files) ---` line, adding the group to `totals`.
+    fn write_header(
+        &self,
+        writer: &mut dyn Write,
+        ext: &str,
+        totals: &mut Totals,
+        options: &ScanOptions,
+        palette: Palette,
+    ) -> io::Result<()> {
+        let label = match ext {
+            "" => "(no extension)",
+            OTHER_GROUP => "other",
+            ext => ext,
+        };
+        let regular = is_regular_group(ext);
+        match ext {
+            DIR_GROUP => totals.dirs += self.files,
+            SYMLINK_GROUP => totals.symlinks += self.files,
+            _ => totals.files += self.files,
+        }
+        let mut counts = vec![count_label(ext, self.files)];
+        if options.sizes && regular {
+            totals.size.add(&self.size);
+            counts.push(self.size.display(options.exact_bytes));
+        }
+        if options.lines && regular {
+            totals.lines += self.lines;
+            counts.push(format!("{} lines", self.lines));
+        }
+        if !self.variants.is_empty() {
+            let variants: Vec<&str> = self.variants.iter().map(String::as_str).collect();
+            counts.push(format!("includes {}", variants.join(", ")));
+        }
+        writeln!(writer, "{}", heading(palette, label, Some(&counts.join(", "))))
+    }
+}
+
+/// A `--- label (counts) ---` line: the label bold and the counts coloured when
+/// printed to a terminal.
+fn heading(palette: Palette, label: &str, counts: Option<&str>) -> String {
+    match counts {
+        Some(counts) => format!(
+            "{} {} {}",
+            palette.heading(&format!("--- {label}")),
+            palette.count(&format!("({counts})")),
+            palette.heading("---")
+        ),
+        None => palette.heading(&format!("--- {label} ---")),
+    }
+}
+
+/// Counts over every group, for the closing `Total:` line.
+#[derive(Default)]
+struct Totals {
+    files: usize,
+    /// Entries of the `--entry-types` groups, counted apart from the files.
+    dirs: usize,
+    symlinks: usize,
+    size: SizeTotal,
+    lines: u64,
+}
+
+/// One file's line: its name, padded to `width` with `--sizes` so the sizes
+/// line up, then its details.
+fn write_line(writer: &mut dyn Write, name: &str, details: &str, width: Option<usize>) -> io::Result<()> {
+    let pad = width.map_or(0, |width| width - name.chars().count());
+    writeln!(writer, "{name}{:pad$}{details}", "")
+}
+
+/// Everything on a file's line after its name: `  (size)` with `--sizes`, then
+/// the tab-separated columns of the other options.
+fn details(ext: &str, entry: &FileEntry, options: &ScanOptions) -> String {
+    let regular = is_regular_group(ext);
+    let mut line = String::new();
+    if options.sizes && regular {
+        let mut size = entry
+            .size
+            .map_or_else(|| "size unknown".to_string(), |s| display_size(s, options.exact_bytes));
+        if options.disk_usage
+            && let Some((bytes, estimated)) = diskusage::on_disk(entry.allocated, entry.size)
+        {
+            let marker = if estimated { "~" } else { "" };
+            size.push_str(&format!(", {marker}{} on disk", display_size(bytes, options.exact_bytes)));
+        }
+        line.push_str(&format!("  ({size})"));
+    }
+    if options.group_by == GroupBy::Exec && regular {
+        line.push('\t');
+        line.push_str(ext_label(&group_key(&entry.path, options)));
+    }
+    if options.hash.is_some() && regular {
+        line.push('\t');
+        line.push_str(hash::display_digest(entry));
+    }
+    if options.lines && regular {
+        line.push('\t');
+        match entry.lines {
+            Some(n) => line.push_str(&n.to_string()),
+            None => line.push('-'),
+        }
+    }
+    if options.perms && regular {
+        line.push('\t');
+        match &entry.permissions {
+            Some(permissions) => line.push_str(&permissions_label(permissions)),
+            None => line.push('?'),
+        }
+    }
+    if options.owner && regular {
+        line.push('\t');
+        match &entry.owner {
+            Some(owner) => line.push_str(&format!("{}:{}", owner.user, owner.group)),
+            None => line.push('?'),
+        }
+    }
+    if options.attributes && regular {
+        line.push('\t');
+        match entry.attributes {
+            Some(bits) => line.push_str(&attributes::label(bits)),
+            None => line.push('?'),
+        }
+    }
+    if options.executables && regular {
+        line.push('\t');
+        line.push_str(match entry.executable {
+            Some(true) => "executable",
+            Some(false) => "-",
+            None => "?",
+        });
+    }
+    if options.mtime && regular {
+        line.push('\t');
+        match entry.modified {
+            Some(time) => line.push_str(&modified_time(time, options)),
+            None => line.push_str("unknown"),
+        }
+    }
+    if options.created && regular {
+        line.push('\t');
+        match entry.created {
+            Some(time) => line.push_str(&modified_time(time, options)),
+            None => line.push_str("unavailable"),
+        }
+    }
+    if let Some(mime) = entry.mime {
+        line.push('\t');
+        line.push_str(mime);
+    }
+    if !entry.xattrs.is_empty() {
+        line.push_str(&format!("\t[{}]", xattrs::labels(&entry.xattrs).join(", ")));
+    }
+    if let Some(encoding) = entry.encoding {
+        line.push('\t');
+        line.push_str(encoding.name());
+    }
+    if let Some(status) = entry.git {
+        line.push('\t');
+        line.push_str(status.name());
+    }
+    line
+}
+
+/// The `Total:` line and the report sections after the groups.
+fn write_footer(
+    writer: &mut dyn Write,
+    grand: &Totals,
+    stats: &ScanStats,
+    options: &ScanOptions,
+    palette: Palette,
+) -> io::Result<()> {
+    let grand_total = &grand.size;
+    let mut totals = vec![format!("{} files", grand.files)];
+    if grand.dirs > 0 {
+        totals.push(format!("{} directories", grand.dirs));
+    }
+    if grand.symlinks > 0 {
+        totals.push(format!("{} symlinks", grand.symlinks));
+    }
+    if options.sizes {
+        if stats.hardlinked > 0 {
+            // Hardlinked copies share their bytes, so the disk holds less than listed.
+            let deduplicated = SizeTotal {
+                bytes: grand_total.bytes.saturating_sub(stats.hardlinked_size),
+                ..*grand_total
+            };
+            totals.push(format!("{} apparent", grand_total.display(options.exact_bytes)));
+            totals.push(format!("{} deduplicated", deduplicated.display(options.exact_bytes)));
+        } else {
+            totals.push(grand_total.display(options.exact_bytes));
+        }
+    }
+    if options.disk_usage {
+        let marker = if stats.allocation_estimated > 0 { "~" } else { "" };
+        totals.push(format!("{marker}{} on disk", display_size(stats.allocated_size, options.exact_bytes)));
+    }
+    if options.lines {
+        totals.push(format!("{} lines", grand.lines));
+    }
+    writeln!(writer, "Total: {}", totals.join(", "))?;
+    if options.duplicates {
+        write_duplicates(writer, &stats.duplicates, options, palette)?;
+    }
+    if options.hardlinks {
+        write_hardlinks(writer, &stats.hardlinks, options, palette)?;
+    }
+    if options.largest.is_some() {
+        write_largest(writer, &stats.largest, options, palette)?;
+    }
+    if options.verify_types {
+        writeln!(writer)?;
+        let count = stats.mismatched.len().to_string();
+        writeln!(writer, "{}", heading(palette, "mismatched files", Some(&count)))?;
+        for file in &stats.mismatched {
+            writeln!(
+                writer,
+                "{}\t{} but content is {}",
+                file.path.to_string_lossy(),
+                ext_label(&file.ext),
+                file.detected
+            )?;
+        }
+    }
+    if options.empty_dirs.is_some() {
+        writeln!(writer)?;
+        let count = stats.empty_dirs.len().to_string();
+        writeln!(writer, "{}", heading(palette, "empty directories", Some(&count)))?;
+        for dir in &stats.empty_dirs {
+            writeln!(writer, "{}/", dir.to_string_lossy())?;
+        }
+    }
+    if options.owner {
+        writeln!(writer)?;
+        let count = stats.owners.len().to_string();
+        writeln!(writer, "{}", heading(palette, "owners", Some(&count)))?;
+        for owner in &stats.owners {
+            writeln!(
+                writer,
+                "{}\t{} files, {}",
+                owner.user,
+                owner.files,
+                display_size(owner.size, options.exact_bytes)
+            )?;
+        }
+    }
+    if options.xattrs {
+        writeln!(writer)?;
+        let count = stats.xattrs.len().to_string();
+        writeln!(writer, "{}", heading(palette, "extended attributes", Some(&count)))?;
+        for (name, files) in &stats.xattrs {
+            writeln!(writer, "{name}\t{files} files")?;
+        }
+    }
+    if options.detect_encoding {
+        writeln!(writer)?;
+        writeln!(writer, "{}", heading(palette, "encodings", None))?;
+        for (encoding, files) in &stats.encodings {
+            writeln!(writer, "{}\t{files} files", encoding.name())?;
+        }
+        writeln!(writer)?;
+        let count = stats.non_utf8.len().to_string();
+        writeln!(writer, "{}", heading(palette, "not UTF-8", Some(&count)))?;
+        for (path, encoding) in &stats.non_utf8 {
+            writeln!(writer, "{}\t{}", path.to_string_lossy(), encoding.name())?;
+        }
+    }
+    if let Some(counts) = &stats.git {
+        writeln!(writer)?;
+        writeln!(writer, "{}", heading(palette, "git status", None))?;
+        for (status, files) in GitStatus::ALL.iter().zip(counts) {
+            writeln!(writer, "{}\t{files} files", status.name())?;
+        }
+    }
+    if !stats.errors.is_empty() {
+        writeln!(writer)?;
+        let count = stats.errors.len().to_string();
+        writeln!(writer, "{}", heading(palette, "errors", Some(&count)))?;
+        for error in &stats.errors {
+            let path = error.path.as_ref().map_or_else(|| "(unknown)".into(), |p| p.to_string_lossy());
+            writeln!(writer, "{}", palette.error(&format!("{path}\t{}", error.message)))?;
+        }
+    }
+    if !stats.case_variants.is_empty() {
         writeln!(writer)?;
+        let count = stats.case_variants.len().to_string();
+        writeln!(writer, "{}", heading(palette, "case variants", Some(&count)))?;
+        for variants in &stats.case_variants {
+            let spellings: Vec<String> = variants
+                .spellings
+                .iter()
+                .map(|(spelling, files)| format!("{spelling} {files}"))
+                .collect();
+            writeln!(writer, "{}: {}", ext_label(&variants.ext), spellings.join(", "))?;
+        }
+    }
+    Ok(())
+}
+
+/// The `--largest` section: size, extension and path, biggest first.
+fn write_largest(
+    writer: &mut dyn Write,
+    files: &[LargeFile],
+    options: &ScanOptions,
+    palette: Palette,
+) -> io::Result<()> {
+    writeln!(writer)?;
+    writeln!(writer, "{}", heading(palette, "largest files", Some(&files.len().to_string())))?;
+    let sizes: Vec<String> = files.iter().map(|f| display_size(f.size, options.exact_bytes)).collect();
+    let size_width = sizes.iter().map(String::len).max().unwrap_or(0);
+    let ext_width = files.iter().map(|f| ext_label(&f.ext).chars().count()).max().unwrap_or(0);
+    for (file, size) in files.iter().zip(sizes) {
+        writeln!(
+            writer,
+            "{size:>size_width$}  {:<ext_width$}  {}",
+            ext_label(&file.ext),
+            file.path.0.to_string_lossy()
+        )?;
     }
+    Ok(())
+}
+
+fn ext_label(ext: &str) -> &str {
+    if ext.is_empty() { "(no extension)" } else { ext }
+}
+
+/// The `--duplicates` section: each set of identical files, most wasteful first.
+fn write_duplicates(
+    writer: &mut dyn Write,
+    groups: &[DuplicateGroup],
+    options: &ScanOptions,
+    palette: Palette,
+) -> io::Result<()> {
+    let size = |bytes| display_size(bytes, options.exact_bytes);
+    writeln!(writer)?;
+    let counts = format!("{} groups, {} reclaimable", groups.len(), size(duplicates::reclaimable(groups)));
+    writeln!(writer, "{}", heading(palette, "duplicates", Some(&counts)))?;
+    for group in groups {
+        writeln!(
+            writer,
+            "{} copies of {} ({} wasted):",
+            group.paths.len(),
+            size(group.size),
+            size(group.wasted())
+        )?;
+        for path in &group.paths {
+            writeln!(writer, "  {}", path.to_string_lossy())?;
+        }
+    }
+    Ok(())
+}
 
-    writer.flush()
+/// The `--hardlinks` section: each file reachable through several paths.
+fn write_hardlinks(
+    writer: &mut dyn Write,
+    groups: &[HardlinkGroup],
+    options: &ScanOptions,
+    palette: Palette,
+) -> io::Result<()> {
+    writeln!(writer)?;
+    let counts = format!("{} groups", groups.len());
+    writeln!(writer, "{}", heading(palette, "hardlinks", Some(&counts)))?;
+    for group in groups {
+        writeln!(
+            writer,
+            "{} links to {}:",
+            group.paths.len(),
+            display_size(group.size, options.exact_bytes)
+        )?;
+        for path in &group.paths {
+            writeln!(writer, "  {}", path.to_string_lossy())?;
+        }
+    }
+    Ok(())
+}
+
+/// A size sum that remembers whether any file's size was unknown.
+#[derive(Default)]
+struct SizeTotal {
+    bytes: u64,
+    incomplete: bool,
+}
+
+impl SizeTotal {
+    fn add(&mut self, other: &SizeTotal) {
+        self.bytes += other.bytes;
+        self.incomplete |= other.incomplete;
+    }
+
+    /// The total, with a trailing `+` when it is only a lower bound.
+    fn display(&self, exact: bool) -> String {
+        let size = display_size(self.bytes, exact);
+        if self.incomplete { format!("{size}+") } else { size }
+    }
+}
+
+/// The path as listed: ` (hardlink)` marks an additional link with `--hardlinks`;
+/// a symlink gets ` -> target`, then ` (broken)` if the target doesn't resolve or
+/// ` => /canonical/target` with `--resolve-links`.
+fn display_name(entry: &FileEntry, options: &ScanOptions) -> String {
+    let mut name = entry.path.to_string_lossy().into_owned();
+    if let Some(target) = &entry.target {
+        name.push_str(" -> ");
+        name.push_str(&target.to_string_lossy());
+    }
+    if entry.hardlink && options.hardlinks {
+        name.push_str(" (hardlink)");
+    }
+    if entry.broken_link {
+        name.push_str(" (broken)");
+    } else if let Some(resolved) = &entry.resolved {
+        name.push_str(" => ");
+        name.push_str(&resolved.to_string_lossy());
+    }
+    name
+}
+
+/// The distinct extensions merged into the `other` group, e.g. `.bak, .tmp`.
+fn folded_extensions(entries: &[&FileEntry], options: &ScanOptions) -> String {
+    let mut extensions: Vec<String> = entries
+        .iter()
+        .map(|e| group_key(&e.path, options))
+        .collect();
+    extensions.sort();
+    extensions.dedup();
+    extensions
+        .iter()
+        .map(|ext| ext_label(ext))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use std::path::PathBuf;
+
+    fn entries(paths: &[&str]) -> Vec<FileEntry> {
+        paths.iter().map(|path| FileEntry::new(PathBuf::from(path), Some(1))).collect()
+    }
+
+    fn written(catalog: &Catalog, args: &[&str]) -> String {
+        let (options, _) = cli::parse_for_test(args);
+        let mut out = Vec::new();
+        write(&mut out, catalog, &ScanStats::new(), &options, Palette::PLAIN).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn directories_and_symlinks_are_counted_apart_from_files() {
+        let catalog = Catalog::from([
+            (".txt".to_string(), entries(&["a.txt", "b/c.txt"])),
+            (DIR_GROUP.to_string(), entries(&["b", "d", "e"])),
+            (SYMLINK_GROUP.to_string(), entries(&["link"])),
+        ]);
+        let text = written(&catalog, &["--entry-types", "file,dir,symlink"]);
+        assert!(text.contains("--- .txt (2 files) ---\n"), "{text}");
+        assert!(text.contains("--- <dir> (3 directories) ---\n"), "{text}");
+        assert!(text.contains("--- <symlink> (1 symlinks) ---\n"), "{text}");
+        assert!(text.ends_with("Total: 2 files, 3 directories, 1 symlinks\n"), "{text}");
+    }
+
+    #[test]
+    fn a_spooled_catalog_is_written_exactly_as_one_held_in_memory() {
+        // Enough of each group to spill several chunks to the file; `.rs` arrives
+        // in reverse and `.md` shuffled, as archive members can.
+        let txt: Vec<String> = (0..1500).map(|i| format!("docs/{i:05}/a-fairly-long-file-name.txt")).collect();
+        let rs: Vec<String> = (0..1500).rev().map(|i| format!("src/{i:05}/module.rs")).collect();
+        let md: Vec<String> = [3, 1, 2].iter().map(|i| format!("notes/{i}.md")).collect();
+        let groups = [(".txt", &txt), (".rs", &rs), (".md", &md)];
+        let (options, _) = cli::parse_for_test(&["--sizes"]);
+
+        let mut spool = Spool::new().unwrap();
+        let mut catalog = Catalog::new();
+        for i in 0..txt.len() {
+            for (ext, paths) in groups {
+                if let Some(path) = paths.get(i) {
+                    let entry = FileEntry::new(PathBuf::from(path), Some(i as u64 * 37));
+                    spool.add(ext.to_string(), &entry, &options);
+                    catalog.entry(ext.to_string()).or_default().push(entry);
+                }
+            }
+        }
+        assert!(spool.len > 2 * CHUNK as u64);
+
+        let mut spooled = Vec::new();
+        spool.finish(&mut spooled, &ScanStats::new(), &options, Palette::PLAIN).unwrap();
+        let mut direct = Vec::new();
+        write(&mut direct, &catalog, &ScanStats::new(), &options, Palette::PLAIN).unwrap();
+        assert!(spooled == direct);
+    }
 }
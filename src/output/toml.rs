@@ -0,0 +1,154 @@
+//! TOML output: a `[catalog]` table followed by one `[[group]]` per extension.
+
+use super::{iso8601_utc, modified_time, permissions_label, sorted_entries, sorted_extensions};
+use crate::attributes;
+use crate::config::ScanOptions;
+use crate::{diskusage, xattrs};
+use crate::scan::{Catalog, SYMLINK_GROUP, ScanStats, is_regular_group};
+use std::io::{self, Write};
+use std::path::Path;
+
+pub fn write(
+    w: &mut dyn Write,
+    catalog: &Catalog,
+    stats: &ScanStats,
+    root: &Path,
+    options: &ScanOptions,
+) -> io::Result<()> {
+    writeln!(w, "[catalog]")?;
+    writeln!(w, "generator = {}", quote(&format!("CreepDir {}", env!("CARGO_PKG_VERSION"))))?;
+    writeln!(w, "root = {}", quote(&root.to_string_lossy()))?;
+    // A bare offset date-time is a native TOML value.
+    writeln!(w, "scanned_at = {}", iso8601_utc(stats.started))?;
+    if let Some(at) = &stats.interrupted {
+        let key = if stats.timed_out { "timed_out_at" } else { "interrupted_at" };
+        writeln!(w, "{key} = {}", quote(&at.to_string_lossy()))?;
+    }
+    if let Some(at) = &stats.truncated {
+        writeln!(w, "truncated_at = {}", quote(&at.to_string_lossy()))?;
+    }
+    writeln!(w, "files = {}", stats.files)?;
+    if options.sizes {
+        writeln!(w, "total_size = {}", stats.total_size)?;
+    }
+
+    // Extensions are always values (`ext = ".tar.gz"`), never keys, so dots are safe.
+    for ext in sorted_extensions(catalog) {
+        let entries = sorted_entries(&catalog[ext]);
+
+        writeln!(w)?;
+        writeln!(w, "[[group]]")?;
+        writeln!(w, "ext = {}", quote(ext))?;
+        writeln!(w, "count = {}", entries.len())?;
+        if options.sizes {
+            let total: u64 = entries.iter().filter_map(|e| e.size).sum();
+            writeln!(w, "total_size = {total}")?;
+        }
+        writeln!(w, "files = [")?;
+        for entry in entries {
+            let path = quote(&entry.path.to_string_lossy());
+            if !options.sizes
+                && !options.mtime
+                && !options.created
+                && !options.lists_links()
+                && !options.xattrs
+                && !options.git_status
+                && !options.executables
+                && !options.attributes
+                && !options.detect_encoding
+                && !options.lines
+                && !options.perms
+                && !options.owner
+                && options.hash.is_none()
+            {
+                writeln!(w, "  {path},")?;
+                continue;
+            }
+            // TOML has no null, so unknown values are left out of the table.
+            write!(w, "  {{ path = {path}")?;
+            if let Some(size) = entry.size {
+                write!(w, ", size = {size}")?;
+            }
+            if options.disk_usage
+                && is_regular_group(ext)
+                && let Some((bytes, estimated)) = diskusage::on_disk(entry.allocated, entry.size)
+            {
+                write!(w, ", allocated = {bytes}")?;
+                if estimated {
+                    write!(w, ", allocated_estimated = true")?;
+                }
+            }
+            if let (Some(algorithm), Some(digest)) = (options.hash, &entry.digest) {
+                write!(w, ", {} = {}", algorithm.name(), quote(digest))?;
+            }
+            if let Some(lines) = entry.lines {
+                write!(w, ", lines = {lines}")?;
+            }
+            if let Some(p) = &entry.permissions {
+                let label = quote(&permissions_label(p));
+                write!(w, ", readonly = {}, permissions = {label}", p.readonly())?;
+            }
+            if let Some(o) = &entry.owner {
+                let (user, group) = (quote(&o.user), quote(&o.group));
+                write!(w, ", uid = {}, user = {user}, gid = {}, group = {group}", o.uid, o.gid)?;
+            }
+            if !entry.xattrs.is_empty() {
+                let labels: Vec<String> =
+                    xattrs::labels(&entry.xattrs).iter().map(|l| quote(l)).collect();
+                write!(w, ", xattrs = [{}]", labels.join(", "))?;
+            }
+            if let Some(bits) = entry.attributes {
+                write!(w, ", attributes = \"{}\"", attributes::label(bits))?;
+            }
+            if let Some(executable) = entry.executable.filter(|_| options.executables) {
+                write!(w, ", executable = {executable}")?;
+            }
+            if let Some(encoding) = entry.encoding {
+                write!(w, ", encoding = \"{}\"", encoding.name())?;
+            }
+            if let Some(status) = entry.git {
+                write!(w, ", git_status = \"{}\"", status.name())?;
+            }
+            if let Some(target) = &entry.target {
+                write!(w, ", target = {}", quote(&target.to_string_lossy()))?;
+            }
+            if ext == SYMLINK_GROUP {
+                write!(w, ", broken = {}", entry.broken_link)?;
+            }
+            if let Some(resolved) = &entry.resolved {
+                write!(w, ", resolved = {}", quote(&resolved.to_string_lossy()))?;
+            }
+            if let Some(time) = entry.modified {
+                write!(w, ", modified = {}", modified_time(time, options))?;
+            }
+            if let Some(time) = entry.created {
+                write!(w, ", created = {}", modified_time(time, options))?;
+            }
+            writeln!(w, " }},")?;
+        }
+        writeln!(w, "]")?;
+    }
+
+    Ok(())
+}
+
+/// Render a string as a TOML basic (double-quoted) string.
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => {
+                out.push_str(&format!("\\u{:04X}", c as u32));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
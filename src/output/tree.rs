@@ -0,0 +1,143 @@
+//! Tree output: catalogued files laid out like the `tree` command, each annotated
+//! with its extension group. Directories are listed before files.
+
+use super::display_size;
+use crate::config::ScanOptions;
+use crate::hash;
+use crate::scan::{Catalog, FileEntry, is_regular_group};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::{Component, Path};
+
+/// Branch/indent glyphs for one rendering style.
+struct Glyphs {
+    branch: &'static str,
+    last: &'static str,
+    pipe: &'static str,
+    blank: &'static str,
+}
+
+const UNICODE: Glyphs = Glyphs {
+    branch: "├── ",
+    last: "└── ",
+    pipe: "│   ",
+    blank: "    ",
+};
+
+const ASCII: Glyphs = Glyphs {
+    branch: "|-- ",
+    last: "`-- ",
+    pipe: "|   ",
+    blank: "    ",
+};
+
+/// A directory in the rendered tree.
+#[derive(Default)]
+struct Node<'a> {
+    dirs: BTreeMap<&'a OsStr, Node<'a>>,
+    files: Vec<(&'a OsStr, &'a str, &'a FileEntry)>,
+}
+
+impl Drop for Node<'_> {
+    /// Tear subtrees down one level at a time; the default drop recurses once
+    /// per nesting level and can overflow the stack on very deep trees.
+    fn drop(&mut self) {
+        let mut pending: Vec<Node> = std::mem::take(&mut self.dirs).into_values().collect();
+        while let Some(mut node) = pending.pop() {
+            pending.extend(std::mem::take(&mut node.dirs).into_values());
+        }
+    }
+}
+
+/// The next thing [`render`] writes: a directory's line (and then its
+/// contents), a directory's contents, or a file's line.
+enum Task<'n, 'a> {
+    Dir { name: &'a OsStr, node: &'n Node<'a>, prefix: String, last: bool },
+    Contents { node: &'n Node<'a>, prefix: String },
+    File { name: &'a OsStr, ext: &'a str, entry: &'a FileEntry, prefix: String, last: bool },
+}
+
+pub fn write(
+    w: &mut dyn Write,
+    catalog: &Catalog,
+    root: &Path,
+    options: &ScanOptions,
+) -> io::Result<()> {
+    let mut tree = Node::default();
+    for (ext, entries) in catalog {
+        for entry in entries {
+            insert(&mut tree, ext, entry);
+        }
+    }
+
+    let glyphs = if options.ascii_tree { &ASCII } else { &UNICODE };
+    writeln!(w, "{}", root.display())?;
+    render(w, &tree, glyphs, options)?;
+    Ok(())
+}
+
+/// Insert a file under its parent directories, creating them as needed.
+fn insert<'a>(tree: &mut Node<'a>, ext: &'a str, entry: &'a FileEntry) {
+    let mut names: Vec<&OsStr> = entry
+        .path
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+    let Some(file_name) = names.pop() else {
+        return;
+    };
+    let mut node = tree;
+    for dir in names {
+        node = node.dirs.entry(dir).or_default();
+    }
+    node.files.push((file_name, ext, entry));
+}
+
+/// Write `tree` depth-first from an explicit stack of [`Task`]s, so nesting depth
+/// is bounded by memory rather than the call stack.
+fn render(w: &mut dyn Write, tree: &Node, glyphs: &Glyphs, options: &ScanOptions) -> io::Result<()> {
+    let mut stack = vec![Task::Contents { node: tree, prefix: String::new() }];
+    while let Some(task) = stack.pop() {
+        match task {
+            Task::Dir { name, node, prefix, last } => {
+                let connector = if last { glyphs.last } else { glyphs.branch };
+                writeln!(w, "{prefix}{connector}{}/", name.to_string_lossy())?;
+                let indent = if last { glyphs.blank } else { glyphs.pipe };
+                stack.push(Task::Contents { node, prefix: format!("{prefix}{indent}") });
+            }
+            Task::Contents { node, prefix } => {
+                let mut files: Vec<_> = node.files.iter().collect();
+                files.sort_by_key(|(name, _, _)| *name);
+                let total = node.dirs.len() + files.len();
+                let dirs = node.dirs.iter().map(|(&name, node)| (name, node));
+                let mut tasks: Vec<Task> = dirs
+                    .enumerate()
+                    .map(|(i, (name, node))| Task::Dir { name, node, prefix: prefix.clone(), last: i + 1 == total })
+                    .collect();
+                for (i, &(name, ext, entry)) in files.into_iter().enumerate() {
+                    let last = node.dirs.len() + i + 1 == total;
+                    tasks.push(Task::File { name, ext, entry, prefix: prefix.clone(), last });
+                }
+                // Reversed, so the first child is popped (written) first.
+                stack.extend(tasks.into_iter().rev());
+            }
+            Task::File { name, ext, entry, prefix, last } => {
+                let connector = if last { glyphs.last } else { glyphs.branch };
+                let label = if ext.is_empty() { "(no extension)" } else { ext };
+                write!(w, "{prefix}{connector}{}  [{label}]", name.to_string_lossy())?;
+                if options.sizes {
+                    write!(w, " {}", display_size(entry.size.unwrap_or(0), options.exact_bytes))?;
+                }
+                if options.hash.is_some() && is_regular_group(ext) {
+                    write!(w, " {}", hash::display_digest(entry))?;
+                }
+                writeln!(w)?;
+            }
+        }
+    }
+    Ok(())
+}
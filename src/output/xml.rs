@@ -0,0 +1,180 @@
+//! XML output for legacy tooling.
+//!
+//! The document layout is a stable interface (schema version `1`); new data may
+//! only be added as optional attributes or elements:
+//!
+//! ```xml
+//! <?xml version="1.0" encoding="UTF-8"?>
+//! <catalog version="1" generator="CreepDir x.y.z" root="/scanned/root"
+//!          generated="2024-05-01T13:45:00Z" files="3">
+//!   <group ext=".jpg" count="2">
+//!     <file>photos/a.jpg</file>
+//!     <file>photos/b.jpg</file>
+//!   </group>
+//!   <group ext="" count="1">
+//!     <file>Makefile</file>
+//!   </group>
+//! </catalog>
+//! ```
+//!
+//! With `--sizes`, `<catalog>` and `<group>` gain a `size` attribute (total bytes)
+//! and each `<file>` a `size` attribute when its size is known (`--disk-usage`
+//! adds `allocated`, and `allocated_estimated="true"` where that is only the
+//! apparent size); `--hash`, `--lines`,
+//! `--perms`, `--owner`, `--mtime` and `--created` add a digest attribute (named
+//! after the algorithm), `lines`, `readonly`/`permissions`,
+//! `uid`/`user`/`gid`/`group`, `modified` and `created` the same way. Listed
+//! symlinks carry `target`, `broken` and, with `--resolve-links`, `resolved`;
+//! `--xattrs` adds `xattrs`, the attribute names (or `name=value`) joined by `; `,
+//! `--attributes` adds `attributes` (e.g. `H-A-`), `--executables` adds
+//! `executable`, `--detect-encoding` adds `encoding` and `--git-status` adds
+//! `git_status`.
+//! Groups are sorted by extension and files by path. Characters not allowed in
+//! XML 1.0 are replaced with U+FFFD.
+
+use super::{iso8601_utc, modified_time, permissions_label, sorted_entries, sorted_extensions};
+use crate::attributes;
+use crate::config::ScanOptions;
+use crate::{diskusage, xattrs};
+use crate::scan::{Catalog, SYMLINK_GROUP, ScanStats, is_regular_group};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Version of the document layout described in the module docs.
+const SCHEMA_VERSION: u32 = 1;
+
+pub fn write(
+    w: &mut dyn Write,
+    catalog: &Catalog,
+    stats: &ScanStats,
+    root: &Path,
+    options: &ScanOptions,
+) -> io::Result<()> {
+    writeln!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    write!(
+        w,
+        "<catalog version=\"{SCHEMA_VERSION}\" generator=\"CreepDir {}\" root=\"{}\" generated=\"{}\" files=\"{}\"",
+        env!("CARGO_PKG_VERSION"),
+        escape(&root.to_string_lossy()),
+        iso8601_utc(stats.started),
+        stats.files,
+    )?;
+    if options.sizes {
+        write!(w, " size=\"{}\"", stats.total_size)?;
+    }
+    if options.disk_usage {
+        write!(w, " allocated=\"{}\"", stats.allocated_size)?;
+    }
+    if let Some(at) = &stats.interrupted {
+        let key = if stats.timed_out { "timed-out-at" } else { "interrupted-at" };
+        write!(w, " {key}=\"{}\"", escape(&at.to_string_lossy()))?;
+    }
+    if let Some(at) = &stats.truncated {
+        write!(w, " truncated-at=\"{}\"", escape(&at.to_string_lossy()))?;
+    }
+    writeln!(w, ">")?;
+
+    for ext in sorted_extensions(catalog) {
+        let entries = sorted_entries(&catalog[ext]);
+
+        write!(w, "  <group ext=\"{}\" count=\"{}\"", escape(ext), entries.len())?;
+        if options.sizes {
+            let total: u64 = entries.iter().filter_map(|e| e.size).sum();
+            write!(w, " size=\"{total}\"")?;
+        }
+        writeln!(w, ">")?;
+        for entry in entries {
+            let path = escape(&entry.path.to_string_lossy());
+            write!(w, "    <file")?;
+            if let Some(size) = entry.size {
+                write!(w, " size=\"{size}\"")?;
+            }
+            if options.disk_usage
+                && is_regular_group(ext)
+                && let Some((bytes, estimated)) = diskusage::on_disk(entry.allocated, entry.size)
+            {
+                write!(w, " allocated=\"{bytes}\"")?;
+                if estimated {
+                    write!(w, " allocated_estimated=\"true\"")?;
+                }
+            }
+            if let (Some(algorithm), Some(digest)) = (options.hash, &entry.digest) {
+                write!(w, " {}=\"{digest}\"", algorithm.name())?;
+            }
+            if let Some(lines) = entry.lines {
+                write!(w, " lines=\"{lines}\"")?;
+            }
+            if let Some(p) = &entry.permissions {
+                write!(w, " readonly=\"{}\" permissions=\"{}\"", p.readonly(), permissions_label(p))?;
+            }
+            if let Some(o) = &entry.owner {
+                write!(
+                    w,
+                    " uid=\"{}\" user=\"{}\" gid=\"{}\" group=\"{}\"",
+                    o.uid,
+                    escape(&o.user),
+                    o.gid,
+                    escape(&o.group)
+                )?;
+            }
+            if !entry.xattrs.is_empty() {
+                let labels = xattrs::labels(&entry.xattrs).join("; ");
+                write!(w, " xattrs=\"{}\"", escape(&labels))?;
+            }
+            if let Some(bits) = entry.attributes {
+                write!(w, " attributes=\"{}\"", attributes::label(bits))?;
+            }
+            if let Some(executable) = entry.executable.filter(|_| options.executables) {
+                write!(w, " executable=\"{executable}\"")?;
+            }
+            if let Some(encoding) = entry.encoding {
+                write!(w, " encoding=\"{}\"", encoding.name())?;
+            }
+            if let Some(status) = entry.git {
+                write!(w, " git_status=\"{}\"", status.name())?;
+            }
+            if let Some(target) = &entry.target {
+                write!(w, " target=\"{}\"", escape(&target.to_string_lossy()))?;
+            }
+            if ext == SYMLINK_GROUP {
+                write!(w, " broken=\"{}\"", entry.broken_link)?;
+            }
+            if let Some(resolved) = &entry.resolved {
+                write!(w, " resolved=\"{}\"", escape(&resolved.to_string_lossy()))?;
+            }
+            if let Some(time) = entry.modified {
+                write!(w, " modified=\"{}\"", modified_time(time, options))?;
+            }
+            if let Some(time) = entry.created {
+                write!(w, " created=\"{}\"", modified_time(time, options))?;
+            }
+            writeln!(w, ">{path}</file>")?;
+        }
+        writeln!(w, "  </group>")?;
+    }
+
+    writeln!(w, "</catalog>")?;
+    Ok(())
+}
+
+/// Escape text for XML content or attribute values, replacing characters that
+/// XML 1.0 forbids (most C0 controls, surrogates, U+FFFE/U+FFFF) with U+FFFD.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 8);
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // Literal tabs/newlines in attributes are normalized away by parsers.
+            '\t' => out.push_str("&#9;"),
+            '\n' => out.push_str("&#10;"),
+            '\r' => out.push_str("&#13;"),
+            c if (c as u32) < 0x20 || c == '\u{fffe}' || c == '\u{ffff}' => out.push('\u{fffd}'),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,200 @@
+//! YAML output: extension keys (sorted) mapping to lists of relative paths.
+
+use super::{iso8601_utc, modified_time, permissions_label, sorted_entries, sorted_extensions};
+use crate::attributes;
+use crate::config::ScanOptions;
+use crate::{diskusage, hash};
+use crate::scan::{Catalog, SYMLINK_GROUP, ScanStats, is_regular_group};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub fn write(
+    w: &mut dyn Write,
+    catalog: &Catalog,
+    stats: &ScanStats,
+    root: &Path,
+    options: &ScanOptions,
+) -> io::Result<()> {
+    writeln!(w, "generator: {}", quote(&format!("CreepDir {}", env!("CARGO_PKG_VERSION"))))?;
+    writeln!(w, "root: {}", quote(&root.to_string_lossy()))?;
+    writeln!(w, "scanned_at: {}", quote(&iso8601_utc(stats.started)))?;
+    if let Some(at) = &stats.interrupted {
+        let key = if stats.timed_out { "timed_out_at" } else { "interrupted_at" };
+        writeln!(w, "{key}: {}", quote(&at.to_string_lossy()))?;
+    }
+    if let Some(at) = &stats.truncated {
+        writeln!(w, "truncated_at: {}", quote(&at.to_string_lossy()))?;
+    }
+    writeln!(w, "files: {}", stats.files)?;
+    if options.sizes {
+        writeln!(w, "total_size: {}", stats.total_size)?;
+    }
+
+    let extensions = sorted_extensions(catalog);
+    if extensions.is_empty() {
+        writeln!(w, "extensions: {{}}")?;
+        return Ok(());
+    }
+
+    // Files become mappings once there is more to say about them than the path.
+    let detailed = options.sizes
+        || options.mtime
+        || options.created
+        || options.lines
+        || options.perms
+        || options.owner
+        || options.lists_links()
+        || options.xattrs
+        || options.git_status
+        || options.executables
+        || options.attributes
+        || options.detect_encoding
+        || options.hash.is_some();
+    writeln!(w, "extensions:")?;
+    for ext in extensions {
+        let entries = sorted_entries(&catalog[ext]);
+
+        writeln!(w, "  {}:", quote(ext))?;
+        for entry in entries {
+            let path = quote(&entry.path.to_string_lossy());
+            if detailed {
+                writeln!(w, "    - path: {path}")?;
+                if options.sizes {
+                    match entry.size {
+                        Some(size) => writeln!(w, "      size: {size}")?,
+                        None => writeln!(w, "      size: null")?,
+                    }
+                }
+                if options.disk_usage
+                    && is_regular_group(ext)
+                    && let Some((bytes, estimated)) = diskusage::on_disk(entry.allocated, entry.size)
+                {
+                    writeln!(w, "      allocated: {bytes}")?;
+                    if estimated {
+                        writeln!(w, "      allocated_estimated: true")?;
+                    }
+                }
+                if let Some(algorithm) = options.hash
+                    && is_regular_group(ext)
+                {
+                    match &entry.digest {
+                        Some(digest) => writeln!(w, "      {}: {}", algorithm.name(), quote(digest))?,
+                        None if entry.too_large => {
+                            writeln!(w, "      {}: {}", algorithm.name(), quote(hash::TOO_LARGE))?;
+                        }
+                        None => writeln!(w, "      {}: null", algorithm.name())?,
+                    }
+                }
+                if options.lines && is_regular_group(ext) {
+                    match entry.lines {
+                        Some(n) => writeln!(w, "      lines: {n}")?,
+                        None => writeln!(w, "      lines: null")?,
+                    }
+                }
+                if options.perms && is_regular_group(ext) {
+                    match &entry.permissions {
+                        Some(p) => {
+                            writeln!(w, "      readonly: {}", p.readonly())?;
+                            writeln!(w, "      permissions: {}", quote(&permissions_label(p)))?;
+                        }
+                        None => {
+                            writeln!(w, "      readonly: null")?;
+                            writeln!(w, "      permissions: null")?;
+                        }
+                    }
+                }
+                if options.owner && is_regular_group(ext) {
+                    match &entry.owner {
+                        Some(o) => {
+                            writeln!(w, "      uid: {}", o.uid)?;
+                            writeln!(w, "      user: {}", quote(&o.user))?;
+                            writeln!(w, "      gid: {}", o.gid)?;
+                            writeln!(w, "      group: {}", quote(&o.group))?;
+                        }
+                        None => {
+                            for key in ["uid", "user", "gid", "group"] {
+                                writeln!(w, "      {key}: null")?;
+                            }
+                        }
+                    }
+                }
+                if !entry.xattrs.is_empty() {
+                    writeln!(w, "      xattrs:")?;
+                    for attr in &entry.xattrs {
+                        let name = quote(&attr.name);
+                        match &attr.value {
+                            _ if !options.xattr_values => writeln!(w, "        - {name}")?,
+                            Some(value) => writeln!(w, "        {name}: {}", quote(value))?,
+                            None => writeln!(w, "        {name}: null")?,
+                        }
+                    }
+                }
+                if let Some(bits) = entry.attributes {
+                    writeln!(w, "      attributes: {}", quote(&attributes::label(bits)))?;
+                }
+                if let Some(executable) = entry.executable.filter(|_| options.executables) {
+                    writeln!(w, "      executable: {executable}")?;
+                }
+                if let Some(encoding) = entry.encoding {
+                    writeln!(w, "      encoding: {}", encoding.name())?;
+                }
+                if let Some(status) = entry.git {
+                    writeln!(w, "      git_status: {}", status.name())?;
+                }
+                if ext == SYMLINK_GROUP {
+                    let path = |p: Option<&PathBuf>| {
+                        p.map_or_else(|| "null".to_string(), |p| quote(&p.to_string_lossy()))
+                    };
+                    writeln!(w, "      target: {}", path(entry.target.as_ref()))?;
+                    writeln!(w, "      broken: {}", entry.broken_link)?;
+                    if options.resolve_links {
+                        writeln!(w, "      resolved: {}", path(entry.resolved.as_ref()))?;
+                    }
+                }
+                if options.mtime {
+                    match entry.modified {
+                        Some(t) => writeln!(w, "      modified: {}", quote(&modified_time(t, options)))?,
+                        None => writeln!(w, "      modified: null")?,
+                    }
+                }
+                if options.created {
+                    match entry.created {
+                        Some(t) => writeln!(w, "      created: {}", quote(&modified_time(t, options)))?,
+                        None => writeln!(w, "      created: null")?,
+                    }
+                }
+            } else {
+                writeln!(w, "    - {path}")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Render a string as a YAML double-quoted scalar.
+///
+/// Every string is quoted so values such as `-a`, `a: b`, `yes`, or `~` are never
+/// reinterpreted; non-printable characters use YAML's escape sequences.
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || ('\u{7f}'..='\u{9f}').contains(&c) => {
+                out.push_str(&format!("\\x{:02x}", c as u32));
+            }
+            '\u{2028}' | '\u{2029}' | '\u{feff}' => {
+                out.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
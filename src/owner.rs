@@ -0,0 +1,124 @@
+//! `--owner`: the user and group owning each file, resolved to names through the
+//! system's user and group databases (Unix only).
+
+use std::collections::HashMap;
+use std::fs::Metadata;
+
+/// Who owns a file. Names fall back to the numeric ID when the databases have
+/// no entry for it.
+#[derive(Clone)]
+pub struct Owner {
+    pub uid: u32,
+    pub user: String,
+    pub gid: u32,
+    pub group: String,
+}
+
+/// Files and bytes owned by one user, for the `--owner` summary.
+#[derive(Clone)]
+pub struct OwnerTotal {
+    pub user: String,
+    pub files: u64,
+    pub size: u64,
+}
+
+/// Resolves owner IDs to names, looking each ID up only once per scan.
+#[derive(Default)]
+pub struct Names {
+    #[cfg(unix)]
+    users: HashMap<u32, String>,
+    #[cfg(unix)]
+    groups: HashMap<u32, String>,
+}
+
+impl Names {
+    /// The owner recorded in `metadata`.
+    #[cfg(unix)]
+    pub fn owner(&mut self, metadata: &Metadata) -> Option<Owner> {
+        use std::os::unix::fs::MetadataExt;
+        let (uid, gid) = (metadata.uid(), metadata.gid());
+        let user = self
+            .users
+            .entry(uid)
+            .or_insert_with(|| unix::user_name(uid).unwrap_or_else(|| uid.to_string()));
+        let group = self
+            .groups
+            .entry(gid)
+            .or_insert_with(|| unix::group_name(gid).unwrap_or_else(|| gid.to_string()));
+        Some(Owner { uid, user: user.clone(), gid, group: group.clone() })
+    }
+
+    /// Owners aren't available here; `--owner` is rejected before scanning.
+    #[cfg(not(unix))]
+    pub fn owner(&mut self, _metadata: &Metadata) -> Option<Owner> {
+        None
+    }
+}
+
+/// Sum `(user, size)` pairs into per-user totals, biggest first.
+pub fn totals(files: HashMap<String, (u64, u64)>) -> Vec<OwnerTotal> {
+    let mut totals: Vec<OwnerTotal> = files
+        .into_iter()
+        .map(|(user, (files, size))| OwnerTotal { user, files, size })
+        .collect();
+    totals.sort_by(|a, b| {
+        b.size
+            .cmp(&a.size)
+            .then_with(|| b.files.cmp(&a.files))
+            .then_with(|| a.user.cmp(&b.user))
+    });
+    totals
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::ffi::{CStr, c_char};
+
+    /// Largest lookup buffer tried before giving up on an entry.
+    const MAX_BUFFER: usize = 1 << 20;
+
+    pub fn user_name(uid: u32) -> Option<String> {
+        let mut buf = vec![0 as c_char; 1024];
+        loop {
+            // SAFETY: `passwd` is plain data that getpwuid_r fills in; its string
+            // fields point into `buf`, which outlives the read of `pw_name`.
+            let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+            let mut result = std::ptr::null_mut();
+            let rc = unsafe {
+                libc::getpwuid_r(uid, &mut entry, buf.as_mut_ptr(), buf.len(), &mut result)
+            };
+            if rc == libc::ERANGE && buf.len() < MAX_BUFFER {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if rc != 0 || result.is_null() {
+                return None;
+            }
+            // SAFETY: on success `pw_name` is a NUL-terminated string inside `buf`.
+            let name = unsafe { CStr::from_ptr(entry.pw_name) };
+            return Some(name.to_string_lossy().into_owned());
+        }
+    }
+
+    pub fn group_name(gid: u32) -> Option<String> {
+        let mut buf = vec![0 as c_char; 1024];
+        loop {
+            // SAFETY: as in `user_name`, for the group database.
+            let mut entry: libc::group = unsafe { std::mem::zeroed() };
+            let mut result = std::ptr::null_mut();
+            let rc = unsafe {
+                libc::getgrgid_r(gid, &mut entry, buf.as_mut_ptr(), buf.len(), &mut result)
+            };
+            if rc == libc::ERANGE && buf.len() < MAX_BUFFER {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            if rc != 0 || result.is_null() {
+                return None;
+            }
+            // SAFETY: on success `gr_name` is a NUL-terminated string inside `buf`.
+            let name = unsafe { CStr::from_ptr(entry.gr_name) };
+            return Some(name.to_string_lossy().into_owned());
+        }
+    }
+}
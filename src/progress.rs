@@ -0,0 +1,114 @@
+//! The `Scanning ...` line on stderr while the walk runs: directories visited,
+//! files found and the path being looked at, redrawn a few times a second.
+
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How often the line is redrawn.
+const INTERVAL: Duration = Duration::from_millis(250);
+
+/// Entries seen between updates of the shared counters, so the walk itself
+/// only touches them (and the current-path lock) once in a while.
+const BATCH: u32 = 64;
+
+/// Width used when `COLUMNS` doesn't say how wide the terminal is.
+const DEFAULT_WIDTH: usize = 80;
+
+#[derive(Default)]
+struct Shared {
+    dirs: AtomicU64,
+    files: AtomicU64,
+    current: Mutex<PathBuf>,
+    finished: AtomicBool,
+}
+
+/// A running progress line; [`finish`](Progress::finish) clears it.
+pub struct Progress {
+    shared: Arc<Shared>,
+    drawer: Option<JoinHandle<()>>,
+    pending: u32,
+}
+
+impl Progress {
+    /// Start drawing, unless `quiet` or stderr isn't an interactive terminal.
+    pub fn start(quiet: bool) -> Option<Progress> {
+        if quiet || !io::stderr().is_terminal() {
+            return None;
+        }
+        let shared = Arc::new(Shared::default());
+        let drawer = {
+            let shared = Arc::clone(&shared);
+            std::thread::spawn(move || draw(&shared))
+        };
+        Some(Progress { shared, drawer: Some(drawer), pending: 0 })
+    }
+
+    /// Note one more entry; every [`BATCH`] entries the counts and `path` are
+    /// handed to the drawing thread.
+    pub fn tick(&mut self, dirs: u64, files: u64, path: impl FnOnce() -> PathBuf) {
+        self.pending += 1;
+        if self.pending < BATCH {
+            return;
+        }
+        self.pending = 0;
+        self.shared.dirs.store(dirs, Ordering::Relaxed);
+        self.shared.files.store(files, Ordering::Relaxed);
+        if let Ok(mut current) = self.shared.current.try_lock() {
+            *current = path();
+        }
+    }
+
+    /// Stop drawing and clear the line.
+    pub fn finish(mut self) {
+        self.shared.finished.store(true, Ordering::Relaxed);
+        if let Some(drawer) = self.drawer.take() {
+            let _ = drawer.join();
+        }
+    }
+}
+
+/// Redraw the line until `finished`, then clear it.
+fn draw(shared: &Shared) {
+    let width = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(DEFAULT_WIDTH);
+    let mut stderr = io::stderr();
+    while !shared.finished.load(Ordering::Relaxed) {
+        let counts = format!(
+            "Scanning: {} directories, {} files",
+            shared.dirs.load(Ordering::Relaxed),
+            shared.files.load(Ordering::Relaxed)
+        );
+        let current = shared.current.lock().map(|p| p.clone()).unwrap_or_default();
+        let room = width.saturating_sub(counts.chars().count() + 4);
+        let line = match tail(&current, room) {
+            Some(path) => format!("{counts}  {path}"),
+            None => counts,
+        };
+        let _ = write!(stderr, "\r\x1b[K{line}");
+        let _ = stderr.flush();
+        std::thread::sleep(INTERVAL);
+    }
+    let _ = write!(stderr, "\r\x1b[K");
+    let _ = stderr.flush();
+}
+
+/// The end of `path` in at most `room` characters, with `…` marking a cut, or
+/// `None` if there's no path yet or no room for one.
+fn tail(path: &Path, room: usize) -> Option<String> {
+    let path = path.to_string_lossy();
+    let len = path.chars().count();
+    if path.is_empty() || room < 2 {
+        return None;
+    }
+    if len <= room {
+        return Some(path.into_owned());
+    }
+    let kept: String = path.chars().skip(len - (room - 1)).collect();
+    Some(format!("…{kept}"))
+}
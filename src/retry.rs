@@ -0,0 +1,55 @@
+//! `--retries`: try directory listings and metadata reads again when they fail
+//! in a way that may clear up by itself, as timeouts on a network share over a
+//! flaky connection do. Errors that won't change on a second look (a missing
+//! file, a denied permission) are reported at once, as without `--retries`.
+
+use crate::interrupt;
+use std::io;
+use std::time::Duration;
+
+/// Longest wait between attempts, however many retries are allowed.
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Whether `e` looks transient: a timeout, an interrupted or would-block call,
+/// or (on Windows) a dropped connection to a network share.
+pub fn is_transient(e: &io::Error) -> bool {
+    if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock) {
+        return true;
+    }
+    // ERROR_NETNAME_DELETED, ERROR_UNEXP_NET_ERR and ERROR_SEM_TIMEOUT, which
+    // don't map to a kind of their own.
+    cfg!(windows) && matches!(e.raw_os_error(), Some(64 | 59 | 121))
+}
+
+/// How long to wait before retry number `attempt` (from 1): `delay`, doubled for
+/// each attempt after the first, up to [`MAX_DELAY`].
+pub fn backoff(delay: Duration, attempt: u32) -> Duration {
+    delay.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(MAX_DELAY)
+}
+
+/// Wait before retry number `attempt`, unless Ctrl-C or `--timeout` stopped the
+/// walk; the wait ends early at the deadline. Returns whether to go on with the
+/// retry.
+pub fn pause(delay: Duration, attempt: u32) -> bool {
+    if interrupt::stopping() {
+        return false;
+    }
+    let wait = backoff(delay, attempt);
+    std::thread::sleep(interrupt::time_left().map_or(wait, |left| wait.min(left)));
+    !interrupt::stopping()
+}
+
+/// Run `op`, and again up to `retries` times while it fails with a transient
+/// error. Also returns whether any retry was needed.
+pub fn run<T>(retries: u32, delay: Duration, mut op: impl FnMut() -> io::Result<T>) -> (io::Result<T>, bool) {
+    let mut result = op();
+    let mut attempt = 0;
+    while attempt < retries
+        && result.as_ref().is_err_and(is_transient)
+        && pause(delay, attempt + 1)
+    {
+        attempt += 1;
+        result = op();
+    }
+    (result, attempt > 0)
+}
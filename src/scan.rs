@@ -1,145 +1,2503 @@
 //! Parallel filesystem traversal that groups files by extension.
 
-use crate::config::{ScanFilter, ScanOptions};
+use crate::archive;
+use crate::attributes;
+use crate::cache::{Cache, Kind, Listing};
+use crate::checkpoint::{Checkpoint, Mark, Resume};
+use crate::charset::TextEncoding;
+use crate::config::{
+    ContentKind, EmptyDirMode, EntryType, FileAttribute, GroupBy, Hidden, ScanFilter, ScanOptions,
+    Substrings,
+};
+use crate::duplicates::DuplicateGroup;
+use crate::gitignore::{CREEPIGNORE, IgnoreStack};
+use crate::gitstatus::{GitCounts, GitStatus, Statuses};
+use crate::hardlinks::{self, HardlinkGroup};
+use crate::owner::{self, Owner, OwnerTotal};
+use crate::progress::Progress;
+use crate::{diskusage, exec, exif, interrupt, mounts, retry, shebang, sniff};
+use crate::xattrs::{self, Xattr};
 use globset::GlobSet;
-use jwalk::{Parallelism, WalkDir};
-use std::collections::HashMap;
-use std::ffi::OsStr;
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use jwalk::{DirEntry, DirEntryIter, Parallelism, WalkDirGeneric};
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, Metadata, Permissions};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// Files grouped by their extension key (e.g. `.rs`).
 pub type Catalog = HashMap<String, Vec<FileEntry>>;
 
+/// What a scan covers: the folders to walk and the `base` that catalogued paths
+/// are relative to, which is the folder itself for a single one and the deepest
+/// directory holding them all for several, so each path starts with its folder's
+/// place under the base. A "folder" may also be a single file, which is
+/// catalogued on its own under its parent directory.
+#[derive(Clone)]
+pub struct Roots {
+    pub base: PathBuf,
+    pub folders: Vec<PathBuf>,
+    /// Listed folders that didn't exist and were left out, with `--paths-from`.
+    pub missing: Vec<PathBuf>,
+    /// Folders left out because another one covers them, as `(folder, covered by)`.
+    pub merged: Vec<(PathBuf, PathBuf)>,
+    /// With `--files-from`, the files to catalog under the one folder instead
+    /// of walking it.
+    pub files: Option<Vec<PathBuf>>,
+}
+
+impl Roots {
+    pub fn single(folder: PathBuf) -> Self {
+        Roots { base: directory_of(&folder), folders: vec![folder], missing: Vec::new(), merged: Vec::new(), files: None }
+    }
+
+    /// Several folders (at least one) under their deepest common directory. A
+    /// folder inside another or the same as an earlier one (however it is
+    /// spelled, or through a symlink) is left to that one so nothing is
+    /// catalogued twice.
+    pub fn new(folders: Vec<PathBuf>) -> Result<Self, String> {
+        let (folders, merged) = merge_overlapping(folders);
+        let mut base = directory_of(folders.first().ok_or("no folder to scan")?);
+        for folder in &folders[1..] {
+            while !folder.starts_with(&base) {
+                if !base.pop() {
+                    return Err(format!(
+                        "'{}' and '{}' have no directory in common",
+                        folders[0].display(),
+                        folder.display()
+                    ));
+                }
+            }
+        }
+        Ok(Roots { base, folders, missing: Vec::new(), merged, files: None })
+    }
+
+    /// A name for default output files: the folder's name, or each folder's
+    /// name joined by `+`.
+    pub fn name(&self) -> String {
+        let name = |folder: &PathBuf| {
+            folder.file_name().map_or_else(|| "output".to_string(), |n| n.to_string_lossy().into_owned())
+        };
+        self.folders.iter().map(name).collect::<Vec<_>>().join("+")
+    }
+
+    /// Whether every path given to scan is a file, so nothing is walked.
+    pub fn only_files(&self) -> bool {
+        self.files.is_none() && self.folders.iter().all(|folder| folder.is_file())
+    }
+
+    /// `path`, found by walking folder `index`, relative to the base.
+    fn relative(&self, index: usize, path: &Path) -> PathBuf {
+        let folder = &self.folders[index];
+        let prefix = folder.strip_prefix(&self.base).unwrap_or(Path::new(""));
+        match path.strip_prefix(folder).unwrap_or(path) {
+            // The folder itself; joining nothing would add a trailing separator.
+            rest if rest.as_os_str().is_empty() => prefix.to_path_buf(),
+            rest => prefix.join(rest),
+        }
+    }
+}
+
+/// `folders` without those another one covers, and the ones left out with the
+/// outermost folder covering each (the earliest among identical ones). Folders
+/// are compared by their canonical paths.
+fn merge_overlapping(folders: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<(PathBuf, PathBuf)>) {
+    let canonical: Vec<PathBuf> =
+        folders.iter().map(|folder| fs::canonicalize(folder).unwrap_or_else(|_| folder.clone())).collect();
+    let mut kept = Vec::new();
+    let mut merged = Vec::new();
+    for (i, folder) in folders.iter().enumerate() {
+        let cover = (0..folders.len())
+            .filter(|&j| j != i && canonical[i].starts_with(&canonical[j]))
+            .filter(|&j| canonical[i] != canonical[j] || j < i)
+            .min_by_key(|&j| (canonical[j].components().count(), j));
+        match cover {
+            Some(j) => merged.push((folder.clone(), folders[j].clone())),
+            None => kept.push(folder.clone()),
+        }
+    }
+    (kept, merged)
+}
+
+/// `folder` itself, or the directory holding it if it is a file.
+fn directory_of(folder: &Path) -> PathBuf {
+    match folder.parent() {
+        Some(parent) if folder.is_file() => parent.to_path_buf(),
+        _ => folder.to_path_buf(),
+    }
+}
+
+/// Catalog group for directories listed with `--entry-types dir`.
+pub const DIR_GROUP: &str = "<dir>";
+/// Catalog group for symlinks listed with `--entry-types symlink`.
+pub const SYMLINK_GROUP: &str = "<symlink>";
+
+/// Whether `group` holds regular files, with content, a size and a modification
+/// time of their own, rather than the directories or symlinks of [`DIR_GROUP`] and
+/// [`SYMLINK_GROUP`].
+pub fn is_regular_group(group: &str) -> bool {
+    !matches!(group, DIR_GROUP | SYMLINK_GROUP)
+}
+/// Catalog group that `--min-group` folds small extension groups into.
+pub const OTHER_GROUP: &str = "<other>";
+
+/// Multi-part extensions grouped as one unless `--no-compound-ext`.
+pub const COMPOUND_EXTENSIONS: &[&str] = &[".tar.gz", ".tar.bz2", ".tar.xz", ".tar.zst"];
+
 /// Summary counts produced by a scan.
-#[derive(Default)]
+#[derive(Clone)]
 pub struct ScanStats {
+    /// Wall-clock time the scan started.
+    pub started: SystemTime,
+    /// How long the walk itself took, for `-v`.
+    pub walk_time: Duration,
     pub files: u64,
     pub dirs: u64,
     pub skipped: u64,
+    /// The entries behind `skipped`, in the order they were met.
+    pub errors: Vec<ScanError>,
     pub symlinks: u64,
+    /// Directory symlinks not followed because they lead back to a directory
+    /// already being walked, with `--follow-symlinks`.
+    pub link_loops: u64,
+    /// Symlinks whose target doesn't exist, found while following links.
+    pub broken_links: u64,
+    /// Directories on another filesystem that `--one-file-system` kept out of,
+    /// sorted.
+    pub mount_points: Vec<PathBuf>,
+    /// Virtual filesystems left out of the walk (without `--include-virtual`),
+    /// sorted.
+    pub virtual_mounts: Vec<PathBuf>,
+    /// Apparent size of the matched files, counting every hardlink.
     pub total_size: u64,
+    /// Files that are additional hardlinks to content already catalogued.
+    pub hardlinked: u64,
+    /// Bytes of `total_size` taken up by those additional links.
+    pub hardlinked_size: u64,
+    /// Content reachable through several catalogued paths, largest first.
+    pub hardlinks: Vec<HardlinkGroup>,
+    /// Files left out by the extension or `--include` filters.
+    pub filtered: u64,
+    /// Entries (files or whole directories) skipped by `.gitignore`/`.creepignore` rules.
+    pub ignored: u64,
+    /// Files in groups below `--min-group` left out by `--drop-small`.
+    pub dropped: u64,
+    /// Zero-length files left out by `--skip-empty`.
+    pub empty: u64,
+    /// This run's own output files found inside the scanned tree and left out.
+    pub outputs_excluded: u64,
+    /// Directories not descended into because of `--no-junk`.
+    pub junk_pruned: u64,
+    /// Directories not descended into because of `--exclude-dir` or `--exclude`.
+    pub pruned: u64,
+    /// Files whose contents could not be read for hashing.
+    pub hash_failures: u64,
+    /// Files left unhashed because they are larger than `--hash-max-size`.
+    pub hash_skipped: u64,
+    /// Files whose permissions could not be read for `--perms`.
+    pub perms_unknown: u64,
+    /// Groups of identical files found by `--duplicates`.
+    pub duplicates: Vec<DuplicateGroup>,
+    /// Relative paths of the directories found by `--empty-dirs`, sorted.
+    pub empty_dirs: Vec<PathBuf>,
+    /// The `--largest` files, biggest first.
+    pub largest: Vec<LargeFile>,
+    /// Files whose content doesn't match their extension, for `--verify-types`.
+    pub mismatched: Vec<Mismatch>,
+    /// Extensions catalogued in more than one casing, sorted.
+    pub case_variants: Vec<CaseVariants>,
+    /// Extended attribute names with how many files carry each, most common first.
+    pub xattrs: Vec<(String, u64)>,
+    /// Files whose extended attributes could not be read.
+    pub xattr_failures: u64,
+    /// Files and bytes per owning user with `--owner`, biggest first.
+    pub owners: Vec<OwnerTotal>,
+    /// Entries catalogued from inside archives, with `--archives`.
+    pub archive_entries: u64,
+    /// Entries catalogued per group (extension, unless `--group-by`), for the
+    /// summary printed after a scan.
+    pub groups: HashMap<String, u64>,
+    /// Directories whose contents came from `--cache` instead of being listed.
+    pub cached_dirs: u64,
+    /// Directory listings and metadata reads that failed at first and were
+    /// tried again, with `--retries`.
+    pub retried: u64,
+    /// Where the walk was when Ctrl-C stopped it (relative to the root); the
+    /// catalog then covers only what came before.
+    pub interrupted: Option<PathBuf>,
+    /// Whether it was `--timeout` rather than Ctrl-C that stopped the walk at
+    /// `interrupted`.
+    pub timed_out: bool,
+    /// The last entry catalogued before `--limit` stopped the walk (relative to
+    /// the root).
+    pub truncated: Option<PathBuf>,
+    /// Folders given that were left to another one covering them, as
+    /// `(folder, covered by)`.
+    pub merged_roots: Vec<(PathBuf, PathBuf)>,
+    /// Executable files, with `--executables` or `--group-by exec`.
+    pub executables: u64,
+    /// Bytes allocated on disk, with `--disk-usage`: additional hardlinks are left
+    /// out, and files whose allocation is unknown count at their apparent size.
+    pub allocated_size: u64,
+    /// Files counted at their apparent size because allocation was unknown.
+    pub allocation_estimated: u64,
+    /// Files per git status, with `--git-status` inside a repository.
+    pub git: Option<GitCounts>,
+    /// Files per detected encoding, most common first, with `--detect-encoding`.
+    pub encodings: Vec<(TextEncoding, u64)>,
+    /// Text files that aren't UTF-8, sorted by path, with `--detect-encoding`.
+    pub non_utf8: Vec<(PathBuf, TextEncoding)>,
+}
+
+/// An entry skipped because it couldn't be read.
+#[derive(Clone)]
+pub struct ScanError {
+    /// Relative to the root (`None` if the walker couldn't say which entry failed).
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+/// The casings one extension was found in, e.g. `.jpg` as `.jpg` and `.JPG`.
+#[derive(Clone)]
+pub struct CaseVariants {
+    /// The lowercase extension key.
+    pub ext: String,
+    /// Each spelling with its file count, most common first.
+    pub spellings: Vec<(String, u64)>,
+}
+
+/// A file whose detected content type doesn't fit its extension.
+#[derive(Clone)]
+pub struct Mismatch {
+    pub path: PathBuf,
+    pub ext: String,
+    pub detected: &'static str,
+}
+
+/// One of the biggest files, for `--largest`.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LargeFile {
+    pub size: u64,
+    /// Ties in size rank by path, earliest first.
+    pub path: Reverse<PathBuf>,
+    pub ext: String,
+}
+
+impl ScanStats {
+    /// Zeroed stats for a scan starting now.
+    pub fn new() -> Self {
+        ScanStats {
+            started: SystemTime::now(),
+            walk_time: Duration::ZERO,
+            files: 0,
+            dirs: 0,
+            skipped: 0,
+            errors: Vec::new(),
+            symlinks: 0,
+            link_loops: 0,
+            broken_links: 0,
+            mount_points: Vec::new(),
+            virtual_mounts: Vec::new(),
+            total_size: 0,
+            hardlinked: 0,
+            hardlinked_size: 0,
+            hardlinks: Vec::new(),
+            filtered: 0,
+            ignored: 0,
+            pruned: 0,
+            junk_pruned: 0,
+            outputs_excluded: 0,
+            empty: 0,
+            dropped: 0,
+            hash_failures: 0,
+            hash_skipped: 0,
+            perms_unknown: 0,
+            duplicates: Vec::new(),
+            empty_dirs: Vec::new(),
+            largest: Vec::new(),
+            mismatched: Vec::new(),
+            case_variants: Vec::new(),
+            owners: Vec::new(),
+            xattrs: Vec::new(),
+            xattr_failures: 0,
+            archive_entries: 0,
+            groups: HashMap::new(),
+            cached_dirs: 0,
+            retried: 0,
+            interrupted: None,
+            timed_out: false,
+            truncated: None,
+            merged_roots: Vec::new(),
+            executables: 0,
+            allocated_size: 0,
+            allocation_estimated: 0,
+            git: None,
+            encodings: Vec::new(),
+            non_utf8: Vec::new(),
+        }
+    }
+
+    /// Count an entry (by relative path) as skipped and remember why.
+    fn record_error(&mut self, path: Option<PathBuf>, message: String) {
+        self.skipped += 1;
+        self.errors.push(ScanError { path, message });
+    }
+}
+
+/// A directory entry as produced by the walker, carrying per-directory
+/// [`DirState`] and, for files, metadata prefetched on the worker that read the
+/// directory.
+type Entry = DirEntry<(DirState, Option<Metadata>)>;
+
+/// State handed down from each directory the walker reads to its children.
+#[derive(Clone, Default, Debug)]
+struct DirState {
+    /// Ignore rules in effect, with `--gitignore` or `.creepignore` files.
+    ignore: IgnoreStack,
+    /// Canonical paths of the directory and everything above it, with
+    /// `--follow-symlinks`, to spot links that lead back up.
+    ancestors: Ancestors,
+}
+
+/// An immutable list of canonical directory paths, innermost first, shared
+/// between sibling directories.
+#[derive(Clone, Default, Debug)]
+struct Ancestors(Option<Arc<(PathBuf, Ancestors)>>);
+
+impl Ancestors {
+    fn push(&mut self, dir: PathBuf) {
+        *self = Ancestors(Some(Arc::new((dir, self.clone()))));
+    }
+
+    fn contains(&self, dir: &Path) -> bool {
+        let mut next = &self.0;
+        while let Some(layer) = next {
+            if layer.0 == dir {
+                return true;
+            }
+            next = &layer.1.0;
+        }
+        false
+    }
 }
 
 /// A single catalogued file.
 pub struct FileEntry {
     pub path: PathBuf,
     pub size: Option<u64>,
+    /// Bytes allocated on disk, with `--disk-usage` (`None` if the platform or
+    /// filesystem doesn't say).
+    pub allocated: Option<u64>,
+    /// Lowercase hex content digest, when hashing was requested and succeeded.
+    pub digest: Option<String>,
+    /// Left unhashed because it is larger than `--hash-max-size`.
+    pub too_large: bool,
+    /// Where a symlink in [`SYMLINK_GROUP`] points, as written, if it could be read.
+    pub target: Option<PathBuf>,
+    /// A symlink whose target doesn't resolve (missing, a cycle, or unreachable).
+    pub broken_link: bool,
+    /// A symlink's canonical target, with `--resolve-links`.
+    pub resolved: Option<PathBuf>,
+    /// Detected MIME type, with `--show-mime`.
+    pub mime: Option<&'static str>,
+    /// Last modification time, with `--mtime` (`None` if it could not be read).
+    pub modified: Option<SystemTime>,
+    /// Creation time, with `--created` (`None` where the platform or filesystem
+    /// doesn't record one).
+    pub created: Option<SystemTime>,
+    /// Line count, with `--lines` (`None` for binary or unreadable files).
+    pub lines: Option<u64>,
+    /// Text encoding, with `--detect-encoding` (`None` if the file couldn't be read).
+    pub encoding: Option<TextEncoding>,
+    /// Permissions, with `--perms` (`None` if the metadata could not be read).
+    pub permissions: Option<Permissions>,
+    /// Owning user and group, with `--owner` (`None` if the metadata could not be read).
+    pub owner: Option<Owner>,
+    /// Windows attribute bits, with `--attributes` (`None` if they couldn't be read).
+    pub attributes: Option<u32>,
+    /// Whether the file is executable, with `--executables` or `--group-by exec`
+    /// (`None` if unknown).
+    pub executable: Option<bool>,
+    /// An additional hardlink to content catalogued under another path.
+    pub hardlink: bool,
+    /// An entry inside an archive (`--archives`), with no file of its own to read.
+    pub archived: bool,
+    /// Extended attributes, with `--xattrs` (empty if there are none).
+    pub xattrs: Vec<Xattr>,
+    /// State in the enclosing git repository, with `--git-status`.
+    pub git: Option<GitStatus>,
 }
 
-/// Walk `root` recursively, invoking `on_file(ext, relative_path, size)` for each
-/// included file, and return the resulting [`ScanStats`].
+impl FileEntry {
+    /// An entry for a file (or directory) at `path` relative to the root.
+    pub fn new(path: PathBuf, size: Option<u64>) -> Self {
+        FileEntry {
+            path,
+            size,
+            allocated: None,
+            digest: None,
+            too_large: false,
+            target: None,
+            broken_link: false,
+            resolved: None,
+            mime: None,
+            modified: None,
+            created: None,
+            lines: None,
+            encoding: None,
+            permissions: None,
+            owner: None,
+            attributes: None,
+            executable: None,
+            hardlink: false,
+            archived: false,
+            xattrs: Vec::new(),
+            git: None,
+        }
+    }
+}
+
+/// Walk `root` recursively, invoking `on_file(group, entry)` for each included file
+/// (and, with `--entry-types`, each directory and symlink), and return the
+/// resulting [`ScanStats`].
 ///
-/// Directory reads run in parallel on a thread pool via `jwalk`. Inaccessible
-/// directories/entries are skipped (counted, and warned about unless `--quiet`)
-/// rather than aborting the scan. By default symlinks/junctions are not followed;
-/// with `--follow-symlinks` jwalk follows them and reports loops as errors.
-pub fn walk<F>(root: &Path, options: &ScanOptions, filter: &ScanFilter, mut on_file: F) -> ScanStats
+/// Directory reads (and the metadata calls for their files) run in parallel on a
+/// work-stealing thread pool via `jwalk`, or serially with `--threads 1`; entries
+/// still arrive depth first with each directory's entries sorted (see
+/// [`walk_key`]), so files come in catalog order and the result depends neither
+/// on the thread count nor on the order the filesystem lists them in.
+/// Inaccessible directories/entries are skipped (recorded in
+/// [`ScanStats::errors`], and warned about unless `--quiet`) rather than aborting
+/// the scan; with `--fail-fast` the walk stops at the first one. By default symlinks/junctions are not followed;
+/// with `--follow-symlinks` directory links are descended, except those whose
+/// canonical target is the directory holding them or one above it, which are
+/// counted as loops instead; files reached through a link keep paths under the
+/// link's name. Unless `--quiet`, an interactive stderr shows a progress line
+/// until the walk is done. Entries
+/// matched by `.creepignore` files (and `.gitignore` with `--gitignore`, which also
+/// skips `.git`) are pruned like excludes, as are hidden entries with
+/// `--hidden exclude` (the root is always scanned).
+pub fn walk<F>(roots: &Roots, options: &ScanOptions, filter: &ScanFilter, on_file: F) -> ScanStats
 where
-    F: FnMut(String, PathBuf, Option<u64>),
+    F: FnMut(String, FileEntry),
 {
-    let mut stats = ScanStats::default();
+    walk_from(roots, options, filter, None, on_file)
+}
 
-    let mut walker = WalkDir::new(root)
-        .skip_hidden(false)
-        .follow_links(options.follow_symlinks);
+/// [`walk`] with `--checkpoint`: progress is saved to `checkpoint` as the walk
+/// goes, and with `resume` the walk carries on where an earlier run of the same
+/// scan stopped. The entries that run catalogued are passed to `on_file` first,
+/// then the walk skips what it had done and goes on from there.
+pub fn walk_checkpointed<F>(
+    roots: &Roots,
+    options: &ScanOptions,
+    filter: &ScanFilter,
+    checkpoint: &Checkpoint,
+    resume: Option<Resume>,
+    on_file: F,
+) -> ScanStats
+where
+    F: FnMut(String, FileEntry),
+{
+    walk_from(roots, options, filter, Some((checkpoint, resume)), on_file)
+}
 
-    if let Some(n) = options.threads {
-        walker = walker.parallelism(Parallelism::RayonNewPool(n.max(1)));
-    }
-    if let Some(depth) = options.max_depth {
-        walker = walker.max_depth(depth);
+fn walk_from<F>(
+    roots: &Roots,
+    options: &ScanOptions,
+    filter: &ScanFilter,
+    checkpoint: Option<(&Checkpoint, Option<Resume>)>,
+    mut on_file: F,
+) -> ScanStats
+where
+    F: FnMut(String, FileEntry),
+{
+    let (checkpoint, resume) = match checkpoint {
+        Some((checkpoint, resume)) => (Some(checkpoint), resume),
+        None => (None, None),
+    };
+    let walk_started = Instant::now();
+    let mut stats = ScanStats::new();
+    stats.merged_roots.clone_from(&roots.merged);
+    if let Some(timeout) = options.timeout {
+        interrupt::start_deadline(timeout);
     }
+    let list_files = options.entry_types.contains(&EntryType::File);
+    let list_dirs = options.entry_types.contains(&EntryType::Dir);
+    let list_links = options.entry_types.contains(&EntryType::Symlink);
+    let mut tree = options.empty_dirs.map(|_| DirTracker::default());
+    // A min-heap of the biggest files so far, never more than `--largest` long.
+    let mut largest: BinaryHeap<Reverse<LargeFile>> = BinaryHeap::new();
+    // Lowercase extension -> literal spelling -> files, for the case-variants report.
+    let mut casings: HashMap<String, HashMap<String, u64>> = HashMap::new();
+    let mut names = options.owner.then(owner::Names::default);
+    // User -> (files, bytes), for the --owner summary.
+    let mut owned: HashMap<String, (u64, u64)> = HashMap::new();
+    let mut links = options.tracks_hardlinks().then(hardlinks::Tracker::default);
+    let mut xattr_counts: HashMap<String, u64> = HashMap::new();
+    let gits: Vec<Option<Statuses>> = roots
+        .folders
+        .iter()
+        .map(|folder| {
+            if !options.git_status {
+                return None;
+            }
+            match Statuses::load(folder) {
+                Ok(statuses) => Some(statuses),
+                Err(e) => {
+                    if !options.quiet {
+                        eprintln!("Warning: --git-status ignored for '{}': {e}", folder.display());
+                    }
+                    None
+                }
+            }
+        })
+        .collect();
+    let mut git_counts = GitCounts::default();
 
-    // Prune excluded paths during traversal so we never descend into them.
-    if let Some(exclude) = filter.exclude() {
-        let exclude = Arc::clone(exclude);
-        let root_buf = root.to_path_buf();
-        walker = walker.process_read_dir(move |_depth, _path, _state, children| {
-            children.retain(|res| match res {
-                Ok(child) => !path_excluded(&exclude, &root_buf, &child.path(), &child.file_name),
-                Err(_) => true,
-            });
-        });
+    let mut counters = Counters::default();
+    if checkpoint.is_some() {
+        counters.unsettled = Some(Arc::default());
     }
-
-    for entry in walker {
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(e) => {
-                stats.skipped += 1;
-                if !options.quiet {
-                    match e.path() {
-                        Some(p) => eprintln!("Warning: skipping '{}': {e}", p.display()),
-                        None => eprintln!("Warning: skipping entry: {e}"),
+    // Where a resumed walk carries on: the folder, and the last entry done in it.
+    let position = resume.as_ref().map(|resume| (resume.folder, resume.position.clone(), resume.dir));
+    let mut groups: HashMap<String, u64> = HashMap::new();
+    let mut count_group = |group: &String| match groups.get_mut(group) {
+        Some(n) => *n += 1,
+        None => {
+            groups.insert(group.clone(), 1);
+        }
+    };
+    if let Some(resume) = resume {
+        for (group, entry) in resume.entries {
+            count_group(&group);
+            on_file(group, entry);
+        }
+        if let Some(links) = &mut links {
+            for (id, path, size) in resume.hardlinks {
+                links.record(id, path, size);
+            }
+        }
+        let started = stats.started;
+        stats = resume.stats;
+        stats.started = started;
+        stats.merged_roots.clone_from(&roots.merged);
+        let tallies = resume.tallies;
+        largest = tallies.largest.into_iter().map(Reverse).collect();
+        casings = tallies.casings;
+        owned = tallies.owned;
+        xattr_counts = tallies.xattrs;
+        git_counts = tallies.git;
+        let totals = [&counters.ignored, &counters.pruned, &counters.junk_pruned, &counters.link_loops];
+        for (counter, total) in totals.into_iter().zip(tallies.counters) {
+            counter.store(total, Ordering::Relaxed);
+        }
+        if let Ok(mut mount_points) = counters.mount_points.lock() {
+            *mount_points = tallies.mount_points;
+        }
+        if let Ok(mut virtual_mounts) = counters.virtual_mounts.lock() {
+            *virtual_mounts = tallies.virtual_mounts;
+        }
+    }
+    let resume_at = position
+        .as_ref()
+        .and_then(|(index, position, dir)| Some((*index, walk_key(&roots.folders.get(*index)?.join(position), *dir))));
+    let cache = options
+        .cache
+        .as_deref()
+        .map(|path| Arc::new(CacheRun::load(path, options.cache_trust, options.quiet)));
+    // Entries catalogued so far and the latest one's path, for `--limit`.
+    let catalogued = Cell::new(0u64);
+    let latest = RefCell::new(PathBuf::new());
+    // Why an entry found was left out of the catalog, at `-vv`.
+    let left_out = |path: &Path, why: &str| {
+        if options.verbose >= 2 {
+            eprintln!("Leaving out '{}': {why}", path.display());
+        }
+    };
+    let mut on_file = |group: String, entry: FileEntry| {
+        catalogued.set(catalogued.get() + 1);
+        if options.limit.is_some() {
+            latest.replace(entry.path.clone());
+        }
+        if let Some(checkpoint) = checkpoint {
+            checkpoint.entry(&group, &entry);
+        }
+        count_group(&group);
+        on_file(group, entry);
+    };
+    let room = || options.limit.map_or(u64::MAX, |limit| limit.saturating_sub(catalogued.get()));
+    let resumed = position.as_ref().map(|(index, position, dir)| (*index, position.as_path(), *dir));
+    let mut entries = Entries::new(roots, options, filter, &counters, cache.clone(), resumed);
+    let mut progress = Progress::start(options.quiet);
+    let _walking = interrupt::Walking::start();
+    // The last entry taken on, by folder, and whether it is a directory, for
+    // `--checkpoint` marks.
+    let mut last: Option<(usize, PathBuf, bool)> = None;
+    for (index, item) in &mut entries {
+        let folder = &roots.folders[index];
+        let git = &gits[index];
+        // The directories holding the entry a resumed walk carries on after are
+        // listed again, for what is left in them; what comes before it is done.
+        if let Some((at_index, at)) = &resume_at
+            && *at_index == index
+            && item.path().is_some_and(|path| walk_key(&path, item.is_dir()) <= *at)
+        {
+            continue;
+        }
+        if interrupt::stopping() {
+            let at = item.path().unwrap_or_else(|| folder.clone());
+            stats.interrupted = Some(roots.relative(index, &at));
+            stats.timed_out = !interrupt::requested();
+            break;
+        }
+        if options.limit.is_some_and(|limit| catalogued.get() >= limit) {
+            stats.truncated = Some(latest.take());
+            break;
+        }
+        if let Some(checkpoint) = checkpoint {
+            if let Some((at_index, at, dir)) = &last
+                && checkpoint.due(stats.dirs)
+            {
+                let at_folder = &roots.folders[*at_index];
+                let (totals, mount_points, virtual_mounts) = counters.settled(Some((at_folder, &walk_key(at, *dir))));
+                checkpoint.mark(&Mark {
+                    folder: *at_index,
+                    position: at.strip_prefix(at_folder).unwrap_or(at),
+                    dir: *dir,
+                    stats: &stats,
+                    largest: &largest,
+                    casings: &casings,
+                    owned: &owned,
+                    xattrs: &xattr_counts,
+                    git: &git_counts,
+                    counters: totals,
+                    mount_points: &mount_points,
+                    virtual_mounts: &virtual_mounts,
+                });
+            }
+            if let Some(path) = item.path() {
+                last = Some((index, path, item.is_dir()));
+            }
+        }
+        if let Some(progress) = &mut progress {
+            progress.tick(stats.dirs, stats.files, || item.path().unwrap_or_default());
+        }
+        let (path, depth, file_name, prefetched) = match item {
+            Item::Cached(cached) => {
+                let relative = roots.relative(index, &cached.path);
+                if let Some(tree) = &mut tree {
+                    tree.record(&relative, cached.depth, cached.kind == Kind::Dir);
+                }
+                match cached.kind {
+                    Kind::File if list_files => {}
+                    Kind::Dir => {
+                        stats.dirs += 1;
+                        if options.verbose > 0 && roots.files.is_none() {
+                            eprintln!("Entering '{}' (listed from --cache)", cached.path.display());
+                        }
+                        if list_dirs {
+                            on_file(DIR_GROUP.to_string(), FileEntry::new(relative, None));
+                        }
+                        continue;
+                    }
+                    Kind::Symlink => {
+                        if list_links {
+                            on_file(SYMLINK_GROUP.to_string(), link_entry(relative, &cached.path, options));
+                        }
+                        stats.symlinks += 1;
+                        continue;
                     }
+                    _ => continue,
+                }
+                (cached.path, cached.depth, cached.name, None)
+            }
+            Item::Unreadable(path, e) => {
+                if !options.quiet {
+                    eprintln!("Warning: skipping '{}': {e}", path.display());
+                }
+                stats.record_error(Some(roots.relative(index, &path)), e.to_string());
+                if options.fail_fast {
+                    break;
                 }
                 continue;
             }
-        };
+            Item::Walked(entry) => {
+                let mut entry = match *entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        // A dangling link fails to resolve when following; it is still a symlink.
+                        if let Some(path) = e.path()
+                            && path.is_symlink()
+                            && !path.exists()
+                        {
+                            stats.symlinks += 1;
+                            stats.broken_links += 1;
+                            if list_links {
+                                on_file(SYMLINK_GROUP.to_string(), link_entry(roots.relative(index, path), path, options));
+                            } else if !options.quiet {
+                                eprintln!("Warning: broken symlink '{}'", path.display());
+                            }
+                            continue;
+                        }
+                        // jwalk catches a link written as the absolute path of an ancestor.
+                        if let (Some(path), Some(ancestor)) = (e.path(), e.loop_ancestor()) {
+                            stats.symlinks += 1;
+                            stats.link_loops += 1;
+                            if list_links {
+                                on_file(SYMLINK_GROUP.to_string(), link_entry(roots.relative(index, path), path, options));
+                            }
+                            if !options.quiet {
+                                eprintln!(
+                                    "Warning: not following '{}': symlink loop back to '{}'",
+                                    path.display(),
+                                    ancestor.display()
+                                );
+                            }
+                            continue;
+                        }
+                        if !options.quiet {
+                            match e.path() {
+                                Some(p) => eprintln!("Warning: skipping '{}': {e}", p.display()),
+                                None => eprintln!("Warning: skipping entry: {e}"),
+                            }
+                        }
+                        let message = e.io_error().map_or_else(|| e.to_string(), ToString::to_string);
+                        stats.record_error(e.path().map(|p| roots.relative(index, p)), message);
+                        if options.fail_fast {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+
+                if let Some(tree) = &mut tree {
+                    tree.record(&roots.relative(index, &entry.path()), entry.depth, entry.file_type().is_dir());
+                }
 
-        // A symlink we're not following is neither descended nor catalogued.
-        if entry.path_is_symlink() && !options.follow_symlinks {
-            stats.symlinks += 1;
+                // A symlink we're not following is neither descended nor catalogued as
+                // its target; with `--entry-types symlink` it is listed as a link.
+                if entry.path_is_symlink() {
+                    if list_links {
+                        on_file(SYMLINK_GROUP.to_string(), link_entry(roots.relative(index, &entry.path()), &entry.path(), options));
+                    }
+                    let looped = entry.file_type().is_dir() && entry.read_children_path.is_none();
+                    if !options.follow_symlinks || looped {
+                        stats.symlinks += 1;
+                        continue;
+                    }
+                }
+
+                let file_type = entry.file_type();
+                if file_type.is_dir() {
+                    stats.dirs += 1;
+                    if options.verbose > 0 {
+                        eprintln!("Entering '{}'", entry.path().display());
+                    }
+                    // The directory itself is listed, but what's inside it is missing.
+                    if let Some(e) = entry.read_children_error.take() {
+                        let path = entry.path();
+                        if !options.quiet {
+                            eprintln!("Warning: skipping '{}': {e}", path.display());
+                        }
+                        let message = e.io_error().map_or_else(|| e.to_string(), ToString::to_string);
+                        stats.record_error(Some(roots.relative(index, &path)), message);
+                        if options.fail_fast {
+                            break;
+                        }
+                    }
+                    if list_dirs && entry.depth > 0 {
+                        let path = entry.path();
+                        on_file(DIR_GROUP.to_string(), FileEntry::new(roots.relative(index, &path), None));
+                    }
+                    continue;
+                }
+                if !file_type.is_file() || !list_files {
+                    continue;
+                }
+                (entry.path(), entry.depth, entry.file_name, entry.client_state)
+            }
+        };
+        if options.min_depth.is_some_and(|min| depth < min) {
+            left_out(&path, "shallower than --min-depth");
+            stats.filtered += 1;
             continue;
         }
 
-        let file_type = entry.file_type();
-        if file_type.is_dir() {
-            stats.dirs += 1;
+        let relative_path = roots.relative(index, &path);
+        if filter.is_own_output(&relative_path) {
+            left_out(&path, "an output file of this scan");
+            stats.outputs_excluded += 1;
             continue;
         }
-        if !file_type.is_file() {
+        let ext = group_key(&path, options);
+        // `--preserve-case` groups by the literal spelling, but filters and type
+        // checks still see the folded, aliased key.
+        let folded = options
+            .preserve_case
+            .then(|| options.aliases.canonical(ext.to_lowercase()));
+        let key = folded.as_deref().unwrap_or(&ext);
+        let included = filter
+            .include()
+            .is_none_or(|include| glob_matches(include, folder, &path, &file_name, false))
+            && (!filter.has_regex() || filter.regex_allowed(&slash_path(folder, &path)))
+            && (filter.substrings().is_empty()
+                || filter.substrings().allows(&slash_path(folder, &path)));
+        if !included || !filter.ext_allowed(key) {
+            left_out(&path, if included { "extension not selected" } else { "path not matched by the include filters" });
+            stats.filtered += 1;
+            // `--ext md --archives zip` still finds the Markdown inside zips.
+            if included
+                && let Some(kind) = archive::kind(&path, &options.archives)
+                && !catalog_archive(&path, (kind, relative_path), options, filter, room(), &mut stats, &mut on_file)
+                && options.fail_fast
+            {
+                break;
+            }
             continue;
         }
-
-        let path = entry.path();
-        let ext = extension_key(&path);
-        if !filter.ext_allowed(&ext) {
+        let attrs_bits = if options.attributes || filter.has_attr_filter() {
+            attributes::read(&path)
+        } else {
+            None
+        };
+        // Unreadable attributes count as none set.
+        if filter.has_attr_filter() && !filter.attributes_allowed(attrs_bits.unwrap_or(0)) {
+            left_out(&path, "attributes not selected");
+            stats.filtered += 1;
             continue;
         }
 
-        let size = if options.sizes {
-            entry.metadata().ok().map(|m| m.len())
+        // Size, emptiness and date filters need every file's metadata; otherwise it is only
+        // read when sizes, times or permissions were requested.
+        let metadata = if options.needs_metadata() || filter.needs_metadata() {
+            // A failed prefetch is retried here, for its error.
+            let (metadata, retried) = match prefetched {
+                Some(metadata) => (Ok(metadata), false),
+                None => retry::run(options.retries, options.retry_delay, || fs::metadata(&path)),
+            };
+            if retried {
+                stats.retried += 1;
+            }
+            match metadata {
+                Ok(metadata) => Some(metadata),
+                Err(e) if filter.has_size_range() => {
+                    if !options.quiet {
+                        eprintln!("Warning: cannot read size of '{}': {e}", path.display());
+                    }
+                    stats.record_error(Some(roots.relative(index, &path)), format!("cannot read size: {e}"));
+                    if options.fail_fast {
+                        break;
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    if options.perms {
+                        stats.perms_unknown += 1;
+                    }
+                    if filter.has_time_range() && !options.quiet {
+                        eprintln!(
+                            "Warning: cannot read modification time of '{}' ({e}); including it",
+                            path.display()
+                        );
+                    }
+                    None
+                }
+            }
         } else {
             None
         };
+        if let Some(metadata) = &metadata {
+            if filter.skip_empty() && metadata.len() == 0 {
+                left_out(&path, "empty (--skip-empty)");
+                stats.empty += 1;
+                continue;
+            }
+            if !filter.size_allowed(metadata.len()) {
+                left_out(&path, "size outside --min-size/--max-size");
+                stats.filtered += 1;
+                continue;
+            }
+            if filter.has_time_range() {
+                match checked_mtime(metadata, stats.started) {
+                    Ok(mtime) if !filter.time_allowed(mtime) => {
+                        left_out(&path, "modified outside --newer-than/--older-than");
+                        stats.filtered += 1;
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(problem) if !options.quiet => eprintln!(
+                        "Warning: '{}' has {problem}; including it",
+                        path.display()
+                    ),
+                    Err(_) => {}
+                }
+            }
+        }
+        let modified = metadata
+            .as_ref()
+            .filter(|_| options.mtime)
+            .and_then(|m| m.modified().ok());
+        let created = metadata
+            .as_ref()
+            .filter(|_| options.created)
+            .and_then(|m| m.created().ok());
+        let permissions = metadata
+            .as_ref()
+            .filter(|_| options.perms)
+            .map(|m| m.permissions());
+        let file_owner = metadata
+            .as_ref()
+            .zip(names.as_mut())
+            .and_then(|(m, names)| names.owner(m));
+        let executable = options
+            .tracks_executables()
+            .then(|| exec::is_executable(&path, metadata.as_ref()))
+            .flatten();
+        let metadata_size = metadata.as_ref().map(|m| m.len());
+        let size = metadata_size.filter(|_| options.sizes);
+
+        // Content sniffing reads the file, so it runs after every cheaper filter.
+        let sniff_filter = filter.only().is_some() || filter.has_mime();
+        let by_mime = options.group_by == GroupBy::Mime;
+        let sample = if sniff_filter || options.show_mime || by_mime || options.verify_types {
+            match sniff::Sample::read(&path) {
+                Ok(sample) => Some(sample),
+                Err(e) => {
+                    if !options.quiet {
+                        eprintln!("Warning: cannot read '{}' to classify it: {e}", path.display());
+                    }
+                    if sniff_filter {
+                        stats.record_error(Some(roots.relative(index, &path)), format!("cannot read to classify: {e}"));
+                        if options.fail_fast {
+                            break;
+                        }
+                        continue;
+                    }
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if let Some(sample) = &sample {
+            let wrong_kind = filter
+                .only()
+                .is_some_and(|only| sample.is_binary() != (only == ContentKind::Binary));
+            if wrong_kind || !filter.mime_allowed(sample.mime_type()) {
+                left_out(&path, if wrong_kind { "content kind not selected (--only)" } else { "MIME type not selected" });
+                stats.filtered += 1;
+                continue;
+            }
+        }
+        if options.verify_types
+            && let Some(detected) = sample.as_ref().and_then(|s| s.detected_type())
+            && !sniff::type_matches_extension(key, detected)
+        {
+            stats.mismatched.push(Mismatch { path: relative_path.clone(), ext: ext.clone(), detected });
+        }
+        if options.case_variants {
+            let literal = extension_key(&path, &options.compound_exts, true);
+            *casings.entry(literal.to_lowercase()).or_default().entry(literal).or_default() += 1;
+        }
+        let archive = archive::kind(&path, &options.archives).map(|kind| (kind, relative_path.clone()));
+        // An unreadable file stays under its extension when grouping by type.
+        let group = match &sample {
+            Some(sample) if by_mime => sample.mime_type().to_string(),
+            _ if options.group_by == GroupBy::ExifDate => {
+                exif::date_group(&path, key, metadata.as_ref(), options)
+            }
+            _ if options.group_by == GroupBy::Exec => exec::group(executable),
+            _ if options.shebang && ext.is_empty() => shebang::group(&path).unwrap_or(ext),
+            _ => ext,
+        };
+        let mime = sample.filter(|_| options.show_mime).map(|s| s.mime_type());
         if let Some(s) = size {
             stats.total_size += s;
         }
+        // Recorded only once the file is sure to be catalogued, so the first link
+        // counted is always one that is listed.
+        let hardlink = match (&mut links, &metadata) {
+            (Some(links), Some(metadata)) => hardlinks::file_id(&path, metadata).is_some_and(|id| {
+                if let Some(checkpoint) = checkpoint {
+                    checkpoint.hardlink(id, &relative_path, metadata.len());
+                }
+                links.record(id, relative_path.clone(), metadata.len())
+            }),
+            _ => false,
+        };
+        if hardlink {
+            stats.hardlinked += 1;
+            stats.hardlinked_size += size.unwrap_or(0);
+        }
+        let allocated = metadata
+            .as_ref()
+            .filter(|_| options.disk_usage)
+            .and_then(|m| diskusage::allocated(&path, m));
+        if options.disk_usage
+            && !hardlink
+            && let Some((bytes, estimated)) = diskusage::on_disk(allocated, size)
+        {
+            stats.allocated_size += bytes;
+            stats.allocation_estimated += u64::from(estimated);
+        }
+
+        let attrs = if options.xattrs {
+            match xattrs::read(&path, options.xattr_values) {
+                Ok(attrs) => attrs,
+                Err(e) => {
+                    stats.xattr_failures += 1;
+                    if !options.quiet {
+                        eprintln!(
+                            "Warning: cannot read extended attributes of '{}': {e}",
+                            path.display()
+                        );
+                    }
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        for attr in &attrs {
+            *xattr_counts.entry(attr.name.clone()).or_default() += 1;
+        }
+
+        if let (Some(n), Some(file_size)) = (options.largest, metadata_size) {
+            largest.push(Reverse(LargeFile {
+                size: file_size,
+                path: Reverse(relative_path.clone()),
+                ext: group_key(&relative_path, options),
+            }));
+            if largest.len() > n {
+                largest.pop();
+            }
+        }
+
+        if let Some(file_owner) = &file_owner {
+            let totals = owned.entry(file_owner.user.clone()).or_default();
+            totals.0 += 1;
+            totals.1 += metadata_size.unwrap_or(0);
+        }
 
-        let relative_path = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        let git_status = git
+            .as_ref()
+            .and_then(|git| git.status(path.strip_prefix(folder).unwrap_or(&path)));
+        if let Some(status) = git_status {
+            git_counts[status as usize] += 1;
+        }
+
+        if executable == Some(true) {
+            stats.executables += 1;
+        }
         stats.files += 1;
-        on_file(ext, relative_path, size);
+        on_file(
+            group,
+            FileEntry {
+                mime,
+                modified,
+                created,
+                permissions,
+                owner: file_owner,
+                attributes: attrs_bits.filter(|_| options.attributes),
+                allocated,
+                executable,
+                hardlink,
+                xattrs: attrs,
+                git: git_status,
+                ..FileEntry::new(relative_path, size)
+            },
+        );
+
+        // Listed right after the archive, grouped by their own extensions.
+        if let Some(archive) = archive
+            && !catalog_archive(&path, archive, options, filter, room(), &mut stats, &mut on_file)
+            && options.fail_fast
+        {
+            break;
+        }
     }
 
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    stats.walk_time = walk_started.elapsed();
+    stats.groups = groups;
+    stats.retried += entries.retried;
+    // A walk cut short leaves the old cache in place rather than a partial one.
+    let finished = stats.interrupted.is_none()
+        && stats.truncated.is_none()
+        && (!options.fail_fast || stats.errors.is_empty());
+    // The last mark covers the whole walk, or where Ctrl-C stopped it. One cut
+    // short by `--fail-fast` resumes from the mark before, to meet its error again.
+    let end = match &last {
+        _ if finished => Some((roots.folders.len(), None)),
+        Some((index, at, dir)) if stats.interrupted.is_some() => Some((*index, Some((roots.folders[*index].as_path(), at.as_path(), *dir)))),
+        _ => None,
+    };
+    if let (Some(checkpoint), Some((index, through))) = (checkpoint, end) {
+        let key = through.map(|(_, at, dir)| walk_key(at, dir));
+        let (totals, mount_points, virtual_mounts) =
+            counters.settled(through.zip(key.as_deref()).map(|((folder, ..), key)| (folder, key)));
+        checkpoint.mark(&Mark {
+            folder: index,
+            position: through.map_or(Path::new(""), |(folder, at, _)| at.strip_prefix(folder).unwrap_or(at)),
+            dir: through.is_some_and(|(.., dir)| dir),
+            stats: &stats,
+            largest: &largest,
+            casings: &casings,
+            owned: &owned,
+            xattrs: &xattr_counts,
+            git: &git_counts,
+            counters: totals,
+            mount_points: &mount_points,
+            virtual_mounts: &virtual_mounts,
+        });
+    }
+    counters.settle(None);
+    stats.ignored = counters.ignored.load(Ordering::Relaxed);
+    stats.pruned = counters.pruned.load(Ordering::Relaxed);
+    stats.junk_pruned = counters.junk_pruned.load(Ordering::Relaxed);
+    stats.link_loops += counters.link_loops.load(Ordering::Relaxed);
+    stats.cached_dirs = entries.served;
+    if let (Some(cache), Some(path), true) = (&cache, &options.cache, finished)
+        && let Ok(new) = cache.new.lock()
+        && let Err(e) = new.save(path)
+        && !options.quiet
+    {
+        eprintln!("Warning: cannot write cache '{}': {e}", path.display());
+    }
+    if let Ok(mut mount_points) = counters.mount_points.lock() {
+        stats.mount_points = std::mem::take(&mut *mount_points);
+        stats.mount_points.sort();
+    }
+    if let Ok(mut virtual_mounts) = counters.virtual_mounts.lock() {
+        stats.virtual_mounts = std::mem::take(&mut *virtual_mounts);
+        stats.virtual_mounts.sort();
+    }
+    stats.mismatched.sort_by_cached_key(|mismatch| sort_key(&mismatch.path));
+    stats.largest = largest.into_sorted_vec().into_iter().map(|Reverse(file)| file).collect();
+    stats.case_variants = case_variants(casings);
+    stats.owners = owner::totals(owned);
+    stats.xattrs = xattrs::counts(xattr_counts);
+    stats.git = gits.iter().any(Option::is_some).then_some(git_counts);
+    if let Some(links) = links {
+        stats.hardlinks = links.groups();
+    }
+    if let (Some(tree), Some(mode)) = (tree, options.empty_dirs) {
+        stats.empty_dirs = tree.empty_dirs(&roots.base, mode);
+    }
     stats
 }
 
-/// Return true if the entry matches any exclude glob. Matched against both the bare
+/// Tallies kept by the walker threads while they prune, shared by every folder.
+#[derive(Clone, Default)]
+struct Counters {
+    ignored: Arc<AtomicU64>,
+    pruned: Arc<AtomicU64>,
+    junk_pruned: Arc<AtomicU64>,
+    link_loops: Arc<AtomicU64>,
+    /// Directories on another filesystem, with `--one-file-system`.
+    mount_points: Arc<Mutex<Vec<PathBuf>>>,
+    /// Virtual filesystems left out, unless `--include-virtual`.
+    virtual_mounts: Arc<Mutex<Vec<PathBuf>>>,
+    /// With `--checkpoint`, what was pruned but not yet passed by the walk, by
+    /// its key in walk order. The walker threads read ahead, and a resumed walk
+    /// judges everything after its last mark again, so a mark only counts what
+    /// came before it.
+    unsettled: Option<Arc<Mutex<Unsettled>>>,
+}
+
+/// Pruned entries by walk key, with their paths and why they were pruned.
+type Unsettled = BTreeMap<Vec<u8>, (PathBuf, Pruned)>;
+
+/// Why an entry was left out of the walk, for [`Counters`].
+#[derive(Clone, Copy)]
+enum Pruned {
+    Ignored,
+    Dir,
+    Junk,
+    Loop,
+    MountPoint,
+    Virtual,
+}
+
+impl Counters {
+    /// Count the entry at `path` as pruned.
+    fn count(&self, why: Pruned, path: &Path, is_dir: bool) {
+        if let Some(unsettled) = &self.unsettled
+            && let Ok(mut unsettled) = unsettled.lock()
+        {
+            unsettled.insert(walk_key(path, is_dir), (path.to_path_buf(), why));
+            return;
+        }
+        self.add(why, path);
+    }
+
+    fn add(&self, why: Pruned, path: &Path) {
+        let counter = match why {
+            Pruned::Ignored => &self.ignored,
+            Pruned::Dir => &self.pruned,
+            Pruned::Junk => &self.junk_pruned,
+            Pruned::Loop => &self.link_loops,
+            Pruned::MountPoint | Pruned::Virtual => {
+                let list = if matches!(why, Pruned::Virtual) { &self.virtual_mounts } else { &self.mount_points };
+                if let Ok(mut list) = list.lock() {
+                    list.push(path.to_path_buf());
+                }
+                return;
+            }
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count what was pruned up to `through` (the walk key of the last entry
+    /// walked in `folder`), or everything once the walk is done.
+    fn settle(&self, through: Option<(&Path, &[u8])>) {
+        let Some(Ok(mut unsettled)) = self.unsettled.as_ref().map(|unsettled| unsettled.lock()) else {
+            return;
+        };
+        unsettled.retain(|key, (path, why)| {
+            // Folders are walked one at a time, so any other folder's are done.
+            let passed = through.is_none_or(|(folder, at)| key.as_slice() <= at || !path.starts_with(folder));
+            if passed {
+                self.add(*why, path);
+            }
+            !passed
+        });
+    }
+
+    /// [`settle`](Self::settle), then the counts so far for a mark: ignored
+    /// entries, pruned and junk directories and symlink loops, then the mount
+    /// points and virtual filesystems left out.
+    fn settled(&self, through: Option<(&Path, &[u8])>) -> ([u64; 4], Vec<PathBuf>, Vec<PathBuf>) {
+        self.settle(through);
+        let totals = [&self.ignored, &self.pruned, &self.junk_pruned, &self.link_loops]
+            .map(|counter| counter.load(Ordering::Relaxed));
+        let mount_points = self.mount_points.lock().map(|m| m.clone()).unwrap_or_default();
+        let virtual_mounts = self.virtual_mounts.lock().map(|m| m.clone()).unwrap_or_default();
+        (totals, mount_points, virtual_mounts)
+    }
+}
+
+/// Decides which entries of one folder are walked: excluded, hidden, ignored and
+/// junk entries are pruned, and so are mount points with `--one-file-system`.
+/// Shared by the walker threads and the replay of cached listings, so both prune
+/// alike.
+struct Pruner {
+    root: PathBuf,
+    exclude: Option<Arc<GlobSet>>,
+    exclude_dir: Option<Arc<GlobSet>>,
+    substrings: Substrings,
+    skip_attr: Vec<FileAttribute>,
+    junk_dirs: Arc<Vec<String>>,
+    skip_hidden: bool,
+    gitignore: bool,
+    creepignore: bool,
+    quiet: bool,
+    verbose: u8,
+    /// The folder's device, with `--one-file-system`.
+    root_device: Option<u64>,
+    /// Mount points of virtual filesystems inside the folder, unless
+    /// `--include-virtual`.
+    virtual_mounts: Vec<PathBuf>,
+    /// The walk key of the last entry walked before a resumed walk of this
+    /// folder: everything up to it is done.
+    resume_at: Option<Vec<u8>>,
+    counters: Counters,
+}
+
+impl Pruner {
+    fn new(
+        root: &Path,
+        options: &ScanOptions,
+        filter: &ScanFilter,
+        counters: &Counters,
+        virtual_mounts: &[PathBuf],
+    ) -> Self {
+        Pruner {
+            root: root.to_path_buf(),
+            exclude: filter.exclude().cloned(),
+            exclude_dir: filter.exclude_dir().cloned(),
+            substrings: filter.substrings().clone(),
+            skip_attr: filter.skip_attr().to_vec(),
+            junk_dirs: Arc::clone(filter.junk_dirs()),
+            skip_hidden: options.hidden == Hidden::Exclude,
+            gitignore: options.gitignore,
+            creepignore: options.creepignore,
+            quiet: options.quiet,
+            verbose: options.verbose,
+            root_device: options.one_file_system.then(|| mounts::device(root)).flatten(),
+            // A folder that is itself one is scanned, as asked.
+            virtual_mounts: virtual_mounts
+                .iter()
+                .filter(|mount| mount.starts_with(root) && mount.as_path() != root)
+                .cloned()
+                .collect(),
+            resume_at: None,
+            counters: counters.clone(),
+        }
+    }
+
+    /// Add the ignore files in `dir` (where `has` says which names are present)
+    /// to the rules its entries are judged by.
+    fn enter(&self, dir: &Path, state: &mut DirState, has: impl Fn(&str) -> bool) {
+        if !self.gitignore && !self.creepignore {
+            return;
+        }
+        let mut files = Vec::new();
+        if self.gitignore && has(".gitignore") {
+            files.push(".gitignore");
+        }
+        if self.creepignore && has(CREEPIGNORE) {
+            files.push(CREEPIGNORE);
+        }
+        state.ignore.push_dir(dir, &files, self.gitignore && has(".git"), self.quiet);
+    }
+
+    /// Whether the entry `name` at `path` is walked, given the ignore rules of
+    /// the directory holding it.
+    fn keeps(&self, name: &OsStr, path: &Path, is_dir: bool, ignore: &IgnoreStack) -> bool {
+        if is_dir && self.junk_dirs.iter().any(|junk| name == junk.as_str()) {
+            self.counters.count(Pruned::Junk, path, is_dir);
+            return self.leave_out(path, "junk directory");
+        }
+        let excluded_dir = is_dir
+            && self
+                .exclude_dir
+                .as_ref()
+                .is_some_and(|names| names.is_match(Path::new(name)));
+        let excluded_dir = excluded_dir || (is_dir && self.substrings.rejects(&slash_path(&self.root, path)));
+        if excluded_dir
+            || self
+                .exclude
+                .as_ref()
+                .is_some_and(|exclude| glob_matches(exclude, &self.root, path, name, is_dir))
+        {
+            if is_dir {
+                self.counters.count(Pruned::Dir, path, is_dir);
+            }
+            return self.leave_out(path, "matched an exclude filter");
+        }
+        if self.skip_hidden && is_hidden(name, path) {
+            return self.leave_out(path, "hidden");
+        }
+        // Checked before descending, so `--skip-attr system` keeps the scan
+        // out of "System Volume Information" and the like.
+        if is_dir
+            && !self.skip_attr.is_empty()
+            && attributes::read(path)
+                .is_some_and(|bits| self.skip_attr.iter().any(|&a| attributes::has(bits, a)))
+        {
+            self.counters.count(Pruned::Dir, path, is_dir);
+            return self.leave_out(path, "has a --skip-attr attribute");
+        }
+        if is_dir && self.virtual_mounts.iter().any(|mount| mount == path) {
+            self.counters.count(Pruned::Virtual, path, is_dir);
+            return self.leave_out(path, "virtual filesystem");
+        }
+        if is_dir && mounts::leaves(self.root_device, path) {
+            self.counters.count(Pruned::MountPoint, path, is_dir);
+            return self.leave_out(path, "another filesystem (--one-file-system)");
+        }
+        // The ignore file itself is configuration, not content.
+        if self.creepignore && !is_dir && name == CREEPIGNORE {
+            return self.leave_out(path, "ignore file");
+        }
+        if (self.gitignore && name == ".git")
+            || ((self.gitignore || self.creepignore) && ignore.is_ignored(path, is_dir))
+        {
+            self.counters.count(Pruned::Ignored, path, is_dir);
+            return self.leave_out(path, "ignored by .gitignore or .creepignore");
+        }
+        true
+    }
+
+    /// Prune the entry at `path`, saying why at `-vv`. Always false, for
+    /// [`Pruner::keeps`] to return.
+    fn leave_out(&self, path: &Path, why: &str) -> bool {
+        if self.verbose >= 2 {
+            eprintln!("Leaving out '{}': {why}", path.display());
+        }
+        false
+    }
+
+    /// Whether the entry at `path` was already walked before this walk resumed,
+    /// along with everything under it: it comes before the entry the walk
+    /// resumes after, without holding it.
+    fn done(&self, path: &Path, is_dir: bool) -> bool {
+        self.resume_at.as_ref().is_some_and(|at| {
+            let key = walk_key(path, is_dir);
+            key < *at && !(is_dir && at.starts_with(&key))
+        })
+    }
+}
+
+/// `--cache` bookkeeping, shared with the walker threads.
+struct CacheRun {
+    /// Listings from the previous run.
+    old: Cache,
+    /// Whether unchanged directories may be served from `old`; off with
+    /// `--no-cache-trust`.
+    trust: bool,
+    /// Listings for the next run: every directory listed or served this time.
+    new: Mutex<Cache>,
+    /// Modification times of directories about to be listed, read beforehand so
+    /// that a change made during the listing is caught next time.
+    pending: Mutex<HashMap<PathBuf, SystemTime>>,
+    /// Directories left unlisted because their cached listing still holds, with
+    /// the state their entries are to be judged in.
+    served: Mutex<HashMap<PathBuf, DirState>>,
+}
+
+impl CacheRun {
+    /// The cache at `path`; one that can't be used is replaced by an empty one
+    /// with a warning, so the scan lists everything.
+    fn load(path: &Path, trust: bool, quiet: bool) -> Self {
+        let old = Cache::load(path).unwrap_or_else(|e| {
+            if !quiet {
+                eprintln!("Warning: ignoring cache '{}' ({e}); scanning everything", path.display());
+            }
+            Cache::default()
+        });
+        CacheRun {
+            old,
+            trust,
+            new: Mutex::default(),
+            pending: Mutex::default(),
+            served: Mutex::default(),
+        }
+    }
+
+    /// Whether `dir`, about to be walked, can be served from the cache instead
+    /// of listed: it is trusted and its modification time hasn't changed. If not,
+    /// its time is kept for [`record`](Self::record).
+    fn unchanged(&self, dir: &Path) -> bool {
+        let Ok(modified) = fs::metadata(dir).and_then(|m| m.modified()) else {
+            return false;
+        };
+        if self.trust && self.old.fresh(dir, modified).is_some() {
+            return true;
+        }
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(dir.to_path_buf(), modified);
+        }
+        false
+    }
+
+    /// Hand `dir` to the replay instead of the walker.
+    fn serve(&self, dir: PathBuf, state: &DirState) {
+        if let Ok(mut served) = self.served.lock() {
+            served.insert(dir, state.clone());
+        }
+    }
+
+    fn take_served(&self, dir: &Path) -> Option<DirState> {
+        self.served.lock().ok()?.remove(dir)
+    }
+
+    /// Remember the listing of `dir` just read, unless part of it couldn't be
+    /// read or named.
+    fn record(&self, dir: &Path, children: &[jwalk::Result<Entry>]) {
+        let Some(modified) = self.pending.lock().ok().and_then(|mut pending| pending.remove(dir)) else {
+            return;
+        };
+        let entries: Option<Vec<_>> = children
+            .iter()
+            .map(|child| {
+                let child = child.as_ref().ok()?;
+                let kind = if child.file_type.is_symlink() {
+                    Kind::Symlink
+                } else if child.file_type.is_dir() {
+                    Kind::Dir
+                } else if child.file_type.is_file() {
+                    Kind::File
+                } else {
+                    Kind::Other
+                };
+                Some((child.file_name.to_str()?.to_string(), kind))
+            })
+            .collect();
+        if let (Some(entries), Ok(mut new)) = (entries, self.new.lock()) {
+            new.insert(dir, Listing { modified, entries });
+        }
+    }
+
+    /// Add `dir`'s cached listing to the next run's cache and return it.
+    fn reuse(&self, dir: &Path) -> Option<Listing> {
+        let listing = self.old.get(dir)?.clone();
+        if let Ok(mut new) = self.new.lock() {
+            new.insert(dir, listing.clone());
+        }
+        Some(listing)
+    }
+}
+
+/// Where a walker starts: at a folder, or at a changed directory inside one
+/// served from the cache, taking over its depth and ignore rules.
+struct Start {
+    dir: PathBuf,
+    depth: usize,
+    state: Option<DirState>,
+}
+
+/// The walker for one folder (or part of it), set up to prune excluded, hidden
+/// and ignored entries, to prefetch metadata as the options require and, with
+/// `--cache`, to leave unchanged directories to the cache.
+fn build_walker(
+    start: Start,
+    pruner: &Arc<Pruner>,
+    options: &ScanOptions,
+    filter: &ScanFilter,
+    counters: &Counters,
+    cache: Option<&Arc<CacheRun>>,
+) -> WalkDirGeneric<(DirState, Option<Metadata>)> {
+    let mut walker = WalkDirGeneric::<(DirState, Option<Metadata>)>::new(&start.dir)
+        .skip_hidden(false)
+        .follow_links(options.follow_symlinks);
+
+    match options.threads {
+        Some(0 | 1) => walker = walker.parallelism(Parallelism::Serial),
+        Some(n) => walker = walker.parallelism(Parallelism::RayonNewPool(n)),
+        None => {}
+    }
+    if let Some(depth) = options.max_depth {
+        walker = walker.max_depth(depth - start.depth);
+    }
+
+    // Prune excluded, hidden and ignored paths during traversal so we never descend
+    // into them. Each directory's ignore rules are handed down to its children.
+    // Metadata calls dominate on slow (network) filesystems, so each file's is
+    // fetched by the worker that read its directory rather than one by one below.
+    let prefetch = options.needs_metadata() || filter.needs_metadata();
+    if prefetch
+        || options.follow_symlinks
+        || options.gitignore
+        || options.creepignore
+        || options.hidden == Hidden::Exclude
+        || filter.exclude().is_some()
+        || filter.exclude_dir().is_some()
+        || !filter.junk_dirs().is_empty()
+        || filter.substrings().has_exclusions()
+        || !filter.skip_attr().is_empty()
+        || options.one_file_system
+        || !pruner.virtual_mounts.is_empty()
+        || cache.is_some()
+        || pruner.resume_at.is_some()
+    {
+        let pruner = Arc::clone(pruner);
+        let cache = cache.cloned();
+        let quiet = options.quiet;
+        let counters = counters.clone();
+        let follow_symlinks = options.follow_symlinks;
+        let offset = start.depth;
+        let max_depth = options.max_depth;
+        match start.state {
+            Some(state) => walker = walker.root_read_dir_state(state),
+            None if options.gitignore => {
+                walker = walker.root_read_dir_state(DirState {
+                    ignore: IgnoreStack::for_root(&start.dir, quiet),
+                    ..DirState::default()
+                });
+            }
+            None => {}
+        }
+        walker = walker.process_read_dir(move |depth, dir, state, children| {
+            sort_children(children);
+            // jwalk first "reads" the root as its own only child; always keep it.
+            if depth.is_none() {
+                if let Some(cache) = &cache {
+                    for root in children.iter_mut().flatten() {
+                        if cache.unchanged(&root.path()) {
+                            root.read_children_path = None;
+                            cache.serve(root.path(), state);
+                        }
+                    }
+                }
+                return;
+            }
+            if follow_symlinks {
+                if let Ok(canonical) = fs::canonicalize(dir) {
+                    state.ancestors.push(canonical);
+                }
+                for child in children.iter_mut().flatten() {
+                    if child.path_is_symlink()
+                        && child.file_type.is_dir()
+                        && let Ok(target) = fs::canonicalize(child.path())
+                        && state.ancestors.contains(&target)
+                    {
+                        // Listed as a symlink below, but never descended.
+                        child.read_children_path = None;
+                        counters.count(Pruned::Loop, &child.path(), true);
+                        if !quiet {
+                            eprintln!(
+                                "Warning: not following '{}': symlink loop back to '{}'",
+                                child.path().display(),
+                                target.display()
+                            );
+                        }
+                    }
+                }
+            }
+            // The whole listing is cached, so changing the filters needs no rescan.
+            if let Some(cache) = &cache {
+                cache.record(dir, children);
+            }
+            pruner.enter(dir, state, |name| children.iter().flatten().any(|c| c.file_name == name));
+            children.retain(|res| {
+                let Ok(child) = res else { return true };
+                let path = child.path();
+                !pruner.done(&path, child.file_type.is_dir()) && pruner.keeps(&child.file_name, &path, child.file_type.is_dir(), &state.ignore)
+            });
+            if let Some(cache) = &cache {
+                for child in children.iter_mut().flatten() {
+                    if child.read_children_path.is_some()
+                        && max_depth.is_none_or(|max| child.depth + offset < max)
+                        && cache.unchanged(&child.path())
+                    {
+                        child.read_children_path = None;
+                        cache.serve(child.path(), state);
+                    }
+                }
+            }
+            if prefetch {
+                for child in children.iter_mut().flatten() {
+                    if child.file_type.is_file() {
+                        child.client_state = child.metadata().ok();
+                    }
+                }
+            }
+        });
+    } else {
+        walker = walker.process_read_dir(|_, _, _, children| sort_children(children));
+    }
+    walker
+}
+
+/// Put a directory's entries in walk order (see [`walk_key`]), with the ones
+/// that couldn't be read last.
+fn sort_children(children: &mut [jwalk::Result<Entry>]) {
+    children.sort_by_cached_key(|child| match child {
+        Ok(child) => (false, walk_key(Path::new(&child.file_name), child.file_type.is_dir())),
+        Err(_) => (true, Vec::new()),
+    });
+}
+
+/// What the walk yields: an entry a walker listed, one replayed from a
+/// directory served from `--cache` or listed by `--files-from`, or a listed path
+/// that couldn't be looked up.
+enum Item {
+    Walked(Box<jwalk::Result<Entry>>),
+    Cached(CachedEntry),
+    Unreadable(PathBuf, io::Error),
+}
+
+impl Item {
+    fn path(&self) -> Option<PathBuf> {
+        match self {
+            Item::Walked(walked) => match &**walked {
+                Ok(entry) => Some(entry.path()),
+                Err(e) => e.path().map(Path::to_path_buf),
+            },
+            Item::Cached(entry) => Some(entry.path.clone()),
+            Item::Unreadable(path, _) => Some(path.clone()),
+        }
+    }
+
+    /// Whether the entry is a directory, for its place in walk order. One that
+    /// couldn't be read is still taken for a directory; a listed path that
+    /// couldn't be looked up, for a file.
+    fn is_dir(&self) -> bool {
+        match self {
+            Item::Walked(walked) => walked.as_ref().as_ref().map_or(true, |entry| entry.file_type.is_dir()),
+            Item::Cached(entry) => entry.kind == Kind::Dir,
+            Item::Unreadable(..) => false,
+        }
+    }
+}
+
+/// An entry of a directory served from the cache.
+struct CachedEntry {
+    path: PathBuf,
+    depth: usize,
+    name: OsString,
+    kind: Kind,
+}
+
+/// Where [`Entries`] takes its next item from.
+enum Source {
+    /// A walker over part of folder `index`, whose depths start at `offset`.
+    Walker {
+        index: usize,
+        offset: usize,
+        iter: DirEntryIter<(DirState, Option<Metadata>)>,
+    },
+    /// The cached listing of `dir`, at `depth`, under the ignore rules in `state`.
+    Cached {
+        index: usize,
+        dir: PathBuf,
+        depth: usize,
+        state: DirState,
+        entries: std::vec::IntoIter<(String, Kind)>,
+    },
+    /// The `--files-from` paths, catalogued under folder `index`.
+    Listed {
+        index: usize,
+        files: std::vec::IntoIter<PathBuf>,
+    },
+}
+
+/// Every entry of every folder with its folder's index, in walk order: each
+/// folder's walker in turn, with the contents of a directory served from the
+/// cache spliced in right after the directory (and a fresh walker for any
+/// changed directory inside it).
+struct Entries<'a> {
+    roots: &'a Roots,
+    options: &'a ScanOptions,
+    filter: &'a ScanFilter,
+    counters: &'a Counters,
+    cache: Option<Arc<CacheRun>>,
+    pruners: Vec<Arc<Pruner>>,
+    next_folder: usize,
+    stack: Vec<Source>,
+    /// Directories served from the cache so far.
+    served: u64,
+    /// Directory listings that needed retries, with `--retries`.
+    retried: u64,
+}
+
+impl<'a> Entries<'a> {
+    fn new(
+        roots: &'a Roots,
+        options: &'a ScanOptions,
+        filter: &'a ScanFilter,
+        counters: &'a Counters,
+        cache: Option<Arc<CacheRun>>,
+        resume: Option<(usize, &Path, bool)>,
+    ) -> Self {
+        let virtual_mounts = if options.include_virtual { Vec::new() } else { mounts::virtual_mounts() };
+        let pruners = roots
+            .folders
+            .iter()
+            .enumerate()
+            .map(|(index, folder)| {
+                let mut pruner = Pruner::new(folder, options, filter, counters, &virtual_mounts);
+                if let Some((_, position, dir)) = resume.filter(|&(at, ..)| at == index) {
+                    pruner.resume_at = Some(walk_key(&folder.join(position), dir));
+                }
+                Arc::new(pruner)
+            })
+            .collect();
+        let next_folder = resume.map_or(0, |(index, ..)| index);
+        Entries { roots, options, filter, counters, cache, pruners, next_folder, stack: Vec::new(), served: 0, retried: 0 }
+    }
+
+    fn push_walker(&mut self, index: usize, start: Start) {
+        let offset = start.depth;
+        let walker = build_walker(
+            start,
+            &self.pruners[index],
+            self.options,
+            self.filter,
+            self.counters,
+            self.cache.as_ref(),
+        );
+        self.stack.push(Source::Walker { index, offset, iter: walker.into_iter() });
+    }
+
+    /// Replay the cached listing of `dir` next, judging its entries by the
+    /// ignore rules of `state` plus its own.
+    fn push_cached(&mut self, index: usize, dir: PathBuf, depth: usize, mut state: DirState) {
+        let Some(listing) = self.cache.as_ref().and_then(|cache| cache.reuse(&dir)) else {
+            return;
+        };
+        self.pruners[index].enter(&dir, &mut state, |name| listing.entries.iter().any(|(n, _)| n == name));
+        self.served += 1;
+        // Walked directories are listed in walk order; a cache written by an
+        // older version may not be.
+        let mut entries = listing.entries;
+        entries.sort_by_cached_key(|(name, kind)| walk_key(Path::new(name), *kind == Kind::Dir));
+        let entries = entries.into_iter();
+        self.stack.push(Source::Cached { index, dir, depth, state, entries });
+    }
+
+    /// List the directory `entry` again, up to `--retries` times while that
+    /// fails with a transient error, and walk it with a fresh walker once it can
+    /// be read. If every attempt fails, `entry` keeps the last one's error.
+    fn retry_listing(&mut self, index: usize, entry: &mut Entry) {
+        self.retried += 1;
+        let dir = entry.path();
+        let state = self.state_at(index, &dir);
+        for attempt in 1..=self.options.retries {
+            if !retry::pause(self.options.retry_delay, attempt) {
+                return;
+            }
+            let start = Start { dir: dir.clone(), depth: entry.depth, state: Some(state.clone()) };
+            let walker = build_walker(
+                start,
+                &self.pruners[index],
+                self.options,
+                self.filter,
+                self.counters,
+                self.cache.as_ref(),
+            );
+            let mut iter = walker.into_iter();
+            // The new walker yields the directory itself first; `entry` stands in
+            // for it.
+            let error = match iter.next() {
+                Some(Ok(mut root)) => root.read_children_error.take(),
+                Some(Err(e)) => Some(e),
+                None => None,
+            };
+            match error {
+                Some(e) if e.io_error().is_some_and(retry::is_transient) && attempt < self.options.retries => {}
+                Some(e) => {
+                    entry.read_children_error = Some(e);
+                    return;
+                }
+                None => {
+                    entry.read_children_error = None;
+                    let offset = entry.depth;
+                    self.stack.push(Source::Walker { index, offset, iter });
+                    return;
+                }
+            }
+        }
+    }
+
+    /// The state a walker starting at `dir` inside folder `index` takes over:
+    /// the ignore rules (and, with `--follow-symlinks`, the canonical paths) of
+    /// every directory from the folder down to `dir`'s parent.
+    fn state_at(&self, index: usize, dir: &Path) -> DirState {
+        let root = &self.roots.folders[index];
+        let mut state = DirState::default();
+        if self.options.gitignore {
+            state.ignore = IgnoreStack::for_root(root, self.options.quiet);
+        }
+        let Ok(below) = dir.strip_prefix(root) else {
+            return state;
+        };
+        let mut current = root.clone();
+        for component in below.components() {
+            if self.options.follow_symlinks
+                && let Ok(canonical) = fs::canonicalize(&current)
+            {
+                state.ancestors.push(canonical);
+            }
+            self.pruners[index].enter(&current, &mut state, |name| current.join(name).exists());
+            current.push(component);
+        }
+        state
+    }
+}
+
+impl Iterator for Entries<'_> {
+    type Item = (usize, Item);
+
+    fn next(&mut self) -> Option<(usize, Item)> {
+        loop {
+            let Some(source) = self.stack.last_mut() else {
+                // Each folder is walked in turn, as if scanned on its own; only
+                // the paths recorded are relative to the common base.
+                let index = self.next_folder;
+                let dir = self.roots.folders.get(index)?.clone();
+                self.next_folder += 1;
+                match &self.roots.files {
+                    Some(files) => self.stack.push(Source::Listed { index, files: files.clone().into_iter() }),
+                    None => self.push_walker(index, Start { dir, depth: 0, state: None }),
+                }
+                continue;
+            };
+            let (index, item) = match source {
+                Source::Walker { index, offset, iter } => {
+                    let (index, offset) = (*index, *offset);
+                    let Some(mut item) = iter.next() else {
+                        self.stack.pop();
+                        continue;
+                    };
+                    if let Ok(entry) = &mut item {
+                        entry.depth += offset;
+                        if self.options.retries > 0
+                            && entry
+                                .read_children_error
+                                .as_ref()
+                                .and_then(jwalk::Error::io_error)
+                                .is_some_and(retry::is_transient)
+                        {
+                            self.retry_listing(index, entry);
+                        }
+                        if entry.file_type.is_dir()
+                            && entry.read_children_path.is_none()
+                            && let Some(state) = self.cache.as_ref().and_then(|c| c.take_served(&entry.path()))
+                        {
+                            self.push_cached(index, entry.path(), entry.depth, state);
+                        }
+                    }
+                    (index, Item::Walked(Box::new(item)))
+                }
+                Source::Cached { index, dir, depth, state, entries } => {
+                    let (index, depth) = (*index, *depth + 1);
+                    let Some((name, kind)) = entries.next() else {
+                        self.stack.pop();
+                        continue;
+                    };
+                    let path = dir.join(&name);
+                    let is_dir = kind == Kind::Dir;
+                    if !self.pruners[index].keeps(OsStr::new(&name), &path, is_dir, &state.ignore) {
+                        continue;
+                    }
+                    if is_dir && self.options.max_depth.is_none_or(|max| depth < max) {
+                        let state = state.clone();
+                        if self.cache.as_ref().is_some_and(|cache| cache.unchanged(&path)) {
+                            self.push_cached(index, path.clone(), depth, state);
+                        } else {
+                            // The new walker yields the directory itself.
+                            self.push_walker(index, Start { dir: path, depth, state: Some(state) });
+                            continue;
+                        }
+                    }
+                    let entry = CachedEntry { path, depth, name: name.into(), kind };
+                    (index, Item::Cached(entry))
+                }
+                Source::Listed { index, files } => {
+                    let index = *index;
+                    let Some(path) = files.next() else {
+                        self.stack.pop();
+                        continue;
+                    };
+                    let root = &self.roots.folders[index];
+                    let depth = path.strip_prefix(root).unwrap_or(&path).components().count();
+                    if self.options.max_depth.is_some_and(|max| depth > max) {
+                        continue;
+                    }
+                    let kind = match fs::symlink_metadata(&path) {
+                        Ok(metadata) if metadata.is_symlink() => Kind::Symlink,
+                        Ok(metadata) if metadata.is_dir() => Kind::Dir,
+                        Ok(metadata) if metadata.is_file() => Kind::File,
+                        Ok(_) => Kind::Other,
+                        Err(e) => return Some((index, Item::Unreadable(path, e))),
+                    };
+                    let name = path.file_name().unwrap_or(path.as_os_str()).to_os_string();
+                    if !self.pruners[index].keeps(&name, &path, kind == Kind::Dir, &IgnoreStack::default()) {
+                        continue;
+                    }
+                    (index, Item::Cached(CachedEntry { path, depth, name, kind }))
+                }
+            };
+            return Some((index, item));
+        }
+    }
+}
+
+/// Catalog the files inside the archive at `path` (`archive_path` relative to the
+/// base) that pass the extension filters, recording it as an error if it can't
+/// be listed (in full). At most `room` entries are catalogued, so `--limit` can stop inside
+/// an archive. Returns whether the archive could be read.
+fn catalog_archive<F>(
+    path: &Path,
+    (kind, archive_path): (archive::Kind, PathBuf),
+    options: &ScanOptions,
+    filter: &ScanFilter,
+    mut room: u64,
+    stats: &mut ScanStats,
+    on_file: &mut F,
+) -> bool
+where
+    F: FnMut(String, FileEntry),
+{
+    let mut members = Vec::new();
+    let listed = archive::members(path, kind, &mut members);
+    if let Err(e) = &listed {
+        let kind = kind.name();
+        if !options.quiet {
+            eprintln!("Warning: cannot list {kind} archive '{}': {e}", path.display());
+        }
+        stats.record_error(Some(archive_path.clone()), format!("cannot list {kind} archive: {e}"));
+    }
+    for member in members {
+        let ext = group_key(Path::new(&member.name), options);
+        let folded = options
+            .preserve_case
+            .then(|| options.aliases.canonical(ext.to_lowercase()));
+        if !filter.ext_allowed(folded.as_deref().unwrap_or(&ext)) {
+            stats.filtered += 1;
+            continue;
+        }
+        if room == 0 {
+            break;
+        }
+        room -= 1;
+        stats.archive_entries += 1;
+        let member_path = archive::member_path(&archive_path, &member.name);
+        let size = Some(member.size).filter(|_| options.sizes);
+        on_file(ext, FileEntry { archived: true, ..FileEntry::new(member_path, size) });
+    }
+    listed.is_ok()
+}
+
+/// The extensions in `casings` spelled more than one way, sorted by extension.
+fn case_variants(casings: HashMap<String, HashMap<String, u64>>) -> Vec<CaseVariants> {
+    let mut variants: Vec<CaseVariants> = casings
+        .into_iter()
+        .filter(|(_, spellings)| spellings.len() > 1)
+        .map(|(ext, spellings)| {
+            let mut spellings: Vec<(String, u64)> = spellings.into_iter().collect();
+            spellings.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            CaseVariants { ext, spellings }
+        })
+        .collect();
+    variants.sort_by(|a, b| a.ext.cmp(&b.ext));
+    variants
+}
+
+/// What the walk saw of each directory, for `--empty-dirs`.
+#[derive(Default)]
+struct DirTracker {
+    /// Every directory below the root, relative to it.
+    dirs: Vec<PathBuf>,
+    /// Directories with at least one entry of any kind.
+    occupied: HashSet<PathBuf>,
+    /// Directories with a non-directory entry somewhere beneath them.
+    holding: HashSet<PathBuf>,
+}
+
+impl DirTracker {
+    fn record(&mut self, relative: &Path, depth: usize, is_dir: bool) {
+        if depth == 0 {
+            return;
+        }
+        let parent = relative.parent().unwrap_or(Path::new(""));
+        self.occupied.insert(parent.to_path_buf());
+        if is_dir {
+            self.dirs.push(relative.to_path_buf());
+            return;
+        }
+        for dir in parent.ancestors() {
+            // Everything above an already-marked directory is marked too.
+            if !self.holding.insert(dir.to_path_buf()) {
+                break;
+            }
+        }
+    }
+
+    /// The empty directories, sorted. Candidates are re-checked on disk, so a
+    /// directory whose contents were only filtered, pruned or ignored (or lay past
+    /// `--max-depth`) is not reported. `Recursive` lists only the outermost
+    /// directory of each file-less tree.
+    fn empty_dirs(self, root: &Path, mode: EmptyDirMode) -> Vec<PathBuf> {
+        let mut empty: Vec<PathBuf> = match mode {
+            EmptyDirMode::Direct => self
+                .dirs
+                .into_iter()
+                .filter(|dir| !self.occupied.contains(dir) && is_empty_on_disk(&root.join(dir)))
+                .collect(),
+            EmptyDirMode::Recursive => {
+                let found: HashSet<PathBuf> = self
+                    .dirs
+                    .into_iter()
+                    .filter(|dir| !self.holding.contains(dir) && holds_no_files(&root.join(dir)))
+                    .collect();
+                found
+                    .iter()
+                    .filter(|dir| dir.ancestors().skip(1).all(|parent| !found.contains(parent)))
+                    .cloned()
+                    .collect()
+            }
+        };
+        empty.sort();
+        empty
+    }
+}
+
+/// Whether `dir` can be read and has no entries at all.
+fn is_empty_on_disk(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none())
+}
+
+/// Whether `dir` can be read and contains nothing but (recursively) empty
+/// directories. Symlinks count as content. Subdirectories wait on a stack rather
+/// than being recursed into, so arbitrarily deep trees can't overflow it.
+fn holds_no_files(dir: &Path) -> bool {
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return false;
+        };
+        for entry in entries {
+            match entry {
+                Ok(entry) if entry.file_type().is_ok_and(|t| t.is_dir()) => pending.push(entry.path()),
+                _ => return false,
+            }
+        }
+    }
+    true
+}
+
+/// Merge extension groups with fewer than `min` files into [`OTHER_GROUP`], or
+/// remove them when `drop` is set, and return how many files were dropped.
+/// Directory and symlink groups are left alone.
+pub fn fold_small_groups(catalog: &mut Catalog, min: usize, drop: bool) -> u64 {
+    let small: Vec<String> = catalog
+        .iter()
+        .filter(|(ext, entries)| {
+            entries.len() < min && is_regular_group(ext)
+        })
+        .map(|(ext, _)| ext.clone())
+        .collect();
+
+    let mut dropped = 0;
+    for ext in small {
+        let entries = catalog.remove(&ext).unwrap_or_default();
+        if drop {
+            dropped += entries.len() as u64;
+        } else {
+            catalog.entry(OTHER_GROUP.to_string()).or_default().extend(entries);
+        }
+    }
+    dropped
+}
+
+/// A [`SYMLINK_GROUP`] entry for the link at `path`, with its target. Failing to
+/// follow the link only marks it broken; it never stops the scan.
+fn link_entry(relative_path: PathBuf, path: &Path, options: &ScanOptions) -> FileEntry {
+    let resolved = fs::canonicalize(path);
+    FileEntry {
+        target: fs::read_link(path).ok(),
+        broken_link: resolved.is_err(),
+        resolved: resolved.ok().filter(|_| options.resolve_links),
+        ..FileEntry::new(relative_path, None)
+    }
+}
+
+/// A file's modification time, or a description of why it can't be trusted.
+fn checked_mtime(metadata: &Metadata, now: SystemTime) -> Result<SystemTime, &'static str> {
+    let mtime = metadata
+        .modified()
+        .map_err(|_| "an unreadable modification time")?;
+    if mtime < UNIX_EPOCH {
+        Err("a modification time before 1970")
+    } else if mtime > now + Duration::from_secs(86_400) {
+        Err("a modification time in the future")
+    } else {
+        Ok(mtime)
+    }
+}
+
+/// Whether an entry is hidden: a dot-prefixed name, or on Windows the
+/// `FILE_ATTRIBUTE_HIDDEN` flag.
+fn is_hidden(name: &OsStr, path: &Path) -> bool {
+    name.to_string_lossy().starts_with('.')
+        || attributes::read(path).is_some_and(|bits| attributes::has(bits, FileAttribute::Hidden))
+}
+
+/// Return true if the entry matches any glob in `set`. Matched against both the bare
 /// file name and the forward-slash relative path, so `*.tmp` matches by name while
-/// `build/**` can target a subtree.
-fn path_excluded(exclude: &GlobSet, root: &Path, full: &Path, file_name: &OsStr) -> bool {
-    if exclude.is_match(Path::new(file_name)) {
+/// `build/**` can target a subtree. Directories are also tried with a trailing
+/// slash, so a pattern for their contents (`*/cache/*`) prunes the whole directory.
+fn glob_matches(set: &GlobSet, root: &Path, full: &Path, file_name: &OsStr, is_dir: bool) -> bool {
+    if set.is_match(Path::new(file_name)) {
+        return true;
+    }
+    let mut normalized = slash_path(root, full);
+    if set.is_match(&normalized) {
         return true;
     }
+    if is_dir {
+        normalized.push('/');
+        return set.is_match(&normalized);
+    }
+    false
+}
+
+/// Bytes that sort paths into catalog order: the components joined by `/` on
+/// every platform and compared byte by byte, so `a-b.txt` (`-` is below `/`)
+/// comes before `a/b.txt`, and `B` before `a`.
+pub fn sort_key(path: &Path) -> Vec<u8> {
+    let mut key = Vec::with_capacity(path.as_os_str().len());
+    for component in path.components() {
+        if (!key.is_empty() && key.last() != Some(&b'/')) || component == Component::RootDir {
+            key.push(b'/');
+        }
+        if component != Component::RootDir {
+            key.extend_from_slice(component.as_os_str().as_encoded_bytes());
+        }
+    }
+    key
+}
+
+/// The order the walk visits entries in: their [`sort_key`] with a `/` after a
+/// directory's, so that walking each directory's entries in this order, depth
+/// first, finds the files in catalog order.
+fn walk_key(path: &Path, is_dir: bool) -> Vec<u8> {
+    let mut key = sort_key(path);
+    if is_dir {
+        key.push(b'/');
+    }
+    key
+}
+
+/// `full` relative to `root` with `/` separators on every platform, so patterns
+/// written for one OS match on the others.
+fn slash_path(root: &Path, full: &Path) -> String {
     let rel = full.strip_prefix(root).unwrap_or(full);
-    let normalized = rel.to_string_lossy().replace('\\', "/");
-    exclude.is_match(normalized)
+    rel.to_string_lossy().replace('\\', "/")
+}
+
+/// The catalog key for a file: its [`extension_key`], with `--alias` applied, or
+/// the literal extension with `--preserve-case`.
+pub fn group_key(path: &Path, options: &ScanOptions) -> String {
+    if options.preserve_case {
+        return extension_key(path, &options.compound_exts, true);
+    }
+    options
+        .aliases
+        .canonical(extension_key(path, &options.compound_exts, false))
 }
 
-/// Build the lowercase, dot-prefixed extension key for a file (e.g. `.txt`).
-/// Files without an extension map to an empty string.
-pub fn extension_key(path: &Path) -> String {
+/// Build the lowercase, dot-prefixed extension key for a file (e.g. `.txt`), or
+/// with `preserve_case` keep the extension as spelled (`.JPG`).
+/// Files without an extension map to an empty string, shown as "(no extension)".
+///
+/// This follows [`Path::extension`]: a dotfile's leading dot doesn't start an
+/// extension, so `.bashrc` and `.gitignore` have none, while `.bashrc.bak` is
+/// `.bak`. A trailing dot (`notes.`) gives an empty extension and so none either.
+pub fn extension_key(path: &Path, compound: &[String], preserve_case: bool) -> String {
+    if !compound.is_empty()
+        && let Some(name) = path.file_name().and_then(|name| name.to_str())
+    {
+        let lower = name.to_lowercase();
+        // Longest first, so `.tar.gz` is never shadowed by a shorter entry; the name
+        // must have a stem of its own (`.tar.gz` alone is a dotfile).
+        if let Some(ext) = compound
+            .iter()
+            .filter(|ext| lower.len() > ext.len() && lower.ends_with(ext.as_str()))
+            .max_by_key(|ext| ext.len())
+        {
+            if preserve_case {
+                let chars = ext.chars().count();
+                return name.chars().skip(name.chars().count() - chars).collect();
+            }
+            return ext.clone();
+        }
+    }
     match path.extension().and_then(|ext| ext.to_str()) {
-        Some(ext) => {
+        Some(ext) if !ext.is_empty() => {
             let mut key = String::with_capacity(ext.len() + 1);
             key.push('.');
-            for c in ext.chars() {
-                key.extend(c.to_lowercase());
+            if preserve_case {
+                key.push_str(ext);
+            } else {
+                for c in ext.chars() {
+                    key.extend(c.to_lowercase());
+                }
             }
             key
         }
-        None => String::new(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use std::fs;
+
+    /// Create the files (and, for names ending in `/`, the directories) under `root`.
+    fn make(root: &Path, names: &[&str]) {
+        for name in names {
+            let path = root.join(name);
+            if name.ends_with('/') {
+                fs::create_dir_all(&path).unwrap();
+            } else {
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, "x").unwrap();
+            }
+        }
+    }
+
+    /// `(group, relative path)` of everything a walk of `root` with the flags
+    /// `args` catalogs, sorted, with `/` separators.
+    fn catalogued(root: &Path, args: &[&str]) -> Vec<(String, String)> {
+        let (options, filter) = cli::parse_for_test(args);
+        let mut found = Vec::new();
+        walk(&Roots::single(root.to_path_buf()), &options, &filter, |group, entry| {
+            found.push((group, entry.path.to_string_lossy().replace('\\', "/")));
+        });
+        found.sort();
+        found
+    }
+
+    fn paths(found: &[(String, String)]) -> Vec<&str> {
+        found.iter().map(|(_, path)| path.as_str()).collect()
+    }
+
+    #[test]
+    fn regexes_see_the_relative_path_with_forward_slashes() {
+        let (_, filter) = cli::parse_for_test(&["--regex", r"^sub/a\.txt$"]);
+        let root = Path::new("root");
+        assert!(filter.regex_allowed(&slash_path(root, &root.join("sub").join("a.txt"))));
+        assert!(filter.regex_allowed(&slash_path(Path::new(""), Path::new(r"sub\a.txt"))));
+        assert!(!filter.regex_allowed(&slash_path(Path::new(""), Path::new("root/sub/a.txt"))));
+    }
+
+    #[test]
+    fn a_regex_is_anchored_at_the_scanned_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        make(dir.path(), &["sub/a.txt", "sub/deep/b.txt", "other/sub/c.txt", "d.txt"]);
+        let found = catalogued(dir.path(), &["--regex", "^sub/"]);
+        assert_eq!(paths(&found), ["sub/a.txt", "sub/deep/b.txt"]);
+        let found = catalogued(dir.path(), &["--regex-exclude", "^sub/|^d"]);
+        assert_eq!(paths(&found), ["other/sub/c.txt"]);
+    }
+
+    const DOTFILES: &[&str] = &[".bashrc", ".hidden.txt", ".dir/inner.txt", "a/.b.txt", "a/c.txt", "visible.txt"];
+
+    #[test]
+    fn the_walk_finds_files_in_catalog_order() {
+        let dir = tempfile::tempdir().unwrap();
+        make(dir.path(), &["a/c/d.txt", "a/b.txt", "a.txt", "a.b/c.txt", "a-b.txt", "B.txt"]);
+        let (options, filter) = cli::parse_for_test(&[]);
+        let mut found = Vec::new();
+        walk(&Roots::single(dir.path().to_path_buf()), &options, &filter, |_, entry| {
+            found.push(entry.path.to_string_lossy().replace('\\', "/"));
+        });
+        assert_eq!(found, ["B.txt", "a-b.txt", "a.b/c.txt", "a.txt", "a/b.txt", "a/c/d.txt"]);
+        assert!(sort_key(Path::new("a-b.txt")) < sort_key(&Path::new("a").join("b.txt")));
+    }
+
+    #[test]
+    fn dotfiles_and_dot_directories_are_catalogued_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        make(dir.path(), DOTFILES);
+        let found = catalogued(dir.path(), &[]);
+        let expected = [
+            ("", ".bashrc"),
+            (".txt", ".dir/inner.txt"),
+            (".txt", ".hidden.txt"),
+            (".txt", "a/.b.txt"),
+            (".txt", "a/c.txt"),
+            (".txt", "visible.txt"),
+        ];
+        let expected: Vec<(String, String)> = expected.iter().map(|(g, p)| (g.to_string(), p.to_string())).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn hidden_exclude_skips_dotfiles_and_everything_under_dot_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        make(dir.path(), DOTFILES);
+        assert_eq!(paths(&catalogued(dir.path(), &["--hidden", "exclude"])), ["a/c.txt", "visible.txt"]);
+    }
+
+    #[test]
+    fn a_hidden_root_or_one_spelled_with_dot_components_is_still_scanned() {
+        let dir = tempfile::tempdir().unwrap();
+        make(dir.path(), &[".root/a.txt", ".root/.b.txt", "sub/"]);
+        let root = dir.path().join(".root");
+        assert_eq!(paths(&catalogued(&root, &["--hidden", "exclude"])), ["a.txt"]);
+        let dotted = dir.path().join("sub").join("..").join(".").join(".root");
+        assert_eq!(paths(&catalogued(&dotted, &["--hidden", "exclude"])), ["a.txt"]);
+        assert_eq!(paths(&catalogued(&dotted, &[])), [".b.txt", "a.txt"]);
+    }
+
+    const CASINGS: &[&str] = &["a.JPG", "b.Jpg", "c.jpg", "d.jpg", "e.png"];
+
+    #[test]
+    fn extensions_group_case_insensitively_unless_case_is_preserved() {
+        let dir = tempfile::tempdir().unwrap();
+        make(dir.path(), CASINGS);
+        let groups = |args: &[&str]| -> Vec<String> {
+            catalogued(dir.path(), args).into_iter().map(|(group, _)| group).collect()
+        };
+        assert_eq!(groups(&[]), [".jpg", ".jpg", ".jpg", ".jpg", ".png"]);
+        assert_eq!(groups(&["--preserve-case"]), [".JPG", ".Jpg", ".jpg", ".jpg", ".png"]);
+    }
+
+    #[test]
+    fn each_spelling_of_an_extension_is_reported_most_common_first() {
+        let dir = tempfile::tempdir().unwrap();
+        make(dir.path(), CASINGS);
+        let (options, filter) = cli::parse_for_test(&[]);
+        let stats = walk(&Roots::single(dir.path().to_path_buf()), &options, &filter, |_, _| {});
+        assert_eq!(stats.case_variants.len(), 1);
+        assert_eq!(stats.case_variants[0].ext, ".jpg");
+        let spellings = [(".jpg".to_string(), 2), (".JPG".to_string(), 1), (".Jpg".to_string(), 1)];
+        assert_eq!(stats.case_variants[0].spellings, spellings);
+
+        let (options, filter) = cli::parse_for_test(&["--no-case-variants"]);
+        let stats = walk(&Roots::single(dir.path().to_path_buf()), &options, &filter, |_, _| {});
+        assert!(stats.case_variants.is_empty());
+    }
+
+    #[test]
+    fn ext_matches_every_casing_with_or_without_preserve_case() {
+        let dir = tempfile::tempdir().unwrap();
+        make(dir.path(), CASINGS);
+        let all = ["a.JPG", "b.Jpg", "c.jpg", "d.jpg"];
+        assert_eq!(paths(&catalogued(dir.path(), &["--ext", "JPG"])), all);
+        assert_eq!(paths(&catalogued(dir.path(), &["--ext", ".jpg", "--preserve-case"])), all);
+        assert_eq!(paths(&catalogued(dir.path(), &["--exclude-ext", "Jpg"])), ["e.png"]);
+    }
+
+    /// Nest `depth` directories named `d` under `root`, with `leaf.txt` and an
+    /// empty `e` at the bottom. Each level is made at the top and the chain so
+    /// far moved into it, so no path used here is ever long.
+    fn deep_chain(root: &Path, depth: usize) {
+        let chain = root.join("d");
+        let level = root.join("level");
+        make(&chain, &["leaf.txt", "e/"]);
+        for _ in 1..depth {
+            fs::create_dir(&level).unwrap();
+            fs::rename(&chain, level.join("d")).unwrap();
+            fs::rename(&level, &chain).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_chain_deeper_than_the_stack_and_fd_limits_is_walked_and_rendered() {
+        // Twice the usual 1024 open-file limit, while the full path (the walk
+        // opens directories by it) stays under Linux's 4096-byte PATH_MAX.
+        const DEPTH: usize = 2000;
+        let dir = tempfile::tempdir().unwrap();
+        deep_chain(dir.path(), DEPTH);
+        let (options, filter) = cli::parse_for_test(&["--empty-dirs"]);
+        let mut catalog = Catalog::new();
+        let stats = walk(&Roots::single(dir.path().to_path_buf()), &options, &filter, |group, entry| {
+            catalog.entry(group).or_default().push(entry);
+        });
+        assert!(stats.errors.is_empty());
+        let leaf = std::iter::repeat_n("d", DEPTH).chain(["leaf.txt"]).collect::<PathBuf>();
+        assert_eq!(catalog[".txt"].iter().map(|e| &e.path).collect::<Vec<_>>(), [&leaf]);
+        assert_eq!(stats.empty_dirs, [leaf.with_file_name("e")]);
+
+        let mut out = Vec::new();
+        crate::output::render(
+            &mut out,
+            crate::config::OutputFormat::Tree,
+            &catalog,
+            &stats,
+            dir.path(),
+            &options,
+            crate::output::color::Palette::PLAIN,
+        )
+        .unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("leaf.txt"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn hidden_exclude_also_skips_files_with_the_hidden_attribute_on_windows() {
+        let dir = tempfile::tempdir().unwrap();
+        make(dir.path(), &["shown.txt", "secret.txt"]);
+        let status = std::process::Command::new("attrib").arg("+h").arg(dir.path().join("secret.txt")).status().unwrap();
+        assert!(status.success());
+        assert_eq!(paths(&catalogued(dir.path(), &["--hidden", "exclude"])), ["shown.txt"]);
+        assert_eq!(paths(&catalogued(dir.path(), &[])), ["secret.txt", "shown.txt"]);
     }
 }
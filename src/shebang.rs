@@ -0,0 +1,38 @@
+//! `--shebang`: group extensionless scripts by interpreter (`#!python3`,
+//! `#!bash`) instead of leaving them all in the no-extension group.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes read from each file; longer `#!` lines are cut off here.
+const PREFIX: u64 = 256;
+
+/// The group for an extensionless file at `path`: `#!` followed by its
+/// interpreter's name, or `None` if it isn't a script or can't be read.
+pub fn group(path: &Path) -> Option<String> {
+    let mut start = Vec::new();
+    File::open(path).ok()?.take(PREFIX).read_to_end(&mut start).ok()?;
+    interpreter(&start).map(|name| format!("#!{name}"))
+}
+
+/// The interpreter named by a `#!` line at the start of `start`, looking past
+/// `/usr/bin/env` (and its options and `NAME=value` assignments) to the program
+/// it runs.
+fn interpreter(start: &[u8]) -> Option<String> {
+    let line = start.strip_prefix(b"#!")?;
+    let line = line.split(|&b| b == b'\n').next()?;
+    let line = String::from_utf8_lossy(line);
+    let mut words = line.split_whitespace();
+    let program = basename(words.next()?);
+    let name = if program == "env" {
+        words.find(|word| !word.starts_with('-') && !word.contains('=')).map(basename)?
+    } else {
+        program
+    };
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+fn basename(program: &str) -> &str {
+    program.rsplit('/').next().unwrap_or(program)
+}
@@ -0,0 +1,138 @@
+//! Content sniffing on the start of each file: text/binary classification for
+//! `--only` (the NUL-byte heuristic of git and grep), magic-byte MIME
+//! detection for `--mime`, and the extension-to-type table behind
+//! `--verify-types`.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// How much of each file is sampled.
+const SAMPLE: usize = 8 * 1024;
+
+/// The NUL-byte heuristic behind [`Sample::is_binary`], applied to the first
+/// 8 KB of `start`.
+pub fn looks_binary(start: &[u8]) -> bool {
+    const BOMS: [&[u8]; 4] = [
+        &[0xFF, 0xFE, 0x00, 0x00],
+        &[0x00, 0x00, 0xFE, 0xFF],
+        &[0xFF, 0xFE],
+        &[0xFE, 0xFF],
+    ];
+    if BOMS.iter().any(|bom| start.starts_with(bom)) {
+        return false;
+    }
+    start[..start.len().min(SAMPLE)].contains(&0)
+}
+
+/// The first few KB of a file.
+pub struct Sample(Vec<u8>);
+
+impl Sample {
+    /// Read up to the first 8 KB of the file at `path`.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let mut buf = Vec::with_capacity(SAMPLE);
+        File::open(path)?
+            .take(SAMPLE as u64)
+            .read_to_end(&mut buf)?;
+        Ok(Sample(buf))
+    }
+
+    /// Whether the sample looks binary: it contains a NUL byte. UTF-16 and UTF-32
+    /// text, which is full of NULs, is recognized by its BOM.
+    pub fn is_binary(&self) -> bool {
+        looks_binary(&self.0)
+    }
+
+    /// The MIME type detected from magic bytes; unrecognized content is
+    /// `text/plain` or `application/octet-stream` depending on [`Self::is_binary`].
+    pub fn mime_type(&self) -> &'static str {
+        match infer::get(&self.0) {
+            Some(kind) => kind.mime_type(),
+            None if self.is_binary() => "application/octet-stream",
+            None => "text/plain",
+        }
+    }
+
+    /// The MIME type recognized from magic bytes, without the text/binary fallback.
+    pub fn detected_type(&self) -> Option<&'static str> {
+        infer::get(&self.0).map(|kind| kind.mime_type())
+    }
+}
+
+/// MIME types produced by ZIP-based formats, any of which a `.zip` may really be.
+const ZIP_TYPES: &[&str] = &[
+    "application/zip",
+    "application/epub+zip",
+    "application/java-archive",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    "application/vnd.oasis.opendocument.text",
+    "application/vnd.oasis.opendocument.spreadsheet",
+    "application/vnd.oasis.opendocument.presentation",
+];
+
+/// Extensions (lowercase, no dot) and the detected types they plausibly hold, for
+/// `--verify-types`. A type ending in `/` matches any type with that prefix.
+/// Extensions not listed are never reported.
+const EXPECTED_TYPES: &[(&[&str], &[&str])] = &[
+    (&["jpg", "jpeg", "jpe", "jfif"], &["image/jpeg"]),
+    (&["png"], &["image/png"]),
+    (&["gif"], &["image/gif"]),
+    (&["bmp"], &["image/bmp"]),
+    (&["webp"], &["image/webp"]),
+    (&["tif", "tiff", "dng", "nef", "arw"], &["image/tiff", "image/x-canon-cr2"]),
+    (&["cr2"], &["image/x-canon-cr2", "image/tiff"]),
+    (&["heic", "heif", "avif"], &["image/heif", "image/avif"]),
+    (&["ico"], &["image/vnd.microsoft.icon"]),
+    (&["psd"], &["image/vnd.adobe.photoshop"]),
+    (&["mp4", "m4v", "mov", "m4a"], &["video/mp4", "video/x-m4v", "video/quicktime", "audio/m4a"]),
+    (&["mkv", "webm"], &["video/x-matroska", "video/webm"]),
+    (&["avi"], &["video/x-msvideo"]),
+    (&["wmv", "wma"], &["video/x-ms-wmv"]),
+    (&["mpg", "mpeg"], &["video/mpeg"]),
+    (&["flv"], &["video/x-flv"]),
+    (&["mp3"], &["audio/mpeg"]),
+    (&["flac"], &["audio/x-flac"]),
+    (&["wav"], &["audio/x-wav"]),
+    (&["ogg", "oga", "ogv", "opus"], &["audio/ogg", "audio/opus"]),
+    (&["mid", "midi"], &["audio/midi"]),
+    (&["pdf"], &["application/pdf"]),
+    (&["zip"], ZIP_TYPES),
+    (&["docx", "xlsx", "pptx", "odt", "ods", "odp", "epub", "jar", "apk"], ZIP_TYPES),
+    (&["doc", "xls", "ppt", "msi"], &[
+        "application/msword",
+        "application/vnd.ms-excel",
+        "application/vnd.ms-powerpoint",
+        "application/x-ole-storage",
+    ]),
+    (&["gz", "tgz"], &["application/gzip"]),
+    (&["bz2", "tbz2"], &["application/x-bzip2"]),
+    (&["xz", "txz"], &["application/x-xz"]),
+    (&["zst"], &["application/zstd"]),
+    (&["7z"], &["application/x-7z-compressed"]),
+    (&["rar"], &["application/vnd.rar"]),
+    (&["tar"], &["application/x-tar"]),
+    (&["exe", "dll", "sys"], &["application/vnd.microsoft.portable-executable"]),
+    (&["sqlite", "sqlite3", "db"], &["application/vnd.sqlite3"]),
+    (&["woff", "woff2"], &["application/font-woff"]),
+    (&["ttf", "otf"], &["application/font-sfnt"]),
+    // Plain text can only be "detected" as one of the few text formats.
+    (&["txt", "md", "csv", "tsv", "log", "json", "yaml", "yml", "toml", "ini", "cfg", "conf",
+       "html", "htm", "xml", "svg", "rs", "py", "js", "c", "h", "cpp", "java", "go", "rb", "sh"],
+     &["text/"]),
+];
+
+/// Whether content detected as `detected` plausibly matches the extension key
+/// `ext` (e.g. `.jpg`; a compound `.tar.gz` is judged by its last part). Unlisted
+/// extensions always match.
+pub fn type_matches_extension(ext: &str, detected: &str) -> bool {
+    let ext = ext.rsplit('.').next().unwrap_or_default();
+    let Some((_, expected)) = EXPECTED_TYPES.iter().find(|(exts, _)| exts.contains(&ext)) else {
+        return true;
+    };
+    expected
+        .iter()
+        .any(|t| if t.ends_with('/') { detected.starts_with(t) } else { detected == *t })
+}
@@ -0,0 +1,120 @@
+//! `--watch`: keep the catalog current. After the first scan, filesystem
+//! notifications trigger a rescan once changes have settled for the `--debounce`
+//! interval. Where notifications can't be set up (unsupported filesystems, or
+//! too many directories for the OS limit), the folders are polled instead.
+
+use crate::{exit_error, interrupt};
+use notify::{Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// How often the loop wakes up to check for Ctrl-C while nothing happens.
+const TICK: Duration = Duration::from_millis(200);
+
+/// Shortest polling interval, so a tiny `--debounce` doesn't stat the whole tree
+/// continuously.
+const MIN_POLL: Duration = Duration::from_secs(1);
+
+/// Watch `folders` and call `rescan` whenever something in them changes, until
+/// Ctrl-C; changes still pending then are written before returning. Events for
+/// `own` files (the outputs and the cache, and anything named after them such as
+/// an SQLite journal) are ignored so rewriting them can't trigger a rescan.
+pub fn run(folders: &[PathBuf], own: &[PathBuf], debounce: Duration, quiet: bool, mut rescan: impl FnMut()) {
+    let (sender, events) = mpsc::channel();
+    let _watcher = start(folders, sender, debounce, quiet);
+    let own: Vec<String> = own.iter().map(|path| absolute(path).to_string_lossy().into_owned()).collect();
+    if !quiet {
+        eprintln!("Watching for changes (Ctrl-C to stop)");
+    }
+    let mut due = None;
+    loop {
+        if interrupt::stop_requested() {
+            if due.is_some() {
+                rescan();
+            }
+            return;
+        }
+        wait(&events, &own, debounce, &mut due, quiet);
+        if due.is_some_and(|due| Instant::now() >= due) {
+            due = None;
+            rescan();
+        }
+    }
+}
+
+/// Wait up to [`TICK`] for an event, pushing the rescan back to `debounce` after
+/// any change that matters.
+fn wait(
+    events: &Receiver<notify::Result<Event>>,
+    own: &[String],
+    debounce: Duration,
+    due: &mut Option<Instant>,
+    quiet: bool,
+) {
+    match events.recv_timeout(TICK) {
+        Ok(Ok(event)) if is_change(&event, own) => *due = Some(Instant::now() + debounce),
+        Ok(Ok(_)) | Err(RecvTimeoutError::Timeout) => {}
+        Ok(Err(e)) if !quiet => eprintln!("Warning: while watching: {e}"),
+        Ok(Err(_)) => {}
+        // Only once the watcher is gone; don't spin if that ever happens.
+        Err(RecvTimeoutError::Disconnected) => std::thread::sleep(TICK),
+    }
+}
+
+/// Set up notifications for every folder, or polling if any of them can't be
+/// watched that way. Paths in events are absolute.
+fn start(
+    folders: &[PathBuf],
+    sender: mpsc::Sender<notify::Result<Event>>,
+    debounce: Duration,
+    quiet: bool,
+) -> Box<dyn Watcher> {
+    let notified = RecommendedWatcher::new(sender.clone(), notify::Config::default())
+        .and_then(|mut watcher| {
+            for folder in folders {
+                watcher.watch(&absolute(folder), RecursiveMode::Recursive)?;
+            }
+            Ok(watcher)
+        });
+    let e = match notified {
+        Ok(watcher) => return Box::new(watcher),
+        Err(e) => e,
+    };
+    let interval = debounce.max(MIN_POLL);
+    if !quiet {
+        eprintln!(
+            "Warning: change notifications unavailable ({e}); polling every {}s instead",
+            interval.as_secs_f64()
+        );
+    }
+    let config = notify::Config::default().with_poll_interval(interval);
+    let polled = PollWatcher::new(sender, config).and_then(|mut watcher| {
+        for folder in folders {
+            watcher.watch(&absolute(folder), RecursiveMode::Recursive)?;
+        }
+        Ok(watcher)
+    });
+    match polled {
+        Ok(watcher) => Box::new(watcher),
+        Err(e) => exit_error(&format!("cannot watch for changes: {e}")),
+    }
+}
+
+/// Whether `event` is a change to the catalogued tree: anything but a read,
+/// touching at least one path that isn't one of our own files.
+fn is_change(event: &Event, own: &[String]) -> bool {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+    // An event without paths (e.g. a rescan request) can't be ruled out.
+    event.paths.is_empty()
+        || event.paths.iter().any(|path| {
+            let path = path.to_string_lossy();
+            !own.iter().any(|own| path.starts_with(own.as_str()))
+        })
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,86 @@
+//! `--xattrs`: extended attribute names (and optionally values) on Unix, such
+//! as macOS quarantine flags or SELinux labels.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// Longest value shown with `--xattr-values`, in characters; longer ones are cut
+/// short with `…`.
+#[cfg(unix)]
+const VALUE_LIMIT: usize = 32;
+
+/// One extended attribute of a file.
+#[derive(Clone)]
+pub struct Xattr {
+    pub name: String,
+    /// The value for display, with `--xattr-values` (`None` if it couldn't be read).
+    pub value: Option<String>,
+}
+
+/// The extended attributes of the file at `path`, sorted by name. Values are
+/// read only when `values` is set; one that fails to read is left out rather
+/// than failing the file.
+/// A filesystem without extended attributes gives an empty list, not an error.
+#[cfg(unix)]
+pub fn read(path: &Path, values: bool) -> io::Result<Vec<Xattr>> {
+    let names = match xattr::list_deref(path) {
+        Ok(names) => names,
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut attrs: Vec<Xattr> = names
+        .map(|name| {
+            let value = if values {
+                xattr::get_deref(path, &name).ok().flatten().map(|v| display_value(&v))
+            } else {
+                None
+            };
+            Xattr { name: name.to_string_lossy().into_owned(), value }
+        })
+        .collect();
+    attrs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(attrs)
+}
+
+/// Extended attributes aren't read here; `--xattrs` is ignored with a warning.
+#[cfg(not(unix))]
+pub fn read(_path: &Path, _values: bool) -> io::Result<Vec<Xattr>> {
+    Ok(Vec::new())
+}
+
+/// Attribute names with the number of files carrying each, most common first.
+pub fn counts(counts: HashMap<String, u64>) -> Vec<(String, u64)> {
+    let mut counts: Vec<(String, u64)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// A value as text: printable UTF-8 as is, anything else as hex, then truncated
+/// to [`VALUE_LIMIT`] characters.
+#[cfg(unix)]
+fn display_value(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(char::is_control) => text.to_string(),
+        _ => crate::hash::hex(bytes),
+    };
+    if text.chars().count() > VALUE_LIMIT {
+        let mut cut: String = text.chars().take(VALUE_LIMIT).collect();
+        cut.push('…');
+        cut
+    } else {
+        text
+    }
+}
+
+/// `name` or `name=value` for each attribute, as shown in text and CSV output.
+pub fn labels(attrs: &[Xattr]) -> Vec<String> {
+    attrs
+        .iter()
+        .map(|attr| match &attr.value {
+            Some(value) => format!("{}={value}", attr.name),
+            None => attr.name.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,58 @@
+//! A scan cut short by `--timeout` and carried on with `--checkpoint`, run after
+//! run, must end with the catalog a single uninterrupted scan writes.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Exit status of a run stopped by `--timeout`.
+const TIMED_OUT: i32 = 124;
+
+/// Enough files that a debug build can't walk them within the timeout, under
+/// names whose byte order differs from their order by path components.
+fn build(root: &Path) {
+    for dir in 0..40 {
+        for sub in ["x", "x-y", "x.y"] {
+            let path = root.join(format!("d{dir}")).join(sub);
+            fs::create_dir_all(&path).unwrap();
+            for file in 0..80 {
+                fs::write(path.join(format!("f{file}.txt")), "x").unwrap();
+            }
+            fs::write(root.join(format!("d{dir}")).join(format!("{sub}.txt")), "x").unwrap();
+        }
+    }
+}
+
+fn scan(root: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_CreepDir"))
+        .arg(root)
+        .args(["-", "--line-endings", "lf", "--quiet"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn a_scan_resumed_after_timeouts_writes_the_uninterrupted_catalog() {
+    let tree = tempfile::tempdir().unwrap();
+    build(tree.path());
+    let journal = tempfile::tempdir().unwrap();
+    let checkpoint = journal.path().join("scan.checkpoint");
+    let checkpoint = checkpoint.to_str().unwrap();
+    let whole = scan(tree.path(), &["--checkpoint", checkpoint]);
+    assert!(whole.status.success(), "{}", String::from_utf8_lossy(&whole.stderr));
+    assert!(!Path::new(checkpoint).exists());
+
+    let mut cuts = 0;
+    let resumed = loop {
+        let run = scan(tree.path(), &["--checkpoint", checkpoint, "--timeout", "30ms"]);
+        if run.status.code() != Some(TIMED_OUT) {
+            break run;
+        }
+        cuts += 1;
+        assert!(cuts < 1000, "the scan never got to the end");
+    };
+    assert!(resumed.status.success(), "{}", String::from_utf8_lossy(&resumed.stderr));
+    assert!(cuts > 0, "the scan was never cut short");
+    assert_eq!(String::from_utf8_lossy(&resumed.stdout), String::from_utf8_lossy(&whole.stdout));
+}
@@ -0,0 +1,17 @@
+//! `--completions` writes a script for each shell that knows the flags.
+
+use std::process::Command;
+
+#[test]
+fn every_shell_gets_a_script_naming_the_flags() {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_CreepDir")).args(["--completions", shell]).output().unwrap();
+        assert!(output.status.success(), "{shell}: {}", String::from_utf8_lossy(&output.stderr));
+        let script = String::from_utf8(output.stdout).unwrap();
+        for flag in ["format", "group-by", "output"] {
+            // Fish names long options without their dashes.
+            let spelled = if shell == "fish" { format!("-l {flag} ") } else { format!("--{flag}") };
+            assert!(script.contains(&spelled), "{shell} script lacks --{flag}");
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! The default text catalog of a fixed tree, which must come out byte for byte
+//! the same however the filesystem happened to store the entries.
+
+use std::fs;
+use std::path::{MAIN_SEPARATOR_STR, Path};
+use std::process::Command;
+
+/// Files of the fixture, chosen so that byte order and case both matter: paths
+/// are compared as bytes with `/` between components, so `a-b.txt` and `a.txt`
+/// (`-` and `.` are below `/`) sort before `a/b.txt`, and `B` before `a`.
+const FIXTURE: &[&str] = &[
+    "a/b.txt",
+    "a/c/d.txt",
+    "a-b.txt",
+    "a.b/c.txt",
+    "B.txt",
+    "a.txt",
+    "notes/README",
+    "notes/todo.md",
+    "src/main.rs",
+    "src/lib.rs",
+    "src/bin/tool.rs",
+    "\u{e4}.txt",
+    "Z.MD",
+];
+
+const GOLDEN: &str = "\
+--- (no extension) (1 files) ---
+notes/README
+
+--- .md (2 files) ---
+Z.MD
+notes/todo.md
+
+--- .rs (3 files) ---
+src/bin/tool.rs
+src/lib.rs
+src/main.rs
+
+--- .txt (7 files) ---
+B.txt
+a-b.txt
+a.b/c.txt
+a.txt
+a/b.txt
+a/c/d.txt
+\u{e4}.txt
+
+Total: 13 files
+
+--- case variants (1) ---
+.md: .MD 1, .md 1
+";
+
+/// Create the fixture under `root`, in the order `files` gives.
+fn build<'a>(root: &Path, files: impl Iterator<Item = &'a &'a str>) {
+    for file in files {
+        let path = root.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, file.as_bytes()).unwrap();
+    }
+}
+
+fn catalog(root: &Path) -> Vec<u8> {
+    let output = Command::new(env!("CARGO_BIN_EXE_CreepDir"))
+        .arg(root)
+        .args(["-", "--line-endings", "lf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+#[test]
+fn the_text_catalog_does_not_depend_on_creation_order() {
+    let forward = tempfile::tempdir().unwrap();
+    let backward = tempfile::tempdir().unwrap();
+    build(forward.path(), FIXTURE.iter());
+    build(backward.path(), FIXTURE.iter().rev());
+    let first = catalog(forward.path());
+    assert_eq!(String::from_utf8_lossy(&first), GOLDEN.replace('/', MAIN_SEPARATOR_STR));
+    assert_eq!(first, catalog(backward.path()));
+    assert_eq!(first, catalog(forward.path()));
+}